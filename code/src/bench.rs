@@ -0,0 +1,245 @@
+use crate::i18n;
+use crate::lint;
+use crate::powershell;
+use crate::prompt::{self, RenderContext, Segment};
+use crate::shell::{self, Shell};
+use crate::xonsh;
+use crate::CliError;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// 计时结果中的中位数与 95 分位
+struct Latency {
+    p50: Duration,
+    p95: Duration,
+}
+
+impl Latency {
+    /// 根据若干次计时计算分位数; 没有计时时都记为零
+    fn from_samples(mut samples: Vec<Duration>) -> Latency {
+        if samples.is_empty() {
+            return Latency { p50: Duration::ZERO, p95: Duration::ZERO };
+        }
+        samples.sort();
+        let pick = |pct: usize| samples[(samples.len() - 1) * pct / 100];
+        Latency { p50: pick(50), p95: pick(95) }
+    }
+}
+
+/// 把同一操作执行若干次并计时
+fn measure<F: FnMut()>(iterations: usize, mut f: F) -> Latency {
+    let samples = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    Latency::from_samples(samples)
+}
+
+/// 供表格显示的片段名称
+fn label(segment: &Segment) -> String {
+    let label = match segment {
+        Segment::Text(text) => format!("{:?}", text),
+        other => other.to_string(),
+    };
+    if label.chars().count() > 30 {
+        format!("{}...", label.chars().take(27).collect::<String>())
+    } else {
+        label
+    }
+}
+
+/// 在右侧补空格到表格第一列的宽度, 按终端列数而不是字符数计算
+fn pad(text: &str) -> String {
+    format!("{}{}", text, " ".repeat(32usize.saturating_sub(lint::columns(text))))
+}
+
+/// 画提示符时运行命令替换的程序与参数; tcsh 与 cmd 的提示符略去命令替换, 为 None
+fn command_runner(shell: Shell) -> Option<(&'static str, &'static [&'static str])> {
+    match shell {
+        Shell::Bash => Some(("bash", &["-c"])),
+        Shell::Fish => Some(("fish", &["--no-config", "-c"])),
+        Shell::Pwsh => Some((powershell::executable(), &["-NoProfile", "-NonInteractive", "-Command"])),
+        Shell::Ksh => Some(("ksh", &["-c"])),
+        Shell::Oils => Some(("osh", &["-c"])),
+        // Elvish 与 xonsh 的提示符通过 sh 运行 bash 风格的命令替换
+        Shell::Posix | Shell::Elvish | Shell::Xonsh => Some(("sh", &["-c"])),
+        Shell::Tcsh | Shell::Cmd => None,
+    }
+}
+
+/// 渲染一个片段; 命令替换用画提示符时实际运行它的 shell 执行
+fn render(shell: Shell, segment: &Segment, ctx: &RenderContext) {
+    match segment {
+        Segment::Command(cmd) => {
+            if let Some((program, args)) = command_runner(shell) {
+                let _ = Command::new(program).args(args).arg(cmd).stdout(Stdio::null()).stderr(Stdio::null()).status();
+            }
+        }
+        _ => {
+            prompt::render_segment(segment, ctx);
+        }
+    }
+}
+
+/// 载入配置后展开 PS1: 用不加引号的 here-doc, 像 shell 画提示符时一样展开变量与命令替换, 又不受 PS1 中引号的影响
+const EXPAND_PS1: &str = ". \"$1\"\neval \"cat <<__prompt_changer_eof\n$PS1\n__prompt_changer_eof\"";
+
+/// `--spawn` 一行计时的内容; 各 shell 启动的方式不同, 彼此不能直接比较
+fn spawn_note(shell: Shell) -> String {
+    let name = shell.display_name();
+    match shell {
+        Shell::Bash => i18n::tr(
+            "bash starts with --norc and --noprofile, so the spawned row is start-up plus expanding PS1",
+            "bash 以 --norc 与 --noprofile 启动, 启动一行是启动加上展开 PS1 的时间",
+        )
+        .to_string(),
+        Shell::Fish | Shell::Pwsh => i18n::tr(
+            &format!("{} loads its full config before drawing the prompt, so the spawned row includes that start-up and is not comparable with bash", name),
+            &format!("{} 画提示符前会载入全部配置, 启动一行包含这段启动时间, 不能与 bash 直接比较", name),
+        )
+        .to_string(),
+        Shell::Ksh | Shell::Posix | Shell::Oils => i18n::tr(
+            &format!("{} sources the config and then expands PS1, so the spawned row includes loading the config", name),
+            &format!("{} 载入配置后再展开 PS1, 启动一行包含载入配置的时间", name),
+        )
+        .to_string(),
+        _ => i18n::tr(
+            &format!("{} cannot print its prompt from a script, so this row only times loading the config", name),
+            &format!("{} 不能在脚本中输出提示符, 这一行只是载入配置的时间", name),
+        )
+        .to_string(),
+    }
+}
+
+/// 启动 shell 渲染一次提示符
+fn spawn_render(shell: Shell, prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = match shell {
+        Shell::Elvish => return Err(Box::new(CliError(
+            i18n::tr(
+                "Elvish only draws its prompt in an interactive session",
+                "Elvish 只在交互式会话中画出提示符",
+            )
+            .to_string(),
+        ))),
+        Shell::Cmd => return Err(Box::new(CliError(i18n::tr("cmd only draws its prompt in an interactive window", "cmd 只在交互式窗口中画出提示符").to_string()))),
+        Shell::Bash => {
+            let mut cmd = Command::new("bash");
+            cmd.args(["--norc", "--noprofile", "-c", r#"PS1=$1; printf '%s' "${PS1@P}""#, "bash", prompt]);
+            cmd
+        }
+        Shell::Fish => {
+            let mut cmd = Command::new("fish");
+            cmd.args(["-c", "fish_prompt"]);
+            cmd
+        }
+        Shell::Pwsh => {
+            let mut cmd = Command::new(powershell::executable());
+            cmd.args(["-NonInteractive", "-Command", "prompt"]);
+            cmd
+        }
+        // 载入配置后像画提示符时一样展开 PS1, 其中的命令替换也会运行
+        Shell::Ksh | Shell::Posix | Shell::Oils => {
+            let name = match shell {
+                Shell::Ksh => "ksh",
+                Shell::Oils => "osh",
+                _ => "sh",
+            };
+            let mut cmd = Command::new(name);
+            cmd.args(["-c", EXPAND_PS1, name]).arg(shell.config_path()?);
+            cmd
+        }
+        // tcsh 没有在脚本中展开 prompt 的办法, 只能计时载入配置
+        Shell::Tcsh => {
+            let mut cmd = Command::new("tcsh");
+            cmd.args(["-f", "-c"]).arg(format!("source {}", shell::single_quote(&shell.config_path()?.display().to_string())));
+            cmd
+        }
+        // 与 tcsh 一样只载入配置; 模板中的字段在显示时才求值
+        Shell::Xonsh => {
+            let mut cmd = Command::new("xonsh");
+            cmd.args(["--no-rc", "-c"]).arg(format!("source {}", xonsh::quote(&shell.config_path()?.display().to_string())));
+            cmd
+        }
+    };
+    let status = cmd.stdout(Stdio::null()).stderr(Stdio::null()).status()?;
+    if !status.success() {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("{} exited with {}", shell.display_name(), status),
+                &format!("{} 退出, 状态为 {}", shell.display_name(), status),
+            )
+            .to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// 测量当前配置的提示符的渲染耗时
+pub fn run(shell: Shell, iterations: usize, spawn: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = shell.config_path()?;
+    let prompt = shell.read_configured_prompt()?.ok_or_else(|| {
+        let path = config_path.display();
+        CliError(i18n::tr(&format!("No prompt is configured in {}", path), &format!("{} 中没有配置提示符", path)).to_string())
+    })?;
+    let segments = prompt::parse_bash(&prompt);
+    let ctx = RenderContext::current();
+
+    println!(
+        "{}",
+        i18n::tr(
+            &format!("Rendering {} segments, {} iterations each", segments.len(), iterations),
+            &format!("渲染 {} 个片段, 每个 {} 次", segments.len(), iterations),
+        )
+    );
+    if segments.iter().any(|s| matches!(s, Segment::Command(_))) {
+        println!(
+            "{}",
+            match command_runner(shell) {
+                Some((program, _)) => i18n::tr(
+                    &format!("Command substitutions run with {}, as in the {} prompt", program, shell.display_name()),
+                    &format!("命令替换与 {} 提示符中一样用 {} 运行", shell.display_name(), program),
+                )
+                .to_string(),
+                None => i18n::tr(
+                    &format!("{} prompts leave out command substitutions, so they are not run", shell.display_name()),
+                    &format!("{} 提示符略去命令替换, 因此不运行它们", shell.display_name()),
+                )
+                .to_string(),
+            }
+        );
+    }
+    println!("{} {:>12} {:>12}", pad(i18n::tr("SEGMENT", "片段")), "P50", "P95");
+    for segment in &segments {
+        let latency = measure(iterations, || render(shell, segment, &ctx));
+        println!("{} {:>12?} {:>12?}", pad(&label(segment)), latency.p50, latency.p95);
+    }
+
+    let total = measure(iterations, || {
+        for segment in &segments {
+            render(shell, segment, &ctx);
+        }
+    });
+    println!("{} {:>12?} {:>12?}", pad(i18n::tr("(whole prompt)", "(整个提示符)")), total.p50, total.p95);
+
+    if spawn {
+        let mut samples = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            spawn_render(shell, &prompt)?;
+            samples.push(start.elapsed());
+        }
+        let latency = Latency::from_samples(samples);
+        let label = match shell {
+            Shell::Tcsh | Shell::Xonsh => {
+                i18n::tr(&format!("({} rc load time)", shell.display_name()), &format!("(载入 {} 配置)", shell.display_name())).to_string()
+            }
+            _ => i18n::tr(&format!("(spawned {})", shell.display_name()), &format!("(启动 {})", shell.display_name())).to_string(),
+        };
+        println!("{} {:>12?} {:>12?}", pad(&label), latency.p50, latency.p95);
+        println!("{}", spawn_note(shell));
+    }
+    Ok(())
+}
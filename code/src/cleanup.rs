@@ -0,0 +1,156 @@
+use crate::i18n;
+use crate::logging;
+use crate::prompt::PromptSet;
+use crate::rc;
+use crate::shell::{self, Shell};
+use crate::CliError;
+use regex::Regex;
+use std::collections::HashSet;
+use std::io::{stdin, BufRead, Write};
+
+/// 可以保留的一个提示符
+struct Candidate {
+    /// 所在行号(从 1 开始)
+    line: usize,
+    prompt: String,
+    /// 是否已在本工具管理的位置
+    managed: bool,
+}
+
+/// 询问用户保留哪一个提示符
+fn ask(candidates: &[Candidate]) -> Result<usize, Box<dyn std::error::Error>> {
+    for (i, candidate) in candidates.iter().enumerate() {
+        let place = if candidate.managed {
+            "prompt-changer".to_string()
+        } else {
+            i18n::tr(&format!("line {}", candidate.line), &format!("第 {} 行", candidate.line)).to_string()
+        };
+        println!("  {}) {}: {}", i + 1, place, candidate.prompt);
+    }
+    print!("{} [{}]: ", i18n::tr("Which one should be kept?", "保留哪一个?"), candidates.len());
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input)?;
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(candidates.len() - 1);
+    }
+    match input.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= candidates.len() => Ok(n - 1),
+        _ => Err(Box::new(CliError(format!("{}: {}", i18n::tr("Invalid choice", "无效的选择"), input)))),
+    }
+}
+
+/// 受管理区块之外旧版本追加的提示符行
+fn legacy_candidates(shell: Shell, content: &str) -> Result<Vec<Candidate>, regex::Error> {
+    let legacy = Regex::new(shell.legacy_line_pattern())?;
+    let block = rc::find_block(content);
+    Ok(content
+        .lines()
+        .enumerate()
+        .filter(|(n, _)| !block.is_some_and(|b| b.contains(*n)))
+        .filter_map(|(n, line)| {
+            legacy.captures(line).map(|caps| Candidate { line: n + 1, prompt: caps[1].to_string(), managed: false })
+        })
+        .collect())
+}
+
+/// 删除给定的行(从 0 开始)后的内容
+fn without_lines(content: &str, lines: &HashSet<usize>) -> String {
+    let remaining: Vec<&str> = content
+        .lines()
+        .enumerate()
+        .filter(|(n, _)| !lines.contains(n))
+        .map(|(_, line)| line)
+        .collect();
+    let mut updated = remaining.join("\n");
+    if !updated.is_empty() {
+        updated.push('\n');
+    }
+    updated
+}
+
+/// 保留一个旧版本追加的提示符行, 删除其余的并迁移到受管理的位置
+pub fn run(shell: Shell, pick: bool) -> Result<(), Box<dyn std::error::Error>> {
+    shell::check_privileges()?;
+    let _lock = shell::lock()?;
+    let path = shell.config_path()?;
+    let content = match rc::read_text(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Box::new(err)),
+    };
+    let block = rc::find_block(&content);
+    let mut candidates = legacy_candidates(shell, &content)?;
+    if candidates.is_empty() {
+        logging::info(i18n::tr(
+            &format!("No legacy prompt lines found in {}.", path.display()),
+            &format!("{} 中没有旧版本追加的提示符行。", path.display()),
+        ));
+        return Ok(());
+    }
+    let legacy_lines: HashSet<usize> = candidates.iter().map(|c| c.line - 1).collect();
+
+    if let Some(prompt) = shell.managed_prompt()? {
+        let line = block.map_or(usize::MAX, |b| b.start + 1);
+        candidates.push(Candidate { line, prompt, managed: true });
+        candidates.sort_by_key(|c| c.line);
+    }
+
+    let keep = if pick {
+        println!(
+            "{}",
+            i18n::tr(&format!("Prompts found for {}:", shell.display_name()), &format!("找到的 {} 提示符:", shell.display_name()))
+        );
+        ask(&candidates)?
+    } else {
+        candidates.len() - 1
+    };
+    let kept = &candidates[keep];
+
+    let updated = without_lines(&content, &legacy_lines);
+    rc::modify(&path, true, |current| {
+        if current != content.replace("\r\n", "\n") {
+            return Err(Box::new(CliError(i18n::tr(
+                &format!("{} changed while choosing; nothing was written, run cleanup again", path.display()),
+                &format!("选择期间 {} 被改动了; 没有写入任何内容, 请重新运行 cleanup", path.display()),
+            ).to_string())));
+        }
+        Ok(Some(updated.clone()))
+    })?;
+    shell.update_prompt(&PromptSet::single(&kept.prompt))?;
+
+    logging::info(i18n::tr(
+        &format!("Removed {} legacy prompt line(s) from {}.", legacy_lines.len(), path.display()),
+        &format!("已从 {} 删除 {} 行旧版本追加的提示符。", path.display(), legacy_lines.len()),
+    ));
+    logging::info(format!("{}: {}", i18n::tr("Kept", "保留"), kept.prompt));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_candidates_skip_the_managed_block() {
+        let content = format!(
+            "export A=1\nPS1='old> '\nPS1='\\u> '\n\n{}\nPS1='managed> '\n{}\n",
+            rc::BLOCK_START,
+            rc::BLOCK_END
+        );
+        let candidates = legacy_candidates(Shell::Bash, &content).unwrap();
+        let found: Vec<(usize, &str)> = candidates.iter().map(|c| (c.line, c.prompt.as_str())).collect();
+        assert_eq!(found, [(2, "old> "), (3, "\\u> ")]);
+    }
+
+    #[test]
+    fn cleanup_leaves_nothing_to_clean_up() {
+        let content = "export A=1\nPS1='a> '\nalias ll='ls -l'\nPS1='b> '\n";
+        let lines: HashSet<usize> = legacy_candidates(Shell::Bash, content).unwrap().iter().map(|c| c.line - 1).collect();
+        let updated = rc::replace_block(&without_lines(content, &lines), "PS1='b> '");
+        assert!(updated.starts_with("export A=1\nalias ll='ls -l'\n\n"));
+        assert!(legacy_candidates(Shell::Bash, &updated).unwrap().is_empty());
+        assert_eq!(without_lines("PS1='a> '\n", &HashSet::from([0])), "");
+    }
+}
@@ -0,0 +1,88 @@
+use crate::i18n;
+use crate::logging;
+use crate::share;
+use crate::CliError;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// 按平台与桌面环境排列的剪贴板命令
+fn commands() -> Vec<(&'static str, &'static [&'static str])> {
+    let mut commands: Vec<(&str, &[&str])> = Vec::new();
+    if cfg!(target_os = "macos") {
+        commands.push(("pbcopy", &[]));
+    }
+    if cfg!(windows) {
+        commands.push(("clip", &[]));
+    }
+    #[cfg(unix)]
+    if crate::shell::is_wsl() {
+        commands.push(("clip.exe", &[]));
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(("wl-copy", &[]));
+    }
+    if env::var_os("DISPLAY").is_some() {
+        commands.push(("xclip", &["-selection", "clipboard"]));
+        commands.push(("xsel", &["--clipboard", "--input"]));
+    }
+    commands
+}
+
+/// 把文本交给剪贴板命令, 命令不存在时返回 Ok(false)
+fn pipe_to(program: &str, args: &[&str], text: &str) -> io::Result<bool> {
+    let mut child = match Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    Ok(child.wait()?.success())
+}
+
+/// 复制所用的方式
+pub enum Method {
+    /// 剪贴板命令, 如 `pbcopy`
+    Command(&'static str),
+    /// 已向终端发出 OSC 52 序列; 终端不支持或关闭了它时会静默忽略, 无法确认是否复制成功
+    Osc52,
+}
+
+/// 用 OSC 52 让终端设置剪贴板, 经 SSH 时也可用; 不是所有终端都支持. 返回是否发出了序列
+fn osc52(text: &str) -> io::Result<bool> {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return Ok(false);
+    }
+    // 终端要求标准的 base64 字母表与填充
+    let mut encoded = share::base64_encode(text.as_bytes()).replace('-', "+").replace('_', "/");
+    while !encoded.len().is_multiple_of(4) {
+        encoded.push('=');
+    }
+    write!(stderr, "\x1b]52;c;{}\x07", encoded)?;
+    stderr.flush()?;
+    Ok(true)
+}
+
+/// 把文本复制到系统剪贴板, 返回所用的方式
+pub fn copy(text: &str) -> Result<Method, CliError> {
+    for (program, args) in commands() {
+        match pipe_to(program, args, text) {
+            Ok(true) => return Ok(Method::Command(program)),
+            Ok(false) => logging::debug("clipboard", format!("{} is not available", program)),
+            Err(err) => logging::debug("clipboard", format!("{} failed: {}", program, err)),
+        }
+    }
+    match osc52(text) {
+        Ok(true) => Ok(Method::Osc52),
+        _ => Err(CliError(
+            i18n::tr(
+                "No clipboard is available; install xclip, xsel or wl-clipboard, or run this in a terminal that supports OSC 52",
+                "没有可用的剪贴板; 请安装 xclip、xsel 或 wl-clipboard, 或在支持 OSC 52 的终端中运行",
+            )
+            .to_string(),
+        )),
+    }
+}
@@ -0,0 +1,170 @@
+use crate::color::{ColorDepth, ColorSlot};
+use crate::i18n;
+use crate::prompt::{self, PromptSet, Segment, SOURCE_MARKER};
+use std::path::{Path, PathBuf};
+
+/// PROMPT 中没有对应代码的 bash 转义
+const UNSUPPORTED_ESCAPES: [&str; 12] = ["W", "T", "@", "!", "#", "j", "l", "r", "a", "007", "v", "V"];
+
+/// 转义在 cmd 的 PROMPT 中是否没有对应代码; 时间与日期的格式由系统区域设置决定, 不支持 `\D{...}`
+pub fn is_unsupported(escape: &str) -> bool {
+    let octal = escape.len() == 3 && escape.chars().all(|c| c.is_digit(8));
+    UNSUPPORTED_ESCAPES.contains(&escape) || escape.starts_with("D{") || (octal && escape != "033")
+}
+
+/// 记下写入的 PROMPT 的文件; cmd 没有配置文件, 提示符保存在用户的环境变量中
+pub fn record_path(home: &Path) -> PathBuf {
+    home.join(".config/prompt-changer/cmd-prompt")
+}
+
+/// 文字中有特殊含义的字符换成对应的 `$` 代码
+fn text_codes(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '$' => out += "$$",
+            '<' => out += "$L",
+            '>' => out += "$G",
+            '|' => out += "$B",
+            '&' => out += "$A",
+            '\n' => out += "$_",
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 把 bash 的反斜杠转义翻译为 PROMPT 代码; 用户名与主机名写成 `%VAR%`, 在 cmd 启动时展开
+fn escape_codes(escape: &str) -> String {
+    match escape {
+        "u" => "%USERNAME%".to_string(),
+        "h" | "H" => "%COMPUTERNAME%".to_string(),
+        "w" => "$P".to_string(),
+        "t" | "A" => "$T".to_string(),
+        "d" => "$D".to_string(),
+        // Windows 没有 root, 用 cmd 惯用的 `>` 作为提示符号
+        "$" => "$G".to_string(),
+        "s" => "cmd".to_string(),
+        "n" => "$_".to_string(),
+        "e" | "033" => "$E".to_string(),
+        "\\" => "\\".to_string(),
+        _ => String::new(),
+    }
+}
+
+/// 把单个片段翻译为 PROMPT 代码; cmd 不计算提示符宽度, `\[ \]` 中的内容原样输出
+fn codes(segment: &Segment, colors: &[Option<String>]) -> String {
+    match segment {
+        Segment::Text(text) => text_codes(text),
+        Segment::Escape(escape) => escape_codes(escape),
+        Segment::NonPrinting(inner) => prompt::parse_bash(inner).iter().map(|s| codes(s, colors)).collect(),
+        Segment::Color(color) => {
+            let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+            format!("$E{}", rest)
+        }
+        Segment::Variable(name) => match name.strip_prefix("__prompt_changer_color_").and_then(|i| i.parse::<usize>().ok()) {
+            Some(i) => colors.get(i).cloned().flatten().map_or_else(String::new, |sgr| format!("$E[{}m", sgr)),
+            None => format!("%{}%", name),
+        },
+        // cmd 不能在显示提示符时运行命令, lint 已把它报为错误
+        Segment::Command(_) => String::new(),
+    }
+}
+
+/// 提示符翻译为 PROMPT 的值; 颜色变量取深色背景下的真彩色, 标题用以 ST 结束的 OSC 序列写在开头.
+/// 末尾的空格写成 `$S`, 以免在批处理文件或 `prompt` 命令中被去掉
+pub fn translate(prompts: &PromptSet) -> String {
+    let colors: Vec<Option<String>> = prompts
+        .colors
+        .iter()
+        .map(|slot: &ColorSlot| slot.dark.at(ColorDepth::TrueColor).map(str::to_string))
+        .collect();
+    let translate = |prompt: &str| -> String { prompt::parse_bash(prompt).iter().map(|s| codes(s, &colors)).collect() };
+    let value = match prompts.title.as_deref().filter(|t| !t.is_empty()) {
+        Some(title) => format!("$E]0;{}$E\\{}", translate(title), translate(&prompts.default)),
+        None => translate(&prompts.default),
+    };
+    let trimmed = value.trim_end_matches(' ');
+    format!("{}{}", trimmed, "$S".repeat(value.len() - trimmed.len()))
+}
+
+/// 略去的设置: PROMPT 只有一个值, 除了标题外都不支持
+pub fn notices(prompts: &PromptSet) -> Vec<String> {
+    let mut dropped = prompts.unsupported_extras(false);
+    if !prompts.hosts.is_empty() {
+        dropped.insert(0, i18n::tr("[[host]] rules", "[[host]] 规则"));
+    }
+    prompt::left_out_notice("cmd", &dropped).into_iter().collect()
+}
+
+/// 写入记录文件的内容: 第一行记下原始的 bash 风格提示符, 第二行是 PROMPT 的值
+pub fn block_body(prompts: &PromptSet) -> String {
+    format!("{}{}\nPROMPT={}", SOURCE_MARKER, prompts.default, translate(prompts))
+}
+
+/// 独立脚本中设置当前窗口提示符的一行; 批处理文件运行时展开其中的 `%VAR%`
+pub fn script_body(prompts: &PromptSet) -> String {
+    format!("@prompt {}", translate(prompts))
+}
+
+/// 通知正在运行的程序用户环境变量已改变(WM_SETTINGCHANGE), 与 `setx` 的做法相同.
+/// 资源管理器随后启动的 cmd 窗口会用新的 PROMPT; 已经打开的窗口保留旧值
+#[cfg(windows)]
+fn broadcast_environment_change() {
+    #[link(name = "user32")]
+    extern "system" {
+        fn SendMessageTimeoutW(
+            hwnd: isize,
+            msg: u32,
+            wparam: usize,
+            lparam: *const u16,
+            flags: u32,
+            timeout: u32,
+            result: *mut usize,
+        ) -> isize;
+    }
+    const HWND_BROADCAST: isize = 0xffff;
+    const WM_SETTINGCHANGE: u32 = 0x001a;
+    const SMTO_ABORTIFHUNG: u32 = 0x0002;
+    let area: Vec<u16> = "Environment\0".encode_utf16().collect();
+    let mut result = 0;
+    // SAFETY: area 是以 NUL 结尾的 UTF-16 字符串, 在调用期间有效; 无响应的窗口最多等待 5 秒
+    let sent = unsafe { SendMessageTimeoutW(HWND_BROADCAST, WM_SETTINGCHANGE, 0, area.as_ptr(), SMTO_ABORTIFHUNG, 5000, &mut result) };
+    if sent == 0 {
+        crate::logging::debug("cmd", "broadcasting WM_SETTINGCHANGE timed out or failed");
+    }
+}
+
+/// 设置或删除当前用户的 PROMPT 环境变量, 之后广播 WM_SETTINGCHANGE 让新开的 cmd 窗口生效.
+/// 用 `reg` 而不是 `setx` 写入: setx 的值不能超过 1024 个字符, 也不能删除变量;
+/// 这里写成 REG_EXPAND_SZ, 以便 `%USERNAME%` 等在 cmd 启动时展开
+#[cfg(windows)]
+pub fn store(value: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    use crate::logging;
+    use crate::CliError;
+    use std::process::{Command, Stdio};
+
+    let mut cmd = Command::new("reg");
+    match value {
+        Some(value) => cmd.args(["add", r"HKCU\Environment", "/v", "PROMPT", "/t", "REG_EXPAND_SZ", "/d", value, "/f"]),
+        None => cmd.args(["delete", r"HKCU\Environment", "/v", "PROMPT", "/f"]),
+    };
+    logging::debug("cmd", format!("running {:?}", cmd));
+    let out = cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped()).output()?;
+    // 删除时变量本来就不存在也算成功
+    if out.status.success() || value.is_none() {
+        broadcast_environment_change();
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        Err(Box::new(CliError(
+            i18n::tr(&format!("reg could not set PROMPT: {}", stderr.trim_end()), &format!("reg 无法设置 PROMPT: {}", stderr.trim_end())).to_string(),
+        )))
+    }
+}
+
+/// 其他平台上没有 cmd 的环境变量可写, 只更新记录文件
+#[cfg(not(windows))]
+pub fn store(_value: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
@@ -0,0 +1,433 @@
+use crate::CliError;
+use crate::i18n;
+use std::env;
+use std::io::IsTerminal;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// 终端能显示的颜色深度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    None,
+    /// 16 色
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+/// 根据 NO_COLOR、COLORTERM 与 terminfo 判断当前终端支持的颜色深度
+pub fn detect() -> ColorDepth {
+    if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return ColorDepth::None;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    if term.is_empty() || term == "dumb" {
+        return ColorDepth::None;
+    }
+    if matches!(env::var("COLORTERM").as_deref(), Ok("truecolor") | Ok("24bit")) {
+        return ColorDepth::TrueColor;
+    }
+    let colors = Command::new("tput")
+        .arg("colors")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8_lossy(&out.stdout).trim().parse::<i32>().ok());
+    match colors {
+        Some(n) if n >= 256 => ColorDepth::Ansi256,
+        Some(n) if n >= 8 => ColorDepth::Basic,
+        Some(_) => ColorDepth::None,
+        None if term.contains("256color") => ColorDepth::Ansi256,
+        None => ColorDepth::Basic,
+    }
+}
+
+/// 本工具自己的输出何时使用颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 标准输出是终端时
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// 命令行中可选的颜色模式
+    pub const NAMES: [&'static str; 3] = ["auto", "always", "never"];
+
+    /// 从命令行参数解析颜色模式
+    pub fn from_name(name: &str) -> Result<ColorMode, CliError> {
+        match name {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            other => Err(CliError(i18n::tr(&format!("Unknown color mode: {}", other), &format!("未知的颜色模式: {}", other)).to_string())),
+        }
+    }
+}
+
+/// 用户用 `--color` 或配置文件指定的颜色模式
+static COLOR_MODE: OnceLock<ColorMode> = OnceLock::new();
+
+/// 设置颜色模式
+pub fn set_color_mode(mode: ColorMode) -> Result<(), CliError> {
+    COLOR_MODE.set(mode).map_err(|_| CliError(i18n::tr("Color mode is already set", "颜色模式已经设置过了").to_string()))
+}
+
+/// 本工具自己的输出可用的颜色深度, 默认在标准输出不是终端时不使用颜色
+pub fn output_depth() -> ColorDepth {
+    static DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+    *DEPTH.get_or_init(|| match COLOR_MODE.get().copied().unwrap_or(ColorMode::Auto) {
+        ColorMode::Never => ColorDepth::None,
+        // 强制使用颜色时至少按 16 色输出
+        ColorMode::Always => detect().max(ColorDepth::Basic),
+        ColorMode::Auto if std::io::stdout().is_terminal() => detect(),
+        ColorMode::Auto => ColorDepth::None,
+    })
+}
+
+/// 用 SGR 参数给输出的文本上色, 不支持颜色时原样返回
+pub fn paint(text: &str, sgr: &str) -> String {
+    if output_depth() == ColorDepth::None {
+        text.to_string()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", sgr, text)
+    }
+}
+
+/// 同一颜色在各颜色深度下的 SGR 参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fallback {
+    pub truecolor: String,
+    pub ansi256: String,
+    pub basic: String,
+}
+
+impl Fallback {
+    /// 在任何颜色深度下都相同的颜色
+    pub fn fixed(sgr: &str) -> Fallback {
+        Fallback { truecolor: sgr.to_string(), ansi256: sgr.to_string(), basic: sgr.to_string() }
+    }
+
+    /// 该颜色深度下使用的 SGR 参数, 不使用颜色时为 None
+    pub fn at(&self, depth: ColorDepth) -> Option<&str> {
+        match depth {
+            ColorDepth::TrueColor => Some(&self.truecolor),
+            ColorDepth::Ansi256 => Some(&self.ansi256),
+            ColorDepth::Basic => Some(&self.basic),
+            ColorDepth::None => None,
+        }
+    }
+}
+
+/// 提示符中一个颜色变量的取值: 深色与浅色背景下各自的颜色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorSlot {
+    pub dark: Fallback,
+    pub light: Fallback,
+}
+
+/// 生成的代码中各颜色深度对应的 case 分支
+const DEPTH_ARMS: [(&str, ColorDepth); 4] = [
+    ("24", ColorDepth::TrueColor),
+    ("8", ColorDepth::Ansi256),
+    ("4", ColorDepth::Basic),
+    ("*", ColorDepth::None),
+];
+
+/// 16 色在 xterm 中的 RGB 值
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// 256 色调色板中某个颜色的 RGB 值
+pub fn palette_rgb(n: u8) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match n {
+        0..=15 => BASIC_RGB[n as usize],
+        16..=231 => {
+            let i = n as usize - 16;
+            (LEVELS[i / 36], LEVELS[i / 6 % 6], LEVELS[i % 6])
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// 调色板 `range` 中与 rgb 最接近的颜色
+fn nearest(rgb: (u8, u8, u8), range: std::ops::RangeInclusive<u8>) -> u8 {
+    let distance = |n: &u8| {
+        let (r, g, b) = palette_rgb(*n);
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+    };
+    range.min_by_key(distance).unwrap_or(0)
+}
+
+/// 16 色中第 n 个颜色的 SGR 参数, `background` 时是背景色
+fn basic_sgr(n: u8, background: bool) -> String {
+    let offset = if background { 10 } else { 0 };
+    if n < 8 {
+        (30 + offset + n).to_string()
+    } else {
+        (82 + offset + n).to_string()
+    }
+}
+
+/// 由前景色或背景色的 SGR 参数(`31`、`38;5;n`、`48;2;r;g;b` 等)算出各颜色深度下最接近的颜色
+pub fn fallback(sgr: &str) -> Fallback {
+    let parts: Vec<u8> = sgr.split(';').filter_map(|p| p.parse().ok()).collect();
+    let (ansi256, basic) = match parts.as_slice() {
+        [kind @ (38 | 48), 2, r, g, b] => {
+            let n = nearest((*r, *g, *b), 16..=255);
+            (format!("{};5;{}", kind, n), basic_sgr(nearest((*r, *g, *b), 0..=15), *kind == 48))
+        }
+        [kind @ (38 | 48), 5, n] if *n < 16 => (sgr.to_string(), basic_sgr(*n, *kind == 48)),
+        [kind @ (38 | 48), 5, n] => (sgr.to_string(), basic_sgr(nearest(palette_rgb(*n), 0..=15), *kind == 48)),
+        _ => (sgr.to_string(), sgr.to_string()),
+    };
+    Fallback { truecolor: sgr.to_string(), ansi256, basic }
+}
+
+/// 把前景色的 SGR 参数换成同一颜色的背景色
+pub fn background_sgr(sgr: &str) -> String {
+    match sgr.split_once(';') {
+        Some(("38", rest)) => format!("48;{}", rest),
+        _ => match sgr.parse::<u8>() {
+            Ok(n @ (30..=37 | 90..=97)) => (n + 10).to_string(),
+            _ => sgr.to_string(),
+        },
+    }
+}
+
+/// 前景色或背景色的 SGR 参数对应的 RGB 值(按 xterm 的调色板), 默认色等没有固定值时为 None
+pub fn sgr_rgb(sgr: &str) -> Option<(u8, u8, u8)> {
+    let parts: Vec<u8> = sgr.split(';').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [38 | 48, 2, r, g, b] => Some((*r, *g, *b)),
+        [38 | 48, 5, n] => Some(palette_rgb(*n)),
+        [n @ (30..=37 | 40..=47)] => Some(BASIC_RGB[(n % 10) as usize]),
+        [n @ (90..=97 | 100..=107)] => Some(BASIC_RGB[8 + (n % 10) as usize]),
+        _ => None,
+    }
+}
+
+/// 依次经过各个颜色的渐变, 均匀取 `count` 个颜色, 首尾是第一个与最后一个颜色
+pub fn gradient(stops: &[(u8, u8, u8)], count: usize) -> Vec<(u8, u8, u8)> {
+    let spans = stops.len().saturating_sub(1);
+    (0..count)
+        .map(|i| {
+            if spans == 0 || count < 2 {
+                return stops.first().copied().unwrap_or_default();
+            }
+            let position = i as f64 / (count - 1) as f64 * spans as f64;
+            let span = (position.floor() as usize).min(spans - 1);
+            let t = position - span as f64;
+            let (from, to) = (stops[span], stops[span + 1]);
+            let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+            (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+        })
+        .collect()
+}
+
+/// 两种颜色的对比度, 按 WCAG 的相对亮度计算, 范围 1-21
+pub fn contrast(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let luminance = |(r, g, b): (u8, u8, u8)| {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+    };
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// 提示符中引用第 i 个回退颜色的变量名
+pub fn color_var(i: usize) -> String {
+    format!("__prompt_changer_color_{}", i)
+}
+
+/// 浅色、深色配色不同时, 先按 PROMPT_CHANGER_APPEARANCE 或 COLORFGBG 中的背景色判断终端是否是浅色背景
+const BASH_APPEARANCE: &str = "__prompt_changer_appearance=$PROMPT_CHANGER_APPEARANCE
+if [ -z \"$__prompt_changer_appearance\" ]; then
+    case \"${COLORFGBG##*;}\" in
+        7|15) __prompt_changer_appearance=light ;;
+        *) __prompt_changer_appearance=dark ;;
+    esac
+fi
+";
+
+/// tcsh 中对应 [`BASH_APPEARANCE`] 的代码; csh 的条件不会短路, 未设置的变量要先用 `$?` 判断
+const TCSH_APPEARANCE: &str = "set __prompt_changer_appearance = dark
+if ( $?PROMPT_CHANGER_APPEARANCE ) then
+    set __prompt_changer_appearance = \"$PROMPT_CHANGER_APPEARANCE\"
+else if ( $?COLORFGBG ) then
+    if ( \"$COLORFGBG\" =~ \"*;7\" || \"$COLORFGBG\" =~ \"*;15\" ) set __prompt_changer_appearance = light
+endif
+";
+
+/// fish 中对应 [`BASH_APPEARANCE`] 的代码
+const FISH_APPEARANCE: &str = "
+set -g __prompt_changer_appearance \"$PROMPT_CHANGER_APPEARANCE\"
+if test -z \"$__prompt_changer_appearance\"
+    switch (string split ';' -- \"$COLORFGBG\")[-1]
+        case 7 15
+            set __prompt_changer_appearance light
+        case '*'
+            set __prompt_changer_appearance dark
+    end
+end";
+
+/// 生成设置颜色变量的 case 分支: 分支标签与各变量的取值, 只有一种配色时不区分深浅
+pub fn setup_arms(colors: &[ColorSlot]) -> Vec<(String, Vec<Option<&str>>)> {
+    let themed = colors.iter().any(|c| c.dark != c.light);
+    let mut arms = Vec::new();
+    for (arm, depth) in DEPTH_ARMS {
+        if themed && depth != ColorDepth::None {
+            arms.push((format!("{}:light", arm), colors.iter().map(|c| c.light.at(depth)).collect()));
+            arms.push((format!("{}:*", arm), colors.iter().map(|c| c.dark.at(depth)).collect()));
+        } else {
+            arms.push((arm.to_string(), colors.iter().map(|c| c.dark.at(depth)).collect()));
+        }
+    }
+    arms
+}
+
+/// bash 中按终端能力与背景设置颜色变量的代码, NO_COLOR 时变量为空; `compat` 时在 screen 与 mosh 中不用真彩色
+pub fn bash_setup(colors: &[ColorSlot], compat: bool) -> String {
+    sh_setup(colors, compat, |sgr| format!("$'\\e[{}m'", sgr))
+}
+
+/// 没有 `$'...'` 的 shell 中对应 [`bash_setup`] 的代码, ESC 取自之前定义的 `__prompt_changer_esc` 变量
+pub fn posix_setup(colors: &[ColorSlot], compat: bool) -> String {
+    sh_setup(colors, compat, |sgr| format!("\"${{__prompt_changer_esc}}[{}m\"", sgr))
+}
+
+/// sh 系 shell 中设置颜色变量的代码, `render` 把 SGR 参数写成赋值的右侧
+fn sh_setup(colors: &[ColorSlot], compat: bool, render: impl Fn(&str) -> String) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let themed = colors.iter().any(|c| c.dark != c.light);
+    let mut code = if themed { BASH_APPEARANCE.to_string() } else { String::new() };
+    code += "if [ -n \"$NO_COLOR\" ]; then\n    __prompt_changer_depth=0\n";
+    if compat {
+        code += "elif [ -z \"$__prompt_changer_compat\" ] && { [ \"$COLORTERM\" = truecolor ] || [ \"$COLORTERM\" = 24bit ]; }; then\n    __prompt_changer_depth=24\n";
+    } else {
+        code += "elif [ \"$COLORTERM\" = truecolor ] || [ \"$COLORTERM\" = 24bit ]; then\n    __prompt_changer_depth=24\n";
+    }
+    code += "elif [ \"$(tput colors 2>/dev/null || echo 8)\" -ge 256 ] 2>/dev/null; then\n    __prompt_changer_depth=8\n";
+    code += "else\n    __prompt_changer_depth=4\nfi\n";
+    code += if themed {
+        "case $__prompt_changer_depth:$__prompt_changer_appearance in"
+    } else {
+        "case $__prompt_changer_depth in"
+    };
+    for (arm, values) in setup_arms(colors) {
+        code += &format!("\n    {})", arm);
+        for (i, value) in values.into_iter().enumerate() {
+            match value {
+                Some(sgr) => code += &format!("\n        {}={}", color_var(i), render(sgr)),
+                None => code += &format!("\n        {}=", color_var(i)),
+            }
+        }
+        code += "\n        ;;";
+    }
+    code += "\nesac\n";
+    code
+}
+
+/// fish 中对应 [`bash_setup`] 的代码
+pub fn fish_setup(colors: &[ColorSlot], compat: bool) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let themed = colors.iter().any(|c| c.dark != c.light);
+    let mut code = if themed { FISH_APPEARANCE.to_string() } else { String::new() };
+    code += "\nif test -n \"$NO_COLOR\"\n    set -g __prompt_changer_depth 0\n";
+    if compat {
+        code += "else if test -z \"$__prompt_changer_compat\"; and contains -- \"$COLORTERM\" truecolor 24bit\n    set -g __prompt_changer_depth 24\n";
+    } else {
+        code += "else if contains -- \"$COLORTERM\" truecolor 24bit\n    set -g __prompt_changer_depth 24\n";
+    }
+    code += "else if test (tput colors 2>/dev/null; or echo 8) -ge 256 2>/dev/null\n    set -g __prompt_changer_depth 8\n";
+    code += "else\n    set -g __prompt_changer_depth 4\nend\n";
+    code += if themed {
+        "switch $__prompt_changer_depth:$__prompt_changer_appearance"
+    } else {
+        "switch $__prompt_changer_depth"
+    };
+    for (arm, values) in setup_arms(colors) {
+        code += &format!("\n    case '{}'", arm);
+        for (i, value) in values.into_iter().enumerate() {
+            match value {
+                Some(sgr) => code += &format!("\n        set -g {} \\e'[{}m'", color_var(i), sgr),
+                None => code += &format!("\n        set -g {} ''", color_var(i)),
+            }
+        }
+    }
+    code += "\nend\n";
+    code
+}
+
+/// tcsh 中对应 [`bash_setup`] 的代码, ESC 取自之前定义的 `__prompt_changer_esc` 变量; case 标签中的 `:` 换成 `-`
+pub fn tcsh_setup(colors: &[ColorSlot]) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let themed = colors.iter().any(|c| c.dark != c.light);
+    let mut code = if themed { TCSH_APPEARANCE.to_string() } else { String::new() };
+    code += "set __prompt_changer_depth = 4
+";
+    code += "if ( `sh -c 'tput colors 2>/dev/null || echo 8'` >= 256 ) set __prompt_changer_depth = 8
+";
+    code += "if ( $?COLORTERM ) then
+    if ( \"$COLORTERM\" == truecolor || \"$COLORTERM\" == 24bit ) set __prompt_changer_depth = 24
+endif
+";
+    code += "if ( $?NO_COLOR ) set __prompt_changer_depth = 0
+";
+    code += if themed {
+        "switch ( \"${__prompt_changer_depth}-${__prompt_changer_appearance}\" )"
+    } else {
+        "switch ( \"$__prompt_changer_depth\" )"
+    };
+    for (arm, values) in setup_arms(colors) {
+        if arm == "*" {
+            code += "\n    default:";
+        } else {
+            code += &format!("\n    case {}:", arm.replace(':', "-"));
+        }
+        for (i, value) in values.into_iter().enumerate() {
+            match value {
+                Some(sgr) => code += &format!("\n        set {} = \"${{__prompt_changer_esc}}[{}m\"", color_var(i), sgr),
+                None => code += &format!("\n        set {} = \"\"", color_var(i)),
+            }
+        }
+        code += "\n        breaksw";
+    }
+    code += "\nendsw\n";
+    code
+}
@@ -0,0 +1,97 @@
+use crate::i18n;
+use crate::themes;
+use crate::CliError;
+use clap::Command;
+use clap_complete::Shell;
+use std::str::FromStr;
+
+/// 可生成补全脚本的 shell
+pub const SHELLS: [&str; 5] = ["bash", "elvish", "fish", "zsh", "powershell"];
+
+/// 补全 `--spec` 时列出的主题名称: 内置的与已安装的; 之后安装的主题要重新生成脚本才会出现
+fn theme_names() -> Vec<String> {
+    themes::all_themes().into_iter().map(|(name, _, _)| name).collect()
+}
+
+/// 在 clap_complete 生成的脚本中让 `--spec` 也补全主题名称; Elvish 与 PowerShell 的脚本不补全选项的值, 保持原样
+fn add_theme_names(shell: Shell, script: String, names: &[String]) -> String {
+    let words = names.join(" ");
+    match shell {
+        Shell::Bash => script.replace(
+            "--spec)\n                    COMPREPLY=($(compgen -f \"${cur}\"))",
+            &format!("--spec)\n                    COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\") $(compgen -f \"${{cur}}\"))", words),
+        ),
+        Shell::Fish => script
+            .lines()
+            .map(|line| if line.contains(" -l spec ") { format!("{} -a \"{}\"\n", line, words) } else { format!("{}\n", line) })
+            .collect(),
+        Shell::Zsh => script
+            .lines()
+            .map(|line| {
+                let action = format!(":_alternative \"themes\\:theme\\:({})\" \"files\\:file\\:_files\"'", words);
+                match line.strip_suffix(":_files' \\") {
+                    Some(start) if line.starts_with("'--spec=") => format!("{}{} \\\n", start, action),
+                    _ => format!("{}\n", line),
+                }
+            })
+            .collect(),
+        _ => script,
+    }
+}
+
+/// 用 clap_complete 按命令行的定义生成 `shell` 的补全脚本
+pub fn generate(shell: &str, command: &Command<'static>) -> Result<String, CliError> {
+    let generator = Shell::from_str(shell).map_err(|_| {
+        CliError(
+            i18n::tr(
+                &format!("Cannot generate completions for {}; choose one of {}", shell, SHELLS.join(", ")),
+                &format!("无法为 {} 生成补全脚本; 可选 {}", shell, SHELLS.join(", ")),
+            )
+            .to_string(),
+        )
+    })?;
+    let mut command = command.clone();
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(generator, &mut command, name, &mut script);
+    Ok(add_theme_names(generator, String::from_utf8_lossy(&script).into_owned(), &theme_names()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 生成脚本, 主题名称固定为 `lean` 与 `my-theme`
+    fn script(shell: Shell) -> String {
+        let mut command = crate::cli();
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut command, "prompt-changer", &mut script);
+        add_theme_names(shell, String::from_utf8(script).unwrap(), &["lean".to_string(), "my-theme".to_string()])
+    }
+
+    #[test]
+    fn spec_completes_theme_names_and_files() {
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("--spec)\n                    COMPREPLY=($(compgen -W \"lean my-theme\" -- \"${cur}\") $(compgen -f \"${cur}\"))"));
+        assert!(!bash.contains("--spec)\n                    COMPREPLY=($(compgen -f"));
+
+        let fish = script(Shell::Fish);
+        let spec: Vec<&str> = fish.lines().filter(|line| line.contains(" -l spec ")).collect();
+        assert!(!spec.is_empty());
+        assert!(spec.iter().all(|line| line.ends_with("-r -F -a \"lean my-theme\"")));
+
+        let zsh = script(Shell::Zsh);
+        let spec: Vec<&str> = zsh.lines().filter(|line| line.starts_with("'--spec=")).collect();
+        assert!(!spec.is_empty());
+        assert!(spec.iter().all(|line| line.contains(":_alternative \"themes\\:theme\\:(lean my-theme)\" \"files\\:file\\:_files\"'")));
+        assert_eq!(zsh.matches("_alternative").count(), spec.len());
+    }
+
+    #[test]
+    fn every_shell_generates_a_script() {
+        for name in SHELLS {
+            assert!(generate(name, &crate::cli()).unwrap().contains("prompt-changer"), "{}", name);
+        }
+        assert!(generate("tcsh", &crate::cli()).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::color::ColorMode;
+use crate::i18n::{self, Lang};
+use crate::logging;
+use crate::output::{self, Json};
+use crate::rc;
+use crate::shell::{self, InstallMode, Shell};
+use crate::suggest;
+use crate::CliError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// 配置文件中的默认值, 命令行参数优先
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// 未指定 `--shell` 时使用的 shell
+    pub shell: Option<String>,
+    /// `set` 未指定 `--spec` 也不交互时使用的描述文件
+    pub spec: Option<PathBuf>,
+    /// 改写 rc 文件前是否先复制一份 `.bak`
+    pub backup: Option<bool>,
+    pub install_mode: Option<String>,
+    pub lang: Option<String>,
+    /// 本工具自己的输出是否使用颜色
+    pub color: Option<String>,
+    /// `themes install NAME` 查找主题的索引或仓库地址
+    pub theme_index: Option<String>,
+    /// 写入新提示符之前运行的命令, 失败时不做改动
+    pub pre_apply: Option<String>,
+    /// 新提示符写入成功后运行的命令, 如 `tmux source-file ~/.tmux.conf`
+    pub post_apply: Option<String>,
+    /// 是否默认使用无障碍模式
+    pub accessible: Option<bool>,
+    /// 改写其中源文件的 chezmoi 或 stow dotfiles 仓库
+    pub dotfiles_dir: Option<PathBuf>,
+    /// 各描述文件共用的颜色槽位, 如 `accent = "#5f87ff"`; 描述文件的 `[slots]` 优先
+    pub slots: BTreeMap<String, String>,
+}
+
+/// 可设置的键及其说明
+pub const KEYS: [(&str, &str); 11] = [
+    ("shell", "shell used when --shell is not given (bash, fish, powershell, ksh, tcsh, posix, elvish, xonsh, oils or cmd)"),
+    ("spec", "spec file `set` applies instead of asking, when --spec is not given"),
+    ("backup", "copy the rc file to <file>.bak before changing it (true or false)"),
+    ("install-mode", "how to install the prompt when --install-mode is not given (append or drop-in)"),
+    ("lang", "language of messages when --lang is not given (en or zh)"),
+    ("color", "color this tool's own output (auto, always or never)"),
+    ("theme-index", "theme index or git repository `themes install NAME` looks names up in"),
+    ("pre-apply", "command run before a new prompt is written; if it fails nothing is changed"),
+    ("post-apply", "command run after a new prompt is written, e.g. to reload tmux"),
+    ("accessible", "prompts that do not rely on color alone, and plain interactive output (true or false)"),
+    ("dotfiles-dir", "chezmoi or stow dotfiles directory whose source files are written instead of the live ones"),
+];
+
+/// 配置文件的位置
+pub fn config_path() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".config/prompt-changer/config.toml"))
+}
+
+/// 检查键名
+fn check_key(key: &str) -> Result<(), CliError> {
+    if KEYS.iter().any(|(k, _)| *k == key) {
+        Ok(())
+    } else {
+        let keys: Vec<&str> = KEYS.iter().map(|(key, _)| *key).collect();
+        match suggest::closest(key, keys.iter().copied()) {
+            Some(close) => Err(CliError(
+                i18n::tr(
+                    &format!("Unknown config key `{}`, did you mean `{}`?", key, close),
+                    &format!("未知的配置键 `{}`, 是不是 `{}`?", key, close),
+                )
+                .to_string(),
+            )),
+            None => Err(CliError(
+                i18n::tr(
+                    &format!("Unknown config key `{}`; use one of {}", key, keys.join(", ")),
+                    &format!("未知的配置键 `{}`; 可用 {}", key, keys.join(", ")),
+                )
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+/// 检查一个键的值, 返回写入配置文件的 TOML 值
+fn parse_value(key: &str, value: &str) -> Result<toml::Value, CliError> {
+    check_key(key)?;
+    match key {
+        "shell" => Shell::from_name(value).map(|_| value.into()),
+        "spec" | "dotfiles-dir" => Ok(value.into()),
+        "backup" | "accessible" => value
+            .parse::<bool>()
+            .map(toml::Value::Boolean)
+            .map_err(|_| CliError(
+                i18n::tr(
+                    &format!("{} must be true or false, not `{}`", key, value),
+                    &format!("{} 必须是 true 或 false, 而不是 `{}`", key, value),
+                )
+                .to_string(),
+            )),
+        "install-mode" => InstallMode::from_name(value).map(|_| value.into()),
+        "lang" => Lang::from_name(value).map(|_| value.into()),
+        "theme-index" if value.contains("://") || value.starts_with("git@") => Ok(value.into()),
+        "pre-apply" | "post-apply" => Ok(value.into()),
+        "theme-index" => Err(CliError(
+            i18n::tr(
+                &format!("theme-index must be an address such as https://…/index.toml, not `{}`", value),
+                &format!("theme-index 必须是 https://…/index.toml 这样的地址, 而不是 `{}`", value),
+            )
+            .to_string(),
+        )),
+        _ => ColorMode::from_name(value).map(|_| value.into()),
+    }
+}
+
+/// 环境变量 `PROMPT_CHANGER_<NAME>` 的值, 空值视为未设置
+pub fn env_value(name: &str) -> Option<String> {
+    env::var(format!("PROMPT_CHANGER_{}", name)).ok().filter(|value| !value.is_empty())
+}
+
+/// 开关类环境变量是否打开: 设置了且不是 0、false 或 no
+pub fn env_flag(name: &str) -> bool {
+    env_value(name).is_some_and(|value| !matches!(value.to_ascii_lowercase().as_str(), "0" | "false" | "no"))
+}
+
+impl Config {
+    /// 用 `PROMPT_CHANGER_SHELL` 等环境变量覆盖配置文件中的值
+    fn apply_env(&mut self) -> Result<(), CliError> {
+        let fields = [
+            ("shell", &mut self.shell),
+            ("install-mode", &mut self.install_mode),
+            ("lang", &mut self.lang),
+            ("color", &mut self.color),
+        ];
+        for (key, field) in fields {
+            let name = key.to_ascii_uppercase().replace('-', "_");
+            if let Some(value) = env_value(&name) {
+                parse_value(key, &value)
+                    .map_err(|err| CliError(
+                        i18n::tr(
+                            &format!("{} (in PROMPT_CHANGER_{})", err, name),
+                            &format!("{} (在 PROMPT_CHANGER_{} 中)", err, name),
+                        )
+                        .to_string(),
+                    ))?;
+                *field = Some(value);
+            }
+        }
+        if let Some(spec) = env_value("SPEC") {
+            self.spec = Some(PathBuf::from(spec));
+        }
+        if let Some(dir) = env_value("DOTFILES_DIR") {
+            self.dotfiles_dir = Some(PathBuf::from(dir));
+        }
+        if env_value("BACKUP").is_some() {
+            self.backup = Some(env_flag("BACKUP"));
+        }
+        if env_flag("NO_BACKUP") {
+            self.backup = Some(false);
+        }
+        if env_value("ACCESSIBLE").is_some() {
+            self.accessible = Some(env_flag("ACCESSIBLE"));
+        }
+        Ok(())
+    }
+}
+
+/// 读取的配置, 启动时读取一次
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// 读取并检查配置文件, 再用环境变量覆盖, 都没有时使用内置默认值; 须在设置 config root 之后调用
+pub fn init() -> Result<(), CliError> {
+    let path = config_path()?;
+    let mut config = match fs::read_to_string(&path) {
+        Ok(content) => {
+            let config: Config =
+                toml::from_str(&content).map_err(|err| {
+                    let message = i18n::tr(&format!("Invalid config {}: {}", path.display(), err), &format!("配置 {} 无效: {}", path.display(), err)).to_string();
+                    CliError(suggest::unknown_field(&message))
+                })?;
+            let values = [
+                ("shell", config.shell.as_deref()),
+                ("install-mode", config.install_mode.as_deref()),
+                ("lang", config.lang.as_deref()),
+                ("color", config.color.as_deref()),
+            ];
+            for (key, value) in values {
+                if let Some(value) = value {
+                    parse_value(key, value)
+                        .map_err(|err| CliError(
+                            i18n::tr(
+                                &format!("{} (in {})", err, path.display()),
+                                &format!("{} (在 {} 中)", err, path.display()),
+                            )
+                            .to_string(),
+                        ))?;
+                }
+            }
+            config
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Config::default(),
+        Err(err) => {
+            return Err(CliError(
+                i18n::tr(
+                    &format!("Cannot read config {}: {}", path.display(), err),
+                    &format!("无法读取配置 {}: {}", path.display(), err),
+                )
+                .to_string(),
+            ))
+        }
+    };
+    config.apply_env()?;
+    CONFIG.set(config).map_err(|_| CliError(i18n::tr("Config is already loaded", "配置已经读取过了").to_string()))
+}
+
+/// 当前的配置
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// 读取配置文件中的原始表, 文件不存在时为空表
+fn read_table() -> Result<toml::Table, Box<dyn std::error::Error>> {
+    let path = config_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(content
+            .parse::<toml::Table>()
+            .map_err(|err| CliError(
+                i18n::tr(
+                    &format!("Invalid config {}: {}", path.display(), err),
+                    &format!("配置 {} 无效: {}", path.display(), err),
+                )
+                .to_string(),
+            ))?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+/// 打印一个键的值, 未指定键时打印全部
+pub fn run_get(key: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let table = read_table()?;
+    let keys: Vec<&str> = match key {
+        Some(key) => {
+            check_key(key)?;
+            vec![key]
+        }
+        None => KEYS.iter().map(|(key, _)| *key).collect(),
+    };
+    let value = |key: &str| table.get(key).map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_string));
+
+    if output::is_json() {
+        let fields = keys.iter().map(|key| Json::Object(vec![("key", (*key).into()), ("value", value(key).into())])).collect();
+        output::print("config get", vec![("file", config_path()?.display().to_string().into()), ("values", Json::Array(fields))]);
+    } else if let Some(key) = key {
+        if let Some(value) = value(key) {
+            println!("{}", value);
+        }
+    } else {
+        for (key, about) in KEYS {
+            let shown = value(key).unwrap_or_else(|| i18n::tr("(not set)", "(未设置)").to_string());
+            println!("{:<14} {:<16} # {}", key, shown, about);
+        }
+        // [slots] 表只能手工编辑
+        for (slot, color) in table.get("slots").and_then(|s| s.as_table()).into_iter().flatten() {
+            let shown = color.as_str().map_or_else(|| color.to_string(), str::to_string);
+            println!("{:<14} {:<16} # {}", format!("slots.{}", slot), shown, i18n::tr("color slot shared by specs", "各描述文件共用的颜色槽位"));
+        }
+    }
+    Ok(())
+}
+
+/// 设置一个键并写回配置文件; 值为空时删除该键
+pub fn run_set(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table = read_table()?;
+    if value.is_empty() {
+        check_key(key)?;
+        table.remove(key);
+    } else {
+        table.insert(key.to_string(), parse_value(key, value)?);
+    }
+    let path = config_path()?;
+    if let Some(dir) = path.parent() {
+        rc::create_dirs(dir)?;
+    }
+    fs::write(&path, toml::to_string(&table)?)?;
+    rc::own_like_parent(&path);
+    if output::is_json() {
+        output::print(
+            "config set",
+            vec![("file", path.display().to_string().into()), ("key", key.into()), ("value", Some(value).filter(|v| !v.is_empty()).into())],
+        );
+    } else if value.is_empty() {
+        logging::info(i18n::tr(&format!("Removed {} from {}", key, path.display()), &format!("已从 {} 中删除 {}", path.display(), key)));
+    } else {
+        logging::info(i18n::tr(&format!("Set {} = {} in {}", key, value, path.display()), &format!("已在 {} 中设置 {} = {}", path.display(), key, value)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_description_lists_every_shell() {
+        let about = KEYS.iter().find(|(key, _)| *key == "shell").map(|(_, about)| *about).unwrap();
+        for name in Shell::NAMES {
+            assert!(about.contains(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::i18n;
+use crate::logging;
+use crate::output;
+use crate::prompt::PromptSet;
+use crate::rc;
+use crate::shell::{self, Shell};
+use crate::CliError;
+use regex::Regex;
+use std::io::{stdin, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+/// 会覆盖或干扰写入的提示符的框架, 以及识别其初始化行的正则
+const FRAMEWORKS: [(&str, &str); 6] = [
+    ("oh-my-zsh", r"oh-my-zsh\.sh"),
+    ("oh-my-bash", r"oh-my-bash\.sh"),
+    ("Starship", r"starship\s+init"),
+    ("powerlevel10k", r"powerlevel10k|p10k\.zsh"),
+    ("bash-git-prompt", r"gitprompt\.sh"),
+    ("oh-my-posh", r"oh-my-posh\s+init"),
+];
+
+/// 被注释掉的行末尾加上的说明, 便于用户找回
+const DISABLED_NOTE: &str = "# disabled by prompt-changer";
+
+/// rc 文件中一个框架的初始化行
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub framework: &'static str,
+    pub path: PathBuf,
+    /// 行号, 从 1 开始
+    pub line: usize,
+    pub text: String,
+}
+
+/// 启动时读取、可能含有框架初始化行的文件
+fn startup_files(shell: Shell) -> Result<Vec<PathBuf>, CliError> {
+    match shell {
+        Shell::Bash | Shell::Pwsh | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => Ok(vec![shell.config_path()?]),
+        Shell::Fish => Shell::fish_config_sources(),
+    }
+}
+
+/// 找出受管理区块以外、未被注释的框架初始化行
+pub fn find(shell: Shell) -> Result<Vec<Conflict>, Box<dyn std::error::Error>> {
+    let patterns: Vec<(&str, Regex)> = FRAMEWORKS
+        .iter()
+        .map(|(name, pattern)| Regex::new(pattern).map(|re| (*name, re)))
+        .collect::<Result<_, _>>()?;
+    let mut conflicts = Vec::new();
+    for path in startup_files(shell)? {
+        let content = match rc::read_text(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let block = rc::find_block(&content);
+        for (n, text) in content.lines().enumerate() {
+            if block.is_some_and(|b| b.contains(n)) || text.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some((framework, _)) = patterns.iter().find(|(_, re)| re.is_match(text)) {
+                conflicts.push(Conflict { framework, path: path.clone(), line: n + 1, text: text.to_string() });
+            }
+        }
+    }
+    logging::debug("conflicts", format!("found {} framework init line(s)", conflicts.len()));
+    Ok(conflicts)
+}
+
+/// .bashrc 中受管理区块之后整个替换 PROMPT_COMMAND 的赋值, 它们会丢掉本工具加入的钩子;
+/// 还没有区块时区块会追加到末尾, 不受影响
+fn prompt_command_overrides() -> Result<Vec<Conflict>, Box<dyn std::error::Error>> {
+    let path = Shell::Bash.config_path()?;
+    let content = match rc::read_text(&path) {
+        Ok(content) => content,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let Some(block) = rc::find_block(&content) else {
+        return Ok(Vec::new());
+    };
+    let assignment = Regex::new(r"^\s*(?:export\s+)?PROMPT_COMMAND=(.*)$")?;
+    Ok(content
+        .lines()
+        .enumerate()
+        .skip(block.end + 1)
+        .filter(|(_, text)| assignment.captures(text).is_some_and(|caps| !caps[1].contains("PROMPT_COMMAND")))
+        .map(|(n, text)| Conflict { framework: "PROMPT_COMMAND", path: path.clone(), line: n + 1, text: text.to_string() })
+        .collect())
+}
+
+/// 注释掉这些行; 行的内容已变化时放弃
+pub fn comment_out(conflicts: &[Conflict]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths: Vec<&PathBuf> = conflicts.iter().map(|c| &c.path).collect();
+    paths.dedup();
+    for path in paths {
+        rc::modify(path, false, |content| {
+            let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+            for conflict in conflicts.iter().filter(|c| &c.path == path) {
+                let line = lines.get_mut(conflict.line - 1).filter(|line| **line == conflict.text).ok_or_else(|| {
+                    CliError(i18n::tr(
+                        &format!("{}:{} changed since it was checked; nothing was commented out", path.display(), conflict.line),
+                        &format!("{}:{} 在检查后被改动了; 没有注释掉任何内容", path.display(), conflict.line),
+                    ).to_string())
+                })?;
+                *line = format!("# {}  {}", line, DISABLED_NOTE);
+            }
+            let mut updated = lines.join("\n");
+            updated.push('\n');
+            Ok(Some(updated))
+        })?;
+    }
+    Ok(())
+}
+
+/// 是否交互式地确认注释掉初始化行
+fn confirm() -> std::io::Result<bool> {
+    print!("{} ", i18n::tr("Comment out these lines? [y/N]", "是否注释掉这些行? [y/N]"));
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_ascii_lowercase().as_str(), "y" | "yes" | "是"))
+}
+
+/// 写入前检查框架冲突与会丢掉钩子的 PROMPT_COMMAND 赋值并给出警告;
+/// `disable` 为真, 或在终端中确认后, 注释掉框架的初始化行
+pub fn check(shell: Shell, prompts: &PromptSet, disable: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if shell == Shell::Bash && shell::bash_block_body(prompts).contains("PROMPT_COMMAND") {
+        for conflict in prompt_command_overrides()? {
+            i18n::warning(i18n::tr(
+                &format!(
+                    "{}:{}: this replaces PROMPT_COMMAND after the prompt-changer block and drops the prompt's hooks: {}; \
+                     add to it instead, e.g. PROMPT_COMMAND+=$'\\n'your_command",
+                    conflict.path.display(),
+                    conflict.line,
+                    conflict.text.trim()
+                ),
+                &format!(
+                    "{}:{}: 这一行在 prompt-changer 区块之后整个替换了 PROMPT_COMMAND, 会丢掉提示符的钩子: {}; \
+                     请改为追加, 例如 PROMPT_COMMAND+=$'\\n'your_command",
+                    conflict.path.display(),
+                    conflict.line,
+                    conflict.text.trim()
+                ),
+            ));
+        }
+    }
+    let conflicts = find(shell)?;
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    for conflict in &conflicts {
+        i18n::warning(i18n::tr(
+            &format!(
+                "{}:{}: {} sets its own prompt and will override or fight with the one written here: {}",
+                conflict.path.display(),
+                conflict.line,
+                conflict.framework,
+                conflict.text.trim()
+            ),
+            &format!(
+                "{}:{}: {} 会设置自己的提示符, 覆盖或干扰这里写入的提示符: {}",
+                conflict.path.display(),
+                conflict.line,
+                conflict.framework,
+                conflict.text.trim()
+            ),
+        ));
+    }
+    let interactive = !output::is_json() && stdin().is_terminal();
+    if disable || (interactive && confirm()?) {
+        comment_out(&conflicts)?;
+        output::notice(i18n::tr(
+            &format!("Commented out {} line(s); remove the leading `# ` to enable them again.", conflicts.len()),
+            &format!("已注释掉 {} 行; 去掉行首的 `# ` 即可恢复。", conflicts.len()),
+        ));
+    } else if !interactive {
+        output::notice(i18n::tr(
+            "Run again with --disable-conflicts to comment out these lines.",
+            "加上 --disable-conflicts 再次运行即可注释掉这些行。",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::i18n;
+use crate::output::Json;
+use crate::prompt::PromptSet;
+use crate::script;
+use crate::shell::Shell;
+use crate::CliError;
+
+/// `export docker` 的输出格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// 追加到 Dockerfile 中的指令
+    Dockerfile,
+    /// devcontainer.json 中的 postCreateCommand
+    Devcontainer,
+}
+
+impl Format {
+    /// 命令行中可选的格式
+    pub const NAMES: [&'static str; 2] = ["dockerfile", "devcontainer"];
+
+    /// 从命令行参数解析格式
+    pub fn from_name(name: &str) -> Result<Format, CliError> {
+        match name {
+            "dockerfile" => Ok(Format::Dockerfile),
+            "devcontainer" => Ok(Format::Devcontainer),
+            other => Err(CliError(i18n::tr(&format!("Unknown export format: {}", other), &format!("未知的导出格式: {}", other)).to_string())),
+        }
+    }
+}
+
+/// 脚本内容中的 here-document 结束标记
+const EOF_MARKER: &str = "PROMPT_CHANGER_EOF";
+
+/// 镜像中所有用户共用的提示符脚本, 以及载入它的命令(fish 自动载入 conf.d)
+fn system_files(shell: Shell) -> (&'static str, Option<&'static str>) {
+    match shell {
+        Shell::Bash => (
+            "/etc/prompt-changer/prompt.bash",
+            // Debian 系的交互式 shell 读取 /etc/bash.bashrc, Fedora 与 Alpine 读取 /etc/bashrc
+            Some(
+                "for rc in /etc/bash.bashrc /etc/bashrc; do [ -f \"$rc\" ] && echo '[ -f /etc/prompt-changer/prompt.bash ] && . /etc/prompt-changer/prompt.bash' >> \"$rc\"; done; true",
+            ),
+        ),
+        Shell::Fish => ("/etc/fish/conf.d/prompt-changer.fish", None),
+        Shell::Pwsh => (
+            "/etc/prompt-changer/prompt.ps1",
+            Some("pwsh -NoProfile -Command 'Add-Content -Path $PROFILE.AllUsersAllHosts -Value \". /etc/prompt-changer/prompt.ps1\"'"),
+        ),
+        Shell::Ksh => (
+            "/etc/prompt-changer/prompt.ksh",
+            // ksh93 的发行版读取 /etc/ksh.kshrc, mksh 读取 /etc/mkshrc
+            Some(
+                "for rc in /etc/ksh.kshrc /etc/mkshrc; do [ -f \"$rc\" ] && echo '[ -f /etc/prompt-changer/prompt.ksh ] && . /etc/prompt-changer/prompt.ksh' >> \"$rc\"; done; true",
+            ),
+        ),
+        Shell::Tcsh => (
+            "/etc/prompt-changer/prompt.tcsh",
+            Some("echo 'if ( -f /etc/prompt-changer/prompt.tcsh ) source /etc/prompt-changer/prompt.tcsh' >> /etc/csh.cshrc"),
+        ),
+        // Elvish 没有全局配置, 由 root 与之后创建的用户(/etc/skel)的 rc.elv 载入
+        Shell::Elvish => (
+            "/etc/prompt-changer/prompt.elv",
+            Some(
+                "for rc in /root/.config/elvish/rc.elv /etc/skel/.config/elvish/rc.elv; do mkdir -p \"$(dirname \"$rc\")\" && echo 'use path; if (path:is-regular /etc/prompt-changer/prompt.elv) { eval (slurp < /etc/prompt-changer/prompt.elv) }' >> \"$rc\"; done",
+            ),
+        ),
+        // 镜像中通常没有设置 $ENV, 登录 shell 读取 /etc/profile
+        Shell::Posix => (
+            "/etc/prompt-changer/prompt.sh",
+            Some("echo '[ -f /etc/prompt-changer/prompt.sh ] && . /etc/prompt-changer/prompt.sh' >> /etc/profile"),
+        ),
+        // xonsh 自动载入 rc.d 中的文件
+        Shell::Xonsh => ("/etc/xonsh/rc.d/prompt-changer.xsh", None),
+        Shell::Cmd => unreachable!("run_export_docker rejects cmd"),
+        // OSH 也没有全局配置, 与 Elvish 一样写入 root 与 /etc/skel 的 oshrc
+        Shell::Oils => (
+            "/etc/prompt-changer/prompt.osh",
+            Some(
+                "for rc in /root/.config/oils/oshrc /etc/skel/.config/oils/oshrc; do mkdir -p \"$(dirname \"$rc\")\" && echo '[ -f /etc/prompt-changer/prompt.osh ] && . /etc/prompt-changer/prompt.osh' >> \"$rc\"; done",
+            ),
+        ),
+    }
+}
+
+/// 开发容器中当前用户的提示符脚本, 以及在用户配置中载入它的命令
+fn user_files(shell: Shell) -> (&'static str, Option<&'static str>) {
+    match shell {
+        Shell::Bash => (
+            "$HOME/.config/prompt-changer/prompt.bash",
+            Some(
+                "grep -qF prompt-changer/prompt.bash ~/.bashrc 2>/dev/null || \
+                 echo '[ -f \"$HOME/.config/prompt-changer/prompt.bash\" ] && . \"$HOME/.config/prompt-changer/prompt.bash\"' >> ~/.bashrc",
+            ),
+        ),
+        Shell::Fish => ("$HOME/.config/fish/conf.d/prompt.fish", None),
+        Shell::Pwsh => (
+            "$HOME/.config/prompt-changer/prompt.ps1",
+            Some(
+                "mkdir -p ~/.config/powershell && { grep -qF prompt-changer/prompt.ps1 ~/.config/powershell/Microsoft.PowerShell_profile.ps1 2>/dev/null || \
+                 echo '. \"$HOME/.config/prompt-changer/prompt.ps1\"' >> ~/.config/powershell/Microsoft.PowerShell_profile.ps1; }",
+            ),
+        ),
+        Shell::Ksh => (
+            "$HOME/.config/prompt-changer/prompt.ksh",
+            Some(
+                "grep -qF prompt-changer/prompt.ksh ~/.kshrc 2>/dev/null || \
+                 echo '[ -f \"$HOME/.config/prompt-changer/prompt.ksh\" ] && . \"$HOME/.config/prompt-changer/prompt.ksh\"' >> ~/.kshrc",
+            ),
+        ),
+        Shell::Tcsh => (
+            "$HOME/.config/prompt-changer/prompt.tcsh",
+            Some(
+                "grep -qF prompt-changer/prompt.tcsh ~/.tcshrc 2>/dev/null || \
+                 echo 'if ( -f ~/.config/prompt-changer/prompt.tcsh ) source ~/.config/prompt-changer/prompt.tcsh' >> ~/.tcshrc",
+            ),
+        ),
+        Shell::Elvish => (
+            "$HOME/.config/prompt-changer/prompt.elv",
+            Some(
+                "mkdir -p ~/.config/elvish && { grep -qF prompt-changer/prompt.elv ~/.config/elvish/rc.elv 2>/dev/null || \
+                 echo 'use path; if (path:is-regular ~/.config/prompt-changer/prompt.elv) { eval (slurp < ~/.config/prompt-changer/prompt.elv) }' >> ~/.config/elvish/rc.elv; }",
+            ),
+        ),
+        Shell::Posix => (
+            "$HOME/.config/prompt-changer/prompt.sh",
+            Some(
+                "grep -qF prompt-changer/prompt.sh ~/.profile 2>/dev/null || \
+                 echo '[ -f \"$HOME/.config/prompt-changer/prompt.sh\" ] && . \"$HOME/.config/prompt-changer/prompt.sh\"' >> ~/.profile",
+            ),
+        ),
+        Shell::Xonsh => ("$HOME/.config/xonsh/rc.d/prompt-changer.xsh", None),
+        Shell::Cmd => unreachable!("run_export_docker rejects cmd"),
+        Shell::Oils => (
+            "$HOME/.config/prompt-changer/prompt.osh",
+            Some(
+                "mkdir -p ~/.config/oils && { grep -qF prompt-changer/prompt.osh ~/.config/oils/oshrc 2>/dev/null || \
+                 echo '[ -f \"$HOME/.config/prompt-changer/prompt.osh\" ] && . \"$HOME/.config/prompt-changer/prompt.osh\"' >> ~/.config/oils/oshrc; }",
+            ),
+        ),
+    }
+}
+
+/// 把提示符写进镜像的 Dockerfile 指令: 用 here-document 写入脚本, 再让 shell 的全局配置载入它
+fn dockerfile(shell: Shell, prompts: &PromptSet, source: &str) -> String {
+    let (path, load) = system_files(shell);
+    let mut out = format!("# {} prompt for the image, exported by prompt-changer.\n", shell.display_name());
+    out += "# COPY with a here-document needs BuildKit (Docker 23+, or `# syntax=docker/dockerfile:1` as the first line).\n";
+    out += &format!("COPY <<'{}' {}\n", EOF_MARKER, path);
+    out += &script::standalone(shell, prompts, source);
+    out += &format!("{}\n", EOF_MARKER);
+    if let Some(load) = load {
+        out += &format!("RUN {}\n", load);
+    }
+    out
+}
+
+/// devcontainer.json 的 postCreateCommand: 容器创建后在用户的家目录中写入脚本并载入它
+fn devcontainer(shell: Shell, prompts: &PromptSet, source: &str) -> String {
+    let (path, load) = user_files(shell);
+    let dir = &path[..path.rfind('/').unwrap_or(path.len())];
+    let mut command = format!("mkdir -p \"{}\" && cat > \"{}\" <<'{}'\n", dir, path, EOF_MARKER);
+    command += &script::standalone(shell, prompts, source);
+    command += &format!("{}\n", EOF_MARKER);
+    if let Some(load) = load {
+        command += load;
+    }
+    format!(
+        "// {} prompt exported by prompt-changer; merge into .devcontainer/devcontainer.json.\n{{\n  \"postCreateCommand\": {}\n}}\n",
+        shell.display_name(),
+        Json::from(command.trim_end())
+    )
+}
+
+/// 把提示符烘焙进容器镜像或开发容器所需的内容
+pub fn export(format: Format, shell: Shell, prompts: &PromptSet, source: &str) -> String {
+    match format {
+        Format::Dockerfile => dockerfile(shell, prompts, source),
+        Format::Devcontainer => devcontainer(shell, prompts, source),
+    }
+}
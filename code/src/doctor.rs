@@ -0,0 +1,409 @@
+use crate::i18n;
+use crate::lint::Severity;
+use crate::oils;
+use crate::output::{self, Json};
+use crate::plugins;
+use crate::prompt::{self, Segment};
+use crate::rc;
+use crate::shell::{self, Shell};
+use regex::Regex;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+/// 一条检查结果
+struct Finding {
+    severity: Severity,
+    file: PathBuf,
+    message: String,
+    fix: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, file: &Path, message: String, fix: String) -> Finding {
+        Finding { severity, file: file.to_path_buf(), message, fix }
+    }
+}
+
+/// 检查文件是否可读写, 可读时返回内容
+fn check_access(path: &Path, findings: &mut Vec<Finding>) -> Option<String> {
+    if !path.exists() {
+        return None;
+    }
+    let content = match rc::read_text(path) {
+        Ok(content) => content,
+        Err(err) => {
+            findings.push(Finding::new(
+                Severity::Error,
+                path,
+                i18n::tr(&format!("cannot be read: {}", err), &format!("无法读取: {}", err)).to_string(),
+                i18n::tr(
+                    &format!("check the file's owner and permissions (e.g. `chmod u+rw {}`)", path.display()),
+                    &format!("检查文件的所有者和权限(例如 `chmod u+rw {}`)", path.display()),
+                )
+                .to_string(),
+            ));
+            return None;
+        }
+    };
+    if let Err(err) = OpenOptions::new().append(true).open(path) {
+        findings.push(Finding::new(
+            Severity::Error,
+            path,
+            i18n::tr(&format!("is not writable: {}", err), &format!("不可写: {}", err)).to_string(),
+            i18n::tr(
+                &format!("check the file's owner and permissions (e.g. `chmod u+rw {}`)", path.display()),
+                &format!("检查文件的所有者和权限(例如 `chmod u+rw {}`)", path.display()),
+            )
+            .to_string(),
+        ));
+    }
+    Some(content)
+}
+
+/// 检查提示符中未被 `\[ \]` 包裹的颜色序列
+fn check_wrapping(path: &Path, prompt: &str, findings: &mut Vec<Finding>) {
+    for segment in prompt::parse_bash(prompt) {
+        if let Segment::Color(color) = segment {
+            findings.push(Finding::new(
+                Severity::Warning,
+                path,
+                i18n::tr(
+                    &format!("color code `{}` is not wrapped in \\[ \\]; bash will miscount the prompt width and line editing will glitch", color),
+                    &format!("颜色代码 `{}` 没有包在 \\[ \\] 中; bash 会算错提示符的宽度, 行编辑会出错", color),
+                )
+                .to_string(),
+                i18n::tr(&format!("write it as `\\[{}\\]`", color), &format!("写成 `\\[{}\\]`", color)).to_string(),
+            ));
+        }
+    }
+}
+
+/// 每一行是否在顶层, 即不在条件分支、循环或函数体中; 分支中的赋值不一定执行, 不算互相覆盖
+fn top_level(shell: Shell, content: &str) -> Vec<bool> {
+    match shell {
+        // xonsh 是 Python, 缩进的行都在某个代码块中; cmd 的记录文件只有一个赋值
+        Shell::Xonsh => return content.lines().map(|line| !line.starts_with([' ', '\t'])).collect(),
+        Shell::Cmd => return content.lines().map(|_| true).collect(),
+        _ => {}
+    }
+    let (openers, closers): (&[&str], &[&str]) = match shell {
+        Shell::Tcsh => (&["switch", "foreach", "while"], &["endif", "endsw", "end"]),
+        _ => (&["if", "case", "for", "while", "until", "select"], &["fi", "esac", "done", "}"]),
+    };
+    let mut depth = 0usize;
+    content
+        .lines()
+        .map(|line| {
+            let top = depth == 0;
+            let code = line.split(" #").next().unwrap_or(line);
+            for piece in code.split(';').map(str::trim).filter(|p| !p.starts_with('#')) {
+                let word = piece.split_whitespace().next().unwrap_or("");
+                // tcsh 只有以 then 结尾的 if 才有 endif
+                let opens = openers.contains(&word) || (shell == Shell::Tcsh && word == "if" && piece.ends_with("then"));
+                if opens || (shell != Shell::Tcsh && piece.ends_with('{')) {
+                    depth += 1;
+                } else if closers.contains(&word) {
+                    depth = depth.saturating_sub(1);
+                }
+            }
+            top
+        })
+        .collect()
+}
+
+/// 检查 bash、ksh、sh、tcsh、xonsh 或 OSH 的配置(cmd 是记录 PROMPT 的文件); 只有 bash 有旧版本写入的行, 其余的颜色由生成的代码标出
+fn check_ps1(shell: Shell, findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = shell.config_path()?;
+    let content = match check_access(&path, findings) {
+        Some(content) => content,
+        None => return Ok(()),
+    };
+    let block = rc::find_block(&content);
+    let assignment = Regex::new(shell.prompt_assignment_pattern())?;
+    let variable = match shell {
+        Shell::Tcsh => "prompt",
+        Shell::Xonsh => "$PROMPT",
+        Shell::Cmd => "PROMPT",
+        _ => "PS1",
+    };
+    let legacy = Regex::new(Shell::Bash.legacy_line_pattern())?;
+
+    let lines: Vec<(usize, &str)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| assignment.is_match(line))
+        .collect();
+    // 区块中按主机名等切换的赋值与分支中的赋值不会互相覆盖, 只数区块外顶层的赋值
+    let top = top_level(shell, &content);
+    let unconditional: Vec<usize> = lines.iter().map(|(n, _)| *n).filter(|n| top[*n] && !block.is_some_and(|b| b.contains(*n))).collect();
+    if unconditional.len() > 1 {
+        let numbers: Vec<String> = unconditional.iter().map(|n| (n + 1).to_string()).collect();
+        findings.push(Finding::new(
+            Severity::Warning,
+            &path,
+            i18n::tr(
+                &format!("{} {} assignments found (lines {}); only the last one takes effect", numbers.len(), variable, numbers.join(", ")),
+                &format!("发现 {} 处 {} 赋值(第 {} 行); 只有最后一处生效", numbers.len(), variable, numbers.join(", ")),
+            )
+            .to_string(),
+            i18n::tr(
+                "keep a single assignment; `prompt-changer set` keeps its own inside the prompt-changer block",
+                "只保留一处赋值; `prompt-changer set` 把自己的赋值放在 prompt-changer 区块中",
+            )
+            .to_string(),
+        ));
+    }
+    for (n, line) in &lines {
+        if block.is_some_and(|b| b.contains(*n)) {
+            continue;
+        }
+        if shell == Shell::Bash && legacy.is_match(line) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                &path,
+                i18n::tr(
+                    &format!("line {} looks like a PS1 line appended by an older prompt-changer version", n + 1),
+                    &format!("第 {} 行看起来是旧版 prompt-changer 追加的 PS1 行", n + 1),
+                )
+                .to_string(),
+                i18n::tr(
+                    &format!("delete line {}; the current prompt lives in the prompt-changer block", n + 1),
+                    &format!("删除第 {} 行; 当前的提示符在 prompt-changer 区块中", n + 1),
+                )
+                .to_string(),
+            ));
+        } else if block.is_some_and(|b| *n > b.end) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                &path,
+                i18n::tr(
+                    &format!("line {} sets {} after the prompt-changer block and overrides it", n + 1, variable),
+                    &format!("第 {} 行在 prompt-changer 区块之后设置了 {}, 覆盖了区块中的设置", n + 1, variable),
+                )
+                .to_string(),
+                i18n::tr(&format!("remove line {} or move it above the block", n + 1), &format!("删除第 {} 行或把它移到区块之前", n + 1)).to_string(),
+            ));
+        }
+    }
+
+    if shell == Shell::Bash {
+        if let Some(prompt) = shell.read_configured_prompt()? {
+            check_wrapping(&path, &prompt, findings);
+        }
+    }
+    if shell == Shell::Oils {
+        check_render_prompt(findings)?;
+    }
+    Ok(())
+}
+
+/// YSH 不读取 oshrc, yshrc 中定义了 renderPrompt 时它画出的提示符与写入的不同
+fn check_render_prompt(findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = oils::ysh_rc_path(&shell::home()?);
+    let content = rc::read_text(&path).unwrap_or_default();
+    let definition = Regex::new(r"^\s*func\s+renderPrompt\s*\(")?;
+    if let Some(n) = content.lines().position(|line| definition.is_match(line)) {
+        findings.push(Finding::new(
+            Severity::Warning,
+            &path,
+            i18n::tr(
+                &format!("line {} defines renderPrompt, so YSH shows that prompt rather than the one written to oshrc", n + 1),
+                &format!("第 {} 行定义了 renderPrompt, YSH 会显示那个提示符而不是写入 oshrc 的提示符", n + 1),
+            )
+            .to_string(),
+            i18n::tr(
+                "prompt-changer only configures OSH; remove renderPrompt and set PS1 in yshrc if YSH should match",
+                "prompt-changer 只配置 OSH; 如果希望 YSH 一致, 请删除 renderPrompt 并在 yshrc 中设置 PS1",
+            )
+            .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 检查 fish 的配置
+fn check_fish(findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let function_path = Shell::fish_function_path()?;
+    check_access(&function_path, findings);
+
+    let sources = Shell::fish_config_sources()?;
+
+    let definition = Regex::new(r"^\s*function\s+fish_prompt(\s|$)")?;
+    let legacy = Regex::new(Shell::Fish.legacy_line_pattern())?;
+    for path in sources {
+        let content = match check_access(&path, findings) {
+            Some(content) => content,
+            None => continue,
+        };
+        let block = rc::find_block(&content);
+        for (n, line) in content.lines().enumerate() {
+            if block.is_some_and(|b| b.contains(n)) {
+                continue;
+            }
+            if definition.is_match(line) {
+                findings.push(Finding::new(
+                    Severity::Error,
+                    &path,
+                    i18n::tr(
+                        &format!("line {} defines fish_prompt, overriding the function generated in {}", n + 1, function_path.display()),
+                        &format!("第 {} 行定义了 fish_prompt, 覆盖了 {} 中生成的函数", n + 1, function_path.display()),
+                    )
+                    .to_string(),
+                    i18n::tr("remove that definition (or move it into its own file under functions/)", "删除该定义(或把它移到 functions/ 下单独的文件中)").to_string(),
+                ));
+            } else if legacy.is_match(line) {
+                findings.push(Finding::new(
+                    Severity::Warning,
+                    &path,
+                    i18n::tr(
+                        &format!("line {} sets a fish_prompt variable, which fish ignores; it was appended by an older prompt-changer version", n + 1),
+                        &format!("第 {} 行设置了 fish 会忽略的 fish_prompt 变量; 它是旧版 prompt-changer 追加的", n + 1),
+                    )
+                    .to_string(),
+                    i18n::tr(
+                        &format!("delete line {} and run `prompt-changer set --shell fish` to generate a real fish_prompt function", n + 1),
+                        &format!("删除第 {} 行并运行 `prompt-changer set --shell fish` 生成真正的 fish_prompt 函数", n + 1),
+                    )
+                    .to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 检查 PowerShell 或 Elvish 的配置文件
+fn check_prompt_function(shell: Shell, findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = shell.config_path()?;
+    let content = match check_access(&path, findings) {
+        Some(content) => content,
+        None => return Ok(()),
+    };
+    let Some(block) = rc::find_block(&content) else {
+        return Ok(());
+    };
+    let definition = Regex::new(shell.prompt_function_pattern())?;
+    let what = if shell == Shell::Elvish { i18n::tr("sets edit:prompt", "设置了 edit:prompt") } else { i18n::tr("defines a prompt function", "定义了提示符函数") };
+    for (n, line) in content.lines().enumerate().skip(block.end + 1) {
+        if definition.is_match(line) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                &path,
+                i18n::tr(
+                    &format!("line {} {} after the prompt-changer block and overrides it", n + 1, what),
+                    &format!("第 {} 行在 prompt-changer 区块之后{}, 覆盖了区块中的设置", n + 1, what),
+                )
+                .to_string(),
+                i18n::tr(
+                    &format!("remove that definition or move it above the block (line {})", block.start + 1),
+                    &format!("删除该定义或把它移到区块(第 {} 行)之前", block.start + 1),
+                )
+                .to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 检查配置的提示符用到的 WASM 插件能否运行
+fn check_plugins(shell: Shell, findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(prompt) = shell.read_configured_prompt()? else {
+        return Ok(());
+    };
+    for segment in prompt::parse_bash(&prompt) {
+        if let Segment::Command(cmd) = segment {
+            if let Some(plugin) = plugins::missing_wasmtime(&cmd) {
+                findings.push(Finding::new(
+                    Severity::Error,
+                    &shell.config_path()?,
+                    i18n::tr(
+                        &format!("the prompt uses the WASM plugin `{}`, but wasmtime is not on PATH, so it shows nothing", plugin),
+                        &format!("提示符用到了 WASM 插件 `{}`, 但 PATH 中没有 wasmtime, 因此它不会显示任何内容", plugin),
+                    )
+                    .to_string(),
+                    i18n::tr("install wasmtime (https://wasmtime.dev)", "安装 wasmtime(https://wasmtime.dev)").to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 检查各 shell 的提示符配置并打印问题与修复建议
+pub fn run(shells: &[Shell]) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut findings = Vec::new();
+    let mut checked = Vec::new();
+    for shell in shells {
+        // ksh 与 sh 可能读取同一个 $ENV 或 .profile, 同一个文件只检查一次
+        let path = shell.config_path().ok();
+        if path.is_some() && checked.contains(&path) {
+            continue;
+        }
+        checked.push(path);
+        match shell {
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Xonsh | Shell::Oils | Shell::Cmd => check_ps1(*shell, &mut findings)?,
+            Shell::Fish => check_fish(&mut findings)?,
+            Shell::Pwsh | Shell::Elvish => check_prompt_function(*shell, &mut findings)?,
+        }
+        check_plugins(*shell, &mut findings)?;
+    }
+
+    let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+    if output::is_json() {
+        let list = findings
+            .iter()
+            .map(|finding| {
+                Json::Object(vec![
+                    ("severity", finding.severity.to_string().into()),
+                    ("file", finding.file.display().to_string().into()),
+                    ("message", finding.message.as_str().into()),
+                    ("fix", finding.fix.as_str().into()),
+                ])
+            })
+            .collect();
+        output::print("doctor", vec![("findings", Json::Array(list)), ("errors", errors.into())]);
+        return Ok(errors);
+    }
+
+    let mut current: Option<&Path> = None;
+    for finding in &findings {
+        if current != Some(finding.file.as_path()) {
+            println!("{}", finding.file.display());
+            current = Some(finding.file.as_path());
+        }
+        println!("  {}: {}", finding.severity.label(), finding.message);
+        println!("    {}: {}", i18n::tr("fix", "修复"), finding.fix);
+    }
+    if findings.is_empty() {
+        println!("{}", i18n::tr("No problems found.", "没有发现问题。"));
+    } else {
+        println!("{}", i18n::tr(&format!("{} problem(s) found.", findings.len()), &format!("发现 {} 个问题。", findings.len())));
+    }
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 行号(从 1 开始)中在顶层的
+    fn top_lines(shell: Shell, content: &str) -> Vec<usize> {
+        top_level(shell, content).iter().enumerate().filter(|(_, top)| **top).map(|(n, _)| n + 1).collect()
+    }
+
+    #[test]
+    fn branches_of_the_stock_debian_bashrc_are_not_top_level() {
+        let content = "if [ \"$color_prompt\" = yes ]; then\n    PS1='color'\nelse\n    PS1='plain'\nfi\n\
+                       case \"$TERM\" in\nxterm*|rxvt*)\n    PS1=\"title$PS1\"\n    ;;\nesac\n\
+                       prompt() { PS1='f'; }\nPS1='mine'\n";
+        assert_eq!(top_lines(Shell::Bash, content), vec![1, 6, 11, 12]);
+    }
+
+    #[test]
+    fn one_line_conditions_and_tcsh_blocks() {
+        assert_eq!(top_lines(Shell::Bash, "if true; then PS1='a'; fi\nPS1='b'\n"), vec![1, 2]);
+        let tcsh = "if ($?prompt) then\n  set prompt = 'a'\nendif\nif ($?x) set prompt = 'b'\nset prompt = 'c'\n";
+        assert_eq!(top_lines(Shell::Tcsh, tcsh), vec![1, 4, 5]);
+        assert_eq!(top_lines(Shell::Xonsh, "if x:\n    $PROMPT = 'a'\n$PROMPT = 'b'\n"), vec![1, 3]);
+    }
+}
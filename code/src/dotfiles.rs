@@ -0,0 +1,210 @@
+use crate::i18n;
+use crate::logging;
+use crate::shell;
+use crate::CliError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// 管理 dotfiles 仓库的工具
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Manager {
+    Chezmoi,
+    Stow,
+}
+
+/// `--dotfiles-dir` 指定的仓库
+struct Dotfiles {
+    dir: PathBuf,
+    manager: Manager,
+    /// 写入后是否运行 `chezmoi apply` 或 `stow --restow`
+    apply: bool,
+}
+
+static DOTFILES: OnceLock<Dotfiles> = OnceLock::new();
+
+/// 仓库中没有对应的 stow 包时, 新文件放在这个包中
+const STOW_PACKAGE: &str = "prompt-changer";
+
+/// chezmoi 源文件名中的属性前缀
+const CHEZMOI_PREFIXES: [&str; 17] = [
+    "after_", "before_", "create_", "empty_", "encrypted_", "exact_", "executable_", "external_", "literal_",
+    "modify_", "once_", "onchange_", "private_", "readonly_", "remove_", "run_", "symlink_",
+];
+
+/// 这些属性的源文件不是目标文件的原样内容, 无法直接改写
+const CHEZMOI_UNEDITABLE: [&str; 6] = ["create_", "encrypted_", "modify_", "remove_", "run_", "symlink_"];
+
+/// 改写 dotfiles 仓库中的源文件, 而不是家目录中的文件; `apply` 时写入后让管理工具更新家目录
+pub fn set_dir(dir: &Path, apply: bool) -> Result<(), CliError> {
+    if !dir.is_dir() {
+        return Err(CliError(
+            i18n::tr(&format!("Dotfiles directory {} is not a directory", dir.display()), &format!("dotfiles 目录 {} 不是目录", dir.display())).to_string(),
+        ));
+    }
+    let manager = detect(dir);
+    logging::debug("dotfiles", format!("{} is a {:?} dotfiles directory", dir.display(), manager));
+    DOTFILES
+        .set(Dotfiles { dir: dir.to_path_buf(), manager, apply })
+        .map_err(|_| CliError(i18n::tr("Dotfiles directory is already set", "dotfiles 目录已经设置过了").to_string()))
+}
+
+/// 是否改写 dotfiles 仓库中的源文件
+pub fn is_active() -> bool {
+    DOTFILES.get().is_some()
+}
+
+/// chezmoi 的源目录中有 `.chezmoi*` 文件或 `dot_` 开头的条目; 其余按 stow 的包目录处理
+fn detect(dir: &Path) -> Manager {
+    let chezmoi = fs::read_dir(dir).into_iter().flatten().flatten().any(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        name.starts_with(".chezmoi") || (!name.starts_with('.') && chezmoi_target(&name).0.starts_with('.'))
+    });
+    if chezmoi {
+        Manager::Chezmoi
+    } else {
+        Manager::Stow
+    }
+}
+
+/// chezmoi 源文件名对应的目标名与其中的属性前缀, 如 `private_dot_bashrc.tmpl` 是 `.bashrc`
+fn chezmoi_target(entry: &str) -> (String, Vec<&'static str>) {
+    let mut name = entry;
+    let mut attributes = Vec::new();
+    while let Some(prefix) = CHEZMOI_PREFIXES.iter().find(|prefix| name.starts_with(*prefix)) {
+        name = &name[prefix.len()..];
+        attributes.push(*prefix);
+        if *prefix == "literal_" {
+            break;
+        }
+    }
+    let name = name.strip_suffix(".tmpl").unwrap_or(name);
+    match name.strip_prefix("dot_") {
+        Some(rest) if !attributes.contains(&"literal_") => (format!(".{}", rest), attributes),
+        _ => (name.to_string(), attributes),
+    }
+}
+
+/// 家目录中的文件在 chezmoi 源目录中的位置; 没有时按 chezmoi 的命名规则新建, 如 `.config` 是 `dot_config`
+fn chezmoi_source(dir: &Path, rel: &Path) -> Result<PathBuf, CliError> {
+    // .chezmoiroot 可以把源目录放在仓库的子目录中
+    let mut source = match fs::read_to_string(dir.join(".chezmoiroot")) {
+        Ok(root) => dir.join(root.trim()),
+        Err(_) => dir.to_path_buf(),
+    };
+    for component in rel.components() {
+        let name = component.as_os_str().to_string_lossy();
+        let found = fs::read_dir(&source)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|entry| !entry.starts_with('.'))
+            .find(|entry| chezmoi_target(entry).0 == name);
+        let entry = match found {
+            Some(entry) => {
+                if let Some(attribute) = chezmoi_target(&entry).1.iter().find(|a| CHEZMOI_UNEDITABLE.contains(a)) {
+                    let attribute = attribute.trim_end_matches('_');
+                    let source = source.join(&entry);
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!(
+                                "chezmoi manages ~/{} with the `{}` attribute ({}), which prompt-changer cannot edit",
+                                rel.display(),
+                                attribute,
+                                source.display()
+                            ),
+                            &format!("chezmoi 以 `{}` 属性管理 ~/{} ({}), prompt-changer 无法编辑", attribute, rel.display(), source.display()),
+                        )
+                        .to_string(),
+                    ));
+                }
+                entry
+            }
+            None => name.strip_prefix('.').map_or_else(|| name.to_string(), |rest| format!("dot_{}", rest)),
+        };
+        source.push(entry);
+    }
+    Ok(source)
+}
+
+/// 家目录中的文件在 stow 包中的位置: 已有这个文件, 或已有它最深一层上级目录的包; 都没有时放在 [`STOW_PACKAGE`] 包中
+fn stow_source(dir: &Path, rel: &Path) -> PathBuf {
+    let mut packages: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.') && entry.path().is_dir())
+        .map(|entry| entry.path())
+        .collect();
+    packages.sort();
+    let depth = |package: &Path| rel.ancestors().filter(|a| !a.as_os_str().is_empty()).position(|a| package.join(a).exists());
+    packages
+        .iter()
+        .filter_map(|package| depth(package).map(|d| (d, package)))
+        .min_by_key(|(d, _)| *d)
+        .map_or_else(|| dir.join(STOW_PACKAGE).join(rel), |(_, package)| package.join(rel))
+}
+
+/// 家目录中的文件在 dotfiles 仓库中对应的源文件; 未指定仓库或文件不在家目录中时原样返回
+pub fn source_path(live: PathBuf) -> Result<PathBuf, CliError> {
+    let Some(dotfiles) = DOTFILES.get() else {
+        return Ok(live);
+    };
+    let home = shell::home()?;
+    let Ok(rel) = live.strip_prefix(&home) else {
+        return Ok(live);
+    };
+    let source = match dotfiles.manager {
+        Manager::Chezmoi => chezmoi_source(&dotfiles.dir, rel)?,
+        Manager::Stow => stow_source(&dotfiles.dir, rel),
+    };
+    logging::debug("dotfiles", format!("{} is written as {}", live.display(), source.display()));
+    Ok(source)
+}
+
+/// `--dotfiles-apply`: 改写源文件后运行 `chezmoi apply` 或对改动的包运行 `stow --restow`, 让改动出现在家目录中
+pub fn apply(written: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(dotfiles) = DOTFILES.get().filter(|dotfiles| dotfiles.apply) else {
+        return Ok(());
+    };
+    let home = shell::home()?;
+    let mut cmd = match dotfiles.manager {
+        Manager::Chezmoi => {
+            let mut cmd = Command::new("chezmoi");
+            cmd.arg("--source").arg(&dotfiles.dir).arg("--destination").arg(&home).arg("apply");
+            cmd
+        }
+        Manager::Stow => {
+            let mut packages: Vec<&std::ffi::OsStr> = written
+                .iter()
+                .filter_map(|path| path.strip_prefix(&dotfiles.dir).ok()?.components().next())
+                .map(|package| package.as_os_str())
+                .collect();
+            packages.sort();
+            packages.dedup();
+            if packages.is_empty() {
+                return Ok(());
+            }
+            let mut cmd = Command::new("stow");
+            cmd.arg("--dir").arg(&dotfiles.dir).arg("--target").arg(&home).arg("--restow").args(packages);
+            cmd
+        }
+    };
+    logging::debug("dotfiles", format!("running {:?}", cmd));
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let out = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| CliError(i18n::tr(&format!("cannot run {}: {}", program, err), &format!("无法运行 {}: {}", program, err)).to_string()))?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(Box::new(CliError(
+            i18n::tr(&format!("{} failed: {}", program, stderr.trim_end()), &format!("{} 失败: {}", program, stderr.trim_end())).to_string(),
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use crate::color::{self, ColorSlot};
+use crate::prompt::{self, PromptSet, Segment, COLOR_NAMES, SOURCE_MARKER};
+use std::path::{Path, PathBuf};
+
+/// Elvish 的提示符中没有对应写法的 bash 转义
+const UNSUPPORTED_ESCAPES: [&str; 5] = ["!", "#", "j", "l", "r"];
+
+/// 转义在 Elvish 中是否没有对应写法; Elvish 会把提示符中的控制字符显示出来, 颜色以外的八进制字符都不支持
+pub fn is_unsupported(escape: &str) -> bool {
+    let octal = escape.len() == 3 && escape.chars().all(|c| c.is_digit(8));
+    UNSUPPORTED_ESCAPES.contains(&escape) || (octal && escape != "033")
+}
+
+/// Elvish 启动时读取的 rc.elv; 0.17 之前的版本读取 ~/.elvish/rc.elv, 只有它存在时沿用
+pub fn rc_path(home: &Path) -> PathBuf {
+    let current = home.join(".config/elvish/rc.elv");
+    let legacy = home.join(".elvish/rc.elv");
+    if !current.exists() && legacy.exists() {
+        legacy
+    } else {
+        current
+    }
+}
+
+/// 提示符中用到的模块与变量; 都在载入时求值, 不随每次显示变化
+const SETUP: &str = "use path
+use platform
+use re
+use str
+var __prompt_changer_user = (whoami)
+var __prompt_changer_host = (platform:hostname)
+var __prompt_changer_short_host = (platform:hostname &strip-domain)
+var __prompt_changer_sign = (if (==s (id -u) 0) { put '#' } else { put '$' })
+";
+
+/// Elvish 的单引号字符串, 其中的单引号写两次
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// 把 SGR 参数翻译为 `styled` 的样式; `0` 换成把各项恢复默认的样式, 以便接在之前的样式之后
+fn transformers(sgr: &str) -> Vec<String> {
+    let codes: Vec<u32> = sgr.split(';').map(|code| code.parse().unwrap_or(0)).collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < codes.len() {
+        let code = codes[i];
+        let extended = |prefix: &str, i: &mut usize| match codes.get(*i + 1) {
+            Some(5) if *i + 2 < codes.len() => {
+                *i += 2;
+                Some(format!("{}color{}", prefix, codes[*i]))
+            }
+            Some(2) if *i + 4 < codes.len() => {
+                *i += 4;
+                Some(format!("{}#{:02x}{:02x}{:02x}", prefix, codes[*i - 2], codes[*i - 1], codes[*i]))
+            }
+            _ => None,
+        };
+        match code {
+            0 => out.extend(
+                ["fg-default", "bg-default", "no-bold", "no-dim", "no-italic", "no-underlined", "no-blink", "no-inverse"]
+                    .map(str::to_string),
+            ),
+            1 => out.push("bold".to_string()),
+            2 => out.push("dim".to_string()),
+            3 => out.push("italic".to_string()),
+            4 => out.push("underlined".to_string()),
+            5 => out.push("blink".to_string()),
+            7 => out.push("inverse".to_string()),
+            22 => out.extend(["no-bold", "no-dim"].map(str::to_string)),
+            23 => out.push("no-italic".to_string()),
+            24 => out.push("no-underlined".to_string()),
+            25 => out.push("no-blink".to_string()),
+            27 => out.push("no-inverse".to_string()),
+            30..=37 => out.push(COLOR_NAMES[(code - 30) as usize].to_string()),
+            38 => out.extend(extended("", &mut i)),
+            39 => out.push("fg-default".to_string()),
+            40..=47 => out.push(format!("bg-{}", COLOR_NAMES[(code - 40) as usize])),
+            48 => out.extend(extended("bg-", &mut i)),
+            49 => out.push("bg-default".to_string()),
+            90..=97 => out.push(format!("bright-{}", COLOR_NAMES[(code - 90) as usize])),
+            100..=107 => out.push(format!("bg-bright-{}", COLOR_NAMES[(code - 100) as usize])),
+            _ => {}
+        }
+        i += 1;
+    }
+    out
+}
+
+/// 样式写成列表中的元素; `#` 在 Elvish 中开始注释, 真彩色要加引号
+fn style_words(style: &[String]) -> String {
+    let word = |t: &String| if t.contains('#') { quote(t) } else { t.clone() };
+    style.iter().map(word).collect::<Vec<_>>().join(" ")
+}
+
+/// 样式列表的字面量
+fn style_list(sgr: &str) -> String {
+    format!("[{}]", style_words(&transformers(sgr)))
+}
+
+/// 按终端能力与背景设置颜色变量的代码, 变量是 `styled` 的样式列表; NO_COLOR 时为空列表
+fn color_setup(colors: &[ColorSlot]) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let themed = colors.iter().any(|c| c.dark != c.light);
+    let mut code = String::new();
+    if themed {
+        code += "var __prompt_changer_appearance = dark\n";
+        code += "if (has-env PROMPT_CHANGER_APPEARANCE) {\n    set __prompt_changer_appearance = $E:PROMPT_CHANGER_APPEARANCE\n";
+        code += "} elif (re:match ';(7|15)$' $E:COLORFGBG) {\n    set __prompt_changer_appearance = light\n}\n";
+    }
+    code += "var __prompt_changer_depth = 4\n";
+    code += "if (has-env NO_COLOR) {\n    set __prompt_changer_depth = 0\n";
+    code += "} elif (has-value [truecolor 24bit] $E:COLORTERM) {\n    set __prompt_changer_depth = 24\n";
+    code += "} elif (>= (sh -c 'tput colors 2>/dev/null || echo 8') 256) {\n    set __prompt_changer_depth = 8\n}\n";
+    code += if themed {
+        "var __prompt_changer_key = $__prompt_changer_depth':'$__prompt_changer_appearance\n"
+    } else {
+        "var __prompt_changer_key = $__prompt_changer_depth\n"
+    };
+    for i in 0..colors.len() {
+        code += &format!("var {} = []\n", color::color_var(i));
+    }
+    let arms = color::setup_arms(colors);
+    for (n, (arm, values)) in arms.iter().enumerate() {
+        let condition = if arm == "*" {
+            None
+        } else if let Some(prefix) = arm.strip_suffix('*') {
+            Some(format!("(str:has-prefix $__prompt_changer_key {})", quote(prefix)))
+        } else {
+            Some(format!("(eq $__prompt_changer_key {})", quote(arm)))
+        };
+        code += &match (n, condition) {
+            (0, Some(condition)) => format!("if {} {{", condition),
+            (_, Some(condition)) => format!("}} elif {} {{", condition),
+            (_, None) => "} else {".to_string(),
+        };
+        for (i, value) in values.iter().enumerate() {
+            code += &format!("\n    set {} = {}", color::color_var(i), value.map_or_else(|| "[]".to_string(), style_list));
+        }
+        code += "\n";
+    }
+    code += "}\n";
+    code
+}
+
+/// 把 bash 的反斜杠转义翻译为求值为字符串的 Elvish 表达式, 没有对应写法时为 None
+fn escape_expr(escape: &str) -> Option<String> {
+    let date = |fmt: &str| format!("(date {})", quote(&format!("+{}", fmt)));
+    Some(match escape {
+        "u" => "$__prompt_changer_user".to_string(),
+        "h" => "$__prompt_changer_short_host".to_string(),
+        "H" => "$__prompt_changer_host".to_string(),
+        "w" => "(tilde-abbr $pwd)".to_string(),
+        "W" => "(path:base (tilde-abbr $pwd))".to_string(),
+        "t" => date("%H:%M:%S"),
+        "T" => date("%I:%M:%S"),
+        "@" => date("%I:%M %p"),
+        "A" => date("%H:%M"),
+        "d" => date("%a %b %d"),
+        "$" => "$__prompt_changer_sign".to_string(),
+        "s" => quote("elvish"),
+        "v" | "V" => "$buildinfo[version]".to_string(),
+        "n" => quote("\n"),
+        "\\" => quote("\\"),
+        _ => {
+            let fmt = escape.strip_prefix("D{")?.strip_suffix('}')?;
+            date(if fmt.is_empty() { "%X" } else { fmt })
+        }
+    })
+}
+
+/// 提示符闭包中的一行: 输出一段带当前样式的文字, 或改变之后文字的样式
+enum Step {
+    Text(String),
+    Style(String),
+}
+
+/// 把单个片段翻译为闭包中的各步; `\[ \]` 中(`nested`)只保留颜色, 其余是 Elvish 无法显示的控制序列
+fn steps(segment: &Segment, nested: bool, out: &mut Vec<Step>) {
+    match segment {
+        Segment::Color(color) => {
+            let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+            let style = transformers(rest.trim_start_matches('[').trim_end_matches('m'));
+            if !style.is_empty() {
+                out.push(Step::Style(format!("[$@style {}]", style_words(&style))));
+            }
+        }
+        Segment::Variable(name) if name.starts_with("__prompt_changer_color") => {
+            out.push(Step::Style(format!("[$@style $@{}]", name)));
+        }
+        Segment::NonPrinting(inner) => {
+            for segment in prompt::parse_bash(inner) {
+                steps(&segment, true, out);
+            }
+        }
+        _ if nested => {}
+        Segment::Text(text) => out.push(Step::Text(quote(text))),
+        Segment::Escape(escape) => out.extend(escape_expr(escape).map(Step::Text)),
+        Segment::Variable(name) => out.push(Step::Text(format!("$E:{}", name))),
+        // bash 的命令替换交给 sh 运行, 去掉输出末尾的换行
+        Segment::Command(cmd) => out.push(Step::Text(format!("(str:trim-right (sh -c {} | slurp) \"\\n\")", quote(cmd)))),
+    }
+}
+
+/// 提示符翻译为 `edit:prompt` 的闭包, 每行缩进 `indent`
+fn closure(prompt: &str, indent: &str) -> String {
+    let mut parts = Vec::new();
+    for segment in prompt::parse_bash(prompt) {
+        steps(&segment, false, &mut parts);
+    }
+    let mut code = format!("{{\n{}    var style = []", indent);
+    for part in parts {
+        code += &match part {
+            Step::Text(expr) if expr == "''" => continue,
+            Step::Text(expr) => format!("\n{}    styled {} $@style", indent, expr),
+            Step::Style(list) => format!("\n{}    set style = {}", indent, list),
+        };
+    }
+    code + &format!("\n{}}}", indent)
+}
+
+/// 设置终端标题的代码: 每次读取命令前把 OSC 0 序列写到终端
+fn title_hook(template: &str) -> String {
+    let mut parts = vec!["\"\\x1b]0;\"".to_string()];
+    for segment in prompt::parse_bash(template) {
+        let mut out = Vec::new();
+        steps(&segment, false, &mut out);
+        parts.extend(out.into_iter().filter_map(|step| match step {
+            Step::Text(expr) => Some(expr),
+            Step::Style(_) => None,
+        }));
+    }
+    parts.push("\"\\x07\"".to_string());
+    format!(
+        "set edit:before-readline = [$@edit:before-readline {{ print {} > /dev/tty }}]\n",
+        parts.join("")
+    )
+}
+
+/// 把 bash 风格的主机名通配模式写成正则表达式; 写入 spec 时已限制了其中的字符
+fn host_regex(pattern: &str) -> String {
+    let body: String = pattern
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            '.' => "\\.".to_string(),
+            c => c.to_string(),
+        })
+        .collect();
+    format!("^{}$", body)
+}
+
+/// 提示符略去的设置
+pub fn notices(prompts: &PromptSet) -> Vec<String> {
+    prompt::left_out_notice("Elvish", &prompts.unsupported_extras(false)).into_iter().collect()
+}
+
+/// 写入受管理区块的代码: 第一行记下原始的 bash 风格提示符. 只支持按主机名切换的变体与标题; 右侧提示符清空, 以免重复显示用户与主机名
+pub fn block_body(prompts: &PromptSet) -> String {
+    let mut body = format!("{}{}\n{}", SOURCE_MARKER, prompts.default, SETUP);
+    body += &color_setup(&prompts.colors);
+    if let Some(title) = prompts.title.as_deref().filter(|t| !t.is_empty()) {
+        body += &title_hook(title);
+    }
+    body += &format!("set edit:prompt = {}\n", closure(&prompts.default, ""));
+    body += "set edit:rprompt = (constantly '')";
+    for (n, (pattern, prompt)) in prompts.hosts.iter().enumerate() {
+        let keyword = if n == 0 { "\nif" } else { " elif" };
+        body += &format!(
+            "{} (re:match {} $__prompt_changer_host) {{\n    set edit:prompt = {}\n}}",
+            keyword,
+            quote(&host_regex(pattern)),
+            closure(prompt, "    ")
+        );
+    }
+    body
+}
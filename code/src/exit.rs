@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::io;
+
+/// 其他失败
+pub const FAILURE: i32 = 1;
+/// 提示符、描述文件或命令行参数无效(clap 的用法错误也是 2)
+pub const INVALID_PROMPT: i32 = 2;
+/// 找不到配置文件、描述文件或已配置的提示符
+pub const NOT_FOUND: i32 = 3;
+/// 没有读写配置文件的权限, 或 `--system` 未以 root 运行
+pub const PERMISSION_DENIED: i32 = 4;
+/// 不支持的 shell
+pub const UNSUPPORTED_SHELL: i32 = 5;
+/// `--check`: 配置中还不是这个提示符, 需要写入
+pub const WOULD_CHANGE: i32 = 6;
+
+/// `--help` 中列出的退出码
+pub const HELP: &str = "EXIT CODES:
+    0    Success
+    1    Other failure
+    2    Invalid prompt, spec or arguments
+    3    Config file, spec or configured prompt not found
+    4    Permission denied (including --system without root)
+    5    Unsupported shell
+    6    --check: the prompt is not written yet and would be changed";
+
+/// 按错误的种类选择退出码: 文件读写错误按 io 错误的种类, 其余为 [`FAILURE`]
+pub fn code_for(err: &(dyn Error + 'static)) -> i32 {
+    match err.downcast_ref::<io::Error>().map(io::Error::kind) {
+        Some(io::ErrorKind::NotFound) => NOT_FOUND,
+        Some(io::ErrorKind::PermissionDenied) => PERMISSION_DENIED,
+        _ => FAILURE,
+    }
+}
@@ -0,0 +1,119 @@
+use crate::i18n;
+use crate::output::{self, Json};
+use crate::rc;
+use crate::shell::{self, Shell};
+use crate::CliError;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// 一条应用过的提示符
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// 应用的时间, RFC 3339 格式
+    pub time: String,
+    pub shell: Shell,
+    pub prompt: String,
+}
+
+/// 历史记录文件, 每行一条, 以制表符分隔时间、shell 与提示符
+fn history_path() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".local/share/prompt-changer/history"))
+}
+
+/// 记下刚应用的提示符; 提示符中不会有制表符与换行
+pub fn record(shell: Shell, prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = history_path()?;
+    if let Some(dir) = path.parent() {
+        rc::create_dirs(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    rc::own_like_parent(&path);
+    writeln!(
+        file,
+        "{}\t{}\t{}",
+        chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        shell.display_name().to_ascii_lowercase(),
+        prompt
+    )?;
+    Ok(())
+}
+
+/// 读取历史记录, 最近应用的在前; 没有记录文件时为空
+pub fn load() -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let path = history_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("Cannot read history {}: {}", path.display(), err),
+                &format!("无法读取历史 {}: {}", path.display(), err),
+            )
+            .to_string(),
+        ))),
+    };
+    let mut entries: Vec<Entry> = content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let time = fields.next()?.to_string();
+            let shell = Shell::from_name(fields.next()?).ok()?;
+            let prompt = fields.next()?.to_string();
+            Some(Entry { time, shell, prompt })
+        })
+        .collect();
+    entries.reverse();
+    Ok(entries)
+}
+
+/// 取出第 `n` 条记录, 1 是最近应用的
+pub fn entry(n: usize) -> Result<Entry, Box<dyn std::error::Error>> {
+    let entries = load()?;
+    let count = entries.len();
+    n.checked_sub(1).and_then(|i| entries.into_iter().nth(i)).ok_or_else(|| {
+        let message = if count == 0 {
+            "The history is empty; it records prompts from the next `prompt-changer set` on".to_string()
+        } else {
+            format!("There is no entry {} in the history; choose 1 to {}", n, count)
+        };
+        Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, message)) as Box<dyn std::error::Error>
+    })
+}
+
+/// 列出历史记录, 编号可用于 `history apply`; 指定 shell 时只列出它的记录
+pub fn list(only: Option<Shell>) -> Result<(), Box<dyn std::error::Error>> {
+    let entries: Vec<(usize, Entry)> = load()?
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| (i + 1, entry))
+        .filter(|(_, entry)| only.is_none_or(|shell| entry.shell == shell))
+        .collect();
+
+    if output::is_json() {
+        let list = entries
+            .iter()
+            .map(|(n, entry)| {
+                Json::Object(vec![
+                    ("number", (*n).into()),
+                    ("time", entry.time.as_str().into()),
+                    ("shell", entry.shell.display_name().to_ascii_lowercase().into()),
+                    ("prompt", entry.prompt.as_str().into()),
+                ])
+            })
+            .collect();
+        output::print("history", vec![("entries", Json::Array(list))]);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("{}", i18n::tr("No prompts have been applied yet.", "还没有应用过提示符。"));
+        return Ok(());
+    }
+    for (n, entry) in &entries {
+        // 只显示到分钟
+        let time = entry.time.get(..16).unwrap_or(&entry.time).replace('T', " ");
+        println!("{:>4}  {}  {:<4}  {}", n, time, entry.shell.display_name().to_ascii_lowercase(), entry.prompt);
+    }
+    Ok(())
+}
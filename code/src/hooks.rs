@@ -0,0 +1,91 @@
+use crate::config;
+use crate::i18n;
+use crate::logging;
+use crate::output;
+use crate::shell::Shell;
+use crate::CliError;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 配置文件中可以设置的钩子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// 写入新提示符之前运行, 失败时不做改动
+    PreApply,
+    /// 新提示符写入成功后运行
+    PostApply,
+}
+
+impl Hook {
+    /// 配置文件中的键名
+    pub fn key(self) -> &'static str {
+        match self {
+            Hook::PreApply => "pre-apply",
+            Hook::PostApply => "post-apply",
+        }
+    }
+
+    /// 配置中的命令
+    fn command(self) -> Option<&'static str> {
+        let config = config::get();
+        match self {
+            Hook::PreApply => config.pre_apply.as_deref(),
+            Hook::PostApply => config.post_apply.as_deref(),
+        }
+    }
+}
+
+/// 用系统的 shell 运行命令
+fn system_shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// 运行配置的钩子, 没有配置时什么也不做. 钩子从环境变量 `PROMPT_CHANGER_HOOK`、`PROMPT_CHANGER_SHELL`
+/// 与 `PROMPT_CHANGER_FILE` (写入的文件, 只有 post-apply 有) 得知改动; 输出 JSON 时钩子的输出改到标准错误
+pub fn run(hook: Hook, shell: Shell, file: Option<&Path>) -> Result<(), CliError> {
+    let Some(command) = hook.command() else {
+        return Ok(());
+    };
+    logging::info(i18n::tr(&format!("Running the {} hook: {}", hook.key(), command), &format!("运行 {} 钩子: {}", hook.key(), command)));
+    let mut cmd = system_shell(command);
+    cmd.env("PROMPT_CHANGER_HOOK", hook.key()).env("PROMPT_CHANGER_SHELL", shell.display_name().to_ascii_lowercase());
+    if let Some(file) = file {
+        cmd.env("PROMPT_CHANGER_FILE", file);
+    }
+    if output::is_json() {
+        cmd.stdout(Stdio::from(std::io::stderr()));
+    }
+    let status = cmd
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|err| CliError(
+            i18n::tr(
+                &format!("Cannot run the {} hook `{}`: {}", hook.key(), command, err),
+                &format!("无法运行 {} 钩子 `{}`: {}", hook.key(), command, err),
+            )
+            .to_string(),
+        ))?;
+    logging::debug("hooks", format!("the {} hook exited with {}", hook.key(), status));
+    if status.success() {
+        return Ok(());
+    }
+    let outcome = match hook {
+        Hook::PreApply => i18n::tr("the prompt was not changed", "提示符没有改动"),
+        Hook::PostApply => i18n::tr("the new prompt was already written", "新的提示符已经写入"),
+    };
+    Err(CliError(
+        i18n::tr(
+            &format!("The {} hook `{}` failed ({}); {}", hook.key(), command, status, outcome),
+            &format!("{} 钩子 `{}` 失败 ({}); {}", hook.key(), command, status, outcome),
+        )
+        .to_string(),
+    ))
+}
@@ -0,0 +1,76 @@
+use crate::output;
+use crate::CliError;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// 界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Zh,
+}
+
+impl Lang {
+    /// 命令行中可选的语言
+    pub const NAMES: [&'static str; 2] = ["en", "zh"];
+
+    /// 从命令行参数解析语言
+    pub fn from_name(name: &str) -> Result<Lang, CliError> {
+        match name {
+            "en" => Ok(Lang::En),
+            "zh" => Ok(Lang::Zh),
+            other => Err(CliError(tr(&format!("Unsupported language: {}", other), &format!("不支持的语言: {}", other)).to_string())),
+        }
+    }
+}
+
+/// 用户用 `--lang` 指定的语言
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// 设置界面语言
+pub fn set_lang(lang: Lang) -> Result<(), CliError> {
+    LANG.set(lang).map_err(|_| CliError(tr("Language is already set", "语言已经设置过了").to_string()))
+}
+
+/// 当前的界面语言: `--lang` 指定的, 否则按 LC_ALL、LC_MESSAGES、LANG 判断, 中文环境下是中文.
+/// 设置 `--lang` 之前输出的信息按环境变量选择语言, 不会因此锁定语言
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_else(|| {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+            .unwrap_or_default();
+        if locale.starts_with("zh") {
+            Lang::Zh
+        } else {
+            Lang::En
+        }
+    })
+}
+
+/// 按界面语言在英文与中文之间选择
+pub fn tr<'a>(en: &'a str, zh: &'a str) -> &'a str {
+    match lang() {
+        Lang::En => en,
+        Lang::Zh => zh,
+    }
+}
+
+/// 打印错误信息; JSON 模式下以 JSON 打印到标准输出
+pub fn error(message: impl Display) {
+    if output::is_json() {
+        output::print_error(message.to_string());
+    } else {
+        eprintln!("{}: {}", tr("Error", "错误"), message);
+    }
+}
+
+/// 打印警告信息; JSON 模式下随结果一起输出
+pub fn warning(message: impl Display) {
+    if output::is_json() {
+        output::record_warning(message.to_string());
+    } else {
+        eprintln!("{}: {}", tr("Warning", "警告"), message);
+    }
+}
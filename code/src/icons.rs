@@ -0,0 +1,120 @@
+use crate::i18n;
+use crate::suggest;
+use crate::CliError;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+/// 可供片段使用的 Nerd Font 图标: 名称、图标与没有补丁字体时的 ASCII 替代
+const ICONS: [(&str, &str, &str); 35] = [
+    ("linux", "\u{f17c}", "linux"),
+    ("apple", "\u{f179}", "mac"),
+    ("windows", "\u{f17a}", "win"),
+    ("ubuntu", "\u{f31b}", "ubuntu"),
+    ("debian", "\u{f306}", "debian"),
+    ("fedora", "\u{f30a}", "fedora"),
+    ("arch", "\u{f303}", "arch"),
+    ("git-branch", "\u{e0a0}", "git:"),
+    ("hg-branch", "\u{e0a0}", "hg:"),
+    ("svn", "\u{e0a0}", "svn:"),
+    ("folder", "\u{f07b}", "dir:"),
+    ("home", "\u{f015}", "~"),
+    ("clock", "\u{f017}", "@"),
+    ("user", "\u{f007}", "user:"),
+    ("server", "\u{f233}", "host:"),
+    ("network", "\u{f0ac}", "ip:"),
+    ("layers", "\u{f5fd}", "lvl:"),
+    ("lock", "\u{f023}", "ro"),
+    ("load", "\u{f0e4}", "load:"),
+    ("memory", "\u{f2db}", "mem:"),
+    ("python", "\u{e73c}", "py"),
+    ("rust", "\u{e7a8}", "rs"),
+    ("node", "\u{e718}", "node"),
+    ("go", "\u{e627}", "go"),
+    ("nix", "\u{f313}", "nix:"),
+    ("env", "\u{f462}", "env:"),
+    ("docker", "\u{f308}", "docker:"),
+    ("container", "\u{f1b2}", "ctr:"),
+    ("terraform", "\u{f1062}", "tf:"),
+    ("aws", "\u{f0e0f}", "aws:"),
+    ("check", "\u{f00c}", "ok"),
+    ("cross", "\u{f00d}", "x"),
+    ("error", "\u{f071}", "!"),
+    ("separator", "\u{e0b0}", ">"),
+    ("arrow", "\u{f054}", ">"),
+];
+
+/// 只起装饰作用的图标, 无障碍模式下不显示
+pub fn is_decorative(name: &str) -> bool {
+    matches!(name, "separator" | "arrow")
+}
+
+/// 本机系统对应的图标名称, 用于 `os` 图标
+fn os_icon() -> &'static str {
+    match env::consts::OS {
+        "macos" => "apple",
+        "windows" => "windows",
+        _ => {
+            let release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+            let id = release
+                .lines()
+                .find_map(|line| line.strip_prefix("ID="))
+                .map(|id| id.trim_matches('"'))
+                .unwrap_or_default();
+            ICONS.iter().map(|(name, _, _)| *name).find(|name| *name == id).unwrap_or("linux")
+        }
+    }
+}
+
+/// 取出名为 `name` 的图标, `ascii` 时取 ASCII 替代; `os` 表示生成提示符的这台机器的系统图标
+pub fn glyph(name: &str, ascii: bool) -> Result<&'static str, CliError> {
+    let name = if name == "os" { os_icon() } else { name };
+    ICONS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, icon, fallback)| if ascii { *fallback } else { *icon })
+        .ok_or_else(|| {
+            let names: Vec<&str> = ICONS.iter().map(|(n, _, _)| *n).collect();
+            match suggest::closest(name, names.iter().copied().chain(["os"])) {
+                Some(close) => CliError(
+                    i18n::tr(
+                        &format!("Unknown icon `{}`, did you mean `{}`?", name, close),
+                        &format!("未知的图标 `{}`, 是不是 `{}`?", name, close),
+                    )
+                    .to_string(),
+                ),
+                None => CliError(
+                    i18n::tr(
+                        &format!("Unknown icon `{}`; choose one of os, {}", name, names.join(", ")),
+                        &format!("未知的图标 `{}`; 可选 os, {}", name, names.join(", ")),
+                    )
+                    .to_string(),
+                ),
+            }
+        })
+}
+
+/// 猜测 Nerd Font 图标是否会显示为方框, 返回原因
+pub fn tofu_reason() -> Option<String> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    let utf8 = locale.to_ascii_lowercase().replace('-', "").contains("utf8");
+    if locale.is_empty() {
+        return Some("no locale is set (LANG is empty)".to_string());
+    }
+    if !utf8 {
+        return Some(format!("the locale `{}` is not UTF-8", locale));
+    }
+    if env::var("TERM").as_deref() == Ok("linux") {
+        return Some("the Linux console cannot show Nerd Font glyphs".to_string());
+    }
+    // 没有 fontconfig 时(如 macOS)无从判断, 不作提示
+    let fonts = Command::new("fc-list").arg(":").arg("family").output().ok().filter(|out| out.status.success())?;
+    if String::from_utf8_lossy(&fonts.stdout).contains("Nerd Font") {
+        None
+    } else {
+        Some("no Nerd Font is installed according to fc-list".to_string())
+    }
+}
@@ -0,0 +1,860 @@
+use crate::i18n;
+use crate::logging;
+use crate::migrate;
+use crate::prompt::COLOR_NAMES;
+use crate::shell::{self, single_quote};
+use crate::CliError;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 展开主题中变量的最大嵌套深度, 防止变量互相引用时无限展开
+const MAX_DEPTH: usize = 8;
+
+
+/// 转换出的一段提示符: bash 风格的内容与颜色
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    text: String,
+    color: Option<String>,
+    background: Option<String>,
+}
+
+/// 从主题转换出的描述文件, 以及无法翻译的部分
+pub struct Imported {
+    pub source: PathBuf,
+    pub spec: String,
+    pub untranslated: Vec<String>,
+}
+
+/// 可以导入主题的提示符框架
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framework {
+    OhMyZsh,
+    OhMyBash,
+    BashIt,
+}
+
+impl Framework {
+    /// 用于输出的名称
+    fn display_name(self) -> &'static str {
+        match self {
+            Framework::OhMyZsh => "oh-my-zsh",
+            Framework::OhMyBash => "oh-my-bash",
+            Framework::BashIt => "bash-it",
+        }
+    }
+
+    /// 框架自带的函数, 在没有安装框架的 shell 中不存在
+    fn functions(self) -> &'static str {
+        match self {
+            Framework::OhMyZsh => r"^(\w+_prompt_(info|status)|parse_git_dirty|git_\w+|vi_mode_\w+|box_name)$",
+            Framework::OhMyBash | Framework::BashIt => {
+                r"^(_omb_\w+|_?scm_\w+|_?git_\w+|battery_\w+|clock_\w+|\w+_version_prompt|\w+_prompt(_\w+)?|\w+_char)$"
+            }
+        }
+    }
+
+    /// 显示 git 分支的函数, 翻译为直接调用 git 的命令
+    fn git_functions(self) -> &'static [&'static str] {
+        match self {
+            Framework::OhMyZsh => &["git_prompt_info"],
+            Framework::OhMyBash | Framework::BashIt => &["scm_prompt_info", "git_prompt_info", "_omb_prompt_git"],
+        }
+    }
+
+    /// git 信息的前缀、后缀与有改动时的标记, 按优先顺序列出变量名
+    fn git_vars(self) -> [&'static [&'static str]; 3] {
+        match self {
+            Framework::OhMyZsh => [&["ZSH_THEME_GIT_PROMPT_PREFIX"], &["ZSH_THEME_GIT_PROMPT_SUFFIX"], &["ZSH_THEME_GIT_PROMPT_DIRTY"]],
+            Framework::OhMyBash | Framework::BashIt => [
+                &["GIT_THEME_PROMPT_PREFIX", "SCM_THEME_PROMPT_PREFIX"],
+                &["GIT_THEME_PROMPT_SUFFIX", "SCM_THEME_PROMPT_SUFFIX"],
+                &["GIT_THEME_PROMPT_DIRTY", "SCM_THEME_PROMPT_DIRTY"],
+            ],
+        }
+    }
+
+    /// 主题的查找位置: 框架目录的环境变量与默认目录、自定义目录的环境变量, 以及主题文件相对 themes 的路径
+    fn theme_location(self, theme: &str) -> (&'static str, &'static str, &'static str, String) {
+        match self {
+            Framework::OhMyZsh => ("ZSH", ".oh-my-zsh", "ZSH_CUSTOM", format!("{}.zsh-theme", theme)),
+            Framework::OhMyBash => ("OSH", ".oh-my-bash", "OSH_CUSTOM", format!("{0}/{0}.theme.sh", theme)),
+            Framework::BashIt => ("BASH_IT", ".bash_it", "BASH_IT_CUSTOM", format!("{0}/{0}.theme.bash", theme)),
+        }
+    }
+}
+
+/// 读取从 `start` 开始的一个 shell 单词(引号内可以跨行), 返回去掉引号后的值与结束位置
+fn read_word(chars: &[char], start: usize) -> (String, usize) {
+    let mut value = String::new();
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '$' if chars.get(i + 1) == Some(&'\'') => {
+                i += 2;
+                while i < chars.len() && chars[i] != '\'' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        match chars[i + 1] {
+                            'n' => value.push('\n'),
+                            'e' | 'E' => value.push('\u{1b}'),
+                            'a' => value.push('\u{7}'),
+                            c => value.push(c),
+                        }
+                        i += 2;
+                    } else {
+                        value.push(chars[i]);
+                        i += 1;
+                    }
+                }
+                i += 1;
+            }
+            '\'' => {
+                let end = chars[i + 1..].iter().position(|c| *c == '\'').map_or(chars.len(), |n| i + 1 + n);
+                value.extend(&chars[i + 1..end]);
+                i = end + 1;
+            }
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    match (chars[i], chars.get(i + 1)) {
+                        ('\\', Some('\n')) => i += 2,
+                        ('\\', Some(&c)) if "\"\\$`".contains(c) => {
+                            value.push(c);
+                            i += 2;
+                        }
+                        (c, _) => {
+                            value.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+            }
+            '\\' => {
+                if let Some(&c) = chars.get(i + 1).filter(|c| **c != '\n') {
+                    value.push(c);
+                }
+                i += 2;
+            }
+            c if c.is_whitespace() || c == ';' => break,
+            c => {
+                value.push(c);
+                i += 1;
+            }
+        }
+    }
+    (value, i.min(chars.len()))
+}
+
+/// 主题中的变量赋值, 依次为变量名、是否是 `+=` 与去掉引号后的值
+fn assignments(content: &str) -> Vec<(String, bool, String)> {
+    let head = Regex::new(r"^\s*(?:local\s+|typeset\s+(?:-g\s+)?|export\s+)?([A-Za-z_][A-Za-z0-9_]*)(\+?)=").unwrap();
+    let chars: Vec<char> = content.chars().collect();
+    let next_line = |from: usize| chars[from..].iter().position(|c| *c == '\n').map_or(chars.len(), |n| from + n + 1);
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let line: String = chars[i..next_line(i)].iter().collect();
+        match head.captures(&line) {
+            Some(caps) => {
+                let (value, end) = read_word(&chars, i + caps[0].chars().count());
+                found.push((caps[1].to_string(), !caps[2].is_empty(), value));
+                i = next_line(end.min(chars.len().saturating_sub(1)));
+            }
+            None => i = next_line(i),
+        }
+    }
+    found
+}
+
+/// 把 zsh 或 bash 框架的颜色名称翻译为描述文件的颜色; `default` 为 Some(None), 不认识时为 None
+fn color_name(name: &str) -> Option<Option<String>> {
+    let name = name.trim().to_ascii_lowercase();
+    match name.as_str() {
+        "default" | "none" | "" => Some(None),
+        "grey" | "gray" => Some(Some("bright-black".to_string())),
+        "purple" => Some(Some("magenta".to_string())),
+        "orange" => Some(Some("208".to_string())),
+        _ if COLOR_NAMES.contains(&name.as_str()) || name.parse::<u8>().is_ok() => Some(Some(name)),
+        _ if name.strip_prefix('#').is_some_and(|h| h.len() == 6 && h.chars().all(|c| c.is_ascii_hexdigit())) => {
+            Some(Some(name))
+        }
+        _ => None,
+    }
+}
+
+/// 把主题的提示符翻译为带颜色的 bash 片段
+struct Translator<'a> {
+    framework: Framework,
+    vars: &'a BTreeMap<String, String>,
+    functions: &'a [String],
+    pieces: Vec<Piece>,
+    color: Option<String>,
+    background: Option<String>,
+    untranslated: Vec<String>,
+}
+
+impl<'a> Translator<'a> {
+    fn new(framework: Framework, vars: &'a BTreeMap<String, String>, functions: &'a [String]) -> Translator<'a> {
+        Translator { framework, vars, functions, pieces: Vec::new(), color: None, background: None, untranslated: Vec::new() }
+    }
+
+    /// 记下无法翻译的部分, 相同的只记一次
+    fn note(&mut self, message: String) {
+        if !self.untranslated.contains(&message) {
+            self.untranslated.push(message);
+        }
+    }
+
+    /// 以当前颜色追加内容
+    fn push(&mut self, text: &str) {
+        let (color, background) = (self.color.clone(), self.background.clone());
+        self.push_colored(text, color, background);
+    }
+
+    /// 以给定颜色追加内容, 与颜色相同的上一段合并
+    fn push_colored(&mut self, text: &str, color: Option<String>, background: Option<String>) {
+        match self.pieces.last_mut() {
+            Some(last) if last.color == color && last.background == background => last.text += text,
+            _ => self.pieces.push(Piece { text: text.to_string(), color, background }),
+        }
+    }
+
+    /// 去掉颜色后的全部内容
+    fn plain_text(&self) -> String {
+        self.pieces.iter().map(|p| p.text.as_str()).collect()
+    }
+
+    /// 翻译一段提示符: zsh 的提示符转义, 或本来就是 bash 的 PS1
+    fn translate(&mut self, prompt: &str, depth: usize) {
+        let zsh = self.framework == Framework::OhMyZsh;
+        let sgr = if zsh {
+            Regex::new(r"^\x1b\[([0-9;]*)m").unwrap()
+        } else {
+            Regex::new(r"^(?:\x1b|\\e|\\033|\\x1[bB])\[([0-9;]*)m").unwrap()
+        };
+        let chars: Vec<char> = prompt.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let rest: String = chars[i..].iter().collect();
+            if let Some(caps) = sgr.captures(&rest) {
+                self.sgr(&caps[1]);
+                i += caps[0].chars().count();
+                continue;
+            }
+            i += match chars[i] {
+                '%' if zsh => self.percent(&rest, depth),
+                '$' => self.dollar(&rest, depth),
+                '\\' if zsh => {
+                    self.push("\\\\");
+                    1
+                }
+                '\\' => self.backslash(&rest),
+                '\n' => {
+                    self.push("\\n");
+                    1
+                }
+                c => {
+                    self.push(&c.to_string());
+                    1
+                }
+            };
+        }
+    }
+
+    /// bash 的反斜杠转义原样保留, 只去掉包裹颜色的 `\[ \]`; 返回消耗的字符数
+    fn backslash(&mut self, rest: &str) -> usize {
+        let escape = Regex::new(r"^\\(?:[\[\]]|D\{[^}]*\}|[0-7]{3}|.)").unwrap();
+        let Some(m) = escape.find(rest) else {
+            self.push("\\\\");
+            return 1;
+        };
+        if !matches!(m.as_str(), "\\[" | "\\]") {
+            self.push(m.as_str());
+        }
+        m.as_str().chars().count()
+    }
+
+    /// 按 SGR 参数改变颜色
+    fn sgr(&mut self, params: &str) {
+        let codes: Vec<u32> = params.split(';').map(|c| c.parse().unwrap_or(0)).collect();
+        let mut i = 0;
+        while i < codes.len() {
+            let code = codes[i];
+            match code {
+                0 => {
+                    self.color = None;
+                    self.background = None;
+                }
+                1 => self.note("bold is not supported in specs and was left out".to_string()),
+                30..=37 => self.color = Some(COLOR_NAMES[(code - 30) as usize].to_string()),
+                39 => self.color = None,
+                40..=47 => self.background = Some(COLOR_NAMES[(code - 40) as usize].to_string()),
+                49 => self.background = None,
+                90..=97 => self.color = Some(format!("bright-{}", COLOR_NAMES[(code - 90) as usize])),
+                100..=107 => self.background = Some(format!("bright-{}", COLOR_NAMES[(code - 100) as usize])),
+                38 | 48 => {
+                    let color = match codes.get(i + 1) {
+                        Some(5) => codes.get(i + 2).map(|n| n.to_string()),
+                        Some(2) if i + 4 < codes.len() => {
+                            Some(format!("#{:02x}{:02x}{:02x}", codes[i + 2], codes[i + 3], codes[i + 4]))
+                        }
+                        _ => None,
+                    };
+                    i += if codes.get(i + 1) == Some(&2) { 4 } else { 2 };
+                    if code == 38 {
+                        self.color = color;
+                    } else {
+                        self.background = color;
+                    }
+                }
+                22 | 23 | 24 | 25 | 27 => {}
+                other => self.note(format!("SGR attribute {} is not supported in specs and was left out", other)),
+            }
+            i += 1;
+        }
+    }
+
+    /// bash 框架的颜色变量, 如 `${bold_green}`、`${_omb_prompt_red}`、`${background_blue}`; 不是颜色时返回 false
+    fn bash_color(&mut self, name: &str) -> bool {
+        let name = name.strip_prefix("_omb_prompt_").unwrap_or(name);
+        let name = name.strip_prefix("echo_").unwrap_or(name);
+        if matches!(name, "normal" | "reset_color") {
+            self.color = None;
+            self.background = None;
+            return true;
+        }
+        let (attribute, base) = match name.split_once('_') {
+            Some((attribute, base)) if ["bold", "underline", "background", "bright"].contains(&attribute) => (attribute, base),
+            _ => ("", name),
+        };
+        let Some(Some(color)) = color_name(base).filter(|_| !base.chars().any(|c| c.is_ascii_digit())) else {
+            return false;
+        };
+        match attribute {
+            "background" => self.background = Some(color),
+            "bright" if COLOR_NAMES.contains(&color.as_str()) => self.color = Some(format!("bright-{}", color)),
+            "bold" | "underline" => {
+                self.note(format!("{} (${{{}_...}}) is not supported in specs and was left out", attribute, attribute));
+                self.color = Some(color);
+            }
+            _ => self.color = Some(color),
+        }
+        true
+    }
+
+    /// 翻译 `%` 开头的提示符转义, 返回消耗的字符数
+    fn percent(&mut self, rest: &str, depth: usize) -> usize {
+        // `%{...%}` 与标明显示宽度的 `%1{...%}`
+        let literal = Regex::new(r"^%\d*\{").unwrap();
+        if let Some(open) = literal.find(rest) {
+            let inner = &rest[open.end()..];
+            let end = inner.find("%}").unwrap_or(inner.len());
+            self.translate(&inner[..end], depth);
+            return rest[..(open.end() + end + 2).min(rest.len())].chars().count();
+        }
+        let braced = Regex::new(r"^%([FKD])\{([^}]*)\}").unwrap();
+        if let Some(caps) = braced.captures(rest) {
+            let value = caps[2].to_string();
+            match &caps[1] {
+                "D" => self.push(&format!("\\D{{{}}}", value)),
+                kind => match color_name(&value) {
+                    Some(color) if kind == "F" => self.color = color,
+                    Some(color) => self.background = color,
+                    None => self.note(format!("unknown color `{}` was left out", value)),
+                },
+            }
+            return caps[0].chars().count();
+        }
+        if rest.starts_with("%(") {
+            return self.conditional(rest, depth);
+        }
+        let path = Regex::new(r"^%(-?\d*)([~/dcC.])").unwrap();
+        if let Some(caps) = path.captures(rest) {
+            match (&caps[1], &caps[2]) {
+                ("", "~") => self.push("\\w"),
+                ("", "/" | "d") => self.push("$PWD"),
+                ("" | "1", _) => self.push("\\W"),
+                (n, kind) => {
+                    self.note(format!("%{}{} (the last {} directories) was shown as the whole path", n, kind, n));
+                    self.push("\\w");
+                }
+            }
+            return caps[0].chars().count();
+        }
+        let Some(c) = rest.chars().nth(1) else {
+            self.push("%");
+            return 1;
+        };
+        let text = match c {
+            'n' => "\\u",
+            'm' => "\\h",
+            'M' => "\\H",
+            '*' => "\\t",
+            'T' => "\\A",
+            't' | '@' => "\\@",
+            'D' => "\\D{%y-%m-%d}",
+            'w' => "\\D{%a %d}",
+            'W' => "\\D{%m/%d/%y}",
+            '#' => "\\$",
+            '%' => "%",
+            ')' => ")",
+            'j' => "\\j",
+            '!' | 'h' => "\\!",
+            '?' => "$?",
+            'l' | 'y' => "\\l",
+            'L' => "$SHLVL",
+            'f' => {
+                self.color = None;
+                ""
+            }
+            'k' => {
+                self.background = None;
+                ""
+            }
+            'B' | 'b' => {
+                self.note("bold (%B) is not supported in specs and was left out".to_string());
+                ""
+            }
+            'U' | 'u' | 'S' | 's' => {
+                self.note(format!("%{} (underline or standout) is not supported in specs and was left out", c));
+                ""
+            }
+            _ => {
+                self.note(format!("%{} has no bash equivalent and was left out", c));
+                ""
+            }
+        };
+        self.push(text);
+        2
+    }
+
+    /// 翻译 `%(x.真.假)` 条件: bash 的提示符没有条件, 只保留条件成立时的分支
+    fn conditional(&mut self, rest: &str, depth: usize) -> usize {
+        let chars: Vec<char> = rest.chars().collect();
+        let mut i = 2;
+        while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '-') {
+            i += 1;
+        }
+        let (Some(condition), Some(&separator)) = (chars.get(i), chars.get(i + 1)) else {
+            return chars.len();
+        };
+        let start = i + 2;
+        let (mut level, mut middle, mut end) = (0, None, chars.len());
+        let mut j = start;
+        while j < chars.len() {
+            match chars[j] {
+                '%' if chars.get(j + 1) == Some(&'(') => {
+                    level += 1;
+                    j += 1;
+                }
+                '%' => j += 1,
+                ')' if level > 0 => level -= 1,
+                ')' => {
+                    end = j;
+                    break;
+                }
+                c if c == separator && level == 0 && middle.is_none() => middle = Some(j),
+                _ => {}
+            }
+            j += 1;
+        }
+        let when_true: String = chars[start..middle.unwrap_or(end)].iter().collect();
+        self.note(format!(
+            "the %({}…) conditional has no bash equivalent; only the branch shown when it is true was kept",
+            condition
+        ));
+        self.translate(&when_true, depth);
+        (end + 1).min(chars.len())
+    }
+
+    /// 翻译 `$` 开头的变量与命令替换, 返回消耗的字符数
+    fn dollar(&mut self, rest: &str, depth: usize) -> usize {
+        let body = &rest[1..];
+        if body.starts_with('(') {
+            let mut level = 0;
+            for (idx, c) in body.char_indices() {
+                match c {
+                    '(' => level += 1,
+                    ')' => level -= 1,
+                    _ => {}
+                }
+                if level == 0 {
+                    self.command(body[1..idx].trim());
+                    return rest[..idx + 2].chars().count();
+                }
+            }
+            self.push("$");
+            return 1;
+        }
+        let variable = Regex::new(r"^(?:\{([A-Za-z_]\w*)(?:\[(\w+)\])?\}|([A-Za-z_]\w*)(?:\[(\w+)\])?)").unwrap();
+        let Some(caps) = variable.captures(body) else {
+            self.push("$");
+            return 1;
+        };
+        let name = caps.get(1).or(caps.get(3)).unwrap().as_str().to_string();
+        let key = caps.get(2).or(caps.get(4)).map(|m| m.as_str().to_string());
+        self.variable(&name, key.as_deref(), depth);
+        caps[0].chars().count() + 1
+    }
+
+    /// 翻译一个变量: 框架的颜色变量、主题自己定义的变量, 其余作为 bash 变量保留
+    fn variable(&mut self, name: &str, key: Option<&str>, depth: usize) {
+        match (name, key) {
+            ("fg" | "fg_bold" | "fg_no_bold" | "bg" | "bg_bold" | "bg_no_bold", Some(key)) => {
+                if name.ends_with("_bold") && !name.contains("no_bold") {
+                    self.note(format!("bold (${}) is not supported in specs and was left out", name));
+                }
+                match color_name(key) {
+                    Some(color) if name.starts_with("fg") => self.color = color,
+                    Some(color) => self.background = color,
+                    None => self.note(format!("unknown color ${}[{}] was left out", name, key)),
+                }
+            }
+            ("FG" | "BG", Some(key)) => {
+                let color = key.trim_start_matches('0').parse::<u8>().unwrap_or(0).to_string();
+                if name == "FG" {
+                    self.color = Some(color);
+                } else {
+                    self.background = Some(color);
+                }
+            }
+            ("FX", Some("reset" | "no_bold" | "none")) | ("reset_color", None) => {
+                self.color = None;
+                self.background = None;
+            }
+            ("FX", Some(key)) => self.note(format!("$FX[{}] is not supported in specs and was left out", key)),
+            (_, None) if self.framework != Framework::OhMyZsh && self.bash_color(name) => {}
+            (_, None) if self.vars.contains_key(name) => {
+                if depth >= MAX_DEPTH {
+                    self.note(format!("${} refers to itself and was left out", name));
+                    return;
+                }
+                let value = self.vars[name].clone();
+                self.translate(&value, depth + 1);
+            }
+            (_, None) => self.push(&format!("${{{}}}", name)),
+            (_, Some(key)) => self.note(format!("${}[{}] has no bash equivalent and was left out", name, key)),
+        }
+    }
+
+    /// 翻译命令替换: 显示 git 分支的函数换成直接调用 git 的命令, 其余框架与主题的函数无法翻译
+    fn command(&mut self, cmd: &str) {
+        let framework = Regex::new(self.framework.functions()).unwrap();
+        let function = cmd.split_whitespace().next().unwrap_or_default();
+        if self.framework.git_functions().contains(&cmd) {
+            self.git(cmd);
+        } else if self.functions.iter().any(|f| f == function) || framework.is_match(function) {
+            self.note(format!("$({}) calls a {} or theme function and was left out", cmd, self.framework.display_name()));
+        } else {
+            self.note(format!("$({}) was kept as is; check that it also works in bash", cmd));
+            self.push(&format!("$({})", cmd));
+        }
+    }
+
+    /// 显示 git 分支的函数: 只在 git 仓库中显示前缀、分支名与后缀; 分支名使用前缀结尾处的颜色
+    fn git(&mut self, function: &str) {
+        let [prefix, suffix, dirty] = self.framework.git_vars();
+        let part = |names: &[&str]| {
+            let mut sub = Translator::new(self.framework, self.vars, self.functions);
+            sub.color = self.color.clone();
+            sub.background = self.background.clone();
+            sub.translate(names.iter().find_map(|name| self.vars.get(*name)).map_or("", String::as_str), 0);
+            sub
+        };
+        let prefix = part(prefix);
+        let suffix = part(suffix);
+        if prefix.pieces.len() > 1 || suffix.pieces.len() > 1 {
+            self.note("the colors inside the git prefix and suffix were merged into one".to_string());
+        }
+        if dirty.iter().any(|name| self.vars.contains_key(*name)) {
+            self.note(format!("{} now shows only the branch; the dirty marker was left out", function));
+        }
+        let text = format!(
+            "$(b=$(git branch --show-current 2>/dev/null) && [ -n \"$b\" ] && printf '%s%s%s' {} \"$b\" {})",
+            single_quote(&prefix.plain_text()),
+            single_quote(&suffix.plain_text())
+        );
+        self.push_colored(&text, prefix.color.clone(), prefix.background.clone());
+    }
+}
+
+/// 以 TOML 字符串写出: 能用字面量字符串时用它, 以免反斜杠需要转义
+fn toml_string(value: &str) -> String {
+    if !value.contains('\'') && !value.contains('\n') {
+        return format!("'{}'", value);
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// 由片段生成描述文件
+fn spec_text(source: &str, pieces: &[Piece], untranslated: &[String]) -> String {
+    let mut out = format!("# Imported by prompt-changer from {}\n", source);
+    for note in untranslated {
+        out += &format!("# note: {}\n", note);
+    }
+    out += &format!("version = {}\n", migrate::SPEC_VERSION);
+    for piece in pieces.iter().filter(|p| !p.text.is_empty()) {
+        out += &format!("\n[[segment]]\ntext = {}\n", toml_string(&piece.text));
+        if let Some(color) = &piece.color {
+            out += &format!("color = \"{}\"\n", color);
+        }
+        if let Some(background) = &piece.background {
+            out += &format!("background = \"{}\"\n", background);
+        }
+    }
+    out
+}
+
+/// 按名称在框架的自定义目录与 themes 目录中查找主题, 也可以直接给出文件
+fn find_theme(framework: Framework, theme: &OsStr) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = Path::new(theme);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    let (root_var, default_root, custom_var, file) = framework.theme_location(&theme.to_string_lossy());
+    let root = env::var_os(root_var).map(PathBuf::from).map_or_else(|| shell::home().map(|h| h.join(default_root)), Ok)?;
+    let custom = env::var_os(custom_var).map_or_else(|| root.join("custom"), PathBuf::from);
+    let candidates = [custom.join("themes").join(&file), root.join("themes").join(&file)];
+    for candidate in &candidates {
+        logging::debug("import", format!("looking for the theme at {}", candidate.display()));
+        if candidate.is_file() {
+            return Ok(candidate.clone());
+        }
+    }
+    Err(Box::new(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "{} theme `{}` not found at {} or {}; pass the path of the theme file",
+            framework.display_name(),
+            theme.to_string_lossy(),
+            candidates[0].display(),
+            candidates[1].display()
+        ),
+    )))
+}
+
+/// 把框架主题的提示符与颜色变量转换为描述文件: oh-my-zsh 取 PROMPT, oh-my-bash 与 bash-it 取 PS1
+pub fn import(framework: Framework, theme: &OsStr) -> Result<Imported, Box<dyn std::error::Error>> {
+    let path = find_theme(framework, theme)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|err| CliError(
+            i18n::tr(
+                &format!("Cannot read {}: {}", path.display(), err),
+                &format!("无法读取 {}: {}", path.display(), err),
+            )
+            .to_string(),
+        ))?;
+    let zsh = framework == Framework::OhMyZsh;
+    let mut vars: BTreeMap<String, String> = BTreeMap::new();
+    let mut prompt_assignments = 0;
+    for (name, append, value) in assignments(&content) {
+        let name = match name.as_str() {
+            "PS1" if zsh => "PROMPT".to_string(),
+            "RPS1" if zsh => "RPROMPT".to_string(),
+            _ => name,
+        };
+        let prompt_var = if zsh { "PROMPT" } else { "PS1" };
+        if !append && name == prompt_var {
+            prompt_assignments += 1;
+        }
+        // 提示符取最后一次赋值; 其他变量在 if/else 中赋值时取第一个分支, 与 %(…) 条件的处理一致
+        match vars.get_mut(&name) {
+            Some(existing) if append => *existing += &value,
+            Some(_) if name != prompt_var => {}
+            _ => {
+                vars.insert(name, value);
+            }
+        }
+    }
+    let prompt_var = if zsh { "PROMPT" } else { "PS1" };
+    let prompt = vars
+        .get(prompt_var)
+        .cloned()
+        .ok_or_else(|| {
+            let (en, zh) = if zsh { ("PROMPT or PS1", "PROMPT 或 PS1") } else { ("PS1", "PS1") };
+            CliError(i18n::tr(&format!("{} does not set {}", path.display(), en), &format!("{} 没有设置 {}", path.display(), zh)).to_string())
+        })?;
+    let definition = Regex::new(r"(?m)^\s*(?:function\s+([\w-]+)|([\w-]+)\s*\(\s*\))")?;
+    let functions: Vec<String> = definition
+        .captures_iter(&content)
+        .filter_map(|caps| caps.get(1).or(caps.get(2)).map(|m| m.as_str().to_string()))
+        .collect();
+    logging::debug("import", format!("{} in {} is {:?}", prompt_var, path.display(), prompt));
+
+    let mut translator = Translator::new(framework, &vars, &functions);
+    if prompt_assignments > 1 {
+        translator.note(format!("the theme sets {} in {} places; the last one was used", prompt_var, prompt_assignments));
+    }
+    translator.translate(&prompt, 0);
+    if zsh && vars.get("RPROMPT").is_some_and(|r| !r.is_empty()) {
+        translator.note("RPROMPT (the right-hand prompt) has no bash equivalent and was left out".to_string());
+    }
+    let source = format!("the {} theme {}", framework.display_name(), path.display());
+    let spec = spec_text(&source, &translator.pieces, &translator.untranslated);
+    Ok(Imported { source: path, spec, untranslated: translator.untranslated })
+}
+
+/// 打印转换出的描述文件, 或保存到 `write`; 无法翻译的部分作为警告打印
+pub fn run(imported: Imported, write: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    for note in &imported.untranslated {
+        i18n::warning(note);
+    }
+    let Some(target) = write else {
+        print!("{}", imported.spec);
+        return Ok(());
+    };
+    if target.exists() {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("{} already exists; remove it or choose another file", target.display()),
+                &format!("{} 已经存在; 请删除它或换一个文件", target.display()),
+            )
+            .to_string(),
+        )));
+    }
+    fs::write(target, &imported.spec)?;
+    logging::info(i18n::tr(
+        &format!(
+            "Converted {} into {}; use it with `prompt-changer set --spec {}`.",
+            imported.source.display(),
+            target.display(),
+            target.display()
+        ),
+        &format!(
+            "已将 {} 转换为 {}; 用 `prompt-changer set --spec {}` 使用它。",
+            imported.source.display(),
+            target.display(),
+            target.display()
+        ),
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::Spec;
+
+    /// 测试用的主题文件, 释放时删除
+    struct ThemeFile(PathBuf);
+
+    impl ThemeFile {
+        fn new(name: &str, content: &str) -> ThemeFile {
+            let path = env::temp_dir().join(format!("prompt-changer-import-test-{}-{}", std::process::id(), name));
+            fs::write(&path, content).unwrap();
+            ThemeFile(path)
+        }
+    }
+
+    impl Drop for ThemeFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// 只翻译一段提示符, 返回 (内容, 颜色) 与无法翻译的部分
+    fn translate(framework: Framework, prompt: &str) -> (Vec<(String, Option<String>)>, Vec<String>) {
+        let vars = BTreeMap::new();
+        let mut translator = Translator::new(framework, &vars, &[]);
+        translator.translate(prompt, 0);
+        (translator.pieces.into_iter().map(|p| (p.text, p.color)).collect(), translator.untranslated)
+    }
+
+    #[test]
+    fn assignments_unquote_values_like_the_shell() {
+        let content = "A='x y' B=2\nexport C=\"a\\\"b\"\nD+=$'\\e[0m'\nlocal E=\"multi\nline\"\nF=x; G=y\nfoo A=1\n";
+        let expected = [("A", false, "x y"), ("C", false, "a\"b"), ("D", true, "\u{1b}[0m"), ("E", false, "multi\nline"), ("F", false, "x")];
+        let found = assignments(content);
+        let found: Vec<(&str, bool, &str)> = found.iter().map(|(n, a, v)| (n.as_str(), *a, v.as_str())).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn zsh_escapes_become_bash_escapes() {
+        let cases = [
+            ("%n@%m %~ %# ", r"\u@\h \w \$ "),
+            ("%1~ %* %D{%H:%M} %%", r"\W \t \D{%H:%M} %"),
+            ("%/ $SHLVL", "$PWD ${SHLVL}"),
+        ];
+        for (zsh, bash) in cases {
+            let (pieces, untranslated) = translate(Framework::OhMyZsh, zsh);
+            assert_eq!(pieces, [(bash.to_string(), None)], "{}", zsh);
+            assert!(untranslated.is_empty(), "{}: {:?}", zsh, untranslated);
+        }
+        let (pieces, untranslated) = translate(Framework::OhMyZsh, "%F{blue}%3~%f %B%j%b");
+        assert_eq!(pieces, [(r"\w".to_string(), Some("blue".to_string())), (r" \j".to_string(), None)]);
+        assert_eq!(untranslated.len(), 2);
+    }
+
+    #[test]
+    fn omz_themes_import_as_valid_specs() {
+        let theme = ThemeFile::new(
+            "robbyrussell.zsh-theme",
+            r#"PROMPT="%(?:%{$fg_bold[green]%}➜ :%{$fg_bold[red]%}➜ )"
+PROMPT+=' %{$fg[cyan]%}%c%{$reset_color%} $(git_prompt_info)'
+RPROMPT='%T'
+
+ZSH_THEME_GIT_PROMPT_PREFIX="%{$fg_bold[blue]%}git:(%{$fg[red]%}"
+ZSH_THEME_GIT_PROMPT_SUFFIX="%{$reset_color%} "
+ZSH_THEME_GIT_PROMPT_DIRTY="%{$fg[blue]%}) %{$fg[yellow]%}✗"
+"#,
+        );
+        let imported = import(Framework::OhMyZsh, theme.0.as_os_str()).unwrap();
+        let spec = Spec::parse(&imported.spec, "test").unwrap();
+        let texts: Vec<(&str, Option<&str>)> = spec.segments.iter().map(|s| (s.text.as_str(), s.color.as_deref())).collect();
+        assert_eq!(texts[..3], [("➜  ", Some("green")), (r"\W", Some("cyan")), (" ", None)]);
+        assert!(texts[3].0.contains("git branch --show-current") && texts[3].0.contains("'git:('"), "{}", texts[3].0);
+        assert_eq!(imported.untranslated.len(), 4, "{:?}", imported.untranslated);
+        assert!(imported.untranslated.iter().any(|note| note.starts_with("RPROMPT")));
+    }
+
+    #[test]
+    fn themes_without_a_prompt_are_rejected() {
+        let theme = ThemeFile::new("empty.zsh-theme", "ZSH_THEME_GIT_PROMPT_PREFIX='('\n");
+        assert!(import(Framework::OhMyZsh, theme.0.as_os_str()).is_err());
+    }
+
+    #[test]
+    fn bash_framework_colors_become_segment_colors() {
+        let (pieces, untranslated) = translate(Framework::OhMyBash, r"${_omb_prompt_green}\u${_omb_prompt_normal}:${background_blue}\w\[\e[0m\]\$ ");
+        let expected = [(r"\u".to_string(), Some("green".to_string())), (":".to_string(), None), (r"\w".to_string(), None), (r"\$ ".to_string(), None)];
+        assert_eq!(pieces, expected);
+        assert!(untranslated.is_empty(), "{:?}", untranslated);
+        let (pieces, _) = translate(Framework::BashIt, r"\[\033[1;31m\]\h${reset_color} ${bright_cyan}\W");
+        assert_eq!(pieces, [(r"\h".to_string(), Some("red".to_string())), (" ".to_string(), None), (r"\W".to_string(), Some("bright-cyan".to_string()))]);
+    }
+
+    #[test]
+    fn bash_it_themes_import_with_notes_for_framework_functions() {
+        let theme = ThemeFile::new(
+            "bobby.theme.bash",
+            r#"SCM_THEME_PROMPT_PREFIX=" ${yellow}("
+SCM_THEME_PROMPT_SUFFIX="${yellow})"
+SCM_THEME_PROMPT_DIRTY=" ${red}✗"
+function prompt_command() {
+    PS1="${bold_green}\u@\h${normal} ${blue}\w${normal}$(scm_prompt_info) $(battery_percentage)\n\$ "
+}
+safe_append_prompt_command prompt_command
+"#,
+        );
+        let imported = import(Framework::BashIt, theme.0.as_os_str()).unwrap();
+        let spec = Spec::parse(&imported.spec, "test").unwrap();
+        let texts: Vec<(&str, Option<&str>)> = spec.segments.iter().map(|s| (s.text.as_str(), s.color.as_deref())).collect();
+        assert_eq!(texts[..3], [(r"\u@\h", Some("green")), (" ", None), (r"\w", Some("blue"))]);
+        assert!(texts[3].0.contains("printf '%s%s%s' ' ('"), "{}", texts[3].0);
+        assert_eq!(texts[4], (" \\n$ ", None));
+        let expected = [
+            "bold (${bold_...}) is not supported in specs and was left out",
+            "the colors inside the git prefix and suffix were merged into one",
+            "scm_prompt_info now shows only the branch; the dirty marker was left out",
+            "$(battery_percentage) calls a bash-it or theme function and was left out",
+        ];
+        assert_eq!(imported.untranslated, expected);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::color::ColorDepth;
+use crate::prompt::PromptSet;
+use crate::rc;
+use crate::shell;
+use crate::CliError;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 系统的 readline 配置; 有 ~/.inputrc 时 readline 不再读取它
+const SYSTEM_INPUTRC: &str = "/etc/inputrc";
+
+/// 写入 readline 设置的文件
+pub fn path() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".inputrc"))
+}
+
+/// 提示符的主色: 第一个颜色变量, 否则是提示符中第一个颜色编码
+fn accent(prompts: &PromptSet) -> Option<String> {
+    if let Some(slot) = prompts.colors.first() {
+        // inputrc 不能按终端能力选择, 用 256 色
+        return slot.dark.at(ColorDepth::Ansi256).map(str::to_string);
+    }
+    let re = Regex::new(r"\\(?:e|033|x1[bB])\[([0-9;]+)m").unwrap();
+    let sgr = re.captures_iter(&prompts.default).map(|c| c[1].to_string()).find(|sgr| sgr != "0" && sgr != "00");
+    sgr
+}
+
+/// 编辑模式的提示文字, 用 `\1 \2` 包裹颜色编码
+fn mode_string(text: &str, sgr: Option<&str>) -> String {
+    match sgr {
+        Some(sgr) => format!("\\1\\e[{}m\\2{}\\1\\e[0m\\2", sgr, text),
+        None => text.to_string(),
+    }
+}
+
+/// 与提示符配套的 readline 设置: 补全列表按 LS_COLORS 着色, vi 模式下在提示符前显示与主题同色的编辑模式
+pub fn settings(prompts: &PromptSet) -> String {
+    let (insert, normal) = prompts.vi_mode.clone().unwrap_or_else(|| ("(ins) ".to_string(), "(cmd) ".to_string()));
+    let sgr = accent(prompts);
+    let mut lines = vec![
+        "set colored-stats on".to_string(),
+        "set colored-completion-prefix on".to_string(),
+        "set show-mode-in-prompt on".to_string(),
+        "set emacs-mode-string \"\"".to_string(),
+    ];
+    lines.push(format!("set vi-ins-mode-string \"{}\"", mode_string(&insert, sgr.as_deref())));
+    lines.push(format!("set vi-cmd-mode-string \"{}\"", mode_string(&normal, sgr.as_deref())));
+    lines.join("\n")
+}
+
+/// 除受管理区块外是否还没有内容
+fn is_fresh(path: &Path) -> bool {
+    fs::read_to_string(path).map_or(true, |content| rc::remove_block(&content).unwrap_or(content).trim().is_empty())
+}
+
+/// 把设置写入 ~/.inputrc 的受管理区块, 返回写入的文件; 新建时先载入系统的配置, 以免它失效
+pub fn write(prompts: &PromptSet) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = path()?;
+    let mut body = settings(prompts);
+    if is_fresh(&path) && Path::new(SYSTEM_INPUTRC).is_file() {
+        body = format!("$include {}\n{}", SYSTEM_INPUTRC, body);
+    }
+    rc::write_block(&path, &body)?;
+    Ok(path)
+}
+
+/// 删除受管理区块, 文件因此变空时一并删除, 以免 readline 不再读取系统的配置; 返回是否有改动
+pub fn remove() -> Result<bool, Box<dyn std::error::Error>> {
+    let path = path()?;
+    if !rc::remove_block_from(&path)? {
+        return Ok(false);
+    }
+    if fs::read_to_string(&path).is_ok_and(|content| content.trim().is_empty()) {
+        fs::remove_file(&path)?;
+    }
+    Ok(true)
+}
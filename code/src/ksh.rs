@@ -0,0 +1,148 @@
+use crate::color;
+use crate::logging;
+use crate::prompt::{self, PromptSet, Segment, SOURCE_MARKER};
+use crate::shell::{single_quote, Shell};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// ksh 中没有对应写法的 bash 转义
+pub const UNSUPPORTED_ESCAPES: [&str; 4] = ["j", "l", "#", "r"];
+
+/// POSIX sh 中没有对应写法的 bash 转义; dash 与 busybox ash 不把 PS1 中的 `!` 换成历史编号
+pub const POSIX_UNSUPPORTED_ESCAPES: [&str; 5] = ["j", "l", "#", "r", "!"];
+
+/// $ENV 指定的、交互式 ksh 与 sh 启动时读取的文件; 没有设置时 ksh93 读取 ~/.kshrc
+pub fn env_path(home: &Path) -> Option<PathBuf> {
+    let value = env::var("ENV").ok().filter(|value| !value.trim().is_empty())?;
+    // $ENV 常以单引号写在 .profile 中, 由 ksh 在启动时展开
+    let rest = ["$HOME", "${HOME}", "~"].iter().find_map(|prefix| value.strip_prefix(prefix));
+    let path = match rest {
+        Some(rest) => home.join(rest.trim_start_matches('/')),
+        None => PathBuf::from(&value),
+    };
+    logging::debug("shell", format!("$ENV is {}, so interactive shells read {}", value, path.display()));
+    Some(path)
+}
+
+/// 提示符中用到的变量与函数, 只用 POSIX 的写法; mksh 与 OpenBSD ksh 用 PS1 开头的 `\001\r` 标出不可打印的部分, ksh93 自己识别转义序列
+const SETUP: &str = "__prompt_changer_esc=$(printf '\\033')
+__prompt_changer_bel=$(printf '\\007')
+__prompt_changer_user=$(id -un 2>/dev/null)
+__prompt_changer_host=$(hostname 2>/dev/null || uname -n)
+__prompt_changer_sign='$'
+if [ \"$(id -u 2>/dev/null)\" = 0 ]; then
+    __prompt_changer_sign='#'
+fi
+__prompt_changer_np=
+__prompt_changer_start=
+case $KSH_VERSION in
+    *MIRBSD*|*'PD KSH'*)
+        __prompt_changer_np=$(printf '\\001')
+        __prompt_changer_start=$__prompt_changer_np$(printf '\\r')
+        ;;
+esac
+__prompt_changer_pwd() {
+    case $PWD in
+        \"$HOME\") echo '~' ;;
+        \"$HOME\"/*) echo \"~${PWD#\"$HOME\"}\" ;;
+        *) echo \"$PWD\" ;;
+    esac
+}
+__prompt_changer_basename() {
+    case $PWD in
+        \"$HOME\") echo '~' ;;
+        /) echo / ;;
+        *) echo \"${PWD##*/}\" ;;
+    esac
+}
+";
+
+/// 把 bash 的反斜杠转义翻译为 PS1 中在显示时展开的内容
+fn escape_word(shell: Shell, escape: &str) -> String {
+    let date = |fmt: &str| format!("$(date {})", single_quote(&format!("+{}", fmt)));
+    match escape {
+        "u" => "${__prompt_changer_user}".to_string(),
+        "h" => "${__prompt_changer_host%%.*}".to_string(),
+        "H" => "${__prompt_changer_host}".to_string(),
+        "w" => "$(__prompt_changer_pwd)".to_string(),
+        "W" => "$(__prompt_changer_basename)".to_string(),
+        "t" => date("%H:%M:%S"),
+        "T" => date("%I:%M:%S"),
+        "@" => date("%I:%M %p"),
+        "A" => date("%H:%M"),
+        "d" => date("%a %b %d"),
+        "$" => "${__prompt_changer_sign}".to_string(),
+        "s" => shell.display_name().to_string(),
+        "v" | "V" if shell == Shell::Ksh => "${KSH_VERSION}".to_string(),
+        // ksh 把 PS1 中的 `!` 换成历史编号
+        "!" if shell == Shell::Ksh => "!".to_string(),
+        "n" => "\n".to_string(),
+        "e" | "033" => "${__prompt_changer_esc}".to_string(),
+        "a" | "007" => "${__prompt_changer_bel}".to_string(),
+        "\\" => "\\\\".to_string(),
+        _ => {
+            if let Some(fmt) = escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}')) {
+                date(if fmt.is_empty() { "%X" } else { fmt })
+            } else if escape.len() == 3 && escape.chars().all(|c| c.is_digit(8)) {
+                format!("$(printf '\\{}')", escape)
+            } else {
+                String::new()
+            }
+        }
+    }
+}
+
+/// 把单个片段翻译为 PS1 中的内容; 颜色序列不在 `\[ \]` 中(`nested`)时同样标为不可打印
+fn word(shell: Shell, segment: &Segment, nested: bool) -> String {
+    let hidden = |inner: String| format!("${{__prompt_changer_np}}{}${{__prompt_changer_np}}", inner);
+    match segment {
+        Segment::Text(text) if shell == Shell::Ksh => text.replace('!', "!!"),
+        Segment::Text(text) => text.clone(),
+        Segment::Escape(escape) => escape_word(shell, escape),
+        Segment::NonPrinting(inner) => hidden(prompt::parse_bash(inner).iter().map(|s| word(shell, s, true)).collect()),
+        Segment::Color(color) => {
+            let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+            let code = format!("${{__prompt_changer_esc}}{}", rest);
+            if nested {
+                code
+            } else {
+                hidden(code)
+            }
+        }
+        Segment::Variable(name) => format!("${{{}}}", name),
+        Segment::Command(cmd) => format!("$({})", cmd),
+    }
+}
+
+/// 提示符翻译为 ksh 或 sh 的 PS1, 与 bash 一样在每次显示时展开
+fn translate(shell: Shell, prompt: &str) -> String {
+    prompt::parse_bash(prompt).iter().map(|s| word(shell, s, false)).collect()
+}
+
+/// ksh 或 sh 的提示符略去的设置
+pub fn notices(shell: Shell, prompts: &PromptSet) -> Vec<String> {
+    prompt::left_out_notice(shell.display_name(), &prompts.unsupported_extras(true)).into_iter().collect()
+}
+
+/// 写入 ksh 或 sh 受管理区块的代码: 第一行记下原始的 bash 风格提示符. 只支持按主机名切换的变体、标题与无障碍模式
+pub fn block_body(shell: Shell, prompts: &PromptSet) -> String {
+    let title = translate(shell, &prompt::bash_title(prompts.title.as_deref()));
+    // 无障碍模式下上一条命令失败时显示 `[err N]`; $? 在 PS1 展开时仍是上一条命令的结束状态
+    let err = if prompts.accessible { "$(__prompt_changer_err $?)" } else { "" };
+    let ps1 = |prompt: &str| single_quote(&format!("${{__prompt_changer_start}}{}{}{}", err, title, translate(shell, prompt)));
+    let mut body = format!("{}{}\n{}", SOURCE_MARKER, prompts.default, SETUP);
+    if prompts.accessible {
+        body += "__prompt_changer_err() {\n    [ \"$1\" -ne 0 ] && printf '[err %s] ' \"$1\"\n}\n";
+    }
+    body += &color::posix_setup(&prompts.colors, false);
+    body += &format!("PS1={}", ps1(&prompts.default));
+    if !prompts.hosts.is_empty() {
+        // 模式不能加引号, 否则通配符不生效; 写入 spec 时已限制了其中的字符
+        body += "\ncase \"$__prompt_changer_host\" in";
+        for (pattern, prompt) in &prompts.hosts {
+            body += &format!("\n    {})\n        PS1={}\n        ;;", pattern, ps1(prompt));
+        }
+        body += "\nesac";
+    }
+    body
+}
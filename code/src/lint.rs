@@ -0,0 +1,473 @@
+use crate::cmd;
+use crate::color;
+use crate::elvish;
+use crate::i18n;
+use crate::ksh;
+use crate::modules;
+use crate::oils;
+use crate::output::{self, Json};
+use crate::plugins;
+use crate::prompt::{self, Segment};
+use crate::shell::Shell;
+use crate::tcsh;
+use crate::xonsh;
+use regex::Regex;
+use std::env;
+use std::fmt;
+
+/// 问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// 输出时使用的带颜色标签
+    pub fn label(self) -> String {
+        match self {
+            Severity::Warning => color::paint(i18n::tr("warning", "警告"), "33"),
+            Severity::Error => color::paint(i18n::tr("error", "错误"), "1;31"),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// 提示符中的一个问题
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(message: String) -> Issue {
+        Issue { severity: Severity::Error, message }
+    }
+
+    fn warning(message: String) -> Issue {
+        Issue { severity: Severity::Warning, message }
+    }
+}
+
+/// bash 能识别的反斜杠转义
+const BASH_ESCAPES: [&str; 25] = [
+    "a", "d", "e", "h", "H", "j", "l", "n", "r", "s", "t", "T", "@", "A", "u", "v", "V", "w", "W", "!",
+    "#", "$", "\\", "[", "]",
+];
+
+/// 判断转义是否能被 bash 识别
+fn is_known_escape(escape: &str) -> bool {
+    BASH_ESCAPES.contains(&escape)
+        || (escape.starts_with("D{") && escape.ends_with('}'))
+        || (escape.len() == 3 && escape.chars().all(|c| c.is_digit(8)))
+}
+
+/// 检查一组片段, 把问题追加到 issues 中
+fn lint_segments(shell: Shell, segments: &[Segment], nested: bool, issues: &mut Vec<Issue>) {
+    for (idx, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::Escape(escape) if escape == "[" => {
+                if nested {
+                    issues.push(Issue::error(i18n::tr("nested \\[ inside a \\[ \\] pair", "\\[ \\] 中嵌套了 \\[").to_string()));
+                } else {
+                    issues.push(Issue::error(i18n::tr("unbalanced \\[: no matching \\] follows it", "\\[ 不成对: 后面没有对应的 \\]").to_string()));
+                }
+            }
+            Segment::Escape(escape) if escape == "]" => {
+                issues.push(Issue::error(i18n::tr("unbalanced \\]: no \\[ opens it", "\\] 不成对: 前面没有对应的 \\[").to_string()));
+            }
+            Segment::Escape(escape) if escape == "e" || escape == "033" => {
+                if let Some(Segment::Text(next)) = segments.get(idx + 1) {
+                    if let Some(rest) = next.strip_prefix('[') {
+                        let params: String = rest.chars().take_while(|c| c.is_ascii_digit() || *c == ';').collect();
+                        issues.push(Issue::error(
+                            i18n::tr(
+                                &format!("unterminated color code \\{}[{}: SGR sequences must end with `m`", escape, params),
+                                &format!("颜色代码 \\{}[{} 没有结束: SGR 序列必须以 `m` 结尾", escape, params),
+                            )
+                            .to_string(),
+                        ));
+                    }
+                }
+            }
+            Segment::Escape(escape) if shell == Shell::Ksh && ksh::UNSUPPORTED_ESCAPES.contains(&escape.as_str()) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in a ksh prompt", escape),
+                        &format!("\\{} 在 ksh 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if shell == Shell::Posix && ksh::POSIX_UNSUPPORTED_ESCAPES.contains(&escape.as_str()) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in a POSIX sh prompt", escape),
+                        &format!("\\{} 在 POSIX sh 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if shell == Shell::Elvish && elvish::is_unsupported(escape) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in an Elvish prompt", escape),
+                        &format!("\\{} 在 Elvish 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if shell == Shell::Cmd && cmd::is_unsupported(escape) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in a cmd prompt", escape),
+                        &format!("\\{} 在 cmd 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if shell == Shell::Oils && oils::UNSUPPORTED_ESCAPES.contains(&escape.as_str()) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in an OSH prompt", escape),
+                        &format!("\\{} 在 OSH 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if shell == Shell::Xonsh && xonsh::is_unsupported(escape) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in a xonsh prompt", escape),
+                        &format!("\\{} 在 xonsh 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if shell == Shell::Tcsh && tcsh::is_unsupported(escape) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("\\{} has no equivalent in a tcsh prompt", escape),
+                        &format!("\\{} 在 tcsh 提示符中没有对应的写法", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Escape(escape) if !is_known_escape(escape) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("unknown escape sequence \\{}; bash prints it literally", escape),
+                        &format!("未知的转义序列 \\{}; bash 会原样输出它", escape),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Color(color) if !nested => {
+                issues.push(Issue::warning(
+                    i18n::tr(
+                        &format!("color code `{}` is not wrapped in \\[ \\]; bash will miscount the prompt width", color),
+                        &format!("颜色代码 `{}` 没有包在 \\[ \\] 中; bash 会算错提示符的宽度", color),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Command(cmd) if cmd.trim().is_empty() => {
+                issues.push(Issue::error(i18n::tr("empty command substitution $()", "空的命令替换 $()").to_string()));
+            }
+            Segment::Command(cmd) if matches!(shell, Shell::Tcsh | Shell::Cmd) => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("{} cannot run commands while drawing the prompt, so `{}` would be left out", shell.display_name(), cmd),
+                        &format!("{} 画提示符时不能运行命令, 因此 `{}` 会被省略", shell.display_name(), cmd),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Command(cmd) if matches!(shell, Shell::Ksh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils) && modules::called(cmd).is_some() => {
+                let name = modules::called(cmd).map_or("", |m| m.name);
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("the `{}` module is not available in {}", name, shell.display_name()),
+                        &format!("`{}` 模块在 {} 中不可用", name, shell.display_name()),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Command(cmd) if matches!(shell, Shell::Ksh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils) && plugins::called(cmd).is_some() => {
+                let plugin = plugins::called(cmd).unwrap_or_default();
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("plugins such as `{}` are not available in {}", plugin, shell.display_name()),
+                        &format!("`{}` 这样的插件在 {} 中不可用", plugin, shell.display_name()),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Command(cmd) if plugins::missing_wasmtime(cmd).is_some() => {
+                let plugin = plugins::called(cmd).unwrap_or_default();
+                issues.push(Issue::warning(
+                    i18n::tr(
+                        &format!("the WASM plugin `{}` needs wasmtime, which is not on PATH, so it shows nothing", plugin),
+                        &format!("WASM 插件 `{}` 需要 wasmtime, 但 PATH 中没有, 因此它不会显示任何内容", plugin),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Command(cmd) if modules::called(cmd).is_some() || plugins::called(cmd).is_some() => {}
+            Segment::Command(cmd) if shell == Shell::Pwsh => {
+                issues.push(Issue::warning(
+                    i18n::tr(
+                        &format!("runs `{}` as PowerShell (not bash) every time the prompt is drawn", cmd),
+                        &format!("每次画提示符时都以 PowerShell(而不是 bash)运行 `{}`", cmd),
+                    )
+                    .to_string(),
+                ));
+            }
+            // xonsh 用自带的 {curr_branch} 字段显示 git 分支
+            Segment::Command(cmd) if shell == Shell::Xonsh && xonsh::is_git_branch(cmd) => {}
+            Segment::Command(cmd) if matches!(shell, Shell::Elvish | Shell::Xonsh) => {
+                issues.push(Issue::warning(
+                    i18n::tr(
+                        &format!("runs `{}` through sh every time the prompt is drawn", cmd),
+                        &format!("每次画提示符时都通过 sh 运行 `{}`", cmd),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Command(cmd) => {
+                issues.push(Issue::warning(
+                    i18n::tr(
+                        &format!("runs `{}` every time the prompt is drawn", cmd),
+                        &format!("每次画提示符时都运行 `{}`", cmd),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::Text(text) if text.contains("$(") || text.contains('`') => {
+                issues.push(Issue::error(
+                    i18n::tr(
+                        &format!("unterminated command substitution in {:?}", text),
+                        &format!("{:?} 中的命令替换没有结束", text),
+                    )
+                    .to_string(),
+                ));
+            }
+            Segment::NonPrinting(inner) => {
+                lint_segments(shell, &prompt::parse_bash(inner), true, issues);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 检查颜色在提示符结尾是否被重置
+fn lint_color_reset(prompt: &str, issues: &mut Vec<Issue>) {
+    let sgr = Regex::new(r"\\(?:e|033)\[([0-9;]*)m").unwrap();
+    if let Some(last) = sgr.captures_iter(prompt).last() {
+        let codes = &last[1];
+        if !(codes.is_empty() || codes.split(';').all(|c| c.parse::<u32>() == Ok(0))) {
+            issues.push(Issue::warning(
+                i18n::tr(
+                    "the prompt leaves a color active at the end; add \\[\\e[0m\\] so typed commands are not colored",
+                    "提示符结尾时颜色仍然有效; 请加上 \\[\\e[0m\\], 以免输入的命令也被着色",
+                )
+                .to_string(),
+            ));
+        }
+    }
+}
+
+/// 终端画成 2 列宽的字符: 东亚宽字符与默认以 emoji 样式显示的字符
+const WIDE_RANGES: [(u32, u32); 14] = [
+    (0x1100, 0x115F),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x3FFFD),
+];
+
+/// 东亚宽度不确定的常见字符: CJK 语言环境下的终端常把它们画成 2 列宽
+const AMBIGUOUS_RANGES: [(u32, u32); 9] = [
+    (0x00A7, 0x00A7),
+    (0x00B0, 0x00B1),
+    (0x00B7, 0x00B7),
+    (0x2022, 0x2026),
+    (0x2190, 0x2199),
+    (0x2460, 0x24E9),
+    (0x2500, 0x257F),
+    (0x25A0, 0x25FF),
+    (0x2605, 0x2606),
+];
+
+/// 判断字符是否落在某个区间内
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&(c as u32)))
+}
+
+/// 文字在终端中占的列数: 宽字符(如中文)占两列
+pub fn columns(text: &str) -> usize {
+    text.chars().map(|c| if in_ranges(c, &WIDE_RANGES) { 2 } else { 1 }).sum()
+}
+
+/// 检查 emoji 与宽字符: 放进 `\[ \]` 的可见字符, 以及各终端画出的宽度与 bash 计算的不一致的字符
+fn lint_width(prompt: &str, issues: &mut Vec<Issue>) {
+    let mut text = String::new();
+    for segment in prompt::parse_bash(prompt) {
+        match segment {
+            Segment::Text(t) => text += &t,
+            // OSC 序列(如窗口标题)中的文字不会显示在提示符里
+            Segment::NonPrinting(inner) if !inner.contains("\\e]") && !inner.contains("\\033]") => {
+                if let Some(c) = inner.chars().find(|c| !c.is_ascii() && !c.is_control()) {
+                    issues.push(Issue::error(
+                        i18n::tr(
+                            &format!("`{}` is inside \\[ \\], so bash counts it as zero columns although the terminal draws it; move it out", c),
+                            &format!("`{}` 在 \\[ \\] 中, bash 把它算作零列, 但终端会画出它; 请把它移出来", c),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    let chars: Vec<char> = text.chars().collect();
+    if let Some(i) = chars.iter().position(|c| *c == '\u{FE0F}') {
+        let base: String = chars[i.saturating_sub(1)..=i].iter().collect();
+        issues.push(Issue::warning(
+            i18n::tr(
+                &format!("`{}` uses an emoji variation selector; bash counts it as 1 column like the plain character, but kitty, WezTerm and iTerm2 draw it 2 columns wide, so the cursor may drift", base),
+                &format!("`{}` 使用了 emoji 变体选择符; bash 像普通字符一样把它算作 1 列, 但 kitty、WezTerm 和 iTerm2 把它画成 2 列宽, 光标可能错位", base),
+            )
+            .to_string(),
+        ));
+    }
+    if chars.iter().any(|c| *c == '\u{200D}' || ('\u{1F3FB}'..='\u{1F3FF}').contains(c)) {
+        issues.push(Issue::warning(
+            i18n::tr(
+                "the prompt contains a joined emoji (ZWJ sequence or skin tone); bash counts each part separately and terminals other than kitty, WezTerm and iTerm2 usually draw the parts side by side",
+                "提示符中含有组合 emoji(ZWJ 序列或肤色); bash 分别计算每个部分, 除 kitty、WezTerm 和 iTerm2 以外的终端通常把各部分并排画出",
+            )
+                .to_string(),
+        ));
+    }
+    if chars.iter().any(|c| ('\u{1F1E6}'..='\u{1F1FF}').contains(c)) {
+        issues.push(Issue::warning(
+            i18n::tr(
+                "the prompt contains a flag emoji; its width differs between terminals (1 or 2 columns per letter), so line editing may misplace the cursor",
+                "提示符中含有旗帜 emoji; 它的宽度因终端而异(每个字母 1 或 2 列), 行编辑时光标可能错位",
+            )
+                .to_string(),
+        ));
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    let cjk = ["zh", "ja", "ko"].iter().any(|p| locale.starts_with(p));
+    if let Some(c) = chars.iter().find(|c| in_ranges(**c, &AMBIGUOUS_RANGES)).filter(|_| cjk) {
+        issues.push(Issue::warning(
+            i18n::tr(
+                &format!("`{}` has an ambiguous width; terminals that draw ambiguous characters 2 columns wide (common with CJK locales) disagree with bash about the prompt length", c),
+                &format!("`{}` 的宽度不确定; 把这类字符画成 2 列宽的终端(在中日韩语言环境中很常见)与 bash 对提示符长度的计算不一致", c),
+            )
+            .to_string(),
+        ));
+    }
+    let wide = chars.iter().filter(|c| in_ranges(**c, &WIDE_RANGES)).count();
+    if wide > 0 && !locale.to_ascii_lowercase().replace('-', "").contains("utf8") {
+        issues.push(Issue::warning(
+            i18n::tr(
+                &format!("the prompt has {} wide character(s) but the locale is not UTF-8, so bash cannot measure them", wide),
+                &format!("提示符中有 {} 个宽字符, 但语言环境不是 UTF-8, bash 无法计算它们的宽度", wide),
+            )
+            .to_string(),
+        ));
+    }
+}
+
+/// 检查会破坏配置文件的字符; 单引号不算问题, `set` 写入时会用 `shell::single_quote` 转义
+fn lint_quoting(prompt: &str, issues: &mut Vec<Issue>) {
+    if prompt.chars().any(|c| c.is_control()) {
+        issues.push(Issue::error(
+            i18n::tr(
+                "the prompt contains control characters (such as a raw newline); use escapes like \\n instead",
+                "提示符中含有控制字符(如直接的换行); 请改用 \\n 这样的转义",
+            )
+            .to_string(),
+        ));
+    }
+}
+
+/// 检查提示符本身的转义、颜色与命令替换
+pub fn check_prompt(shell: Shell, prompt: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    lint_segments(shell, &prompt::parse_bash(prompt), false, &mut issues);
+    lint_color_reset(prompt, &mut issues);
+    lint_width(prompt, &mut issues);
+    issues
+}
+
+/// 检查提示符字符串, 返回发现的问题
+pub fn lint(shell: Shell, prompt: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    lint_quoting(prompt, &mut issues);
+    issues.extend(check_prompt(shell, prompt));
+    issues
+}
+
+/// 打印检查结果, 返回错误数量
+pub fn run(shell: Shell, prompt: &str) -> usize {
+    let issues = lint(shell, prompt);
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    if output::is_json() {
+        let list = issues
+            .iter()
+            .map(|issue| Json::Object(vec![("severity", issue.severity.to_string().into()), ("message", issue.message.as_str().into())]))
+            .collect();
+        output::print(
+            "lint",
+            vec![
+                ("shell", shell.display_name().to_ascii_lowercase().into()),
+                ("prompt", prompt.into()),
+                ("issues", Json::Array(list)),
+                ("errors", errors.into()),
+            ],
+        );
+        return errors;
+    }
+    for issue in &issues {
+        println!("{}: {}", issue.severity.label(), issue.message);
+    }
+    if issues.is_empty() {
+        println!("{}", i18n::tr("No problems found.", "没有发现问题。"));
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_quotes_are_left_to_set() {
+        assert!(lint(Shell::Bash, "it's \\w \\$ ").is_empty());
+        assert!(lint(Shell::Bash, "a\nb").iter().any(|issue| issue.severity == Severity::Error));
+    }
+}
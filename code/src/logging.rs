@@ -0,0 +1,109 @@
+use crate::CliError;
+use crate::i18n;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// 日志级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    /// 成功信息等平常的输出, `--quiet` 时不显示
+    Info,
+    /// 探测了哪些文件、写入了什么
+    Debug,
+    /// 写入的完整内容
+    Trace,
+}
+
+impl Level {
+    /// 从 PROMPT_CHANGER_LOG 中的名称解析级别
+    fn from_name(name: &str) -> Result<Level, CliError> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            other => Err(CliError(
+                i18n::tr(
+                    &format!("Unknown log level `{}` in PROMPT_CHANGER_LOG; use error, warn, info, debug or trace", other),
+                    &format!("PROMPT_CHANGER_LOG 中的日志级别 `{}` 未知; 可用 error、warn、info、debug 或 trace", other),
+                )
+                .to_string(),
+            )),
+        }
+    }
+}
+
+/// 各模块的日志级别, 如 `PROMPT_CHANGER_LOG=info,rc=trace`
+#[derive(Debug)]
+struct Filter {
+    default: Level,
+    targets: Vec<(String, Level)>,
+}
+
+impl Filter {
+    /// 解析 `级别` 与 `模块=级别` 组成的逗号分隔列表
+    fn parse(spec: &str, default: Level) -> Result<Filter, CliError> {
+        let mut filter = Filter { default, targets: Vec::new() };
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((target, level)) => filter.targets.push((target.trim().to_string(), Level::from_name(level)?)),
+                None => filter.default = Level::from_name(part)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// 该模块的该级别日志是否显示
+    fn enabled(&self, target: &str, level: Level) -> bool {
+        let max = self.targets.iter().rev().find(|(t, _)| t == target).map_or(self.default, |(_, l)| *l);
+        level <= max
+    }
+}
+
+/// 生效的日志过滤规则
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+/// 按 `-q`、`-v` 的次数与 PROMPT_CHANGER_LOG 设置日志级别, 环境变量优先
+pub fn init(quiet: bool, verbose: u64) -> Result<(), CliError> {
+    let default = match (quiet, verbose) {
+        (true, _) => Level::Warn,
+        (false, 0) => Level::Info,
+        (false, 1) => Level::Debug,
+        (false, _) => Level::Trace,
+    };
+    let filter = match env::var("PROMPT_CHANGER_LOG") {
+        Ok(spec) => Filter::parse(&spec, default)?,
+        Err(_) => Filter { default, targets: Vec::new() },
+    };
+    FILTER.set(filter).map_err(|_| CliError(i18n::tr("Logging is already set up", "日志已经设置过了").to_string()))
+}
+
+/// 该模块的该级别日志是否显示
+fn enabled(target: &str, level: Level) -> bool {
+    FILTER.get_or_init(|| Filter { default: Level::Info, targets: Vec::new() }).enabled(target, level)
+}
+
+/// 打印平常的输出, 如操作成功的信息
+pub fn info(message: impl Display) {
+    if enabled("main", Level::Info) {
+        println!("{}", message);
+    }
+}
+
+/// 打印调试信息到标准错误
+pub fn debug(target: &str, message: impl Display) {
+    if enabled(target, Level::Debug) {
+        eprintln!("debug[{}]: {}", target, message);
+    }
+}
+
+/// 打印最详细的跟踪信息到标准错误
+pub fn trace(target: &str, message: impl Display) {
+    if enabled(target, Level::Trace) {
+        eprintln!("trace[{}]: {}", target, message);
+    }
+}
@@ -1,10 +1,67 @@
-use clap::{Command, Arg};
-use regex::Regex;
-use std::io::{Write, BufReader, BufRead, stdin};
-use std::fs::OpenOptions;
-use dirs::home_dir; // 添加 dirs crate 的引用
+use clap::{Command, Arg, ArgMatches, ValueHint};
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
 use std::process;
 
+mod bench;
+mod cleanup;
+mod cmd;
+mod clipboard;
+mod color;
+mod completions;
+mod config;
+mod conflicts;
+mod docker;
+mod doctor;
+mod dotfiles;
+mod elvish;
+mod exit;
+mod history;
+mod hooks;
+mod i18n;
+mod icons;
+mod import;
+mod inputrc;
+mod ksh;
+mod lint;
+mod logging;
+mod migrate;
+mod modules;
+mod oils;
+mod output;
+mod palette;
+mod picker;
+mod plugins;
+mod policy;
+mod powershell;
+mod preview;
+mod prompt;
+mod rc;
+mod script;
+mod remote;
+mod share;
+mod shell;
+mod show;
+mod spec;
+mod suggest;
+mod swatches;
+mod tcsh;
+mod terminal;
+mod themes;
+mod tmux;
+mod verify;
+mod wizard;
+mod xonsh;
+
+use i18n::Lang;
+use hooks::Hook;
+use import::Framework;
+use color::ColorMode;
+use output::OutputFormat;
+use prompt::PromptSet;
+use shell::{InstallMode, Shell};
+use terminal::Terminal;
+
 /// 代表一个命令行参数错误
 #[derive(Debug)]
 struct CliError(String);
@@ -17,85 +74,1848 @@ impl std::fmt::Display for CliError {
 
 impl std::error::Error for CliError {}
 
-/// 更新 Bash 的提示符
-fn update_bash_prompt(new_prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let home = home_dir().ok_or_else(|| CliError("Failed to get home directory".to_string()))?;
-    let config_path = home.join(".bashrc");
-    let mut file = OpenOptions::new().append(true).open(config_path)?;
-    writeln!(file, "PS1='{}'", new_prompt)?;
-    Ok(())
-}
-
-/// 更新 Fish 的提示符
-fn update_fish_prompt(new_prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let home = home_dir().ok_or_else(|| CliError("Failed to get home directory".to_string()))?;
-    let config_path = home.join(".config/fish/config.fish");
-    let mut file = OpenOptions::new().append(true).open(config_path)?;
-    writeln!(file, "set -gx fish_prompt '{}'", new_prompt)?;
-    Ok(())
-}
-
-/// 读取用户输入的新提示符
-fn read_new_prompt() -> Result<String, Box<dyn std::error::Error>> {
-    print!("Enter the new prompt format: ");
-    std::io::stdout().flush()?;
-    let mut reader = BufReader::new(stdin());
-    let mut input = String::new();
-    reader.read_line(&mut input)?;
-    Ok(input.trim().to_string())
-}
-
-/// 验证提示符格式
-fn validate_prompt(prompt: &str) -> Result<(), CliError> {
-    let re = Regex::new(r"^[^\x00-\x1F\x7F]*$").map_err(|_| CliError("Invalid regex".to_string()))?;
-    if !re.is_match(prompt) {
-        Err(CliError("The prompt contains invalid characters.".to_string()))
+/// `--shell` 参数
+fn shell_arg() -> Arg<'static> {
+    Arg::new("shell")
+        .short('s')
+        .long("shell")
+        .value_name("SHELL")
+        .help("Choose the shell to change the prompt for (default: `shell` in the config file) \
+               [env: PROMPT_CHANGER_SHELL]")
+        .possible_values(Shell::NAMES)
+}
+
+/// `--force` 参数
+fn force_arg() -> Arg<'static> {
+    Arg::new("force")
+        .long("force")
+        .help("Apply the prompt even if validation finds errors [env: PROMPT_CHANGER_FORCE=1]")
+}
+
+/// `--copy` 参数
+fn copy_arg() -> Arg<'static> {
+    Arg::new("copy")
+        .long("copy")
+        .help("Copy the generated script to the clipboard instead, to paste into your dotfiles by hand")
+}
+
+/// `--install-mode` 参数
+fn install_mode_arg() -> Arg<'static> {
+    Arg::new("install-mode")
+        .long("install-mode")
+        .value_name("MODE")
+        .possible_values(InstallMode::NAMES)
+        .help("Write the prompt into the rc file (append) or into a separate sourced file (drop-in); \
+               defaults to `install-mode` in the config file, then to how it is installed now \
+               [env: PROMPT_CHANGER_INSTALL_MODE]")
+}
+
+/// `--spec` 参数
+fn spec_arg() -> Arg<'static> {
+    Arg::new("spec")
+        .long("spec")
+        .value_name("FILE")
+        .value_hint(ValueHint::FilePath)
+        .allow_invalid_utf8(true)
+        .help("Build the prompt from a TOML spec file (or an installed theme's name), including per-host overrides, instead of asking \
+               [env: PROMPT_CHANGER_SPEC, used by set]")
+}
+
+/// `import` 的主题参数
+fn theme_arg() -> Arg<'static> {
+    Arg::new("theme")
+        .value_name("THEME")
+        .value_hint(ValueHint::FilePath)
+        .required(true)
+        .allow_invalid_utf8(true)
+}
+
+/// `import` 的 `--write` 参数
+fn theme_write_arg() -> Arg<'static> {
+    Arg::new("write")
+        .long("write")
+        .value_name("FILE")
+        .value_hint(ValueHint::FilePath)
+        .allow_invalid_utf8(true)
+        .help("Save the spec to FILE instead of printing it")
+}
+
+/// `--reload` 参数
+fn reload_arg() -> Arg<'static> {
+    Arg::new("reload")
+        .long("reload")
+        .help("After writing, start the shell with the new config to check that it loads without errors")
+}
+
+/// `--check` 参数
+fn check_arg() -> Arg<'static> {
+    Arg::new("check")
+        .long("check")
+        .help("Write nothing; exit with 0 if the config already has this prompt and 6 if applying it would change files")
+}
+
+/// `--disable-conflicts` 参数
+fn disable_conflicts_arg() -> Arg<'static> {
+    Arg::new("disable-conflicts")
+        .long("disable-conflicts")
+        .help("Comment out init lines of prompt frameworks (Starship, oh-my-bash, bash-git-prompt, ...) that would override the new prompt")
+}
+
+/// `--no-contrast-check` 参数
+fn no_contrast_check_arg() -> Arg<'static> {
+    Arg::new("no-contrast-check")
+        .long("no-contrast-check")
+        .help("Do not warn about spec segments whose text color is hard to read on their background")
+}
+
+/// `--ascii` 参数
+fn ascii_arg() -> Arg<'static> {
+    Arg::new("ascii")
+        .long("ascii")
+        .help("Show ASCII stand-ins for the spec's Nerd Font icons, for terminals without a patched font \
+               [env: PROMPT_CHANGER_ASCII=1]")
+}
+
+/// `--title` 参数
+fn title_arg() -> Arg<'static> {
+    Arg::new("title")
+        .long("title")
+        .value_name("TEMPLATE")
+        .help("Also set the terminal title from a template such as '\\u@\\h: \\w'")
+}
+
+/// `--no-title` 参数
+fn no_title_arg() -> Arg<'static> {
+    Arg::new("no-title")
+        .long("no-title")
+        .conflicts_with("title")
+        .help("Do not let the prompt set the terminal title, for terminals that manage it themselves")
+}
+
+/// `--report-cwd` 参数
+fn report_cwd_arg() -> Arg<'static> {
+    Arg::new("report-cwd")
+        .long("report-cwd")
+        .help("Report the current directory to the terminal (OSC 7) so new tabs open in the same place")
+}
+
+/// `--semantic-marks` 参数
+fn semantic_marks_arg() -> Arg<'static> {
+    Arg::new("semantic-marks")
+        .long("semantic-marks")
+        .help("Mark the prompt, input and command output (OSC 133) so the terminal can jump between prompts")
+}
+
+/// `--screen-compat` 参数
+fn screen_compat_arg() -> Arg<'static> {
+    Arg::new("screen-compat")
+        .long("screen-compat")
+        .help("Inside GNU screen or mosh, fall back to 256/16 colors and leave out the title and OSC 7/133 sequences")
+}
+
+/// `--terminal` 参数
+fn terminal_arg() -> Arg<'static> {
+    Arg::new("terminal")
+        .long("terminal")
+        .value_name("TERMINAL")
+        .possible_values(Terminal::NAMES)
+        .help("Include the shell integration of iTerm2, WezTerm or kitty (auto: whichever one the shell runs in); \
+               also turns on --report-cwd and --semantic-marks")
+}
+
+/// 读取 `--spec` 指定的描述文件, 除非给出 `--no-contrast-check`, 否则提示对比度过低的片段
+fn load_spec(matches: &ArgMatches, default: Option<&Path>) -> Option<spec::Spec> {
+    let value = matches.value_of_os("spec").map(Path::new).or(default)?;
+    let spec = themes::load(value).unwrap_or_else(|err| {
+        i18n::error(&err);
+        process::exit(match exit::code_for(err.as_ref()) {
+            exit::FAILURE => exit::INVALID_PROMPT,
+            code => code,
+        });
+    });
+    Some(checked_spec(matches, spec))
+}
+
+/// 读取后的描述文件按命令行与环境调整, 并提示图标与对比度的问题
+fn checked_spec(matches: &ArgMatches, mut spec: spec::Spec) -> spec::Spec {
+    if output::is_accessible() {
+        spec.make_accessible();
+    }
+    spec.ascii |= matches.is_present("ascii") || config::env_flag("ASCII");
+    if spec.uses_icons() {
+        if let Some(reason) = icons::tofu_reason() {
+            i18n::warning(i18n::tr(
+                &format!("the spec's icons will likely show as boxes because {}; pass --ascii to use plain text", reason),
+                &format!("描述文件中的图标很可能显示为方框, 因为{}; 使用 --ascii 改用纯文本", reason),
+            ));
+        }
+    }
+    if !matches.is_present("no-contrast-check") {
+        let warnings = spec.contrast_warnings();
+        for warning in &warnings {
+            i18n::warning(warning);
+        }
+        if !warnings.is_empty() && !output::is_json() {
+            eprintln!("{}", i18n::tr("Pass --no-contrast-check to hide these warnings.", "使用 --no-contrast-check 可不显示这些警告。"));
+        }
+    }
+    spec
+}
+
+/// `set -` 或 `--stdin-format`: 从标准输入读取描述文件或提示符字符串; 只给出 `-` 时是提示符字符串
+fn stdin_prompts(matches: &ArgMatches) -> Option<PromptSet> {
+    let format = match matches.value_of("stdin-format") {
+        Some(format) => format,
+        None if matches.is_present("stdin") => "string",
+        None => return None,
+    };
+    let mut content = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut content) {
+        i18n::error(i18n::tr(&format!("cannot read standard input: {}", err), &format!("无法读取标准输入: {}", err)));
+        process::exit(exit::INVALID_PROMPT);
+    }
+    if format == "toml" {
+        let spec = spec::Spec::parse(&content, "from standard input").unwrap_or_else(|err| {
+            i18n::error(&err);
+            process::exit(exit::INVALID_PROMPT);
+        });
+        return Some(checked_spec(matches, spec).prompts().unwrap_or_else(|err| {
+            i18n::error(err);
+            process::exit(exit::INVALID_PROMPT);
+        }));
+    }
+    // 只去掉 echo 等加上的换行, 保留提示符末尾的空格
+    let prompt = content.strip_suffix('\n').map_or(content.as_str(), |p| p.strip_suffix('\r').unwrap_or(p));
+    if prompt.is_empty() {
+        i18n::error(i18n::tr("standard input is empty; pipe in the prompt string", "标准输入为空; 请用管道传入提示符字符串"));
+        process::exit(exit::INVALID_PROMPT);
+    }
+    Some(PromptSet::single(prompt))
+}
+
+/// 需要交互时标准输入不是终端(如在管道或脚本中)则直接失败, 而不是等待或读到空的回答
+fn require_terminal(what: &str) {
+    if io::stdin().is_terminal() {
+        return;
+    }
+    i18n::error(i18n::tr(
+        &format!("{} needs an interactive terminal, but standard input is not one; pass --spec, or pipe the prompt in with `-` or --stdin-format toml", what),
+        &format!("{}需要交互式终端, 但标准输入不是终端; 请使用 --spec, 或用 `-` 或 --stdin-format toml 通过管道传入提示符", what),
+    ));
+    process::exit(exit::INVALID_PROMPT);
+}
+
+/// 读取 `--spec` 指定的描述文件, 生成提示符
+fn spec_prompts(matches: &ArgMatches, default: Option<&Path>) -> Option<PromptSet> {
+    let spec = load_spec(matches, default)?;
+    Some(spec.prompts().unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::INVALID_PROMPT);
+    }))
+}
+
+/// 是否指定了 `--force` 或 PROMPT_CHANGER_FORCE
+fn forced(matches: &ArgMatches) -> bool {
+    matches.is_present("force") || config::env_flag("FORCE")
+}
+
+/// 从参数中取出已选择的 shell, 未指定时使用配置文件中的 shell
+fn selected_shell(matches: &ArgMatches) -> Shell {
+    let Some(name) = matches.value_of("shell").or(config::get().shell.as_deref()) else {
+        i18n::error(i18n::tr(
+            "choose a shell with --shell, or set a default with `prompt-changer config set shell bash`",
+            "请用 --shell 选择 shell, 或用 `prompt-changer config set shell bash` 设置默认的 shell",
+        ));
+        process::exit(exit::INVALID_PROMPT);
+    };
+    Shell::from_name(name).unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::UNSUPPORTED_SHELL);
+    })
+}
+
+/// 写入前的检查: 安装方式、配置文件与权限
+fn prepare_write(matches: &ArgMatches, shell: Shell) {
+    if let Some(mode) = matches.value_of("install-mode").or(config::get().install_mode.as_deref()) {
+        if let Err(err) = InstallMode::from_name(mode).and_then(shell::set_install_mode) {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+    if let Err(err) = shell.check_config_file() {
+        if forced(matches) {
+            i18n::warning(err);
+        } else {
+            i18n::error(format!("{} {}", err, i18n::tr("(use --force to write to it anyway)", "(使用 --force 仍然写入)")));
+            process::exit(exit::FAILURE);
+        }
+    }
+    if let Err(err) = shell::check_privileges() {
+        i18n::error(err);
+        process::exit(exit::PERMISSION_DENIED);
+    }
+}
+
+/// 用 `\[ \]` 包裹向导输入的颜色编码
+fn wrap_wizard_colors(new_prompt: &str) -> String {
+    let (new_prompt, wrapped) = prompt::wrap_colors(new_prompt);
+    if wrapped > 0 {
+        output::notice(i18n::tr(
+            &format!("Wrapped {} color code(s) in \\[ \\] so bash measures the prompt width correctly.", wrapped),
+            &format!("已用 \\[ \\] 包裹 {} 个颜色编码, 以便 bash 正确计算提示符宽度。", wrapped),
+        ));
+    }
+    new_prompt
+}
+
+/// 交互式构建新的提示符并写入配置文件
+fn run_set(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    let mut copy = matches.is_present("copy");
+    let mut write_inputrc = matches.is_present("inputrc");
+    if write_inputrc && shell != Shell::Bash {
+        i18n::error(i18n::tr("--inputrc only applies to bash", "--inputrc 只适用于 bash"));
+        process::exit(exit::FAILURE);
+    }
+    if !copy {
+        prepare_write(matches, shell);
+    }
+    let piped = stdin_prompts(matches);
+    let new_prompt = match piped.or_else(|| spec_prompts(matches, config::get().spec.as_deref())) {
+        Some(prompts) => prompts,
+        None if matches.is_present("random") => {
+            require_terminal("--random");
+            random_theme(matches)
+        }
+        None if matches.is_present("pick") => picked_prompt(matches, shell),
+        None => {
+            require_terminal(i18n::tr("The prompt wizard", "提示符向导"));
+            match shell {
+                Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => wizard::bash_hint(),
+                Shell::Fish => wizard::fish_hint(),
+                Shell::Pwsh => wizard::powershell_hint(),
+            }
+            let new_prompt = match wizard::build_prompt() {
+                Ok(Some(new_prompt)) => new_prompt,
+                Ok(None) => {
+                    eprintln!("{}", i18n::tr("Cancelled; nothing was written.", "已取消, 未写入任何内容。"));
+                    process::exit(exit::FAILURE);
+                }
+                Err(err) => {
+                    eprintln!("{}", i18n::tr(&format!("Error reading prompt: {}", err), &format!("读取提示符出错: {}", err)));
+                    process::exit(exit::FAILURE);
+                }
+            };
+            copy = copy || wizard::ask_copy().unwrap_or(false);
+            if shell == Shell::Bash && !copy && !write_inputrc {
+                write_inputrc = wizard::ask_inputrc().unwrap_or(false);
+            }
+            PromptSet::single(&wrap_wizard_colors(&new_prompt))
+        }
+    };
+    let new_prompt = with_integration(matches, new_prompt);
+    if copy {
+        check_prompts(matches, shell, &new_prompt);
+        copy_script(shell, &new_prompt, "prompt-changer set");
+        return;
+    }
+    let settings = write_inputrc.then(|| new_prompt.clone());
+    write_prompt(matches, shell, new_prompt, "set");
+    if let Some(prompts) = settings {
+        match inputrc::write(&prompts) {
+            Ok(path) => logging::info(i18n::tr(
+                &format!("Wrote matching readline settings to {}; they apply to new shells.", path.display()),
+                &format!("已把配套的 readline 设置写入 {}, 新开的 shell 中生效。", path.display()),
+            )),
+            Err(err) => {
+                i18n::error(err);
+                process::exit(exit::FAILURE);
+            }
+        }
+    }
+}
+
+/// 把独立的提示符脚本复制到剪贴板, 不写入配置文件
+fn copy_script(shell: Shell, prompts: &PromptSet, source: &str) {
+    shell.notice_unsupported(prompts);
+    match clipboard::copy(&script::standalone(shell, prompts, source)) {
+        Ok(clipboard::Method::Command(program)) => output::notice(i18n::tr(
+            &format!("Copied the {} prompt script to the clipboard via {}; nothing was written.", shell.display_name(), program),
+            &format!("已通过 {} 把 {} 提示符脚本复制到剪贴板, 未写入任何内容。", program, shell.display_name()),
+        )),
+        Ok(clipboard::Method::Osc52) => output::notice(i18n::tr(
+            &format!(
+                "Asked the terminal to copy the {} prompt script with OSC 52; terminals without OSC 52 support ignore this silently, \
+                 so check your clipboard. Nothing was written.",
+                shell.display_name()
+            ),
+            &format!(
+                "已通过 OSC 52 请求终端复制 {} 提示符脚本; 不支持 OSC 52 的终端会静默忽略, 请检查剪贴板。未写入任何内容。",
+                shell.display_name()
+            ),
+        )),
+        Err(err) => {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+}
+
+/// `set --random`: 随机挑选主题, 用户接受后返回它的提示符
+fn random_theme(matches: &ArgMatches) -> PromptSet {
+    let tags: Vec<&str> = matches.values_of("tag").map(Iterator::collect).unwrap_or_default();
+    let max_colors: Option<usize> = matches.is_present("max-colors").then(|| matches.value_of_t("max-colors").unwrap_or_else(|err| err.exit()));
+    let ascii = matches.is_present("ascii") || config::env_flag("ASCII");
+    match themes::random(&tags, max_colors, ascii) {
+        Ok(Some((name, spec))) => {
+            logging::debug("themes", format!("applying the random theme {}", name));
+            spec.prompts().unwrap_or_else(|err| {
+                i18n::error(err);
+                process::exit(exit::INVALID_PROMPT);
+            })
+        }
+        Ok(None) => {
+            eprintln!("{}", i18n::tr("Cancelled; nothing was written.", "已取消, 未写入任何内容。"));
+            process::exit(exit::FAILURE);
+        }
+        Err(err) => {
+            i18n::error(&err);
+            process::exit(exit::NOT_FOUND);
+        }
+    }
+}
+
+/// `set --pick`: 从主题与应用过的提示符中挑选
+fn picked_prompt(matches: &ArgMatches, shell: Shell) -> PromptSet {
+    let ascii = matches.is_present("ascii") || config::env_flag("ASCII");
+    match picker::pick(shell, ascii) {
+        Ok(Some(prompts)) => prompts,
+        Ok(None) => {
+            eprintln!("{}", i18n::tr("Cancelled; nothing was written.", "已取消, 未写入任何内容。"));
+            process::exit(exit::FAILURE);
+        }
+        Err(err) => {
+            i18n::error(&err);
+            process::exit(exit::code_for(err.as_ref()));
+        }
+    }
+}
+
+/// 逐部分修改当前的提示符并写回配置文件
+fn run_edit(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    prepare_write(matches, shell);
+    let current = shell
+        .managed_prompt()
+        .and_then(|managed| match managed {
+            Some(prompt) => Ok(Some(prompt)),
+            None => shell.read_configured_prompt(),
+        })
+        .unwrap_or_else(|err| {
+            i18n::error(i18n::tr(
+                &format!("reading the {} prompt failed: {}", shell.display_name(), err),
+                &format!("读取 {} 提示符失败: {}", shell.display_name(), err),
+            ));
+            process::exit(exit::code_for(err.as_ref()));
+        });
+    let Some(current) = current else {
+        i18n::error(i18n::tr(
+            &format!("No {} prompt is configured; create one with `prompt-changer set` first", shell.display_name()),
+            &format!("没有已配置的 {} 提示符, 请先用 `prompt-changer set` 创建", shell.display_name()),
+        ));
+        process::exit(exit::NOT_FOUND);
+    };
+    let new_prompt = match wizard::edit_prompt(&current) {
+        Ok(Some(new_prompt)) => new_prompt,
+        Ok(None) => {
+            eprintln!("{}", i18n::tr("Cancelled; nothing was written.", "已取消, 未写入任何内容。"));
+            process::exit(exit::FAILURE);
+        }
+        Err(err) => {
+            eprintln!("{}", i18n::tr(&format!("Error reading prompt: {}", err), &format!("读取提示符出错: {}", err)));
+            process::exit(exit::FAILURE);
+        }
+    };
+    let new_prompt = PromptSet::single(&wrap_wizard_colors(&new_prompt));
+    write_prompt(matches, shell, with_integration(matches, new_prompt), "edit");
+}
+
+/// 按命令行参数加上标题与终端集成, 再合并团队策略锁定的片段
+fn with_integration(matches: &ArgMatches, mut new_prompt: PromptSet) -> PromptSet {
+    if let Some(template) = matches.value_of("title") {
+        if let Err(err) = prompt::check_title(template) {
+            i18n::error(err);
+            process::exit(exit::INVALID_PROMPT);
+        }
+        new_prompt.title = Some(template.to_string());
+    } else if matches.is_present("no-title") {
+        new_prompt.title = Some(String::new());
+    }
+    if matches.is_present("report-cwd") {
+        new_prompt.report_cwd = true;
+    }
+    if matches.is_present("semantic-marks") {
+        new_prompt.semantic_marks = true;
+    }
+    if matches.is_present("screen-compat") {
+        new_prompt.screen_compat = true;
+    }
+    if output::is_accessible() {
+        new_prompt.accessible = true;
+    }
+    if let Some(name) = matches.value_of("terminal") {
+        new_prompt.integrate(Terminal::from_name(name).unwrap_or_else(|err| {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }));
+    }
+    with_policy(new_prompt)
+}
+
+/// 合并团队策略锁定的片段
+fn with_policy(new_prompt: PromptSet) -> PromptSet {
+    let merged = policy::apply(new_prompt.clone()).unwrap_or_else(|err| {
+        i18n::error(&err);
+        process::exit(exit::code_for(err.as_ref()));
+    });
+    if merged != new_prompt {
+        // 导出脚本时标准输出是脚本本身, 所以只记入调试日志
+        logging::debug("policy", format!("added the locked segments of {}", policy::path().display()));
+    }
+    merged
+}
+
+/// 检查各个提示符, 打印警告; 有错误时退出
+fn check_prompts(matches: &ArgMatches, shell: Shell, new_prompt: &PromptSet) {
+    for variant in new_prompt.prompts() {
+        match prompt::validate_prompt(shell, variant, forced(matches)) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    i18n::warning(warning.message);
+                }
+            }
+            Err(err) => {
+                i18n::error(err);
+                process::exit(exit::INVALID_PROMPT);
+            }
+        }
+    }
+}
+
+/// 检查后写入配置文件, 并记入历史; 配置中已是这个提示符时不改动任何文件
+fn write_prompt(matches: &ArgMatches, shell: Shell, new_prompt: PromptSet, command: &str) {
+    check_prompts(matches, shell, &new_prompt);
+    shell.notice_unsupported(&new_prompt);
+    // 先加锁再比较, 以免另一个实例在比较与写入之间改动配置, 使报告与历史描述的不是实际写入的改动
+    let _lock = shell::lock().unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    });
+    let pending = shell.pending_changes(&new_prompt).unwrap_or_else(|err| {
+        i18n::error(i18n::tr(
+            &format!("reading the {} config failed: {}", shell.display_name(), err),
+            &format!("读取 {} 配置失败: {}", shell.display_name(), err),
+        ));
+        process::exit(exit::code_for(err.as_ref()));
+    });
+    if pending.is_empty() || matches.is_present("check") {
+        report_pending(shell, &new_prompt, &pending, command);
+        if !pending.is_empty() {
+            process::exit(exit::WOULD_CHANGE);
+        }
+        return;
+    }
+    if let Err(err) = hooks::run(Hook::PreApply, shell, None) {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    }
+    let snapshot = shell.snapshot().unwrap_or_else(|err| {
+        i18n::error(i18n::tr(
+            &format!("updating the {} prompt failed: {}", shell.display_name(), err),
+            &format!("更新 {} 提示符失败: {}", shell.display_name(), err),
+        ));
+        process::exit(exit::code_for(err.as_ref()));
+    });
+    let failing_before = verify::failing_files(shell);
+    let started_before = verify::starts_cleanly(shell);
+    if let Err(err) = conflicts::check(shell, &new_prompt, matches.is_present("disable-conflicts")) {
+        i18n::error(err);
+        if let Err(err) = snapshot.restore() {
+            i18n::error(err);
+        }
+        process::exit(exit::FAILURE);
+    }
+    let path = shell.update_prompt(&new_prompt).unwrap_or_else(|err| {
+        i18n::error(i18n::tr(
+            &format!("updating the {} prompt failed: {}", shell.display_name(), err),
+            &format!("更新 {} 提示符失败: {}", shell.display_name(), err),
+        ));
+        process::exit(exit::code_for(err.as_ref()));
+    });
+    if let Err(err) = verify::check_written(shell, &path, &snapshot, &failing_before, started_before) {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    }
+    if let Err(err) = dotfiles::apply(&pending) {
+        i18n::error(i18n::tr(
+            &format!("the prompt was written to {}, but updating the home directory failed: {}", path.display(), err),
+            &format!("提示符已写入 {}, 但更新家目录失败: {}", path.display(), err),
+        ));
+        process::exit(exit::FAILURE);
+    }
+    if matches.is_present("reload") {
+        match shell.verify_startup(&path) {
+            Ok(()) => logging::info(i18n::tr(
+                &format!("{} loaded the new config without errors.", shell.display_name()),
+                &format!("{} 载入新配置时没有出错。", shell.display_name()),
+            )),
+            Err(err) if exit::code_for(err.as_ref()) == exit::NOT_FOUND => i18n::warning(i18n::tr(
+                &format!("could not start {} to check the new config: {}", shell.display_name().to_ascii_lowercase(), err),
+                &format!("无法启动 {} 检查新配置: {}", shell.display_name().to_ascii_lowercase(), err),
+            )),
+            Err(err) => {
+                i18n::error(i18n::tr(
+                    &format!("the prompt was written to {}, but {}", path.display(), err),
+                    &format!("提示符已写入 {}, 但{}", path.display(), err),
+                ));
+                process::exit(exit::FAILURE);
+            }
+        }
+    }
+    let reload = shell.reload_command().ok();
+    if let Err(err) = history::record(shell, &new_prompt.default) {
+        i18n::warning(i18n::tr(&format!("could not add the prompt to the history: {}", err), &format!("无法记入历史: {}", err)));
+    }
+    if output::is_json() {
+        let prompts: Vec<&str> = new_prompt.prompts().collect();
+        output::print(
+            command,
+            vec![
+                ("shell", shell.display_name().to_ascii_lowercase().into()),
+                ("file", path.display().to_string().into()),
+                ("prompts", prompts.into()),
+                ("changed", true.into()),
+                ("reload", reload.into()),
+            ],
+        );
+    } else {
+        logging::info(i18n::tr(&format!("{} prompt updated successfully.", shell.display_name()), &format!("{} 提示符已更新。", shell.display_name())));
+        if let Some(reload) = reload {
+            logging::info(i18n::tr(
+                &format!("Open a new terminal, or run `{}` to use it in this one.", reload),
+                &format!("请打开新的终端, 或运行 `{}` 在当前终端中使用。", reload),
+            ));
+        }
+    }
+    if let Err(err) = hooks::run(Hook::PostApply, shell, Some(&path)) {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    }
+}
+
+/// 报告写入提示符会改动哪些文件, 没有时说明配置已是最新; 输出不随运行而变, 便于配置管理工具判断是否有改动
+fn report_pending(shell: Shell, new_prompt: &PromptSet, pending: &[PathBuf], command: &str) {
+    let files: Vec<String> = pending.iter().map(|path| path.display().to_string()).collect();
+    if output::is_json() {
+        let prompts: Vec<&str> = new_prompt.prompts().collect();
+        output::print(
+            command,
+            vec![
+                ("shell", shell.display_name().to_ascii_lowercase().into()),
+                ("prompts", prompts.into()),
+                ("changed", (!pending.is_empty()).into()),
+                ("files", files.into()),
+            ],
+        );
+    } else if files.is_empty() {
+        logging::info(i18n::tr(
+            &format!("{} prompt is already up to date; nothing was written.", shell.display_name()),
+            &format!("{} 提示符已是最新, 未写入任何内容。", shell.display_name()),
+        ));
     } else {
-        Ok(())
+        logging::info(i18n::tr(
+            &format!("{} prompt would change: {}", shell.display_name(), files.join(", ")),
+            &format!("{} 提示符需要更新: {}", shell.display_name(), files.join(", ")),
+        ));
+    }
+}
+
+/// 检查写入的配置能否被 shell 载入
+fn run_verify(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    match verify::run(shell) {
+        Ok(true) => {}
+        Ok(false) => process::exit(exit::FAILURE),
+        Err(err) => {
+            i18n::error(i18n::tr(&format!("verifying the config failed: {}", err), &format!("检查配置失败: {}", err)));
+            process::exit(exit::code_for(err.as_ref()));
+        }
+    }
+}
+
+/// 列出应用过的提示符
+fn run_history_list(matches: &ArgMatches) {
+    let shell = matches.value_of("shell").map(|_| selected_shell(matches));
+    if let Err(err) = history::list(shell) {
+        i18n::error(i18n::tr(&format!("reading the history failed: {}", err), &format!("读取历史失败: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 重新应用历史中的一条提示符
+fn run_history_apply(matches: &ArgMatches) {
+    let n: usize = matches.value_of_t("number").unwrap_or_else(|err| err.exit());
+    let entry = history::entry(n).unwrap_or_else(|err| {
+        i18n::error(err.to_string());
+        process::exit(exit::code_for(err.as_ref()));
+    });
+    prepare_write(matches, entry.shell);
+    write_prompt(matches, entry.shell, with_policy(PromptSet::single(&entry.prompt)), "history apply");
+}
+
+/// 打印配置文件中的默认值
+fn run_config_get(matches: &ArgMatches) {
+    if let Err(err) = config::run_get(matches.value_of("key")) {
+        i18n::error(i18n::tr(&format!("reading the config failed: {}", err), &format!("读取配置失败: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 修改配置文件中的默认值
+fn run_config_set(matches: &ArgMatches) {
+    if let Err(err) = config::run_set(matches.value_of("key").unwrap(), matches.value_of("value").unwrap()) {
+        i18n::error(i18n::tr(&format!("updating the config failed: {}", err), &format!("更新配置失败: {}", err)));
+        process::exit(match exit::code_for(err.as_ref()) {
+            exit::FAILURE => exit::INVALID_PROMPT,
+            code => code,
+        });
     }
 }
 
+/// 测量当前提示符的渲染耗时
+fn run_bench(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    let iterations = *matches.get_one::<u64>("iterations").unwrap() as usize;
+    if let Err(err) = bench::run(shell, iterations, matches.is_present("spawn")) {
+        eprintln!("{}", i18n::tr(&format!("Error benchmarking {} prompt: {}", shell.display_name(), err), &format!("测量 {} 提示符出错: {}", shell.display_name(), err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 检查提示符配置中的常见问题
+fn run_doctor(matches: &ArgMatches) {
+    let shells = match matches.value_of("shell") {
+        Some(_) => vec![selected_shell(matches)],
+        None => Shell::ALL.to_vec(),
+    };
+    match doctor::run(&shells) {
+        Ok(0) => {}
+        Ok(_) => process::exit(exit::FAILURE),
+        Err(err) => {
+            i18n::error(i18n::tr(&format!("running checks failed: {}", err), &format!("检查失败: {}", err)));
+            process::exit(exit::code_for(err.as_ref()));
+        }
+    }
+}
+
+/// 打印所选 shell 的提示符速查表
+fn run_hints(matches: &ArgMatches) {
+    wizard::hints(matches.value_of("shell").unwrap());
+}
+
+/// 检查任意提示符字符串
+fn run_lint(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    if lint::run(shell, matches.value_of("prompt").unwrap()) > 0 {
+        process::exit(exit::INVALID_PROMPT);
+    }
+}
+
+/// 恢复 shell 的默认提示符
+fn run_reset(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    if let Err(err) = shell::check_privileges() {
+        i18n::error(err);
+        process::exit(exit::PERMISSION_DENIED);
+    }
+    let _lock = shell::lock().unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    });
+    match shell.reset_prompt() {
+        Ok(changes) if changes.is_empty() => {
+            logging::info(i18n::tr(&format!("Nothing to reset: no prompt-changer content found for {}.", shell.display_name()), &format!("无需恢复: 没有找到 prompt-changer 为 {} 写入的内容。", shell.display_name())));
+        }
+        Ok(changes) => {
+            for change in changes {
+                logging::info(change);
+            }
+            logging::info(i18n::tr(&format!("{} prompt reset to the default.", shell.display_name()), &format!("{} 提示符已恢复为默认。", shell.display_name())));
+        }
+        Err(err) => {
+            eprintln!("{}", i18n::tr(&format!("Error resetting {} prompt: {}", shell.display_name(), err), &format!("恢复 {} 提示符出错: {}", shell.display_name(), err)));
+            process::exit(exit::code_for(err.as_ref()));
+        }
+    }
+}
+
+/// 显示当前生效的提示符
+fn run_show(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    if let Err(err) = show::run(shell) {
+        i18n::error(i18n::tr(
+            &format!("reading the {} prompt failed: {}", shell.display_name(), err),
+            &format!("读取 {} 提示符失败: {}", shell.display_name(), err),
+        ));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 清理旧版本追加的提示符行
+fn run_cleanup(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    if let Err(err) = cleanup::run(shell, matches.is_present("pick")) {
+        eprintln!("{}", i18n::tr(&format!("Error cleaning up {} config: {}", shell.display_name(), err), &format!("清理 {} 配置出错: {}", shell.display_name(), err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 把提示符推送到远程主机
+fn run_apply(matches: &ArgMatches) {
+    let shell = matches.value_of("shell").map(|_| selected_shell(matches));
+    let hosts: Vec<&str> = matches.values_of("host").unwrap().collect();
+    let new_prompt = spec_prompts(matches, None).or_else(|| matches.value_of("prompt").map(PromptSet::single));
+    if remote::run(&hosts, shell, new_prompt.as_ref(), forced(matches)) > 0 {
+        process::exit(exit::FAILURE);
+    }
+}
+
+/// 把描述文件导出为 tmux 状态栏
+fn run_export_tmux(matches: &ArgMatches) {
+    let spec = load_spec(matches, None).unwrap();
+    if let Err(err) = tmux::run(&spec, matches.is_present("right"), matches.is_present("write")) {
+        eprintln!("{}", i18n::tr(&format!("Error exporting to tmux: {}", err), &format!("导出到 tmux 出错: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 把提示符导出为独立的脚本: 来自描述文件、--prompt 或本工具写入的提示符
+fn run_export_script(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    let (new_prompt, source) = chosen_prompts(matches);
+    let new_prompt = with_integration(matches, new_prompt);
+    check_prompts(matches, shell, &new_prompt);
+    if matches.is_present("copy") {
+        copy_script(shell, &new_prompt, &source);
+    } else {
+        shell.notice_unsupported(&new_prompt);
+        print!("{}", script::standalone(shell, &new_prompt, &source));
+    }
+}
+
+/// 输出把提示符烘焙进容器镜像的 Dockerfile 指令, 或 devcontainer.json 的 postCreateCommand
+fn run_export_docker(matches: &ArgMatches) {
+    let shell = selected_shell(matches);
+    let format = docker::Format::from_name(matches.value_of("format").unwrap()).unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    });
+    if shell == Shell::Cmd {
+        i18n::error(i18n::tr("cmd does not run in Linux containers", "Linux 容器中没有 cmd"));
+        process::exit(exit::FAILURE);
+    }
+    let (new_prompt, source) = chosen_prompts(matches);
+    let new_prompt = with_integration(matches, new_prompt);
+    check_prompts(matches, shell, &new_prompt);
+    shell.notice_unsupported(&new_prompt);
+    print!("{}", docker::export(format, shell, &new_prompt, &source));
+}
+
+/// 预览提示符, 可输出为 HTML 或 SVG
+fn run_preview(matches: &ArgMatches) {
+    if let Some(spec) = matches.value_of_os("watch") {
+        let ascii = matches.is_present("ascii") || config::env_flag("ASCII");
+        if let Err(err) = preview::watch(&themes::spec_path(Path::new(spec)), ascii, matches.is_present("light")) {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+        return;
+    }
+    let format = preview::Format::from_name(matches.value_of("format").unwrap()).unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    });
+    let (prompts, _) = chosen_prompts(matches);
+    print!("{}", preview::render(&prompts, format, matches.is_present("light")));
+}
+
+/// `--spec`、`--prompt` 指定的提示符, 都没有时是用本工具设置的提示符; 同时返回来源的说明
+fn chosen_prompts(matches: &ArgMatches) -> (PromptSet, String) {
+    if let Some(prompts) = spec_prompts(matches, None) {
+        (prompts, format!("the spec {}", Path::new(matches.value_of_os("spec").unwrap()).display()))
+    } else if let Some(prompt) = matches.value_of("prompt") {
+        (PromptSet::single(prompt), "--prompt".to_string())
+    } else {
+        let shell = selected_shell(matches);
+        match shell.managed_prompt() {
+            Ok(Some(prompt)) => (PromptSet::single(&prompt), format!("the {} prompt set with prompt-changer", shell.display_name())),
+            Ok(None) => {
+                i18n::error(i18n::tr(
+                    &format!("no {} prompt has been set with prompt-changer; pass --spec or --prompt", shell.display_name()),
+                    &format!("还没有用 prompt-changer 设置 {} 提示符; 请使用 --spec 或 --prompt", shell.display_name()),
+                ));
+                process::exit(exit::NOT_FOUND);
+            }
+            Err(err) => {
+                i18n::error(&err);
+                process::exit(exit::code_for(err.as_ref()));
+            }
+        }
+    }
+}
+
+/// 把描述文件编码为分享字符串, `--gist` 时同时上传为 gist
+fn run_share(matches: &ArgMatches) {
+    let spec = matches.value_of_os("spec").map(PathBuf::from).or_else(|| config::get().spec.clone());
+    let Some(path) = spec.map(|spec| themes::spec_path(&spec)) else {
+        i18n::error(i18n::tr(
+            "pass the spec to share with --spec, or set a default with `prompt-changer config set spec FILE`",
+            "请用 --spec 指定要分享的描述文件, 或用 `prompt-changer config set spec FILE` 设置默认值",
+        ));
+        process::exit(exit::INVALID_PROMPT);
+    };
+    let fail = |err: Box<dyn std::error::Error>| -> ! {
+        i18n::error(&err);
+        process::exit(match exit::code_for(err.as_ref()) {
+            exit::FAILURE => exit::INVALID_PROMPT,
+            code => code,
+        });
+    };
+    let code = share::encode(&path).unwrap_or_else(|err| fail(err));
+    println!("{}", code);
+    if matches.is_present("gist") {
+        match share::upload_gist(&path, &code, matches.is_present("public")) {
+            Ok(url) => logging::info(i18n::tr(&format!("Uploaded to {}", url), &format!("已上传到 {}", url))),
+            Err(err) => {
+                i18n::error(&err);
+                process::exit(exit::FAILURE);
+            }
+        }
+    }
+}
+
+/// 应用别人分享的提示符; `--write` 时只保存为描述文件
+fn run_import_string(matches: &ArgMatches) {
+    let content = share::decode(matches.value_of("from-string").unwrap()).unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::INVALID_PROMPT);
+    });
+    if let Some(target) = matches.value_of_os("write").map(Path::new) {
+        if target.exists() {
+            i18n::error(i18n::tr(
+                &format!("{} already exists; remove it or choose another file", target.display()),
+                &format!("{} 已存在; 请删除它或换一个文件", target.display()),
+            ));
+            process::exit(exit::FAILURE);
+        }
+        if let Err(err) = std::fs::write(target, &content) {
+            i18n::error(&err);
+            process::exit(exit::code_for(&err));
+        }
+        logging::info(i18n::tr(
+            &format!("Saved the shared spec to {0}; apply it with `prompt-changer set --spec {0}`.", target.display()),
+            &format!("已把分享的描述文件保存到 {0}; 用 `prompt-changer set --spec {0}` 应用它。", target.display()),
+        ));
+        return;
+    }
+    let shell = selected_shell(matches);
+    prepare_write(matches, shell);
+    let prompts = spec::Spec::parse(&content, "in the share code").and_then(|spec| Ok(spec.prompts()?));
+    let prompts = prompts.unwrap_or_else(|err| {
+        i18n::error(err);
+        process::exit(exit::INVALID_PROMPT);
+    });
+    write_prompt(matches, shell, with_integration(matches, prompts), "import");
+}
+
+/// 把其他框架的主题转换为描述文件
+fn run_import(matches: &ArgMatches, framework: Framework) {
+    let result = import::import(framework, matches.value_of_os("theme").unwrap())
+        .and_then(|imported| import::run(imported, matches.value_of_os("write").map(Path::new)));
+    if let Err(err) = result {
+        eprintln!("{}", i18n::tr(&format!("Error importing theme: {}", err), &format!("导入主题出错: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 导入终端配色为调色板
+fn run_palette_import(matches: &ArgMatches) {
+    let path = Path::new(matches.value_of_os("file").unwrap());
+    if let Err(err) = palette::run(path, matches.value_of("name")) {
+        eprintln!("{}", i18n::tr(&format!("Error importing palette: {}", err), &format!("导入调色板出错: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 安装主题
+fn run_themes_install(matches: &ArgMatches) {
+    let result = themes::install(
+        matches.value_of("source").unwrap(),
+        matches.value_of("name"),
+        matches.value_of("sha256"),
+        matches.value_of("index"),
+        matches.is_present("insecure"),
+        forced(matches),
+    );
+    if let Err(err) = result {
+        eprintln!("{}", i18n::tr(&format!("Error installing themes: {}", err), &format!("安装主题出错: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 更新安装的主题
+fn run_themes_update(matches: &ArgMatches) {
+    let names: Vec<&str> = matches.values_of("name").map(Iterator::collect).unwrap_or_default();
+    if let Err(err) = themes::update(&names) {
+        eprintln!("{}", i18n::tr(&format!("Error updating themes: {}", err), &format!("更新主题出错: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 预览所有主题
+fn run_themes_preview(matches: &ArgMatches) {
+    if let Err(err) = themes::preview(matches.value_of("tag"), matches.is_present("ascii")) {
+        eprintln!("{}", i18n::tr(&format!("Error previewing themes: {}", err), &format!("预览主题出错: {}", err)));
+        process::exit(exit::code_for(err.as_ref()));
+    }
+}
+
+/// 打印补全脚本
+fn run_completions(matches: &ArgMatches) {
+    match completions::generate(matches.value_of("shell").unwrap(), &cli()) {
+        Ok(script) => print!("{}", script),
+        Err(err) => {
+            i18n::error(err);
+            process::exit(exit::UNSUPPORTED_SHELL);
+        }
+    }
+}
+
+/// 命令行的定义
+fn cli() -> Command<'static> {
+    Command::new("prompt-changer")
+        .about("Change the command prompt in bash, fish, PowerShell, ksh, tcsh, sh, Elvish, xonsh, Oils or cmd.")
+        .after_help(exit::HELP)
+        .arg(shell_arg())
+        .arg(force_arg())
+        .arg(install_mode_arg())
+        .arg(spec_arg())
+        .arg(no_contrast_check_arg())
+        .arg(ascii_arg())
+        .arg(title_arg())
+        .arg(no_title_arg())
+        .arg(report_cwd_arg())
+        .arg(semantic_marks_arg())
+        .arg(terminal_arg())
+        .arg(screen_compat_arg())
+        .arg(reload_arg())
+        .arg(disable_conflicts_arg())
+        .arg(
+            Arg::new("config-root")
+                .long("config-root")
+                .value_name("DIR")
+                .value_hint(ValueHint::DirPath)
+                .global(true)
+                .allow_invalid_utf8(true)
+                .help("Read and write shell configs under DIR instead of the home directory \
+                       [env: PROMPT_CHANGER_CONFIG_ROOT]"),
+        )
+        .arg(
+            Arg::new("config-file")
+                .long("config-file")
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .global(true)
+                .allow_invalid_utf8(true)
+                .help("Use FILE instead of the shell's usual config file [env: PROMPT_CHANGER_CONFIG_FILE]"),
+        )
+        .arg(
+            Arg::new("system")
+                .long("system")
+                .global(true)
+                .conflicts_with("config-file")
+                .help("Install the prompt for all users under /etc (requires root) [env: PROMPT_CHANGER_SYSTEM=1]"),
+        )
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .value_name("NAME")
+                .global(true)
+                .help("Change NAME's prompt instead of your own; other users need root. Under sudo the invoking user \
+                       is the default [env: PROMPT_CHANGER_USER]"),
+        )
+        .arg(
+            Arg::new("no-follow-symlinks")
+                .long("no-follow-symlinks")
+                .global(true)
+                .help("Replace a symlinked rc file with a regular file instead of writing to the file it points to \
+                       [env: PROMPT_CHANGER_NO_FOLLOW_SYMLINKS=1]"),
+        )
+        .arg(
+            Arg::new("dotfiles-dir")
+                .long("dotfiles-dir")
+                .value_name("DIR")
+                .value_hint(ValueHint::DirPath)
+                .global(true)
+                .allow_invalid_utf8(true)
+                .help("Write to the matching source files in a chezmoi or stow dotfiles directory (e.g. dot_bashrc) \
+                       instead of the live files [env: PROMPT_CHANGER_DOTFILES_DIR]"),
+        )
+        .arg(
+            Arg::new("dotfiles-apply")
+                .long("dotfiles-apply")
+                .global(true)
+                .help("After writing to the dotfiles directory, run `chezmoi apply` or `stow --restow` to update the home directory"),
+        )
+        .arg(
+            Arg::new("lang")
+                .long("lang")
+                .value_name("LANG")
+                .global(true)
+                .possible_values(Lang::NAMES)
+                .help("Language of messages and the interactive wizard (default: `lang` in the config file, then $LANG) \
+                       [env: PROMPT_CHANGER_LANG]"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FORMAT")
+                .global(true)
+                .possible_values(OutputFormat::NAMES)
+                .help("Print results as text (default) or as JSON for provisioning tools [env: PROMPT_CHANGER_OUTPUT]"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .global(true)
+                .possible_values(ColorMode::NAMES)
+                .help("Color this tool's own output (default: `color` in the config file, then auto) \
+                       [env: PROMPT_CHANGER_COLOR]"),
+        )
+        .arg(
+            Arg::new("accessible")
+                .long("accessible")
+                .global(true)
+                .help("Generate prompts that do not rely on color alone (e.g. `[err 1]` after a failed command), without \
+                       decorative icons and with high-contrast text; also ask questions line by line instead of in \
+                       full-screen pickers, for screen readers [env: PROMPT_CHANGER_ACCESSIBLE=1]"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .conflicts_with("verbose")
+                .help("Only print warnings and errors [env: PROMPT_CHANGER_LOG=warn]"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .multiple_occurrences(true)
+                .help("Show which files are probed and written (-vv: also what is written) \
+                       [env: PROMPT_CHANGER_LOG, e.g. info,rc=trace]"),
+        )
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("set")
+                .about("Interactively build a new prompt and write it to the shell config")
+                .arg(shell_arg())
+                .arg(force_arg())
+                .arg(install_mode_arg())
+                .arg(spec_arg())
+                .arg(
+                    Arg::new("stdin")
+                        .value_name("-")
+                        .possible_values(["-"])
+                        .conflicts_with("spec")
+                        .help("Read the prompt string from standard input, e.g. `echo '\\u \\$ ' | prompt-changer set --shell bash -`"),
+                )
+                .arg(
+                    Arg::new("stdin-format")
+                        .long("stdin-format")
+                        .value_name("FORMAT")
+                        .possible_values(["toml", "string"])
+                        .conflicts_with("spec")
+                        .help("Read the prompt from standard input as a TOML spec or a plain prompt string, e.g. `--stdin-format toml < prompt.toml`"),
+                )
+                .arg(
+                    Arg::new("random")
+                        .long("random")
+                        .conflicts_with_all(&["spec", "stdin", "stdin-format"])
+                        .help("Pick a random theme, preview it and ask whether to keep it or try another"),
+                )
+                .arg(
+                    Arg::new("pick")
+                        .long("pick")
+                        .conflicts_with_all(&["spec", "random", "stdin", "stdin-format"])
+                        .help("Choose from the themes and previously applied prompts in a filterable list with a preview"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .multiple_occurrences(true)
+                        .requires("random")
+                        .help("With --random, only pick themes with this tag (repeat to require several)"),
+                )
+                .arg(
+                    Arg::new("max-colors")
+                        .long("max-colors")
+                        .value_name("N")
+                        .requires("random")
+                        .help("With --random, only pick themes using at most N colors"),
+                )
+                .arg(no_contrast_check_arg())
+                .arg(ascii_arg())
+                .arg(title_arg())
+                .arg(no_title_arg())
+                .arg(report_cwd_arg())
+                .arg(semantic_marks_arg())
+                .arg(terminal_arg())
+                .arg(screen_compat_arg())
+                .arg(reload_arg())
+                .arg(disable_conflicts_arg())
+                .arg(copy_arg().conflicts_with_all(&["install-mode", "reload", "disable-conflicts"]))
+                .arg(check_arg().conflicts_with_all(&["copy", "random", "pick", "reload"]))
+                .arg(
+                    Arg::new("inputrc")
+                        .conflicts_with("check")
+                        .long("inputrc")
+                        .conflicts_with("copy")
+                        .help("Also write matching readline settings (colored completions, vi mode strings in the theme's color) to ~/.inputrc (bash only)"),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Change the color or text of parts of the current prompt")
+                .arg(shell_arg())
+                .arg(force_arg())
+                .arg(install_mode_arg())
+                .arg(title_arg())
+                .arg(no_title_arg())
+                .arg(report_cwd_arg())
+                .arg(semantic_marks_arg())
+                .arg(terminal_arg())
+                .arg(screen_compat_arg())
+                .arg(reload_arg())
+                .arg(disable_conflicts_arg())
+                .arg(check_arg().conflicts_with("reload")),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Measure how long the configured prompt takes to render")
+                .arg(shell_arg())
+                .arg(
+                    Arg::new("iterations")
+                        .short('n')
+                        .long("iterations")
+                        .value_name("N")
+                        .default_value("100")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("How many times to render each segment"),
+                )
+                .arg(
+                    Arg::new("spawn")
+                        .long("spawn")
+                        .help("Also time the prompt by spawning the shell itself"),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Show or change the defaults in ~/.config/prompt-changer/config.toml")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("get")
+                        .about("Print the value of a key, or of every key")
+                        .arg(Arg::new("key").value_name("KEY").possible_values(config::KEYS.map(|(key, _)| key))),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a key; an empty value removes it")
+                        .arg(Arg::new("key").value_name("KEY").required(true).possible_values(config::KEYS.map(|(key, _)| key)))
+                        .arg(Arg::new("value").value_name("VALUE").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the shell config for common prompt problems (every supported shell unless --shell is given)")
+                .arg(shell_arg().required(false)),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("List the prompts applied so far and switch back to one of them")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List applied prompts, most recent first")
+                        .arg(shell_arg().required(false).help("Only list prompts applied to this shell")),
+                )
+                .subcommand(
+                    Command::new("apply")
+                        .about("Apply a prompt from the history to the shell it was applied to")
+                        .arg(
+                            Arg::new("number")
+                                .value_name("N")
+                                .required(true)
+                                .help("Number of the entry as shown by `history list` (1 is the most recent)"),
+                        )
+                        .arg(force_arg())
+                        .arg(install_mode_arg())
+                        .arg(reload_arg())
+                        .arg(disable_conflicts_arg())
+                        .arg(check_arg().conflicts_with("reload")),
+                ),
+        )
+        .subcommand(
+            Command::new("hints")
+                .about("Print the prompt placeholders and color syntax of a shell")
+                .arg(
+                    Arg::new("shell")
+                        .long("shell")
+                        .value_name("SHELL")
+                        .possible_values(wizard::HINT_SHELLS)
+                        .default_value("bash")
+                        .help("Shell to show the cheatsheet for"),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Check a prompt string for mistakes without applying it")
+                .arg(shell_arg())
+                .arg(
+                    Arg::new("prompt")
+                        .value_name("PROMPT")
+                        .help("The prompt string to check, as it would appear in PS1")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Syntax-check the files prompt-changer wrote and start the shell with them")
+                .arg(shell_arg()),
+        )
+        .subcommand(
+            Command::new("reset")
+                .about("Remove everything prompt-changer wrote and restore the default prompt")
+                .arg(shell_arg()),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show the currently configured prompt and what each part means")
+                .arg(shell_arg()),
+        )
+        .subcommand(
+            Command::new("cleanup")
+                .about("Remove prompt lines appended by older versions, keeping one in the managed block")
+                .arg(shell_arg())
+                .arg(
+                    Arg::new("pick")
+                        .long("pick")
+                        .help("Ask which prompt to keep instead of keeping the last one"),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a tab completion script for bash, Elvish, fish, zsh or PowerShell")
+                .arg(
+                    Arg::new("shell")
+                        .value_name("SHELL")
+                        .required(true)
+                        .possible_values(completions::SHELLS)
+                        .help("Shell to generate the completion script for"),
+                ),
+        )
+        .subcommand(
+            Command::new("apply")
+                .about("Write the prompt into the rc file of remote machines over SSH")
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .value_name("USER@HOST")
+                        .required(true)
+                        .multiple_occurrences(true)
+                        .help("Remote machine to apply the prompt to; may be given more than once"),
+                )
+                .arg(shell_arg().required(false).help("Shell to configure on the remote machine (default: its login shell)"))
+                .arg(
+                    Arg::new("prompt")
+                        .long("prompt")
+                        .value_name("PROMPT")
+                        .help("Prompt string to apply (default: the prompt set locally with prompt-changer)"),
+                )
+                .arg(spec_arg().conflicts_with("prompt"))
+                .arg(no_contrast_check_arg())
+                .arg(ascii_arg())
+                .arg(force_arg()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Convert a prompt spec into settings for other programs")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("tmux")
+                        .about("Build a tmux status line from the spec's segments and colors")
+                        .arg(spec_arg().required(true))
+                        .arg(no_contrast_check_arg())
+                        .arg(ascii_arg())
+                        .arg(
+                            Arg::new("right")
+                                .long("right")
+                                .help("Set status-right instead of status-left"),
+                        )
+                        .arg(
+                            Arg::new("write")
+                                .long("write")
+                                .help("Write the setting into ~/.tmux.conf instead of printing it"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("script")
+                        .about("Print a self-contained, commented script defining the prompt, to keep in dotfiles and source by hand")
+                        .arg(shell_arg())
+                        .arg(
+                            Arg::new("prompt")
+                                .long("prompt")
+                                .value_name("PROMPT")
+                                .help("Prompt string to export (default: the prompt set with prompt-changer)"),
+                        )
+                        .arg(spec_arg().conflicts_with("prompt"))
+                        .arg(no_contrast_check_arg())
+                        .arg(ascii_arg())
+                        .arg(title_arg())
+                        .arg(no_title_arg())
+                        .arg(report_cwd_arg())
+                        .arg(semantic_marks_arg())
+                        .arg(terminal_arg())
+                        .arg(screen_compat_arg())
+                        .arg(force_arg())
+                        .arg(copy_arg()),
+                )
+                .subcommand(
+                    Command::new("docker")
+                        .about("Print Dockerfile lines or a devcontainer postCreateCommand that install the prompt in a container")
+                        .arg(shell_arg())
+                        .arg(
+                            Arg::new("prompt")
+                                .long("prompt")
+                                .value_name("PROMPT")
+                                .help("Prompt string to export (default: the prompt set with prompt-changer)"),
+                        )
+                        .arg(spec_arg().conflicts_with("prompt"))
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .possible_values(docker::Format::NAMES)
+                                .default_value("dockerfile")
+                                .help("dockerfile: COPY and RUN lines for every user of the image; \
+                                       devcontainer: a postCreateCommand for .devcontainer/devcontainer.json"),
+                        )
+                        .arg(no_contrast_check_arg())
+                        .arg(ascii_arg())
+                        .arg(title_arg())
+                        .arg(no_title_arg())
+                        .arg(report_cwd_arg())
+                        .arg(semantic_marks_arg())
+                        .arg(terminal_arg())
+                        .arg(screen_compat_arg())
+                        .arg(force_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("preview")
+                .about("Render the prompt with sample data, as colored text or as an HTML snippet or SVG image for docs")
+                .arg(shell_arg())
+                .arg(
+                    Arg::new("prompt")
+                        .long("prompt")
+                        .value_name("PROMPT")
+                        .help("Prompt string to preview (default: the prompt set with prompt-changer)"),
+                )
+                .arg(spec_arg().conflicts_with("prompt"))
+                .arg(no_contrast_check_arg())
+                .arg(ascii_arg())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(preview::Format::NAMES)
+                        .default_value("text")
+                        .help("text for the terminal, html for a <pre> snippet, svg for a standalone image"),
+                )
+                .arg(
+                    Arg::new("light")
+                        .long("light")
+                        .help("Render on a light background, using the spec's light colors"),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .value_name("SPEC")
+                        .value_hint(ValueHint::FilePath)
+                        .allow_invalid_utf8(true)
+                        .conflicts_with_all(&["prompt", "spec", "format"])
+                        .help("Render SPEC again every time it is saved, until Ctrl-C"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Apply a prompt shared with `share`, or convert a theme from another prompt framework into a spec")
+                .subcommand_negates_reqs(true)
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("from-string")
+                        .long("from-string")
+                        .value_name("CODE")
+                        .required(true)
+                        .help("Share code printed by `prompt-changer share` (starts with pc1:)"),
+                )
+                .arg(
+                    Arg::new("write")
+                        .long("write")
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .allow_invalid_utf8(true)
+                        .help("Save the shared spec to FILE instead of applying it"),
+                )
+                .arg(shell_arg())
+                .arg(force_arg())
+                .arg(install_mode_arg())
+                .arg(title_arg())
+                .arg(no_title_arg())
+                .arg(report_cwd_arg())
+                .arg(semantic_marks_arg())
+                .arg(terminal_arg())
+                .arg(screen_compat_arg())
+                .arg(reload_arg())
+                .arg(disable_conflicts_arg())
+                .arg(check_arg().conflicts_with_all(&["write", "reload"]))
+                .subcommand(
+                    Command::new("omz")
+                        .about("Convert an oh-my-zsh theme's PROMPT and color variables into a spec")
+                        .arg(theme_arg().help("Theme name in $ZSH_CUSTOM/themes or $ZSH/themes (default ~/.oh-my-zsh), or a .zsh-theme file"))
+                        .arg(theme_write_arg()),
+                )
+                .subcommand(
+                    Command::new("omb")
+                        .about("Convert an oh-my-bash theme's PS1 and color variables into a spec")
+                        .arg(theme_arg().help("Theme name in $OSH_CUSTOM/themes or $OSH/themes (default ~/.oh-my-bash), or a .theme.sh file"))
+                        .arg(theme_write_arg()),
+                )
+                .subcommand(
+                    Command::new("bash-it")
+                        .about("Convert a bash-it theme's PS1 and color variables into a spec")
+                        .arg(theme_arg().help("Theme name in $BASH_IT/custom/themes or $BASH_IT/themes (default ~/.bash_it), or a .theme.bash file"))
+                        .arg(theme_write_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("share")
+                .about("Encode a spec as a short string others can apply with `import --from-string`")
+                .arg(spec_arg())
+                .arg(
+                    Arg::new("gist")
+                        .long("gist")
+                        .help("Also upload the spec as a GitHub gist (needs the gh CLI, logged in)"),
+                )
+                .arg(
+                    Arg::new("public")
+                        .long("public")
+                        .requires("gist")
+                        .help("Make the gist public instead of secret"),
+                ),
+        )
+        .subcommand(
+            Command::new("palette")
+                .about("Manage color palettes that spec colors can refer to")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("import")
+                        .about("Create a palette from a base16, WezTerm or Alacritty color scheme")
+                        .arg(
+                            Arg::new("file")
+                                .value_name("FILE")
+                                .value_hint(ValueHint::FilePath)
+                                .required(true)
+                                .allow_invalid_utf8(true)
+                                .help("base16 .yaml, WezTerm .toml or Alacritty .yml/.toml file"),
+                        )
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .value_name("NAME")
+                                .help("Name to save the palette under (default: the file name)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("themes")
+                .about("Preview built-in themes and install more into ~/.config/prompt-changer/themes; use one with `set --spec NAME`")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Download themes from a git repository, a theme index or a spec URL, checking their SHA-256")
+                        .arg(
+                            Arg::new("source")
+                                .value_name("URL-OR-NAME")
+                                .value_hint(ValueHint::Url)
+                                .required(true)
+                                .help("Git repository (*.git), https:// index.toml or spec file, or a theme name to look up in the index"),
+                        )
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .value_name("NAME")
+                                .help("Only install this theme from an index or repository; name to save a single spec under"),
+                        )
+                        .arg(
+                            Arg::new("sha256")
+                                .long("sha256")
+                                .value_name("HEX")
+                                .help("Expected SHA-256 of a spec URL; index entries carry their own"),
+                        )
+                        .arg(
+                            Arg::new("insecure")
+                                .long("insecure")
+                                .conflicts_with("sha256")
+                                .help("Install a spec URL without --sha256, or a repository without index.toml, unverified"),
+                        )
+                        .arg(
+                            Arg::new("index")
+                                .long("index")
+                                .value_name("URL")
+                                .value_hint(ValueHint::Url)
+                                .help("Index or repository to look theme names up in [default: config key theme-index]"),
+                        )
+                        .arg(force_arg().help("Replace themes of the same name installed from elsewhere")),
+                )
+                .subcommand(
+                    Command::new("preview")
+                        .about("Show every built-in and installed theme on one line each, rendered with sample data")
+                        .arg(
+                            Arg::new("tag")
+                                .long("tag")
+                                .value_name("TAG")
+                                .help("Only show themes with this tag, such as minimal, powerline or two-line"),
+                        )
+                        .arg(ascii_arg()),
+                )
+                .subcommand(
+                    Command::new("update")
+                        .about("Download installed themes again and replace the ones that changed")
+                        .arg(Arg::new("name").value_name("NAME").multiple_values(true).help("Themes to update (default: all)")),
+                ),
+        )
+}
+
 /// 主函数
 fn main() {
-    let matches = Command::new("prompt-changer")
-        .about("Change the command prompt in Bash or Fish.")
-        .arg(
-            Arg::new("shell")
-                .short('s')
-                .long("shell")
-                .value_name("SHELL")
-                .help("Choose the shell to change the prompt for (bash or fish)")
-                .required(true)
-                .possible_values(["bash", "fish"]),
-        )
-        .get_matches();
-
-    let shell = matches.value_of("shell").unwrap();
-    let new_prompt = read_new_prompt().unwrap_or_else(|err| {
-        eprintln!("Error reading prompt: {}", err);
-        process::exit(1);
-    });
+    let matches = cli().get_matches();
+
+    let quiet = matches.is_present("quiet") || matches.subcommand().is_some_and(|(_, sub)| sub.is_present("quiet"));
+    let verbose = matches.occurrences_of("verbose").max(matches.subcommand().map_or(0, |(_, sub)| sub.occurrences_of("verbose")));
+    if let Err(err) = logging::init(quiet, verbose) {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
+    }
+
+    let format = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of("output"))
+        .or_else(|| matches.value_of("output"))
+        .map(str::to_string)
+        .or_else(|| config::env_value("OUTPUT"));
+    if let Some(name) = format {
+        if let Err(err) = OutputFormat::from_name(&name).and_then(output::set_format) {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+
+    let config_root = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of_os("config-root"))
+        .or_else(|| matches.value_of_os("config-root"))
+        .map(PathBuf::from)
+        .or_else(|| config::env_value("CONFIG_ROOT").map(PathBuf::from));
+    if let Some(root) = config_root {
+        if let Err(err) = shell::set_config_root(&root) {
+            i18n::error(err);
+            process::exit(exit::NOT_FOUND);
+        }
+    }
+
+    let config_file = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of_os("config-file"))
+        .or_else(|| matches.value_of_os("config-file"))
+        .map(PathBuf::from)
+        .or_else(|| config::env_value("CONFIG_FILE").map(PathBuf::from));
+    if let Some(path) = config_file {
+        if let Err(err) = shell::set_config_file(&path) {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+
+    let system = matches.is_present("system")
+        || matches.subcommand().is_some_and(|(_, sub)| sub.is_present("system"))
+        || config::env_flag("SYSTEM");
+    if system {
+        if let Err(err) = shell::set_system_wide() {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
 
-    if let Err(err) = validate_prompt(&new_prompt) {
-        eprintln!("Error: {}", err);
-        process::exit(1);
+    let user = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of("user"))
+        .or_else(|| matches.value_of("user"))
+        .map(str::to_string)
+        .or_else(|| config::env_value("USER"));
+    let target = match user {
+        Some(name) => shell::set_user(&name),
+        None => shell::follow_sudo_user(),
+    };
+    if let Err(err) = target {
+        i18n::error(err);
+        process::exit(exit::FAILURE);
     }
 
-    match shell {
-        "bash" => {
-            if let Err(err) = update_bash_prompt(&new_prompt) {
-                eprintln!("Error updating Bash prompt: {}", err);
-                process::exit(1);
+    let no_follow_symlinks = matches.is_present("no-follow-symlinks")
+        || matches.subcommand().is_some_and(|(_, sub)| sub.is_present("no-follow-symlinks"))
+        || config::env_flag("NO_FOLLOW_SYMLINKS");
+    if no_follow_symlinks {
+        if let Err(err) = rc::set_replace_symlinks() {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+
+    if let Err(err) = config::init() {
+        i18n::error(err);
+        process::exit(exit::INVALID_PROMPT);
+    }
+
+    let dotfiles_dir = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of_os("dotfiles-dir"))
+        .or_else(|| matches.value_of_os("dotfiles-dir"))
+        .map(PathBuf::from)
+        .or_else(|| config::get().dotfiles_dir.clone());
+    let dotfiles_apply = matches.is_present("dotfiles-apply") || matches.subcommand().is_some_and(|(_, sub)| sub.is_present("dotfiles-apply"));
+    match dotfiles_dir {
+        Some(dir) => {
+            if let Err(err) = dotfiles::set_dir(&dir, dotfiles_apply) {
+                i18n::error(err);
+                process::exit(exit::NOT_FOUND);
             }
-            println!("Bash prompt updated successfully.");
+        }
+        None if dotfiles_apply => {
+            i18n::error(i18n::tr(
+                "--dotfiles-apply needs a dotfiles directory: pass --dotfiles-dir or set it with `prompt-changer config set dotfiles-dir DIR`",
+                "--dotfiles-apply 需要 dotfiles 仓库: 请使用 --dotfiles-dir, 或用 `prompt-changer config set dotfiles-dir DIR` 设置",
+            ));
+            process::exit(exit::INVALID_PROMPT);
+        }
+        None => {}
+    }
+
+    let lang = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of("lang"))
+        .or_else(|| matches.value_of("lang"))
+        .or(config::get().lang.as_deref());
+    if let Some(name) = lang {
+        if let Err(err) = Lang::from_name(name).and_then(i18n::set_lang) {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+
+    let color = matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.value_of("color"))
+        .or_else(|| matches.value_of("color"))
+        .or(config::get().color.as_deref());
+    if let Some(name) = color {
+        if let Err(err) = ColorMode::from_name(name).and_then(color::set_color_mode) {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+
+    let accessible = matches.is_present("accessible")
+        || matches.subcommand().is_some_and(|(_, sub)| sub.is_present("accessible"))
+        || config::get().accessible == Some(true);
+    if accessible {
+        if let Err(err) = output::set_accessible() {
+            i18n::error(err);
+            process::exit(exit::FAILURE);
+        }
+    }
+
+    match matches.subcommand() {
+        Some(("apply", sub)) => run_apply(sub),
+        Some(("bench", sub)) => run_bench(sub),
+        Some(("cleanup", sub)) => run_cleanup(sub),
+        Some(("completions", sub)) => run_completions(sub),
+        Some(("config", sub)) => match sub.subcommand() {
+            Some(("get", sub)) => run_config_get(sub),
+            Some(("set", sub)) => run_config_set(sub),
+            _ => unreachable!("clap requires a config command"),
         },
-        "fish" => {
-            if let Err(err) = update_fish_prompt(&new_prompt) {
-                eprintln!("Error updating Fish prompt: {}", err);
-                process::exit(1);
-            }
-            println!("Fish prompt updated successfully.");
+        Some(("doctor", sub)) => run_doctor(sub),
+        Some(("edit", sub)) => run_edit(sub),
+        Some(("export", sub)) => match sub.subcommand() {
+            Some(("tmux", sub)) => run_export_tmux(sub),
+            Some(("script", sub)) => run_export_script(sub),
+            Some(("docker", sub)) => run_export_docker(sub),
+            _ => unreachable!("clap requires an export target"),
+        },
+        Some(("hints", sub)) => run_hints(sub),
+        Some(("import", sub)) => match sub.subcommand() {
+            Some(("omz", sub)) => run_import(sub, Framework::OhMyZsh),
+            Some(("omb", sub)) => run_import(sub, Framework::OhMyBash),
+            Some(("bash-it", sub)) => run_import(sub, Framework::BashIt),
+            _ => run_import_string(sub),
         },
-        _ => unreachable!(),
+        Some(("history", sub)) => match sub.subcommand() {
+            Some(("list", sub)) => run_history_list(sub),
+            Some(("apply", sub)) => run_history_apply(sub),
+            _ => unreachable!("clap requires a history command"),
+        },
+        Some(("lint", sub)) => run_lint(sub),
+        Some(("palette", sub)) => match sub.subcommand() {
+            Some(("import", sub)) => run_palette_import(sub),
+            _ => unreachable!("clap requires a palette command"),
+        },
+        Some(("themes", sub)) => match sub.subcommand() {
+            Some(("install", sub)) => run_themes_install(sub),
+            Some(("preview", sub)) => run_themes_preview(sub),
+            Some(("update", sub)) => run_themes_update(sub),
+            _ => unreachable!("clap requires a themes command"),
+        },
+        Some(("reset", sub)) => run_reset(sub),
+        Some(("preview", sub)) => run_preview(sub),
+        Some(("share", sub)) => run_share(sub),
+        Some(("show", sub)) => run_show(sub),
+        Some(("set", sub)) => run_set(sub),
+        Some(("verify", sub)) => run_verify(sub),
+        _ => run_set(&matches),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,81 @@
+use crate::i18n;
+use crate::logging;
+use crate::CliError;
+use toml::{Table, Value};
+
+/// 描述文件格式的当前版本; 没有 `version` 的文件按第 1 版读取
+pub const SPEC_VERSION: u32 = 1;
+
+/// 把描述文件从 `from` 版升级到下一版的一步
+struct Migration {
+    from: u32,
+    /// 调试日志中的说明
+    about: &'static str,
+    apply: fn(&mut Table) -> Result<(), CliError>,
+}
+
+/// 依次执行的升级步骤. 改动描述文件的格式时, 在末尾加上从当前版本升级的一步, 再递增 [`SPEC_VERSION`]
+const MIGRATIONS: &[Migration] = &[];
+
+/// 描述文件声明的格式版本
+fn declared_version(table: &Table, origin: &str) -> Result<u32, CliError> {
+    let invalid = || {
+        CliError(
+            i18n::tr(
+                &format!("Invalid spec {}: `version` must be a positive integer", origin),
+                &format!("描述文件 {} 无效: `version` 必须是正整数", origin),
+            )
+            .to_string(),
+        )
+    };
+    match table.get("version") {
+        None => Ok(1),
+        Some(Value::Integer(n)) => u32::try_from(*n).ok().filter(|n| *n >= 1).ok_or_else(invalid),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// 把旧版本的描述文件升级到当前版本, 不改动文件本身; 已是当前版本时返回 None, 由调用者直接解析原文,
+/// 错误信息中保留行号. 比本工具新的版本无法读取
+pub fn upgrade(content: &str, origin: &str) -> Result<Option<Table>, CliError> {
+    let mut table: Table = toml::from_str(content)
+        .map_err(|err| CliError(i18n::tr(&format!("Invalid spec {}: {}", origin, err), &format!("描述文件 {} 无效: {}", origin, err)).to_string()))?;
+    let mut version = declared_version(&table, origin)?;
+    if version > SPEC_VERSION {
+        return Err(CliError(
+            i18n::tr(
+                &format!(
+                    "The spec {} uses format version {}, but this prompt-changer reads up to version {}; upgrade prompt-changer to use it",
+                    origin, version, SPEC_VERSION
+                ),
+                &format!(
+                    "描述文件 {} 使用格式版本 {}, 而这个 prompt-changer 最高只能读取版本 {}; 请升级 prompt-changer 后再使用",
+                    origin, version, SPEC_VERSION
+                ),
+            )
+            .to_string(),
+        ));
+    }
+    if version == SPEC_VERSION {
+        return Ok(None);
+    }
+    while version < SPEC_VERSION {
+        let step = MIGRATIONS
+            .iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| {
+                CliError(
+                    i18n::tr(
+                        &format!("Cannot upgrade the spec {} from format version {}", origin, version),
+                        &format!("无法把描述文件 {} 从格式版本 {} 升级", origin, version),
+                    )
+                    .to_string(),
+                )
+            })?;
+        logging::debug("spec", format!("upgrading {} from format version {}: {}", origin, version, step.about));
+        (step.apply)(&mut table)?;
+        version += 1;
+    }
+    table.insert("version".to_string(), Value::Integer(i64::from(SPEC_VERSION)));
+    Ok(Some(table))
+}
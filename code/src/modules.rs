@@ -0,0 +1,753 @@
+use crate::i18n;
+use crate::icons;
+use crate::shell::Shell;
+use crate::suggest;
+use crate::CliError;
+
+/// 内置的片段模块: 只在相应环境中显示内容, 为每种 shell 生成原生的函数
+pub struct Module {
+    pub name: &'static str,
+    /// 显示在内容前的图标名称
+    icon: &'static str,
+    /// 说明, 用于错误信息
+    pub about: &'static str,
+    /// bash 函数体, `{icon}` 会换成图标; 生效时输出内容与一个空格, 否则不输出
+    bash: &'static str,
+    /// fish 函数体
+    fish: &'static str,
+    /// PowerShell 函数体, 返回要显示的文字
+    powershell: &'static str,
+    /// 预览中显示的示例内容
+    pub sample: &'static str,
+    /// 按显示的值改用颜色的方式, 函数体中的 `{colors}` 会换成设置 `color` 的代码
+    pub coloring: Coloring,
+    /// 函数是否以参数接收普通用户与 root 的符号 (`symbol`、`root_symbol`), 向导生成的提示符也能使用
+    pub symbols: bool,
+}
+
+/// 模块按显示的值改用颜色的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coloring {
+    /// 不支持
+    None,
+    /// 按通配模式匹配 `name` (`value_colors`)
+    Patterns,
+    /// 按数值阈值比较 `value` (`thresholds`): 阈值乘以 `scale` 后取整; `rising` 时值越大越严重, 否则越小越严重
+    Thresholds { scale: u32, rising: bool },
+}
+
+/// 所有模块
+const MODULES: [Module; 17] = [
+    Module {
+        name: "nix",
+        icon: "nix",
+        about: "inside nix-shell or nix develop ($IN_NIX_SHELL)",
+        bash: r#"    [ -n "$IN_NIX_SHELL" ] || return 0
+    if [ "$IN_NIX_SHELL" = pure ]; then
+        printf '%s ' '{icon} nix-shell (pure)'
+    else
+        printf '%s ' '{icon} nix-shell'
+    fi"#,
+        fish: r#"    set -q IN_NIX_SHELL; or return 0
+    if test "$IN_NIX_SHELL" = pure
+        printf '%s ' '{icon} nix-shell (pure)'
+    else
+        printf '%s ' '{icon} nix-shell'
+    end"#,
+        powershell: r#"    if (-not $env:IN_NIX_SHELL) { return '' }
+    if ($env:IN_NIX_SHELL -eq 'pure') { '{icon} nix-shell (pure) ' } else { '{icon} nix-shell ' }"#,
+        sample: "nix-shell",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "direnv",
+        icon: "env",
+        about: "in a directory whose .envrc direnv has loaded ($DIRENV_DIR)",
+        bash: r#"    [ -n "$DIRENV_DIR" ] || return 0
+    local dir=${DIRENV_DIR#-}
+    printf '%s %s ' '{icon}' "${dir##*/}""#,
+        fish: r#"    set -q DIRENV_DIR; or return 0
+    printf '%s %s ' '{icon}' (basename (string replace -r -- '^-' '' $DIRENV_DIR))"#,
+        powershell: r#"    if (-not $env:DIRENV_DIR) { return '' }
+    '{icon} ' + (Split-Path -Leaf ($env:DIRENV_DIR -replace '^-', '')) + ' '"#,
+        sample: "demo",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "container",
+        icon: "container",
+        about: "when the shell itself runs inside a docker or podman container",
+        bash: r#"    if [ -f /run/.containerenv ]; then
+        local name
+        name=$(sed -n 's/^name="\(.*\)"$/\1/p' /run/.containerenv 2>/dev/null)
+        printf '%s %s ' '{icon}' "${name:-podman}"
+    elif [ -f /.dockerenv ] || grep -qsE '/(docker|libpod|kubepods|containerd)' /proc/1/cgroup; then
+        printf '%s %s ' '{icon}' docker
+    fi"#,
+        fish: r#"    if test -f /run/.containerenv
+        set -l name (string match -r -g '^name="(.*)"$' < /run/.containerenv)
+        test -n "$name"; or set name podman
+        printf '%s %s ' '{icon}' $name
+    else if test -f /.dockerenv; or begin; test -r /proc/1/cgroup; and string match -q -r '/(docker|libpod|kubepods|containerd)' < /proc/1/cgroup; end
+        printf '%s %s ' '{icon}' docker
+    end"#,
+        powershell: r#"    if (Test-Path /run/.containerenv) {
+        $match = Select-String -Path /run/.containerenv -Pattern '^name="(.*)"$' | Select-Object -First 1
+        $name = if ($match) { $match.Matches[0].Groups[1].Value } else { 'podman' }
+        '{icon} ' + $name + ' '
+    } elseif ((Test-Path /.dockerenv) -or ((Test-Path /proc/1/cgroup) -and (Select-String -Quiet -Path /proc/1/cgroup -Pattern '/(docker|libpod|kubepods|containerd)'))) {
+        '{icon} docker '
+    } else { '' }"#,
+        sample: "docker",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "docker-context",
+        icon: "docker",
+        about: "the docker context or podman connection in use, unless it is the default ($DOCKER_CONTEXT, ~/.docker/config.json)",
+        bash: r#"    local ctx=${DOCKER_CONTEXT:-${CONTAINER_CONNECTION-}}
+    local config=${DOCKER_CONFIG:-$HOME/.docker}/config.json
+    if [ -z "$ctx" ] && [ -f "$config" ]; then
+        ctx=$(sed -n 's/.*"currentContext"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p' "$config")
+    fi
+    [ -n "$ctx" ] && [ "$ctx" != default ] || return 0
+    printf '%s %s ' '{icon}' "$ctx""#,
+        fish: r#"    set -l ctx $DOCKER_CONTEXT $CONTAINER_CONNECTION
+    set -l config ~/.docker/config.json
+    set -q DOCKER_CONFIG; and set config $DOCKER_CONFIG/config.json
+    if test -z "$ctx[1]"; and test -f $config
+        set ctx (string match -r -g '"currentContext"\s*:\s*"([^"]*)"' < $config)
+    end
+    test -n "$ctx[1]"; and test "$ctx[1]" != default; or return 0
+    printf '%s %s ' '{icon}' $ctx[1]"#,
+        powershell: r#"    $ctx = if ($env:DOCKER_CONTEXT) { $env:DOCKER_CONTEXT } else { $env:CONTAINER_CONNECTION }
+    $dir = if ($env:DOCKER_CONFIG) { $env:DOCKER_CONFIG } else { Join-Path $HOME .docker }
+    $config = Join-Path $dir config.json
+    if (-not $ctx -and (Test-Path $config)) {
+        try { $ctx = (Get-Content -Raw $config | ConvertFrom-Json).currentContext } catch { }
+    }
+    if (-not $ctx -or $ctx -eq 'default') { return '' }
+    '{icon} ' + $ctx + ' '"#,
+        sample: "remote",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "terraform",
+        icon: "terraform",
+        about: "the Terraform workspace, only in directories that contain .terraform/",
+        bash: r#"    [ -d .terraform ] || return 0
+    local ws=${TF_WORKSPACE-}
+    if [ -z "$ws" ] && [ -f .terraform/environment ]; then
+        ws=$(< .terraform/environment)
+    fi
+    printf '%s %s ' '{icon}' "${ws:-default}""#,
+        fish: r#"    test -d .terraform; or return 0
+    set -l ws $TF_WORKSPACE
+    if test -z "$ws"; and test -f .terraform/environment
+        read ws < .terraform/environment
+    end
+    test -n "$ws"; or set ws default
+    printf '%s %s ' '{icon}' $ws"#,
+        powershell: r#"    if (-not (Test-Path .terraform -PathType Container)) { return '' }
+    $ws = $env:TF_WORKSPACE
+    if (-not $ws -and (Test-Path .terraform/environment)) { $ws = (Get-Content -Raw .terraform/environment).Trim() }
+    if (-not $ws) { $ws = 'default' }
+    '{icon} ' + $ws + ' '"#,
+        sample: "staging",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "aws",
+        icon: "aws",
+        about: "the AWS profile in use ($AWS_PROFILE), with the region when `region = true`",
+        bash: r#"    local name=${AWS_PROFILE:-${AWS_DEFAULT_PROFILE:-${AWS_VAULT-}}}
+    [ -n "$name" ] || return 0
+    local region={region} color=
+{colors}
+    [ -n "$color" ] && printf '\001\033[%sm\002' "$color"
+    printf '%s %s' '{icon}' "$name"
+    [ -n "$region" ] && printf ' (%s)' "$region"
+    [ -n "$color" ] && printf '\001\033[39m\002'
+    printf ' '"#,
+        fish: r#"    set -l name $AWS_PROFILE $AWS_DEFAULT_PROFILE $AWS_VAULT
+    test -n "$name[1]"; or return 0
+    set name $name[1]
+    set -l region {region}
+    set -l color
+{colors}
+    test -n "$color"; and printf '\e[%sm' $color
+    printf '%s %s' '{icon}' $name
+    test -n "$region[1]"; and printf ' (%s)' $region[1]
+    test -n "$color"; and printf '\e[39m'
+    printf ' '"#,
+        powershell: r#"    $name = @($env:AWS_PROFILE, $env:AWS_DEFAULT_PROFILE, $env:AWS_VAULT) | Where-Object { $_ } | Select-Object -First 1
+    if (-not $name) { return '' }
+    $region = {region}
+    $color = $null
+{colors}
+    $text = '{icon} ' + $name
+    if ($region) { $text += " ($region)" }
+    if ($color) { $text = "$([char]27)[${color}m$text$([char]27)[39m" }
+    $text + ' '"#,
+        sample: "prod",
+        coloring: Coloring::Patterns,
+        symbols: false,
+    },
+    Module {
+        name: "rust",
+        icon: "rust",
+        about: "the rustc version, only in directories that contain Cargo.toml",
+        bash: r#"    [ -f Cargo.toml ] || return 0
+    local version
+    version=$(rustc --version 2>/dev/null) || return 0
+    version=${version#rustc }
+    printf '%s %s ' '{icon}' "${version%% *}""#,
+        fish: r#"    test -f Cargo.toml; or return 0
+    set -l version (rustc --version 2>/dev/null); or return 0
+    printf '%s %s ' '{icon}' (string split ' ' -- $version)[2]"#,
+        powershell: r#"    if (-not (Test-Path Cargo.toml) -or -not (Get-Command rustc -ErrorAction Ignore)) { return '' }
+    '{icon} ' + ((rustc --version) -split ' ')[1] + ' '"#,
+        sample: "1.95.0",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "node",
+        icon: "node",
+        about: "the node version, only in directories that contain package.json",
+        bash: r#"    [ -f package.json ] || return 0
+    local version
+    version=$(node --version 2>/dev/null) || return 0
+    printf '%s %s ' '{icon}' "${version#v}""#,
+        fish: r#"    test -f package.json; or return 0
+    set -l version (node --version 2>/dev/null); or return 0
+    printf '%s %s ' '{icon}' (string replace -r '^v' '' -- $version)"#,
+        powershell: r#"    if (-not (Test-Path package.json) -or -not (Get-Command node -ErrorAction Ignore)) { return '' }
+    '{icon} ' + ((node --version) -replace '^v', '') + ' '"#,
+        sample: "22.11.0",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "python",
+        icon: "python",
+        about: "the python version, only in directories that contain pyproject.toml",
+        bash: r#"    [ -f pyproject.toml ] || return 0
+    local version
+    version=$(python3 --version 2>/dev/null || python --version 2>/dev/null) || return 0
+    printf '%s %s ' '{icon}' "${version#Python }""#,
+        fish: r#"    test -f pyproject.toml; or return 0
+    set -l version (python3 --version 2>/dev/null; or python --version 2>/dev/null); or return 0
+    printf '%s %s ' '{icon}' (string replace 'Python ' '' -- $version)"#,
+        powershell: r#"    if (-not (Test-Path pyproject.toml)) { return '' }
+    $python = Get-Command python3, python -ErrorAction Ignore | Select-Object -First 1
+    if (-not $python) { return '' }
+    '{icon} ' + ((& $python --version) -replace '^Python ', '') + ' '"#,
+        sample: "3.12.4",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "go",
+        icon: "go",
+        about: "the go version, only in directories that contain go.mod",
+        bash: r#"    [ -f go.mod ] || return 0
+    local version
+    version=$(go env GOVERSION 2>/dev/null) || return 0
+    printf '%s %s ' '{icon}' "${version#go}""#,
+        fish: r#"    test -f go.mod; or return 0
+    set -l version (go env GOVERSION 2>/dev/null); or return 0
+    printf '%s %s ' '{icon}' (string replace -r '^go' '' -- $version)"#,
+        powershell: r#"    if (-not (Test-Path go.mod) -or -not (Get-Command go -ErrorAction Ignore)) { return '' }
+    '{icon} ' + ((go env GOVERSION) -replace '^go', '') + ' '"#,
+        sample: "1.23.2",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "hg",
+        icon: "hg-branch",
+        about: "the Mercurial branch and active bookmark, read from .hg/ without running hg",
+        bash: r#"    local dir=$PWD
+    until [ -d "$dir/.hg" ]; do
+        [ -n "$dir" ] || return 0
+        dir=${dir%/*}
+    done
+    local branch=default bookmark=
+    [ -f "$dir/.hg/branch" ] && branch=$(< "$dir/.hg/branch")
+    [ -f "$dir/.hg/bookmarks.current" ] && bookmark=$(< "$dir/.hg/bookmarks.current")
+    printf '%s %s%s ' '{icon}' "$branch" "${bookmark:+ ($bookmark)}""#,
+        fish: r#"    set -l dir $PWD
+    while not test -d $dir/.hg
+        test -n "$dir"; or return 0
+        set dir (string replace -r '/[^/]*$' '' -- $dir)
+    end
+    set -l branch default
+    set -l bookmark
+    test -f $dir/.hg/branch; and read branch < $dir/.hg/branch
+    test -f $dir/.hg/bookmarks.current; and read bookmark < $dir/.hg/bookmarks.current
+    printf '%s %s' '{icon}' $branch
+    test -n "$bookmark"; and printf ' (%s)' $bookmark
+    printf ' '"#,
+        powershell: r#"    $dir = $PWD.ProviderPath
+    while ($dir -and -not (Test-Path (Join-Path $dir .hg))) { $dir = Split-Path -Parent $dir }
+    if (-not $dir) { return '' }
+    $branch = if (Test-Path (Join-Path $dir .hg/branch)) { (Get-Content -Raw (Join-Path $dir .hg/branch)).Trim() } else { 'default' }
+    $text = '{icon} ' + $branch
+    if (Test-Path (Join-Path $dir .hg/bookmarks.current)) { $text += ' (' + (Get-Content -Raw (Join-Path $dir .hg/bookmarks.current)).Trim() + ')' }
+    $text + ' '"#,
+        sample: "default",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "svn",
+        icon: "svn",
+        about: "the Subversion revision; svn only runs inside a working copy (.svn/)",
+        bash: r#"    local dir=$PWD
+    until [ -d "$dir/.svn" ]; do
+        [ -n "$dir" ] || return 0
+        dir=${dir%/*}
+    done
+    local revision
+    revision=$(svn info --show-item revision 2>/dev/null) || return 0
+    printf '%s r%s ' '{icon}' "$revision""#,
+        fish: r#"    set -l dir $PWD
+    while not test -d $dir/.svn
+        test -n "$dir"; or return 0
+        set dir (string replace -r '/[^/]*$' '' -- $dir)
+    end
+    set -l revision (svn info --show-item revision 2>/dev/null); or return 0
+    printf '%s r%s ' '{icon}' $revision"#,
+        powershell: r#"    $dir = $PWD.ProviderPath
+    while ($dir -and -not (Test-Path (Join-Path $dir .svn))) { $dir = Split-Path -Parent $dir }
+    if (-not $dir -or -not (Get-Command svn -ErrorAction Ignore)) { return '' }
+    $revision = svn info --show-item revision 2>$null
+    if (-not $revision) { return '' }
+    '{icon} r' + $revision + ' '"#,
+        sample: "r1024",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "load",
+        icon: "load",
+        about: "the 1-minute load average, from /proc/loadavg or sysctl",
+        bash: r#"    local load
+    if [ -r /proc/loadavg ]; then
+        read -r load _ < /proc/loadavg
+    else
+        load=$(sysctl -n vm.loadavg 2>/dev/null) || return 0
+        load=${load#\{ }
+        load=${load%% *}
+    fi
+    local frac=${load#*.}00 color= mark=
+    local value=$(( 10#${load%%.*} * 100 + 10#${frac:0:2} ))
+{colors}
+    [ -n "$color" ] && printf '\001\033[%sm\002' "$color"
+    printf '%s %s%s' '{icon}' "$load" "$mark"
+    [ -n "$color" ] && printf '\001\033[39m\002'
+    printf ' '"#,
+        fish: r#"    set -l load
+    if test -r /proc/loadavg
+        read load _ < /proc/loadavg
+    else
+        set load (sysctl -n vm.loadavg 2>/dev/null | string split ' ')[2]
+    end
+    test -n "$load"; or return 0
+    set -l value (math --scale 0 "$load * 100")
+    set -l color
+    set -l mark
+{colors}
+    test -n "$color"; and printf '\e[%sm' $color
+    printf '%s %s%s' '{icon}' $load "$mark"
+    test -n "$color"; and printf '\e[39m'
+    printf ' '"#,
+        powershell: r#"    if (-not (Test-Path /proc/loadavg)) { return '' }
+    $load = ((Get-Content -Raw /proc/loadavg) -split ' ')[0]
+    $value = [int]([double]::Parse($load, [Globalization.CultureInfo]::InvariantCulture) * 100)
+    $color = $null
+    $mark = ''
+{colors}
+    $text = '{icon} ' + $load + $mark
+    if ($color) { $text = "$([char]27)[${color}m$text$([char]27)[39m" }
+    $text + ' '"#,
+        sample: "0.42",
+        coloring: Coloring::Thresholds { scale: 100, rising: true },
+        symbols: false,
+    },
+    Module {
+        name: "memory",
+        icon: "memory",
+        about: "the percentage of memory still available, from /proc/meminfo",
+        bash: r#"    [ -r /proc/meminfo ] || return 0
+    local key amount total= available= color= mark=
+    while read -r key amount _; do
+        case $key in
+            MemTotal:) total=$amount ;;
+            MemAvailable:) available=$amount ;;
+        esac
+    done < /proc/meminfo
+    [ -n "$total" ] && [ -n "$available" ] || return 0
+    local value=$(( available * 100 / total ))
+{colors}
+    [ -n "$color" ] && printf '\001\033[%sm\002' "$color"
+    printf '%s %s%%%s' '{icon}' "$value" "$mark"
+    [ -n "$color" ] && printf '\001\033[39m\002'
+    printf ' '"#,
+        fish: r#"    test -r /proc/meminfo; or return 0
+    set -l total (string match -r -g '^MemTotal:\s+(\d+)' < /proc/meminfo)
+    set -l available (string match -r -g '^MemAvailable:\s+(\d+)' < /proc/meminfo)
+    test -n "$total"; and test -n "$available"; or return 0
+    set -l value (math --scale 0 "$available * 100 / $total")
+    set -l color
+    set -l mark
+{colors}
+    test -n "$color"; and printf '\e[%sm' $color
+    printf '%s %s%%%s' '{icon}' $value "$mark"
+    test -n "$color"; and printf '\e[39m'
+    printf ' '"#,
+        powershell: r#"    if (-not (Test-Path /proc/meminfo)) { return '' }
+    $info = Get-Content -Raw /proc/meminfo
+    if ($info -notmatch 'MemTotal:\s+(\d+)') { return '' }
+    $total = [long]$Matches[1]
+    if ($info -notmatch 'MemAvailable:\s+(\d+)') { return '' }
+    $value = [int]([long]$Matches[1] * 100 / $total)
+    $color = $null
+    $mark = ''
+{colors}
+    $text = '{icon} ' + $value + '%' + $mark
+    if ($color) { $text = "$([char]27)[${color}m$text$([char]27)[39m" }
+    $text + ' '"#,
+        sample: "63%",
+        coloring: Coloring::Thresholds { scale: 1, rising: false },
+        symbols: false,
+    },
+    Module {
+        name: "ip",
+        icon: "network",
+        about: "the local address of the default route and whether a VPN interface is up; looked up at most once a minute",
+        bash: r#"    local cache=${XDG_RUNTIME_DIR:-${TMPDIR:-/tmp}}/prompt-changer-ip-${USER-} now stamp= ip= vpn=
+    printf -v now '%(%s)T' -1
+    [ -r "$cache" ] && read -r stamp ip vpn < "$cache"
+    if [ -z "$stamp" ] || [ $(( now - stamp )) -ge 60 ]; then
+        ip=$(ip -4 route get 1.1.1.1 2>/dev/null | sed -n 's/.* src \([0-9.]*\).*/\1/p')
+        [ -n "$ip" ] || ip=$(ipconfig getifaddr en0 2>/dev/null)
+        vpn=
+        local dev
+        for dev in /sys/class/net/tun* /sys/class/net/wg* /sys/class/net/tailscale*; do
+            [ -e "$dev" ] && vpn=vpn
+        done
+        printf '%s %s %s\n' "$now" "${ip:--}" "$vpn" > "$cache" 2>/dev/null
+    fi
+    [ -n "$ip" ] && [ "$ip" != - ] || return 0
+    printf '%s %s%s ' '{icon}' "$ip" "${vpn:+ ($vpn)}""#,
+        fish: r#"    set -l now (date +%s)
+    if not set -q __prompt_changer_ip_time; or test (math $now - $__prompt_changer_ip_time) -ge 60
+        set -g __prompt_changer_ip_time $now
+        set -g __prompt_changer_ip (ip -4 route get 1.1.1.1 2>/dev/null | string match -r -g ' src (\S+)')
+        test -n "$__prompt_changer_ip"; or set -g __prompt_changer_ip (ipconfig getifaddr en0 2>/dev/null)
+        set -g __prompt_changer_vpn
+        for dev in /sys/class/net/tun* /sys/class/net/wg* /sys/class/net/tailscale*
+            set -g __prompt_changer_vpn vpn
+        end
+    end
+    test -n "$__prompt_changer_ip"; or return 0
+    printf '%s %s' '{icon}' $__prompt_changer_ip
+    test -n "$__prompt_changer_vpn"; and printf ' (%s)' $__prompt_changer_vpn
+    printf ' '"#,
+        powershell: r#"    $now = Get-Date
+    if (-not $global:__prompt_changer_ip_time -or ($now - $global:__prompt_changer_ip_time).TotalSeconds -ge 60) {
+        $global:__prompt_changer_ip_time = $now
+        $global:__prompt_changer_ip = try {
+            $socket = [Net.Sockets.Socket]::new('InterNetwork', 'Dgram', 'Udp')
+            $socket.Connect('1.1.1.1', 53)
+            $socket.LocalEndPoint.Address.ToString()
+            $socket.Dispose()
+        } catch { $null }
+        $global:__prompt_changer_vpn = [Net.NetworkInformation.NetworkInterface]::GetAllNetworkInterfaces() |
+            Where-Object { $_.OperationalStatus -eq 'Up' -and ($_.NetworkInterfaceType -eq 'Ppp' -or $_.Name -match '^(tun|wg|tailscale|utun)') }
+    }
+    if (-not $global:__prompt_changer_ip) { return '' }
+    $text = '{icon} ' + $global:__prompt_changer_ip
+    if ($global:__prompt_changer_vpn) { $text += ' (vpn)' }
+    $text + ' '"#,
+        sample: "192.168.1.23",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "shlvl",
+        icon: "layers",
+        about: "the shell nesting level ($SHLVL) when above 1, or `symbol` repeated once per level",
+        bash: r#"    [ "${SHLVL:-1}" -gt 1 ] 2>/dev/null || return 0
+    local symbol='{symbol}'
+    if [ -n "$symbol" ]; then
+        local text= i
+        for (( i = 0; i < SHLVL; i++ )); do
+            text+=$symbol
+        done
+        printf '%s ' "$text"
+    else
+        printf '%s %s ' '{icon}' "$SHLVL"
+    fi"#,
+        fish: r#"    set -q SHLVL; and test "$SHLVL" -gt 1 2>/dev/null; or return 0
+    set -l symbol '{symbol}'
+    if test -n "$symbol"
+        printf '%s ' (string repeat -n $SHLVL -- $symbol)
+    else
+        printf '%s %s ' '{icon}' $SHLVL
+    end"#,
+        powershell: r#"    $level = 0
+    if (-not [int]::TryParse($env:SHLVL, [ref]$level) -or $level -le 1) { return '' }
+    $symbol = '{symbol}'
+    if ($symbol) { ($symbol * $level) + ' ' } else { '{icon} ' + $level + ' ' }"#,
+        sample: "2",
+        coloring: Coloring::None,
+        symbols: false,
+    },
+    Module {
+        name: "char",
+        icon: "",
+        about: "the prompt character, with a different `root_symbol` for root",
+        bash: r#"    if [ "$EUID" -eq 0 ]; then
+        printf '%s' "$2"
+    else
+        printf '%s' "$1"
+    fi"#,
+        fish: r#"    if test (id -u) -eq 0
+        printf '%s' $argv[2]
+    else
+        printf '%s' $argv[1]
+    end"#,
+        powershell: r#"    if ($global:__prompt_changer_elevated) { $args[1] } else { $args[0] }"#,
+        sample: "$",
+        coloring: Coloring::None,
+        symbols: true,
+    },
+];
+
+/// 生成的函数名的前缀
+const FUNCTION_PREFIX: &str = "__prompt_changer_module_";
+
+/// 按名称查找模块
+pub fn find(name: &str) -> Result<&'static Module, CliError> {
+    MODULES.iter().find(|m| m.name == name).ok_or_else(|| {
+        if let Some(close) = suggest::closest(name, MODULES.iter().map(|m| m.name)) {
+            return CliError(
+                i18n::tr(
+                    &format!("Unknown module `{}`, did you mean `{}`?", name, close),
+                    &format!("未知的模块 `{}`, 是不是 `{}`?", name, close),
+                )
+                .to_string(),
+            );
+        }
+        let names: Vec<String> = MODULES.iter().map(|m| format!("{} ({})", m.name, m.about)).collect();
+        CliError(
+            i18n::tr(
+                &format!("Unknown module `{}`; use one of: {}", name, names.join(", ")),
+                &format!("未知的模块 `{}`; 可用: {}", name, names.join(", ")),
+            )
+            .to_string(),
+        )
+    })
+}
+
+/// 提示符中用到的一个模块, 图标已按是否使用 ASCII 选好
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Used {
+    pub name: &'static str,
+    pub glyph: String,
+    /// 是否同时显示区域
+    pub region: bool,
+    /// 代替数字重复显示的符号
+    pub symbol: Option<String>,
+    /// 值的通配模式与匹配时使用的 SGR 参数, 按模式的字母顺序取第一个匹配的
+    pub value_colors: Vec<(String, String)>,
+    /// 换算后的阈值与达到时使用的 SGR 参数, 最严重的在前
+    pub thresholds: Vec<(u64, String)>,
+    /// 达到阈值时在值后加上 `[warn]`、`[crit]` 等文字, 不只用颜色表示
+    pub markers: bool,
+}
+
+impl Module {
+    /// 生成的函数名
+    pub fn function(&self) -> String {
+        format!("{}{}", FUNCTION_PREFIX, self.name)
+    }
+
+    /// 提示符中的用法, 与 bash 的命令替换相同
+    pub fn call(&self) -> String {
+        format!("$({})", self.function())
+    }
+
+    /// 带参数的用法, 参数用单引号引用, 不能含单引号
+    pub fn call_with(&self, args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|a| format!("'{}'", a)).collect();
+        format!("$({} {})", self.function(), args.join(" "))
+    }
+
+    /// 在提示符中使用这个模块
+    pub fn used(&'static self, ascii: bool) -> Used {
+        Used {
+            name: self.name,
+            glyph: if self.icon.is_empty() { String::new() } else { icons::glyph(self.icon, ascii).unwrap_or_default().to_string() },
+            region: false,
+            symbol: None,
+            value_colors: Vec::new(),
+            thresholds: Vec::new(),
+            markers: false,
+        }
+    }
+
+    /// 是否可以显示区域
+    pub fn has_region(&self) -> bool {
+        self.bash.contains("{region}")
+    }
+
+    /// 是否可以用重复的符号代替数字
+    pub fn has_symbol(&self) -> bool {
+        self.bash.contains("{symbol}")
+    }
+}
+
+/// 命令替换是否调用模块, 是时返回模块
+pub fn called(cmd: &str) -> Option<&'static Module> {
+    let rest = cmd.trim().strip_prefix(FUNCTION_PREFIX)?;
+    find(rest.split_whitespace().next().unwrap_or_default()).ok()
+}
+
+/// 模块调用中单引号引用的参数
+pub fn arguments(cmd: &str) -> Vec<String> {
+    cmd.split('\'').skip(1).step_by(2).map(str::to_string).collect()
+}
+
+/// 普通用户与 root 的符号, 都是默认值时用 bash 的 `\$`
+pub fn prompt_char(symbol: &str, root_symbol: &str) -> String {
+    if symbol == "$" && root_symbol == "#" {
+        return "\\$".to_string();
+    }
+    find("char").map(|m| m.call_with(&[symbol, root_symbol])).unwrap_or_default()
+}
+
+/// 取区域的表达式, 不显示区域时为空
+fn region(shell: Shell, shown: bool) -> &'static str {
+    match (shell, shown) {
+        (Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd, true) => "${AWS_REGION:-${AWS_DEFAULT_REGION-}}",
+        (Shell::Fish, true) => "$AWS_REGION $AWS_DEFAULT_REGION",
+        (Shell::Pwsh, true) => "@($env:AWS_REGION, $env:AWS_DEFAULT_REGION) | Where-Object { $_ } | Select-Object -First 1",
+        (Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd, false) | (Shell::Fish, false) => "",
+        (Shell::Pwsh, false) => "$null",
+    }
+}
+
+/// 按 `name` 的值设置 `color` 的代码, 以换行结尾
+fn value_colors(shell: Shell, colors: &[(String, String)]) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let mut code = String::new();
+    match shell {
+        Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => {
+            code += "    case $name in\n";
+            for (pattern, sgr) in colors {
+                code += &format!("        {}) color='{}' ;;\n", pattern, sgr);
+            }
+            code += "    esac\n";
+        }
+        Shell::Fish => {
+            code += "    switch $name\n";
+            for (pattern, sgr) in colors {
+                code += &format!("        case '{}'\n            set color '{}'\n", pattern, sgr);
+            }
+            code += "    end\n";
+        }
+        Shell::Pwsh => {
+            code += "    switch -Wildcard ($name) {\n";
+            for (pattern, sgr) in colors {
+                code += &format!("        '{}' {{ $color = '{}'; break }}\n", pattern, sgr);
+            }
+            code += "    }\n";
+        }
+    }
+    code
+}
+
+/// 按 `value` 与阈值设置 `color` 的代码, 以换行结尾; `markers` 时同时设置 `mark`, 最严重的一档是 `[crit]`, 其余是 `[warn]`
+fn thresholds(shell: Shell, rising: bool, thresholds: &[(u64, String)], markers: bool) -> String {
+    // 三种 shell 的比较运算符写法相同
+    let op = if rising { "-ge" } else { "-le" };
+    let mut code = String::new();
+    for (i, (limit, sgr)) in thresholds.iter().enumerate() {
+        let mark = match (markers, i == 0 && thresholds.len() > 1) {
+            (false, _) => "",
+            (true, true) => " [crit]",
+            (true, false) => " [warn]",
+        };
+        let set = match shell {
+            _ if mark.is_empty() => String::new(),
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => format!(" mark='{}'", mark),
+            Shell::Fish => format!("\n        set mark '{}'", mark),
+            Shell::Pwsh => format!("; $mark = '{}'", mark),
+        };
+        code += &match (shell, i) {
+            (Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd, 0) => format!("    if [ \"$value\" {} {} ]; then color='{}'{}\n", op, limit, sgr, set),
+            (Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd, _) => format!("    elif [ \"$value\" {} {} ]; then color='{}'{}\n", op, limit, sgr, set),
+            (Shell::Fish, 0) => format!("    if test $value {} {}\n        set color '{}'{}\n", op, limit, sgr, set),
+            (Shell::Fish, _) => format!("    else if test $value {} {}\n        set color '{}'{}\n", op, limit, sgr, set),
+            (Shell::Pwsh, 0) => format!("    if ($value {} {}) {{ $color = '{}'{} }}\n", op, limit, sgr, set),
+            (Shell::Pwsh, _) => format!("    elseif ($value {} {}) {{ $color = '{}'{} }}\n", op, limit, sgr, set),
+        };
+    }
+    if !thresholds.is_empty() {
+        code += match shell {
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => "    fi\n",
+            Shell::Fish => "    end\n",
+            Shell::Pwsh => "",
+        };
+    }
+    code
+}
+
+/// 各模块的函数体, 已换上图标与选项
+fn bodies(used: &[Used], shell: Shell) -> impl Iterator<Item = (&'static Module, String)> + '_ {
+    used.iter().filter_map(move |u| {
+        let module = find(u.name).ok()?;
+        let code = match shell {
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => module.bash,
+            Shell::Fish => module.fish,
+            Shell::Pwsh => module.powershell,
+        };
+        let colors = match module.coloring {
+            Coloring::None => String::new(),
+            Coloring::Patterns => value_colors(shell, &u.value_colors),
+            Coloring::Thresholds { rising, .. } => thresholds(shell, rising, &u.thresholds, u.markers),
+        };
+        let body = code
+            .replace("{icon}", &u.glyph.replace('\'', ""))
+            .replace("{region}", region(shell, u.region))
+            .replace("{symbol}", &u.symbol.as_deref().unwrap_or_default().replace('\'', ""))
+            .replace("{colors}\n", &colors);
+        Some((module, body))
+    })
+}
+
+/// 定义 bash 函数的代码
+pub fn bash_functions(used: &[Used]) -> String {
+    bodies(used, Shell::Bash).map(|(m, body)| format!("{}() {{\n{}\n}}\n", m.function(), body)).collect()
+}
+
+/// 定义 fish 函数的代码
+pub fn fish_functions(used: &[Used]) -> String {
+    bodies(used, Shell::Fish).map(|(m, body)| format!("\nfunction {}\n{}\nend\n", m.function(), body)).collect()
+}
+
+/// 定义 PowerShell 函数的代码
+pub fn powershell_functions(used: &[Used]) -> String {
+    bodies(used, Shell::Pwsh).map(|(m, body)| format!("function global:{} {{\n{}\n}}\n", m.function(), body)).collect()
+}
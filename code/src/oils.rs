@@ -0,0 +1,46 @@
+use crate::color;
+use crate::prompt::{self, PromptSet, SOURCE_MARKER};
+use crate::shell::single_quote;
+use std::path::{Path, PathBuf};
+
+/// OSH 的 PS1 中没有实现的 bash 转义
+pub const UNSUPPORTED_ESCAPES: [&str; 4] = ["!", "#", "j", "l"];
+
+/// 交互式 OSH 读取的配置文件
+pub fn rc_path(home: &Path) -> PathBuf {
+    home.join(".config/oils/oshrc")
+}
+
+/// YSH 读取的配置文件; 其中定义的 `renderPrompt` 函数代替 PS1 画出提示符
+pub fn ysh_rc_path(home: &Path) -> PathBuf {
+    home.join(".config/oils/yshrc")
+}
+
+/// 提示符略去的设置
+pub fn notices(prompts: &PromptSet) -> Vec<String> {
+    prompt::left_out_notice("Oils", &prompts.unsupported_extras(true)).into_iter().collect()
+}
+
+/// 写入受管理区块的代码: 第一行记下原始的 bash 风格提示符. OSH 与 bash 一样展开 PS1 中的转义,
+/// 只支持按主机名切换的变体、标题与无障碍模式
+pub fn block_body(prompts: &PromptSet) -> String {
+    let title = prompt::bash_title(prompts.title.as_deref());
+    // 与 ksh 一样在 PS1 展开时读取上一条命令的结束状态, 不依赖 PROMPT_COMMAND
+    let err = if prompts.accessible { "$(__prompt_changer_err $?)" } else { "" };
+    let ps1 = |prompt: &str| single_quote(&format!("{}{}{}", err, title, prompt));
+    let mut body = format!("{}{}\n", SOURCE_MARKER, prompts.default);
+    if prompts.accessible {
+        body += "__prompt_changer_err() {\n    [ \"$1\" -ne 0 ] && printf '[err %s] ' \"$1\"\n}\n";
+    }
+    body += &color::bash_setup(&prompts.colors, false);
+    body += &format!("PS1={}", ps1(&prompts.default));
+    if !prompts.hosts.is_empty() {
+        // 模式不能加引号, 否则通配符不生效; 写入 spec 时已限制了其中的字符
+        body += "\ncase \"${HOSTNAME:-$(hostname)}\" in";
+        for (pattern, prompt) in &prompts.hosts {
+            body += &format!("\n    {})\n        PS1={}\n        ;;", pattern, ps1(prompt));
+        }
+        body += "\nesac";
+    }
+    body
+}
@@ -0,0 +1,184 @@
+use crate::i18n;
+use crate::logging;
+use crate::CliError;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// 结果的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    /// 供 Ansible 等工具读取的 JSON
+    Json,
+}
+
+impl OutputFormat {
+    /// 命令行中可选的输出格式
+    pub const NAMES: [&'static str; 2] = ["text", "json"];
+
+    /// 从命令行参数解析输出格式
+    pub fn from_name(name: &str) -> Result<OutputFormat, CliError> {
+        match name {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(CliError(i18n::tr(&format!("Unsupported output format: {}", other), &format!("不支持的输出格式: {}", other)).to_string())),
+        }
+    }
+}
+
+/// 用户用 `--output` 指定的格式
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// 设置输出格式
+pub fn set_format(format: OutputFormat) -> Result<(), CliError> {
+    FORMAT.set(format).map_err(|_| CliError(i18n::tr("Output format is already set", "输出格式已经设置过了").to_string()))
+}
+
+/// 是否以 JSON 输出
+pub fn is_json() -> bool {
+    FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+/// 是否用 `--accessible` 或配置打开了无障碍模式
+static ACCESSIBLE: OnceLock<bool> = OnceLock::new();
+
+/// 打开无障碍模式: 生成不只靠颜色表达信息的提示符, 交互界面改为逐行的问答, 便于读屏软件朗读
+pub fn set_accessible() -> Result<(), CliError> {
+    ACCESSIBLE.set(true).map_err(|_| CliError(i18n::tr("Accessible mode is already set", "无障碍模式已经设置过了").to_string()))
+}
+
+/// 是否处于无障碍模式
+pub fn is_accessible() -> bool {
+    ACCESSIBLE.get() == Some(&true)
+}
+
+/// JSON 模式下收集的警告, 随结果一起输出
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// 记下一条警告
+pub fn record_warning(message: String) {
+    WARNINGS.lock().unwrap().push(message);
+}
+
+/// 取出收集到的警告
+fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap())
+}
+
+/// 打印一条提示; JSON 模式下作为警告随结果输出, 以免破坏 JSON
+pub fn notice(message: &str) {
+    if is_json() {
+        record_warning(message.to_string());
+    } else {
+        logging::info(message);
+    }
+}
+
+/// JSON 值
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Number(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Json {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// 按 JSON 的规则转义字符串
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(text) => write_string(f, text),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// 打印一个命令的 JSON 结果, 附上收集到的警告
+pub fn print(command: &str, fields: Vec<(&'static str, Json)>) {
+    let mut object = vec![("command", Json::from(command))];
+    object.extend(fields);
+    object.push(("warnings", Json::from(take_warnings())));
+    println!("{}", Json::Object(object));
+}
+
+/// 打印 JSON 格式的错误
+pub fn print_error(message: String) {
+    println!("{}", Json::Object(vec![("error", Json::from(message)), ("warnings", Json::from(take_warnings()))]));
+}
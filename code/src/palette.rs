@@ -0,0 +1,255 @@
+use crate::i18n;
+use crate::logging;
+use crate::rc;
+use crate::shell;
+use crate::CliError;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 调色板: 槽位名称(如 `base08`、`accent`)到 `#rrggbb` 的映射
+pub type Palette = BTreeMap<String, String>;
+
+/// 保存的调色板文件
+#[derive(Debug, Deserialize)]
+struct PaletteFile {
+    colors: Palette,
+}
+
+/// ANSI 16 色在调色板中的槽位名称
+const ANSI_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// 调色板保存的目录
+fn palettes_dir() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".config/prompt-changer/palettes"))
+}
+
+/// 读取已导入的调色板
+pub fn load(name: &str) -> Result<Palette, Box<dyn std::error::Error>> {
+    let path = palettes_dir()?.join(format!("{}.toml", name));
+    let content = fs::read_to_string(&path).map_err(|err| {
+        CliError(
+            i18n::tr(
+                &format!("Cannot read palette `{}` ({}): {}; import it with `prompt-changer palette import`", name, path.display(), err),
+                &format!("无法读取调色板 `{}` ({}): {}; 请用 `prompt-changer palette import` 导入", name, path.display(), err),
+            )
+            .to_string(),
+        )
+    })?;
+    let file: PaletteFile =
+        toml::from_str(&content).map_err(|err| CliError(
+            i18n::tr(
+                &format!("Invalid palette {}: {}", path.display(), err),
+                &format!("调色板 {} 无效: {}", path.display(), err),
+            )
+            .to_string(),
+        ))?;
+    Ok(file.colors)
+}
+
+/// 把 `#rrggbb`、`0xrrggbb` 或 `rrggbb` 统一为 `#rrggbb`
+fn normalize_hex(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let hex = value.strip_prefix('#').or_else(|| value.strip_prefix("0x")).unwrap_or(value);
+    (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).then(|| format!("#{}", hex.to_ascii_lowercase()))
+}
+
+/// 逐行读取简单的 YAML, 返回每个带值的键的完整路径(如 `colors.normal.red`)与值
+fn yaml_entries(content: &str) -> Vec<(String, String)> {
+    let line_re = Regex::new(r"^(\s*)([A-Za-z0-9_]+)\s*:\s*(.*?)\s*$").unwrap();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut entries = Vec::new();
+    for line in content.lines() {
+        let line = line.split(" #").next().unwrap_or_default();
+        let caps = match line_re.captures(line) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let indent = caps[1].len();
+        while stack.last().is_some_and(|(i, _)| *i >= indent) {
+            stack.pop();
+        }
+        let key = caps[2].to_string();
+        if caps[3].is_empty() {
+            stack.push((indent, key));
+        } else {
+            let path: Vec<&str> = stack.iter().map(|(_, k)| k.as_str()).chain(std::iter::once(key.as_str())).collect();
+            entries.push((path.join("."), caps[3].to_string()));
+        }
+    }
+    entries
+}
+
+/// 从 base16 配色(YAML)中取出 base00-base0F
+fn parse_base16(content: &str) -> Palette {
+    let mut palette = Palette::new();
+    for (path, value) in yaml_entries(content) {
+        let key = path.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+        let is_slot = key.len() == 6 && key.starts_with("base0") && key[5..].chars().all(|c| c.is_ascii_hexdigit());
+        if let (true, Some(hex)) = (is_slot, normalize_hex(&value)) {
+            palette.insert(key, hex);
+        }
+    }
+    // 按 base16 的约定: base00 是背景, base05 是前景, base0D 常用作强调色
+    for (alias, slot) in [("background", "base00"), ("foreground", "base05"), ("accent", "base0d")] {
+        if let Some(hex) = palette.get(slot).cloned() {
+            palette.insert(alias.to_string(), hex);
+        }
+    }
+    palette
+}
+
+/// 由前景、背景与 16 个 ANSI 颜色生成调色板, 蓝色作为强调色
+fn ansi_palette(foreground: Option<String>, background: Option<String>, colors: Vec<Option<String>>) -> Palette {
+    let mut palette = Palette::new();
+    for (i, hex) in colors.into_iter().enumerate() {
+        if let Some(hex) = hex {
+            palette.insert(format!("color{}", i), hex);
+        }
+    }
+    if let Some(hex) = palette.get("color4").cloned() {
+        palette.insert("accent".to_string(), hex);
+    }
+    palette.extend(foreground.map(|hex| ("foreground".to_string(), hex)));
+    palette.extend(background.map(|hex| ("background".to_string(), hex)));
+    palette
+}
+
+/// 读取 Alacritty 的 YAML 配色
+fn parse_alacritty_yaml(content: &str) -> Palette {
+    let entries: BTreeMap<String, String> = yaml_entries(content).into_iter().collect();
+    let get = |path: String| entries.get(&path).and_then(|v| normalize_hex(v));
+    let colors = ["normal", "bright"]
+        .iter()
+        .flat_map(|group| ANSI_NAMES.iter().map(move |name| format!("colors.{}.{}", group, name)))
+        .map(get)
+        .collect();
+    ansi_palette(
+        get("colors.primary.foreground".to_string()),
+        get("colors.primary.background".to_string()),
+        colors,
+    )
+}
+
+/// 读取 WezTerm 或 Alacritty 的 TOML 配色
+fn parse_toml(content: &str) -> Result<Palette, CliError> {
+    let value: toml::Value =
+        toml::from_str(content).map_err(|err| CliError(i18n::tr(&format!("Invalid TOML: {}", err), &format!("TOML 无效: {}", err)).to_string()))?;
+    let colors = value.get("colors").ok_or_else(|| CliError(i18n::tr("No [colors] table found", "没有找到 [colors] 表").to_string()))?;
+    let hex = |v: Option<&toml::Value>| v.and_then(|v| v.as_str()).and_then(normalize_hex);
+    if let Some(ansi) = colors.get("ansi") {
+        // WezTerm: ansi 与 brights 各 8 个颜色
+        let list = |v: Option<&toml::Value>| -> Vec<Option<String>> {
+            let items = v.and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            (0..8).map(|i| hex(items.get(i))).collect()
+        };
+        let mut all = list(Some(ansi));
+        all.extend(list(colors.get("brights")));
+        return Ok(ansi_palette(hex(colors.get("foreground")), hex(colors.get("background")), all));
+    }
+    // Alacritty: [colors.primary]、[colors.normal] 与 [colors.bright]
+    let all = ["normal", "bright"]
+        .iter()
+        .flat_map(|group| ANSI_NAMES.iter().map(move |name| hex(colors.get(group).and_then(|g| g.get(name)))))
+        .collect();
+    let primary = colors.get("primary");
+    Ok(ansi_palette(
+        hex(primary.and_then(|p| p.get("foreground"))),
+        hex(primary.and_then(|p| p.get("background"))),
+        all,
+    ))
+}
+
+/// 导入配色文件并保存为调色板, 返回保存的位置与颜色数量
+pub fn import(path: &Path, name: Option<&str>) -> Result<(String, PathBuf, usize), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| CliError(
+            i18n::tr(
+                &format!("Cannot read {}: {}", path.display(), err),
+                &format!("无法读取 {}: {}", path.display(), err),
+            )
+            .to_string(),
+        ))?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    let palette = match ext.as_str() {
+        "yaml" | "yml" => {
+            let base16 = parse_base16(&content);
+            if base16.is_empty() {
+                parse_alacritty_yaml(&content)
+            } else {
+                base16
+            }
+        }
+        "toml" => parse_toml(&content)?,
+        _ => {
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("Cannot tell the format of {}; expected a base16 .yaml, a WezTerm .toml or an Alacritty .yml/.toml file", path.display()),
+                    &format!("无法判断 {} 的格式; 应为 base16 的 .yaml、WezTerm 的 .toml 或 Alacritty 的 .yml/.toml 文件", path.display()),
+                )
+                .to_string(),
+            )))
+        }
+    };
+    if palette.is_empty() {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("No colors found in {}", path.display()),
+                &format!("{} 中没有找到颜色", path.display()),
+            )
+            .to_string(),
+        )));
+    }
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string(),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("Palette name `{}` may only contain letters, digits, `-` and `_`; pass --name", name),
+                &format!("调色板名 `{}` 只能包含字母、数字、`-` 与 `_`; 请用 --name 指定", name),
+            )
+            .to_string(),
+        )));
+    }
+    let mut out = format!("# Imported by prompt-changer from {}\n[colors]\n", path.display());
+    for (slot, hex) in &palette {
+        out += &format!("{} = \"{}\"\n", slot, hex);
+    }
+    let target = palettes_dir()?.join(format!("{}.toml", name));
+    if let Some(parent) = target.parent() {
+        rc::create_dirs(parent)?;
+    }
+    fs::write(&target, out)?;
+    rc::own_like_parent(&target);
+    Ok((name, target, palette.len()))
+}
+
+/// 导入配色并打印可用的槽位
+pub fn run(path: &Path, name: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let (name, target, count) = import(path, name)?;
+    let palette = load(&name)?;
+    logging::info(
+        i18n::tr(
+            &format!("Imported {} color(s) into palette `{}` ({}).", count, name, target.display()),
+            &format!("已把 {} 种颜色导入调色板 `{}`({})。", count, name, target.display()),
+        ),
+    );
+    logging::info(
+        i18n::tr(
+            &format!("Slots: {}", palette.keys().cloned().collect::<Vec<_>>().join(", ")),
+            &format!("槽位: {}", palette.keys().cloned().collect::<Vec<_>>().join(", ")),
+        ),
+    );
+    logging::info(
+        i18n::tr(
+            &format!("Use it with `palette = \"{}\"` in a spec and colors such as `color = \"accent\"`.", name),
+            &format!("在描述文件中用 `palette = \"{}\"` 和 `color = \"accent\"` 这样的颜色使用它。", name),
+        ),
+    );
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use crate::color;
+use crate::history;
+use crate::i18n;
+#[cfg(unix)]
+use crate::output;
+use crate::preview;
+use crate::prompt::PromptSet;
+use crate::shell::Shell;
+use crate::themes;
+use std::io::{self, BufRead, Write};
+#[cfg(unix)]
+use std::io::IsTerminal;
+
+/// 可供挑选的一项: 主题或历史中应用过的提示符
+struct Choice {
+    /// 列表中显示、也用于搜索的名称
+    label: String,
+    /// 预览窗格中名称下的说明; 只有终端中的列表有预览窗格
+    #[cfg_attr(not(unix), allow(dead_code))]
+    about: String,
+    prompts: PromptSet,
+}
+
+/// 所有主题与该 shell 应用过的提示符, 主题在前; 无法载入的主题给出警告后跳过
+fn choices(shell: Shell, ascii: bool) -> Result<Vec<Choice>, Box<dyn std::error::Error>> {
+    let mut choices = Vec::new();
+    for (name, builtin, spec) in themes::all_themes() {
+        let prompts = spec.and_then(|mut spec| {
+            spec.ascii |= ascii;
+            Ok((spec.prompts()?, spec.tags))
+        });
+        let (prompts, tags) = match prompts {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                i18n::warning(i18n::tr(&format!("skipping theme `{}`: {}", name, err), &format!("跳过主题 `{}`: {}", name, err)));
+                continue;
+            }
+        };
+        let mut about = if builtin { "built-in theme".to_string() } else { "installed theme".to_string() };
+        if !tags.is_empty() {
+            about += &format!(", {}", tags.join(", "));
+        }
+        choices.push(Choice { label: name, about, prompts });
+    }
+    let mut seen = Vec::new();
+    for entry in history::load()?.into_iter().filter(|e| e.shell == shell) {
+        if seen.contains(&entry.prompt) {
+            continue;
+        }
+        seen.push(entry.prompt.clone());
+        choices.push(Choice {
+            label: format!("history: {}", entry.prompt),
+            about: format!("applied {}", entry.time),
+            prompts: PromptSet::single(&entry.prompt),
+        });
+    }
+    Ok(choices)
+}
+
+/// 模糊匹配: 查询的字符依次出现在名称中(不分大小写)时返回得分, 连续匹配与词首匹配得分更高
+fn score(label: &str, query: &str) -> Option<i64> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut last: Option<usize> = None;
+    let mut from = 0;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let i = from + label[from..].iter().position(|c| *c == q)?;
+        score += match last {
+            Some(prev) if prev + 1 == i => 8,
+            _ if i == 0 || !label[i - 1].is_alphanumeric() => 6,
+            _ => 1,
+        };
+        score -= i64::try_from(i - from).unwrap_or(0).min(4);
+        last = Some(i);
+        from = i + 1;
+    }
+    Some(score)
+}
+
+/// 按查询筛选并排序, 返回各项的下标; 查询为空时保持原来的顺序
+fn filter(choices: &[Choice], query: &str) -> Vec<usize> {
+    let mut matched: Vec<(i64, usize)> = choices.iter().enumerate().filter_map(|(i, c)| score(&c.label, query).map(|s| (s, i))).collect();
+    matched.sort_by_key(|(score, i)| (-score, *i));
+    matched.into_iter().map(|(_, i)| i).collect()
+}
+
+/// 挑选主题或应用过的提示符: 在终端中是可以输入筛选的列表与预览窗格, 否则(以及无障碍模式下)按编号选择; 取消时返回 None
+pub fn pick(shell: Shell, ascii: bool) -> Result<Option<PromptSet>, Box<dyn std::error::Error>> {
+    let mut choices = choices(shell, ascii)?;
+    #[cfg(unix)]
+    if io::stdin().is_terminal() && io::stdout().is_terminal() && !output::is_accessible() {
+        let picked = interactive(&choices)?;
+        return Ok(picked.map(|i| choices.swap_remove(i).prompts));
+    }
+    let picked = numbered(&choices)?;
+    Ok(picked.map(|i| choices.swap_remove(i).prompts))
+}
+
+/// 不在终端中时: 列出各项, 读入编号或名称
+fn numbered(choices: &[Choice]) -> io::Result<Option<usize>> {
+    for (i, choice) in choices.iter().enumerate() {
+        // 多行的提示符换行后缩进到编号之后
+        let sample = preview::sample(&choice.prompts, color::output_depth(), false).replace('\n', "\n     ");
+        println!("{:>3}  {}  {}", i + 1, choice.label, sample);
+    }
+    print!("{}", i18n::tr("Number or name to apply (empty to cancel): ", "要应用的编号或名称 (留空取消): "));
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().lock().read_line(&mut input)?;
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let by_number = input.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).filter(|i| *i < choices.len());
+    Ok(by_number.or_else(|| filter(choices, input).first().copied()))
+}
+
+/// 关闭回显与行缓冲, 离开时恢复终端原来的设置
+#[cfg(unix)]
+pub struct RawMode {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        // SAFETY: termios 是只含整数的 C 结构体, 全零是合法的初始值, 随后由 tcgetattr 填写
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: 文件描述符 0 是标准输入, original 指向有效的结构体
+        if unsafe { libc::tcgetattr(0, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        // 保留输出处理, 换行仍然回到行首; 关闭 ISIG 后 Ctrl-C 作为按键读入, 以便先恢复终端
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: 同上, raw 是由 tcgetattr 得到的设置修改而来
+        if unsafe { libc::tcsetattr(0, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: 恢复 enable 时保存的设置
+        unsafe { libc::tcsetattr(0, libc::TCSAFLUSH, &self.original) };
+    }
+}
+
+/// 终端的行数与列数, 取不到时按 24x80
+#[cfg(unix)]
+pub fn terminal_size() -> (usize, usize) {
+    // SAFETY: winsize 是只含整数的 C 结构体, 全零是合法的初始值
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    // SAFETY: TIOCGWINSZ 把终端大小写入 size
+    let ok = unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_row > 0 && size.ws_col > 0 {
+        (usize::from(size.ws_row), usize::from(size.ws_col))
+    } else {
+        (24, 80)
+    }
+}
+
+/// 读到的按键
+#[cfg(unix)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    Tab,
+    Enter,
+    Cancel,
+    Other,
+}
+
+/// 读入一个字节, 带超时时到时返回 None
+#[cfg(unix)]
+fn read_byte(timeout_ms: i32) -> io::Result<Option<u8>> {
+    let mut fd = libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 };
+    // SAFETY: fd 指向一个有效的 pollfd
+    if timeout_ms >= 0 && unsafe { libc::poll(&mut fd, 1, timeout_ms) } <= 0 {
+        return Ok(None);
+    }
+    let mut byte = 0u8;
+    // SAFETY: 向一个字节的缓冲区读入最多一个字节
+    match unsafe { libc::read(0, (&mut byte as *mut u8).cast(), 1) } {
+        1 => Ok(Some(byte)),
+        0 => Ok(None),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// 读入一个按键; 单独的 Esc 与 Ctrl-C、Ctrl-D 都是取消
+#[cfg(unix)]
+pub fn read_key() -> io::Result<Key> {
+    let Some(byte) = read_byte(-1)? else {
+        return Ok(Key::Cancel);
+    };
+    Ok(match byte {
+        b'\r' | b'\n' => Key::Enter,
+        3 | 4 => Key::Cancel,
+        127 | 8 => Key::Backspace,
+        b'\t' => Key::Tab,
+        14 => Key::Down,
+        16 => Key::Up,
+        27 => match read_byte(50)? {
+            None => Key::Cancel,
+            Some(b'[' | b'O') => match read_byte(50)? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                _ => Key::Other,
+            },
+            Some(_) => Key::Other,
+        },
+        byte if byte < 0x80 && !byte.is_ascii_control() => Key::Char(char::from(byte)),
+        byte if byte >= 0xc0 => {
+            // UTF-8 多字节字符
+            let len = if byte >= 0xf0 { 4 } else if byte >= 0xe0 { 3 } else { 2 };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                bytes.extend(read_byte(50)?);
+            }
+            String::from_utf8(bytes).ok().and_then(|s| s.chars().next()).map_or(Key::Other, Key::Char)
+        }
+        _ => Key::Other,
+    })
+}
+
+/// 截断到终端宽度, 不计颜色编码
+#[cfg(unix)]
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// 画出查询行、筛选后的列表与预览窗格
+#[cfg(unix)]
+fn draw(choices: &[Choice], query: &str, matched: &[usize], selected: usize) -> io::Result<()> {
+    let (rows, columns) = terminal_size();
+    let preview_rows = 4;
+    let list_rows = rows.saturating_sub(preview_rows + 2).max(1);
+    let top = selected.saturating_sub(list_rows - 1);
+    let mut out = String::from("\x1b[H\x1b[2J");
+    out += &format!(
+        "{}  {}\r\n",
+        color::paint(&format!("{}/{}", matched.len(), choices.len()), "2"),
+        i18n::tr("type to filter, ↑/↓ to move, Enter to apply, Esc to cancel", "输入以筛选, ↑/↓ 移动, Enter 应用, Esc 取消")
+    );
+    out += &format!("> {}\r\n", query);
+    for (row, i) in matched.iter().enumerate().skip(top).take(list_rows) {
+        let label = fit(&choices[*i].label, columns.saturating_sub(2));
+        if row == selected {
+            out += &format!("\x1b[7m> {}\x1b[0m\r\n", label);
+        } else {
+            out += &format!("  {}\r\n", label);
+        }
+    }
+    if let Some(choice) = matched.get(selected).map(|i| &choices[*i]) {
+        out += &format!("\x1b[{};1H{}\r\n", rows.saturating_sub(preview_rows) + 1, "─".repeat(columns));
+        out += &format!("{}\r\n", color::paint(&fit(&choice.about, columns), "2"));
+        let sample = preview::sample(&choice.prompts, color::output_depth(), false);
+        out += &format!("{}\x1b[0m", sample.replace('\n', "\r\n"));
+    }
+    out += &format!("\x1b[2;{}H", query.chars().count() + 3);
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(out.as_bytes())?;
+    stdout.flush()
+}
+
+/// 在备用屏幕中运行挑选界面, 返回选中项的下标
+#[cfg(unix)]
+fn interactive(choices: &[Choice]) -> io::Result<Option<usize>> {
+    let _raw = RawMode::enable()?;
+    print!("\x1b[?1049h");
+    let result = (|| {
+        let mut query = String::new();
+        let mut selected = 0;
+        loop {
+            let matched = filter(choices, &query);
+            selected = selected.min(matched.len().saturating_sub(1));
+            draw(choices, &query, &matched, selected)?;
+            match read_key()? {
+                Key::Char(c) => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Key::Backspace => {
+                    query.pop();
+                    selected = 0;
+                }
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected += 1,
+                Key::Enter if !matched.is_empty() => return Ok(Some(matched[selected])),
+                Key::Enter | Key::Left | Key::Right | Key::Tab | Key::Other => {}
+                Key::Cancel => return Ok(None),
+            }
+        }
+    })();
+    print!("\x1b[?1049l");
+    io::stdout().flush()?;
+    result
+}
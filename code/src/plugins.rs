@@ -0,0 +1,234 @@
+use crate::i18n;
+use crate::prompt::COLOR_NAMES;
+use crate::shell::{self, Shell};
+use crate::CliError;
+use regex::Regex;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// 调用插件的 shell 函数
+const FUNCTION: &str = "__prompt_changer_plugin";
+
+/// 插件所在的目录; 插件是其中的可执行文件, 文件名即插件名; 也可以是 `名称.wasm` 的 WASM 模块,
+/// 由 wasmtime 在沙箱中运行, 只能只读地访问当前目录、读取列出的环境变量, 不能写文件、联网或运行其他程序
+pub fn dir() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".config/prompt-changer/plugins"))
+}
+
+/// 插件是否是在沙箱中运行的 WASM 模块
+pub fn is_wasm(name: &str) -> bool {
+    dir().is_ok_and(|dir| dir.join(format!("{}.wasm", name)).is_file())
+}
+
+/// wasmtime 只读地预先打开当前目录, 在模块中也叫 `.`
+const PREOPEN_CWD: &str = ".::.::readonly";
+
+/// 调用的是 WASM 插件但没有安装 wasmtime 时返回插件名; 这时生成的函数什么也不显示
+pub fn missing_wasmtime(cmd: &str) -> Option<String> {
+    called(cmd).filter(|name| is_wasm(name) && !shell::on_path("wasmtime"))
+}
+
+/// 检查插件名, 只能用字母、数字、`-` 与 `_`, 以免拼出目录外的路径
+pub fn check_name(name: &str) -> Result<(), CliError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(CliError(
+            i18n::tr(
+                &format!("Plugin name `{}` may only contain letters, digits, `-` and `_`", name),
+                &format!("插件名 `{}` 只能包含字母、数字、`-` 与 `_`", name),
+            )
+            .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 检查交给 WASM 插件的环境变量名
+pub fn check_env(name: &str) -> Result<(), CliError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(CliError(
+            i18n::tr(&format!("`{}` is not a valid environment variable name", name), &format!("`{}` 不是有效的环境变量名", name)).to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 提示符中调用插件的写法, WASM 插件能读取的环境变量跟在插件名后
+pub fn call(name: &str, env: &[String]) -> String {
+    let args: String = env.iter().map(|var| format!(" '{}'", var)).collect();
+    format!("$({} '{}'{})", FUNCTION, name, args)
+}
+
+/// 命令替换调用的插件名与环境变量, 不是插件调用时为 None
+fn arguments(cmd: &str) -> Option<Vec<String>> {
+    let rest = cmd.trim().strip_prefix(FUNCTION)?;
+    let args: Vec<String> = rest.split('\'').skip(1).step_by(2).map(str::to_string).collect();
+    Some(args).filter(|args| args.first().is_some_and(|name| check_name(name).is_ok()))
+}
+
+/// 命令替换调用的插件名, 不是插件调用时为 None
+pub fn called(cmd: &str) -> Option<String> {
+    arguments(cmd)?.into_iter().next()
+}
+
+/// 插件输出的 JSON 中 `text` 字段的值, 只处理 `\"` 与 `\\` 转义
+fn text_field(output: &str) -> Option<String> {
+    let re = Regex::new(r#""text"\s*:\s*"((?:[^"\\]|\\.)*)""#).unwrap();
+    let text = re.captures(output)?.get(1)?.as_str().replace("\\\"", "\"").replace("\\\\", "\\");
+    Some(text).filter(|t| !t.is_empty())
+}
+
+/// 预览中实际运行插件调用, 取它显示的文字; 插件不存在或出错时为空
+pub fn sample(cmd: &str) -> String {
+    let (Some(args), Ok(dir)) = (arguments(cmd), dir()) else {
+        return String::new();
+    };
+    let wasm = dir.join(format!("{}.wasm", args[0]));
+    let mut command = if wasm.is_file() {
+        let mut command = Command::new("wasmtime");
+        command.args(["run", "--dir", PREOPEN_CWD]);
+        for var in &args[1..] {
+            command.arg("--env").arg(format!("{}={}", var, std::env::var(var).unwrap_or_default()));
+        }
+        command.arg(wasm);
+        command
+    } else {
+        Command::new(dir.join(&args[0]))
+    };
+    let output = command.arg("--json").stdin(Stdio::null()).stderr(Stdio::null()).output();
+    match output {
+        Ok(output) if output.status.success() => {
+            text_field(&String::from_utf8_lossy(&output.stdout)).map(|t| format!("{} ", t)).unwrap_or_default()
+        }
+        _ => String::new(),
+    }
+}
+
+/// 插件可以用的颜色名称与 SGR 参数, `bright-` 开头的是亮色
+fn colors() -> Vec<(String, u32)> {
+    let names = COLOR_NAMES.iter().enumerate();
+    names
+        .clone()
+        .map(|(i, name)| (name.to_string(), 30 + i as u32))
+        .chain(names.map(|(i, name)| (format!("bright-{}", name), 90 + i as u32)))
+        .collect()
+}
+
+/// bash 函数. 插件以 `--json` 运行, 输出如 `{"text": "…", "color": "green", "bold": true}` 的 JSON;
+/// 失败或 `text` 为空时不显示. `{colors}` 会换成按颜色名称设置 `sgr` 的代码
+const BASH: &str = r#"    local dir="$HOME/.config/prompt-changer/plugins" out text color= sgr= var args=()
+    if [ -f "$dir/$1.wasm" ]; then
+        command -v wasmtime >/dev/null || return 0
+        for var in "${@:2}"; do
+            args+=(--env "$var=${!var-}")
+        done
+        out=$(wasmtime run --dir {preopen} ${args[@]+"${args[@]}"} "$dir/$1.wasm" --json 2>/dev/null </dev/null) || return 0
+    else
+        [ -x "$dir/$1" ] || return 0
+        out=$("$dir/$1" --json 2>/dev/null </dev/null) || return 0
+    fi
+    local re='"text"[[:space:]]*:[[:space:]]*"(([^"\\]|\\.)*)"'
+    [[ $out =~ $re ]] || return 0
+    text=${BASH_REMATCH[1]//\\\"/\"}
+    text=${text//\\\\/\\}
+    [ -n "$text" ] || return 0
+    re='"color"[[:space:]]*:[[:space:]]*"([a-z-]+)"'
+    [[ $out =~ $re ]] && color=${BASH_REMATCH[1]}
+{colors}
+    re='"bold"[[:space:]]*:[[:space:]]*true'
+    [[ $out =~ $re ]] && sgr=${sgr:+$sgr;}1
+    [ -n "$sgr" ] && printf '\001\033[%sm\002' "$sgr"
+    printf '%s' "$text"
+    [ -n "$sgr" ] && printf '\001\033[22;39m\002'
+    printf ' '"#;
+
+/// fish 函数
+const FISH: &str = r#"    set -l dir $HOME/.config/prompt-changer/plugins
+    set -l out
+    if test -f $dir/$argv[1].wasm
+        command -q wasmtime; or return 0
+        set -l vars $argv
+        set -e vars[1]
+        set -l args
+        for var in $vars
+            set -a args --env "$var=$$var"
+        end
+        set out (wasmtime run --dir {preopen} $args $dir/$argv[1].wasm --json 2>/dev/null </dev/null); or return 0
+    else
+        test -x $dir/$argv[1]; or return 0
+        set out ($dir/$argv[1] --json 2>/dev/null </dev/null); or return 0
+    end
+    set -l text (string match -r -- '"text"\s*:\s*"((?:[^"\\\\]|\\\\.)*)"' $out)[2]
+    set text (string replace -a -- '\\"' '"' $text | string replace -a -- '\\\\' '\\')
+    test -n "$text"; or return 0
+    set -l sgr
+    set -l color (string match -r -- '"color"\s*:\s*"([a-z-]+)"' $out)[2]
+{colors}
+    string match -qr -- '"bold"\s*:\s*true' $out; and set sgr $sgr 1
+    test -n "$sgr"; and printf '\e[%sm' (string join ';' $sgr)
+    printf '%s' $text
+    test -n "$sgr"; and printf '\e[22;39m'
+    printf ' '"#;
+
+/// PowerShell 函数; Windows 上插件可以带扩展名, 如 `name.ps1`、`name.exe`
+const POWERSHELL: &str = r#"    $dir = Join-Path $HOME '.config/prompt-changer/plugins'
+    $wasm = Join-Path $dir "$($args[0]).wasm"
+    if (Test-Path $wasm) {
+        if (-not (Get-Command wasmtime -ErrorAction Ignore)) { return '' }
+        $vars = @($args | Select-Object -Skip 1 | ForEach-Object { '--env'; "$_=$([Environment]::GetEnvironmentVariable($_))" })
+        $raw = wasmtime run --dir {preopen} @vars $wasm --json 2>$null
+    } else {
+        $plugin = @(Get-Item -Path (Join-Path $dir $args[0]), (Join-Path $dir "$($args[0]).*") -ErrorAction Ignore)[0]
+        if (-not $plugin) { return '' }
+        $raw = & $plugin.FullName --json 2>$null
+    }
+    if ($LASTEXITCODE) { return '' }
+    try { $out = ($raw -join "`n") | ConvertFrom-Json } catch { return '' }
+    if (-not $out.text) { return '' }
+    $sgr = @()
+    $color = [string]$out.color
+{colors}
+    if ($out.bold -eq $true) { $sgr += 1 }
+    $text = [string]$out.text
+    if ($sgr) { $text = "$([char]27)[$($sgr -join ';')m$text$([char]27)[22;39m" }
+    $text + ' '"#;
+
+/// 换掉函数模板中的 `{colors}` 与 `{preopen}`
+fn fill(template: &str, shell: Shell) -> String {
+    template.replace("{colors}", &color_cases(shell)).replace("{preopen}", &format!("'{}'", PREOPEN_CWD))
+}
+
+/// 按颜色名称设置 `sgr` 的代码
+fn color_cases(shell: Shell) -> String {
+    let cases = colors().into_iter();
+    match shell {
+        Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => {
+            let arms: String = cases.map(|(name, sgr)| format!("        {}) sgr={} ;;\n", name, sgr)).collect();
+            format!("    case $color in\n{}    esac", arms)
+        }
+        Shell::Fish => {
+            let arms: String = cases.map(|(name, sgr)| format!("        case {}\n            set sgr {}\n", name, sgr)).collect();
+            format!("    switch \"$color\"\n{}    end", arms)
+        }
+        Shell::Pwsh => {
+            let arms: String = cases.map(|(name, sgr)| format!("        '{}' {{ $sgr += {} }}\n", name, sgr)).collect();
+            format!("    switch ($color) {{\n{}    }}", arms)
+        }
+    }
+}
+
+/// 定义 bash 函数的代码
+pub fn bash_function() -> String {
+    format!("{}() {{\n{}\n}}\n", FUNCTION, fill(BASH, Shell::Bash))
+}
+
+/// 定义 fish 函数的代码
+pub fn fish_function() -> String {
+    format!("\nfunction {}\n{}\nend\n", FUNCTION, fill(FISH, Shell::Fish))
+}
+
+/// 定义 PowerShell 函数的代码
+pub fn powershell_function() -> String {
+    format!("function global:{} {{\n{}\n}}\n", FUNCTION, fill(POWERSHELL, Shell::Pwsh))
+}
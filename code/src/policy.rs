@@ -0,0 +1,110 @@
+use crate::color;
+use crate::config;
+use crate::i18n;
+use crate::logging;
+use crate::prompt::PromptSet;
+use crate::shell;
+use crate::spec::Spec;
+use crate::CliError;
+use regex::Regex;
+use std::path::PathBuf;
+
+/// 团队策略文件: PROMPT_CHANGER_POLICY 指定的文件, 否则是 /etc/prompt-changer/policy.toml
+pub fn path() -> PathBuf {
+    match config::env_value("POLICY") {
+        Some(path) => PathBuf::from(path),
+        None => shell::system_root().join("etc/prompt-changer/policy.toml"),
+    }
+}
+
+/// 读取策略, 没有策略文件时为 None. 策略是一个描述文件, 其中的片段总是显示在用户提示符的前面;
+/// 颜色只能按主机名变化, 因为用户按目录、时间段切换的提示符会取代按主机名选择的提示符
+fn load() -> Result<Option<PromptSet>, Box<dyn std::error::Error>> {
+    let path = path();
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let locked = Spec::load(&path)?.prompts()?;
+    if !locked.directories.is_empty() || !locked.times.is_empty() {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("The team policy {} may only vary its colors by [[host]]; remove its [[directory]] and [[time]] rules", path.display()),
+                &format!("团队策略 {} 只能按 [[host]] 改变颜色; 请删除其中的 [[directory]] 与 [[time]] 规则", path.display()),
+            )
+            .to_string(),
+        )));
+    }
+    logging::debug("policy", format!("read the team policy {}", path.display()));
+    Ok(Some(locked))
+}
+
+/// 策略的提示符中引用的颜色变量依次后移 `offset` 个, 接在用户的颜色之后
+fn shift_colors(prompt: &str, offset: usize) -> String {
+    let re = Regex::new(r"__prompt_changer_color_(\d+)\b").unwrap();
+    re.replace_all(prompt, |caps: &regex::Captures| color::color_var(caps[1].parse::<usize>().unwrap_or(0) + offset)).into_owned()
+}
+
+/// 去掉提示符开头已有的锁定片段(如从历史或已写入的提示符重新应用时), 以免重复
+fn bare<'a>(prompt: &'a str, locked: &[String]) -> &'a str {
+    locked.iter().find_map(|l| prompt.strip_prefix(l.as_str())).unwrap_or(prompt)
+}
+
+/// 把团队策略锁定的片段合并进用户的提示符, 用户的设置无法去掉它们; 没有策略时原样返回
+pub fn apply(mut prompts: PromptSet) -> Result<PromptSet, Box<dyn std::error::Error>> {
+    let Some(locked) = load()? else {
+        return Ok(prompts);
+    };
+    let offset = prompts.colors.len();
+    if !locked.hosts.is_empty() && (!prompts.directories.is_empty() || !prompts.times.is_empty()) {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!(
+                    "The team policy {} colors its segments by host, which cannot be combined with [[directory]] or [[time]] rules; remove them from your spec",
+                    path().display()
+                ),
+                &format!(
+                    "团队策略 {} 按主机名为片段着色, 不能与 [[directory]] 或 [[time]] 规则同时使用; 请从你的描述文件中删除它们",
+                    path().display()
+                ),
+            )
+            .to_string(),
+        )));
+    }
+    let locked_default = shift_colors(&locked.default, offset);
+    let locked_hosts: Vec<(String, String)> = locked.hosts.iter().map(|(p, prompt)| (p.clone(), shift_colors(prompt, offset))).collect();
+    let variants: Vec<String> = std::iter::once(locked_default.clone()).chain(locked_hosts.iter().map(|(_, p)| p.clone())).collect();
+    let join = |locked: &str, prompt: &str| format!("{}{}", locked, bare(prompt, &variants));
+    // 策略的主机规则在前; 用户有同一模式的规则时合并, 其余主机上使用用户自己的规则
+    let mut hosts = Vec::new();
+    for (pattern, locked_prompt) in &locked_hosts {
+        let own = prompts.hosts.iter().find(|(p, _)| p == pattern).map_or(&prompts.default, |(_, prompt)| prompt);
+        hosts.push((pattern.clone(), join(locked_prompt, own)));
+    }
+    for (pattern, prompt) in &prompts.hosts {
+        if !locked_hosts.iter().any(|(p, _)| p == pattern) {
+            hosts.push((pattern.clone(), join(&locked_default, prompt)));
+        }
+    }
+    prompts.hosts = hosts;
+    for prompt in prompts.directories.iter_mut().map(|(_, p)| p).chain(prompts.times.iter_mut().map(|(_, p)| p)) {
+        *prompt = join(&locked_default, prompt);
+    }
+    prompts.default = join(&locked_default, &prompts.default);
+    prompts.colors.extend(locked.colors);
+    for used in locked.modules {
+        match prompts.modules.iter().find(|u| u.name == used.name) {
+            Some(other) if *other != used => {
+                return Err(Box::new(CliError(
+                    i18n::tr(
+                        &format!("The `{}` module is used by both the team policy and your prompt with different settings", used.name),
+                        &format!("团队策略与你的提示符都用了 `{}` 模块, 但设置不同", used.name),
+                    )
+                    .to_string(),
+                )));
+            }
+            Some(_) => {}
+            None => prompts.modules.push(used),
+        }
+    }
+    Ok(prompts)
+}
@@ -0,0 +1,302 @@
+use crate::color::{self, ColorSlot};
+use crate::i18n;
+use crate::logging;
+use crate::modules;
+use crate::plugins;
+use crate::prompt::{self, PromptSet, Segment, SOURCE_MARKER};
+use crate::shell;
+use crate::terminal;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// 系统中的 PowerShell: PowerShell 7 的 pwsh 优先, 没有时用 Windows 自带的 powershell
+pub fn executable() -> &'static str {
+    static EXECUTABLE: OnceLock<&'static str> = OnceLock::new();
+    EXECUTABLE.get_or_init(|| if shell::on_path("pwsh") || !cfg!(windows) { "pwsh" } else { "powershell" })
+}
+
+/// 向 PowerShell 询问 $PROFILE; 文档目录可能被重定向(如 OneDrive), 只有它自己知道确切位置
+fn query_profile() -> Option<PathBuf> {
+    static PROFILE: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PROFILE
+        .get_or_init(|| {
+            let out = Command::new(executable())
+                .args(["-NoProfile", "-NonInteractive", "-Command", "$PROFILE"])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .output()
+                .ok()
+                .filter(|out| out.status.success())?;
+            let path = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            logging::debug("shell", format!("{} reports $PROFILE as {}", executable(), path));
+            Some(PathBuf::from(path)).filter(|p| p.is_absolute())
+        })
+        .clone()
+}
+
+/// 当前用户、当前主机的配置文件($PROFILE). `home` 是 config root 时不询问 PowerShell, 按约定的位置推算
+pub fn profile_path(home: &Path, use_real_profile: bool) -> PathBuf {
+    if use_real_profile {
+        if let Some(path) = query_profile() {
+            return path;
+        }
+    }
+    if cfg!(windows) {
+        let dir = if executable() == "pwsh" { "PowerShell" } else { "WindowsPowerShell" };
+        let documents = if use_real_profile { dirs::document_dir() } else { None };
+        documents.unwrap_or_else(|| home.join("Documents")).join(dir).join("Microsoft.PowerShell_profile.ps1")
+    } else {
+        home.join(".config/powershell/Microsoft.PowerShell_profile.ps1")
+    }
+}
+
+/// 用 PowerShell 的单引号引用文本
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// 把单个片段翻译为 PowerShell 表达式
+fn word(segment: &Segment) -> String {
+    match segment {
+        Segment::Text(text) => quote(text),
+        // 本工具自己的颜色变量是全局变量, 其余按环境变量读取
+        Segment::Variable(name) if name.starts_with("__prompt_changer") => format!("$global:{}", name),
+        Segment::Variable(name) => format!("$env:{}", name),
+        Segment::Command(cmd) => format!("(({}) -join ' ')", cmd),
+        Segment::NonPrinting(inner) => prompt::parse_bash(inner).iter().map(word).collect::<Vec<_>>().join(", "),
+        Segment::Color(color) => {
+            let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+            format!("$e, {}", quote(rest))
+        }
+        Segment::Escape(escape) => escape_word(escape),
+    }
+}
+
+/// 把 bash 的反斜杠转义翻译为 PowerShell 表达式; 函数开头定义了 `$e`(ESC) 与 `$cwd`(家目录缩写为 ~ 的当前目录)
+pub fn escape_word(escape: &str) -> String {
+    let date = |fmt: &str| format!("(Get-Date -UFormat {})", quote(fmt));
+    match escape {
+        "u" => "[Environment]::UserName".to_string(),
+        "h" => "[Environment]::MachineName".to_string(),
+        "H" => "[Net.Dns]::GetHostName()".to_string(),
+        "w" => "$cwd".to_string(),
+        "W" => "(Split-Path -Leaf $cwd)".to_string(),
+        "t" => date("%H:%M:%S"),
+        "T" => date("%I:%M:%S"),
+        "@" => date("%I:%M %p"),
+        "A" => date("%H:%M"),
+        "d" => date("%a %b %d"),
+        "$" => "$(if ($global:__prompt_changer_elevated) { '#' } else { '$' })".to_string(),
+        "s" => quote(executable()),
+        "v" | "V" => "$PSVersionTable.PSVersion.ToString()".to_string(),
+        "j" => "@(Get-Job).Count".to_string(),
+        "!" => "((Get-History -Count 1).Id + 1)".to_string(),
+        "#" => "(@(Get-History).Count + 1)".to_string(),
+        "n" => "\"`n\"".to_string(),
+        "e" => "$e".to_string(),
+        "a" => "\"`a\"".to_string(),
+        "r" => "\"`r\"".to_string(),
+        "\\" => quote("\\"),
+        "[" | "]" | "l" => "''".to_string(),
+        _ => {
+            if let Some(fmt) = escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}')) {
+                date(if fmt.is_empty() { "%X" } else { fmt })
+            } else if let Ok(code) = u8::from_str_radix(escape, 8) {
+                format!("[char]{}", code)
+            } else {
+                quote(&format!("\\{}", escape))
+            }
+        }
+    }
+}
+
+/// 拼出一个提示符的 PowerShell 表达式
+fn expression(prompt: &str) -> String {
+    let words: Vec<String> = prompt::parse_bash(prompt).iter().map(word).collect();
+    if words.is_empty() {
+        return "''".to_string();
+    }
+    format!("(@({}) -join '')", words.join(", "))
+}
+
+/// 目录模式对应的 `switch -Wildcard` 模式: 与 bash 相同地处理 `~`、`**` 与结尾的 `/*`, 路径分隔符统一为 `/`
+fn directory_patterns(pattern: &str) -> Vec<String> {
+    let escape = |text: &str| text.replace('`', "``").replace('"', "`\"").replace('$', "`$");
+    let pattern = pattern.replace("**", "*");
+    let mut globs = vec![pattern.clone()];
+    if let Some(base) = pattern.strip_suffix("/*").filter(|b| !b.is_empty()) {
+        globs.insert(0, base.to_string());
+    }
+    globs
+        .iter()
+        .map(|glob| match glob.strip_prefix('~') {
+            Some(rest) => format!("\"$($HOME.Replace('\\', '/')){}\"", escape(rest)),
+            None => format!("\"{}\"", escape(glob)),
+        })
+        .collect()
+}
+
+/// 按终端能力与背景设置颜色变量的代码, 与 bash 的 [`color::bash_setup`] 相对应
+fn color_setup(colors: &[ColorSlot], compat: bool) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let mut code = String::from("$__prompt_changer_appearance = $env:PROMPT_CHANGER_APPEARANCE\n");
+    code += "if (-not $__prompt_changer_appearance) {\n";
+    code += "    $__prompt_changer_appearance = if (($env:COLORFGBG -split ';')[-1] -in '7', '15') { 'light' } else { 'dark' }\n}\n";
+    let truecolor = "($env:COLORTERM -in 'truecolor', '24bit' -or $env:WT_SESSION)";
+    let truecolor = if compat { format!("(-not $__prompt_changer_compat -and {})", truecolor) } else { truecolor.to_string() };
+    code += &format!("$__prompt_changer_depth = if ($env:NO_COLOR) {{ '0' }} elseif {} {{ '24' }} else {{ '8' }}\n", truecolor);
+    code += "switch -Wildcard (\"${__prompt_changer_depth}:$__prompt_changer_appearance\") {";
+    for (arm, values) in color::setup_arms(colors) {
+        // bash 的分支标签只有深度时要匹配任意背景
+        let arm = if arm.contains(':') { arm } else { format!("{}:*", arm) };
+        code += &format!("\n    {} {{", quote(&arm));
+        for (i, value) in values.into_iter().enumerate() {
+            let value = value.map_or_else(|| "''".to_string(), |sgr| format!("\"$([char]27)[{}m\"", sgr));
+            code += &format!("\n        $global:{} = {}", color::color_var(i), value);
+        }
+        code += "\n        break\n    }";
+    }
+    code += "\n}\n";
+    code
+}
+
+/// 提示符与 bash 风格描述的差别
+pub fn notices(prompts: &PromptSet) -> Vec<String> {
+    let mut notices = Vec::new();
+    if !prompts.terminals.is_empty() {
+        notices.push(
+            i18n::tr(
+                "PowerShell prompts do not include the iTerm2 and WezTerm extras; OSC 7 and OSC 133 are still written",
+                "PowerShell 提示符不包含 iTerm2 与 WezTerm 的附加功能; 仍会写入 OSC 7 与 OSC 133",
+            )
+            .to_string(),
+        );
+    }
+    if prompts.vi_mode.is_some() {
+        notices.push(
+            i18n::tr(
+                "PowerShell shows the vi editing mode through the cursor shape instead of prompt text",
+                "PowerShell 通过光标形状而不是提示符文字显示 vi 编辑模式",
+            )
+            .to_string(),
+        );
+    }
+    notices
+}
+
+/// 写入受管理区块的代码: 定义 prompt 函数, 第一行记下原始的 bash 风格提示符
+pub fn block_body(prompts: &PromptSet) -> String {
+    let mut code = format!("{}{}\n", SOURCE_MARKER, prompts.default);
+    if prompts.screen_compat {
+        code += terminal::POWERSHELL_COMPAT;
+    }
+    code += &color_setup(&prompts.colors, prompts.screen_compat);
+    let modules = prompts.used_modules();
+    if prompts.prompts().any(|p| p.contains("\\$")) || modules.iter().any(|u| u.name == "char") {
+        code += "$global:__prompt_changer_elevated = if ($IsLinux -or $IsMacOS) { (id -u) -eq 0 } else {\n";
+        code += "    ([Security.Principal.WindowsPrincipal][Security.Principal.WindowsIdentity]::GetCurrent()).IsInRole([Security.Principal.WindowsBuiltInRole]::Administrator)\n}\n";
+    }
+    code += &modules::powershell_functions(&modules);
+    if prompts.uses_plugins() {
+        code += &plugins::powershell_function();
+    }
+    if prompts.vi_mode.is_some() {
+        // PSReadLine 不能在提示符中显示文字, 改用光标形状表示编辑模式
+        code += "if ((Get-Module PSReadLine) -and (Get-PSReadLineOption).EditMode -eq 'Vi') { Set-PSReadLineOption -ViModeIndicator Cursor }\n";
+    }
+    code += "function global:prompt {\n";
+    code += "    $ok = $?\n    $e = [char]27\n    $cwd = $PWD.Path\n";
+    code += "    if ($cwd.StartsWith($HOME)) { $cwd = '~' + $cwd.Substring($HOME.Length) }\n";
+    code += &format!("    $p = {}\n", expression(&prompts.default));
+    if !prompts.hosts.is_empty() {
+        code += "    switch -Wildcard ([Environment]::MachineName) {\n";
+        for (pattern, prompt) in &prompts.hosts {
+            code += &format!("        {} {{ $p = {}; break }}\n", quote(pattern), expression(prompt));
+        }
+        code += "    }\n";
+    }
+    if !prompts.times.is_empty() {
+        code += "    $now = [int](Get-Date -Format HHmm)\n";
+        for (i, (window, prompt)) in prompts.times.iter().enumerate() {
+            let keyword = if i == 0 { "if" } else { "elseif" };
+            let condition = window.condition("$now", "-ge", "-lt", "-and", "-or");
+            code += &format!("    {} ({}) {{ $p = {} }}\n", keyword, condition, expression(prompt));
+        }
+    }
+    if !prompts.directories.is_empty() {
+        code += "    switch -Wildcard ($PWD.Path.Replace('\\', '/')) {\n";
+        for (pattern, prompt) in &prompts.directories {
+            for glob in directory_patterns(pattern) {
+                code += &format!("        {} {{ $p = {}; break }}\n", glob, expression(prompt));
+            }
+        }
+        code += "    }\n";
+    }
+    // screen 与 mosh 中不设置标题, 也不发出 OSC 7 与 OSC 133
+    let guarded = |line: String| {
+        if prompts.screen_compat {
+            format!("    if (-not $global:__prompt_changer_compat) {{ {} }}\n", line)
+        } else {
+            format!("    {}\n", line)
+        }
+    };
+    match prompts.title.as_deref() {
+        Some(template) if !template.is_empty() => {
+            code += &guarded(format!("$Host.UI.RawUI.WindowTitle = {}", expression(template)));
+        }
+        _ => {}
+    }
+    if prompts.accessible {
+        // 上一条命令失败时在提示符前显示文字, 不只用颜色表示
+        code += "    if (-not $ok) { $p = '[err] ' + $p }\n";
+    }
+    if let Some(fit) = &prompts.fit {
+        // 过宽时依次去掉各片段的输出, 宽度不计颜色编码
+        let pieces: Vec<String> = fit.drop.iter().map(|piece| expression(piece)).collect();
+        code += &format!("    $budget = [Math]::Min([int]([Console]::WindowWidth / 2), {})\n", fit.max_width);
+        code += &format!("    foreach ($piece in @({})) {{\n", pieces.join(", "));
+        code += "        if (($p -replace \"$e\\[[0-9;]*m\", '').Split(\"`n\")[-1].Length -le $budget) { break }\n";
+        code += "        if ($piece) { $p = $p.Replace($piece, '') }\n    }\n";
+    }
+    if let Some(symbol) = &prompts.transient {
+        code += &format!("    if ($global:__prompt_changer_transient) {{ $global:__prompt_changer_transient = $false; $p = {} }}\n", quote(symbol));
+    }
+    if prompts.report_cwd {
+        code += &guarded("$p = \"$e]7;file://$([Environment]::MachineName)/$($PWD.Path.Replace('\\', '/').TrimStart('/').Replace(' ', '%20'))$e\\\" + $p".to_string());
+    }
+    if prompts.semantic_marks {
+        code += &guarded("$p = \"$e]133;D;$(if ($ok) { 0 } else { 1 })`a$e]133;A`a\" + $p + \"$e]133;B`a\"".to_string());
+    }
+    code += "    $p\n}";
+    if prompts.transient.is_some() {
+        // 回车时先标记并重绘提示符, 再执行命令
+        code += "\nif (Get-Module PSReadLine) {\n    Set-PSReadLineKeyHandler -Key Enter -ScriptBlock {\n";
+        code += "        $global:__prompt_changer_transient = $true\n        [Microsoft.PowerShell.PSConsoleReadLine]::InvokePrompt()\n";
+        code += "        [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()\n    }\n}";
+    }
+    code
+}
+
+/// 检查脚本语法的 PowerShell 命令, 有错时把错误写到标准错误并以 1 退出
+pub fn syntax_check_command(path: &Path) -> Command {
+    let script = format!(
+        "$errors = $null; [void][System.Management.Automation.Language.Parser]::ParseFile({}, [ref]$null, [ref]$errors); \
+         foreach ($err in $errors) {{ [Console]::Error.WriteLine(\"$($err.Extent.File):$($err.Extent.StartLineNumber): $($err.Message)\") }}; \
+         if ($errors) {{ exit 1 }}",
+        quote(&path.display().to_string())
+    );
+    let mut cmd = Command::new(executable());
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    cmd
+}
+
+/// 载入配置文件并渲染一次提示符的 PowerShell 命令
+pub fn startup_command(path: &Path) -> Command {
+    let script = format!(". {}; $null = prompt", quote(&path.display().to_string()));
+    let mut cmd = Command::new(executable());
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    cmd
+}
@@ -0,0 +1,326 @@
+use crate::color::{self, ColorDepth};
+use crate::i18n;
+use crate::modules;
+use crate::plugins;
+use crate::prompt::{self, PromptSet, RenderContext, Segment};
+use crate::spec::Spec;
+use crate::CliError;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// 预览的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// 带颜色的终端文本
+    Text,
+    Html,
+    Svg,
+}
+
+impl Format {
+    /// 命令行中可选的格式
+    pub const NAMES: [&'static str; 3] = ["text", "html", "svg"];
+
+    /// 从命令行参数解析格式
+    pub fn from_name(name: &str) -> Result<Format, CliError> {
+        match name {
+            "text" => Ok(Format::Text),
+            "html" => Ok(Format::Html),
+            "svg" => Ok(Format::Svg),
+            other => Err(CliError(i18n::tr(&format!("Unknown preview format: {}", other), &format!("未知的预览格式: {}", other)).to_string())),
+        }
+    }
+}
+
+/// 用示例数据渲染一个片段: 命令不实际运行, 颜色变量按给定的颜色深度与背景取值
+fn sample_segment(segment: &Segment, prompts: &PromptSet, depth: ColorDepth, light: bool, ctx: &RenderContext) -> String {
+    let colors = &prompts.colors;
+    match segment {
+        Segment::NonPrinting(inner) => {
+            prompt::parse_bash(inner).iter().map(|s| sample_segment(s, prompts, depth, light, ctx)).collect()
+        }
+        Segment::Color(_) if depth == ColorDepth::None => String::new(),
+        Segment::Variable(name) => match (0..colors.len()).find(|i| color::color_var(*i) == *name) {
+            Some(i) => {
+                let slot = if light { &colors[i].light } else { &colors[i].dark };
+                slot.at(depth).map(|sgr| format!("\x1b[{}m", sgr)).unwrap_or_default()
+            }
+            None => format!("${}", name),
+        },
+        Segment::Command(cmd) if modules::called(cmd).is_some() => {
+            let module = modules::called(cmd).unwrap();
+            if module.symbols {
+                return modules::arguments(cmd).into_iter().next().unwrap_or_default();
+            }
+            let used = prompts.modules.iter().find(|u| u.name == module.name);
+            match used.and_then(|u| u.symbol.as_ref()) {
+                Some(symbol) => format!("{} ", symbol.repeat(module.sample.parse().unwrap_or(1))),
+                None => format!("{} {} ", used.map_or("", |u| u.glyph.as_str()), module.sample),
+            }
+        }
+        Segment::Command(cmd) if plugins::called(cmd).is_some() => plugins::sample(cmd),
+        Segment::Command(cmd) if cmd.contains("git") => "main".to_string(),
+        Segment::Command(_) => "…".to_string(),
+        other => prompt::render_segment(other, ctx),
+    }
+}
+
+/// 用示例用户、主机与目录渲染默认提示符, 颜色是 ANSI 转义序列
+pub fn sample(prompts: &PromptSet, depth: ColorDepth, light: bool) -> String {
+    let ctx = RenderContext::sample();
+    prompt::parse_bash(&prompts.default).iter().map(|s| sample_segment(s, prompts, depth, light, &ctx)).collect()
+}
+
+/// 一段文字的样式
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Style {
+    fg: Option<(u8, u8, u8)>,
+    bg: Option<(u8, u8, u8)>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    /// 按一个 SGR 序列的参数更新样式
+    fn apply(&mut self, params: &str) {
+        let parts: Vec<&str> = params.split(';').collect();
+        let mut i = 0;
+        while i < parts.len() {
+            let n: u8 = parts[i].parse().unwrap_or(0);
+            match n {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                39 => self.fg = None,
+                49 => self.bg = None,
+                30..=37 | 90..=97 => self.fg = color::sgr_rgb(parts[i]),
+                40..=47 | 100..=107 => self.bg = color::sgr_rgb(parts[i]),
+                38 | 48 => {
+                    let len = if parts.get(i + 1) == Some(&"2") { 5 } else { 3 };
+                    let rgb = parts.get(i..i + len).and_then(|p| color::sgr_rgb(&p.join(";")));
+                    if n == 38 {
+                        self.fg = rgb;
+                    } else {
+                        self.bg = rgb;
+                    }
+                    i += len - 1;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// 把带 ANSI 颜色的文本拆成各行中样式相同的片段
+fn spans(text: &str) -> Vec<Vec<(Style, String)>> {
+    let mut lines: Vec<Vec<(Style, String)>> = vec![Vec::new()];
+    let mut style = Style::default();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("\x1b[") {
+            if let Some(end) = after.find('m') {
+                style.apply(&after[..end]);
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        rest = &rest[c.len_utf8()..];
+        if c == '\n' {
+            lines.push(Vec::new());
+            continue;
+        }
+        if c.is_control() {
+            continue;
+        }
+        let line = lines.last_mut().unwrap();
+        match line.last_mut() {
+            Some((last, text)) if *last == style => text.push(c),
+            _ => line.push((style.clone(), c.to_string())),
+        }
+    }
+    lines
+}
+
+/// HTML 与 SVG 中的 `#rrggbb`
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// 转义 HTML 与 SVG 中的特殊字符
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// 终端的默认前景色与背景色
+fn defaults(light: bool) -> ((u8, u8, u8), (u8, u8, u8)) {
+    if light {
+        ((0x1e, 0x1e, 0x1e), (0xff, 0xff, 0xff))
+    } else {
+        ((0xd4, 0xd4, 0xd4), (0x1e, 0x1e, 0x1e))
+    }
+}
+
+/// 可以直接贴进文档的 HTML 片段
+fn html(lines: &[Vec<(Style, String)>], light: bool) -> String {
+    let (fg, bg) = defaults(light);
+    let mut out = format!(
+        "<pre class=\"prompt-changer-preview\" style=\"background: {}; color: {}; padding: 0.75em 1em; border-radius: 6px; \
+         font-family: ui-monospace, Menlo, Consolas, monospace;\">",
+        hex(bg),
+        hex(fg)
+    );
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        for (style, text) in line {
+            let mut css = Vec::new();
+            css.extend(style.fg.map(|c| format!("color: {}", hex(c))));
+            css.extend(style.bg.map(|c| format!("background: {}", hex(c))));
+            if style.bold {
+                css.push("font-weight: bold".to_string());
+            }
+            if style.dim {
+                css.push("opacity: 0.6".to_string());
+            }
+            if style.italic {
+                css.push("font-style: italic".to_string());
+            }
+            if style.underline {
+                css.push("text-decoration: underline".to_string());
+            }
+            if css.is_empty() {
+                out += &escape(text);
+            } else {
+                out += &format!("<span style=\"{}\">{}</span>", css.join("; "), escape(text));
+            }
+        }
+    }
+    out += "</pre>\n";
+    out
+}
+
+/// 独立的 SVG 图片, 按等宽字体的字符格排列
+fn svg(lines: &[Vec<(Style, String)>], light: bool) -> String {
+    const CHAR_WIDTH: f64 = 8.4;
+    const LINE_HEIGHT: f64 = 20.0;
+    const PADDING: f64 = 12.0;
+    let (fg, bg) = defaults(light);
+    let columns = lines.iter().map(|line| line.iter().map(|(_, t)| t.chars().count()).sum::<usize>()).max().unwrap_or(0).max(1);
+    let width = PADDING * 2.0 + columns as f64 * CHAR_WIDTH;
+    let height = PADDING * 2.0 + lines.len() as f64 * LINE_HEIGHT;
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"100%\" height=\"100%\" rx=\"6\" fill=\"{}\"/>\n",
+        hex(bg),
+        w = format!("{:.1}", width),
+        h = format!("{:.1}", height)
+    );
+    for (row, line) in lines.iter().enumerate() {
+        let y = PADDING + row as f64 * LINE_HEIGHT;
+        let mut column = 0;
+        for (style, text) in line {
+            let count = text.chars().count();
+            if let Some(color) = style.bg {
+                out += &format!(
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/>\n",
+                    PADDING + column as f64 * CHAR_WIDTH,
+                    y,
+                    count as f64 * CHAR_WIDTH,
+                    LINE_HEIGHT,
+                    hex(color)
+                );
+            }
+            column += count;
+        }
+        out += &format!(
+            "<text x=\"{}\" y=\"{:.1}\" font-family=\"ui-monospace, Menlo, Consolas, monospace\" font-size=\"14\" fill=\"{}\" xml:space=\"preserve\">",
+            PADDING,
+            y + LINE_HEIGHT * 0.72,
+            hex(fg)
+        );
+        for (style, text) in line {
+            let mut attrs = String::new();
+            if let Some(color) = style.fg {
+                attrs += &format!(" fill=\"{}\"", hex(color));
+            }
+            if style.bold {
+                attrs += " font-weight=\"bold\"";
+            }
+            if style.dim {
+                attrs += " fill-opacity=\"0.6\"";
+            }
+            if style.italic {
+                attrs += " font-style=\"italic\"";
+            }
+            if style.underline {
+                attrs += " text-decoration=\"underline\"";
+            }
+            out += &format!("<tspan{}>{}</tspan>", attrs, escape(text));
+        }
+        out += "</text>\n";
+    }
+    out += "</svg>\n";
+    out
+}
+
+/// 检查描述文件是否改动的间隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 读取描述文件并渲染文本预览, 连同对比度警告
+fn watched_preview(path: &Path, ascii: bool, light: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let mut spec = Spec::load(path)?;
+    spec.ascii |= ascii;
+    let mut out = format!("{}\n", sample(&spec.prompts()?, color::output_depth(), light));
+    for warning in spec.contrast_warnings() {
+        out += &format!("\n{}: {}", i18n::tr("warning", "警告"), warning);
+    }
+    Ok(out)
+}
+
+/// 描述文件每次保存后清屏并重新渲染预览, 直到按 Ctrl-C; 描述文件有错时显示错误并继续等待
+pub fn watch(path: &Path, ascii: bool, light: bool) -> io::Result<()> {
+    let mut last = None;
+    let mut first = true;
+    loop {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        if first || modified != last {
+            (first, last) = (false, modified);
+            let body = watched_preview(path, ascii, light).unwrap_or_else(|err| format!("{}: {}\n", i18n::tr("error", "错误"), err));
+            let mut stdout = io::stdout().lock();
+            write!(stdout, "\x1b[2J\x1b[H")?;
+            writeln!(
+                stdout,
+                "{}\n",
+                i18n::tr(
+                    &format!("Watching {} ({}); save it to update the preview, Ctrl-C to stop.", path.display(), chrono::Local::now().format("%H:%M:%S")),
+                    &format!("正在监视 {} ({}); 保存后更新预览, 按 Ctrl-C 停止。", path.display(), chrono::Local::now().format("%H:%M:%S")),
+                )
+            )?;
+            write!(stdout, "{}", body)?;
+            stdout.flush()?;
+        }
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// 按格式渲染提示符的预览; HTML 与 SVG 总是使用真彩色
+pub fn render(prompts: &PromptSet, format: Format, light: bool) -> String {
+    match format {
+        Format::Text => format!("{}\n", sample(prompts, color::output_depth(), light)),
+        Format::Html => html(&spans(&sample(prompts, ColorDepth::TrueColor, light)), light),
+        Format::Svg => svg(&spans(&sample(prompts, ColorDepth::TrueColor, light)), light),
+    }
+}
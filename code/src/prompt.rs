@@ -0,0 +1,903 @@
+use crate::color::{self, ColorSlot};
+use crate::i18n;
+use crate::lint::{self, Issue, Severity};
+use crate::modules;
+use crate::plugins;
+use crate::shell::Shell;
+use crate::terminal::{self, Terminal};
+use crate::CliError;
+use regex::Regex;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::process::Command;
+
+/// 验证提示符格式, 返回不影响写入的警告; `force` 时错误也只作为警告返回
+pub fn validate_prompt(shell: Shell, prompt: &str, force: bool) -> Result<Vec<Issue>, CliError> {
+    let re = Regex::new(r"^[^\x00-\x1F\x7F]*$").map_err(|_| CliError(i18n::tr("Invalid regex", "正则表达式无效").to_string()))?;
+    if !re.is_match(prompt) {
+        return Err(CliError(i18n::tr("The prompt contains invalid characters.", "提示符中含有无效的字符。").to_string()));
+    }
+    let (errors, warnings): (Vec<Issue>, Vec<Issue>) = lint::check_prompt(shell, prompt)
+        .into_iter()
+        .partition(|issue| issue.severity == Severity::Error);
+    if errors.is_empty() || force {
+        Ok(errors.into_iter().chain(warnings).collect())
+    } else {
+        let messages: Vec<String> = errors.into_iter().map(|issue| issue.message).collect();
+        let messages = messages.join("; ");
+        Err(CliError(i18n::tr(&format!("{} (use --force to apply it anyway)", messages), &format!("{} (使用 --force 仍然应用)", messages)).to_string()))
+    }
+}
+
+/// bash 提示符中的一个片段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// 普通文本
+    Text(String),
+    /// 反斜杠转义, 保存反斜杠之后的部分, 如 `u`、`D{%H}`
+    Escape(String),
+    /// `\[ \]` 包裹的不可打印内容
+    NonPrinting(String),
+    /// 未被 `\[ \]` 包裹的颜色序列, 如 `\e[31m`
+    Color(String),
+    /// `$VAR` 或 `${VAR}`
+    Variable(String),
+    /// `$(...)` 或反引号命令替换
+    Command(String),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Text(text) => write!(f, "{}", text),
+            Segment::Escape(escape) => write!(f, "\\{}", escape),
+            Segment::NonPrinting(inner) => write!(f, "\\[{}\\]", inner),
+            Segment::Color(color) => write!(f, "{}", color),
+            Segment::Variable(name) => write!(f, "${{{}}}", name),
+            Segment::Command(cmd) => write!(f, "$({})", cmd),
+        }
+    }
+}
+
+/// 把 bash 提示符拆分为片段
+pub fn parse_bash(prompt: &str) -> Vec<Segment> {
+    let chars: Vec<char> = prompt.chars().collect();
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let rest: String = chars[i..].iter().collect();
+        let (segment, len) = match chars[i] {
+            '\\' => parse_backslash(&rest),
+            '$' => parse_dollar(&rest),
+            '`' => match rest[1..].find('`') {
+                Some(end) => (Some(Segment::Command(rest[1..end + 1].to_string())), rest[..end + 2].chars().count()),
+                None => (None, 1),
+            },
+            _ => (None, 1),
+        };
+        match segment {
+            Some(segment) => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(segment);
+            }
+            None => text.extend(&chars[i..i + len]),
+        }
+        i += len;
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+/// 解析 `\` 开头的片段, 返回片段和消耗的字符数
+fn parse_backslash(rest: &str) -> (Option<Segment>, usize) {
+    let body = &rest[1..];
+    if let Some(inner) = body.strip_prefix('[') {
+        if let Some(end) = inner.find("\\]") {
+            return (Some(Segment::NonPrinting(inner[..end].to_string())), rest[..end + 4].chars().count());
+        }
+    }
+    let color = Regex::new(r"^\\(?:e|033)\[[0-9;]*m").unwrap();
+    if let Some(m) = color.find(rest) {
+        return (Some(Segment::Color(m.as_str().to_string())), m.as_str().chars().count());
+    }
+    let escape = Regex::new(r"^(?:[0-7]{3}|D\{[^}]*\})").unwrap();
+    if let Some(m) = escape.find(body) {
+        return (Some(Segment::Escape(m.as_str().to_string())), m.as_str().chars().count() + 1);
+    }
+    match body.chars().next() {
+        Some(c) => (Some(Segment::Escape(c.to_string())), 2),
+        None => (None, 1),
+    }
+}
+
+/// 解析 `$` 开头的片段, 返回片段和消耗的字符数
+fn parse_dollar(rest: &str) -> (Option<Segment>, usize) {
+    let body = &rest[1..];
+    if body.starts_with('(') {
+        let mut depth = 0;
+        for (idx, c) in body.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                let cmd = body[1..idx].to_string();
+                return (Some(Segment::Command(cmd)), rest[..idx + 2].chars().count());
+            }
+        }
+        return (None, 1);
+    }
+    if let Some(inner) = body.strip_prefix('{') {
+        return match inner.find('}') {
+            Some(end) => (Some(Segment::Variable(inner[..end].to_string())), rest[..end + 3].chars().count()),
+            None => (None, 1),
+        };
+    }
+    let name: String = body.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect();
+    if name.is_empty() {
+        (None, 1)
+    } else {
+        let len = name.chars().count() + 1;
+        (Some(Segment::Variable(name)), len)
+    }
+}
+
+/// 用 `\[ \]` 包裹未包裹的颜色序列, 返回新提示符和包裹的数量
+pub fn wrap_colors(prompt: &str) -> (String, usize) {
+    let color = Regex::new(r"^\\(?:e|033)\[[0-9;]*m").unwrap();
+    let mut out = String::with_capacity(prompt.len());
+    let mut inside = false;
+    let mut wrapped = 0;
+    let mut rest = prompt;
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with("\\[") {
+            inside = true;
+            2
+        } else if rest.starts_with("\\]") {
+            inside = false;
+            2
+        } else if let Some(m) = color.find(rest).filter(|_| !inside) {
+            out.push_str("\\[");
+            out.push_str(m.as_str());
+            out.push_str("\\]");
+            wrapped += 1;
+            rest = &rest[m.end()..];
+            continue;
+        } else if c == '\\' {
+            rest.chars().nth(1).map_or(1, |next| 1 + next.len_utf8())
+        } else {
+            c.len_utf8()
+        };
+        out.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    (out, wrapped)
+}
+
+/// 渲染提示符时需要的环境信息
+pub struct RenderContext {
+    user: String,
+    host: String,
+    home: String,
+    cwd: String,
+}
+
+impl RenderContext {
+    /// 从当前进程环境收集信息
+    pub fn current() -> RenderContext {
+        let user = env::var("USER")
+            .or_else(|_| env::var("LOGNAME"))
+            .unwrap_or_default();
+        let host = env::var("HOSTNAME")
+            .ok()
+            .or_else(|| fs::read_to_string("/etc/hostname").ok())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let home = dirs::home_dir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        let cwd = env::current_dir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        RenderContext { user, host, home, cwd }
+    }
+
+    /// 预览用的示例环境
+    pub fn sample() -> RenderContext {
+        RenderContext {
+            user: "alice".to_string(),
+            host: "laptop.local".to_string(),
+            home: "/home/alice".to_string(),
+            cwd: "/home/alice/projects/demo".to_string(),
+        }
+    }
+}
+
+/// 像 bash 一样展开单个片段
+pub fn render_segment(segment: &Segment, ctx: &RenderContext) -> String {
+    match segment {
+        Segment::Text(text) => text.clone(),
+        Segment::NonPrinting(inner) => inner.replace("\\e", "\x1b").replace("\\033", "\x1b"),
+        Segment::Color(color) => color.replace("\\e", "\x1b").replace("\\033", "\x1b"),
+        Segment::Variable(name) => env::var(name).unwrap_or_default(),
+        Segment::Command(cmd) => Command::new("bash")
+            .arg("-c")
+            .arg(cmd)
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim_end_matches('\n').to_string())
+            .unwrap_or_default(),
+        Segment::Escape(escape) => render_escape(escape, ctx),
+    }
+}
+
+/// 展开单个反斜杠转义
+fn render_escape(escape: &str, ctx: &RenderContext) -> String {
+    let now = chrono::Local::now();
+    let short_cwd = || {
+        if !ctx.home.is_empty() && ctx.cwd.starts_with(&ctx.home) {
+            format!("~{}", &ctx.cwd[ctx.home.len()..])
+        } else {
+            ctx.cwd.clone()
+        }
+    };
+    match escape {
+        "u" => ctx.user.clone(),
+        "h" => ctx.host.split('.').next().unwrap_or_default().to_string(),
+        "H" => ctx.host.clone(),
+        "w" => short_cwd(),
+        "W" => {
+            let cwd = short_cwd();
+            cwd.rsplit('/').find(|s| !s.is_empty()).unwrap_or("/").to_string()
+        }
+        "t" => now.format("%H:%M:%S").to_string(),
+        "T" => now.format("%I:%M:%S").to_string(),
+        "@" => now.format("%I:%M %p").to_string(),
+        "A" => now.format("%H:%M").to_string(),
+        "d" => now.format("%a %b %d").to_string(),
+        "$" => if ctx.user == "root" { "#".to_string() } else { "$".to_string() },
+        "!" | "#" => "1".to_string(),
+        "s" => "bash".to_string(),
+        "v" | "V" => env::var("BASH_VERSION").unwrap_or_default(),
+        "n" => "\n".to_string(),
+        "e" => "\x1b".to_string(),
+        "a" => "\x07".to_string(),
+        "\\" => "\\".to_string(),
+        "[" | "]" => String::new(),
+        _ => {
+            if let Some(fmt) = escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}')) {
+                let fmt = if fmt.is_empty() { "%X" } else { fmt };
+                now.format(fmt).to_string()
+            } else if let Ok(code) = u8::from_str_radix(escape, 8) {
+                (code as char).to_string()
+            } else {
+                format!("\\{}", escape)
+            }
+        }
+    }
+}
+
+/// 生成的提示符脚本中记录原始提示符的注释前缀, 各 shell 共用
+pub const SOURCE_MARKER: &str = "# prompt-changer-source: ";
+
+/// 一天中的时间段, 时间以 HHMM 的整数表示; 包含开始, 不包含结束, 开始晚于结束时跨过午夜
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub from: u16,
+    pub to: u16,
+}
+
+impl TimeWindow {
+    /// 由 `HH:MM` 格式的开始与结束时间构造
+    pub fn new(from: &str, to: &str) -> Result<TimeWindow, CliError> {
+        let parse = |text: &str| {
+            let (h, m) = text.trim().split_once(':').unwrap_or((text, ""));
+            match (h.parse::<u16>(), m.parse::<u16>()) {
+                (Ok(hour), Ok(minute)) if hour < 24 && minute < 60 && m.len() == 2 => Ok(hour * 100 + minute),
+                _ => Err(CliError(
+                    i18n::tr(
+                        &format!("`{}` is not a time of day; use HH:MM, e.g. 18:00", text),
+                        &format!("`{}` 不是时刻; 请使用 HH:MM, 如 18:00", text),
+                    )
+                    .to_string(),
+                )),
+            }
+        };
+        let window = TimeWindow { from: parse(from)?, to: parse(to)? };
+        if window.from == window.to {
+            return Err(CliError(
+                i18n::tr(&format!("The time window {} starts and ends at the same time", window), &format!("时间段 {} 的开始与结束时间相同", window)).to_string(),
+            ));
+        }
+        Ok(window)
+    }
+
+    /// 判断时间段的条件, `now` 是 HHMM 整数的表达式, `ge`、`lt`、`and`、`or` 是各 shell 中的运算符
+    pub fn condition(&self, now: &str, ge: &str, lt: &str, and: &str, or: &str) -> String {
+        let join = if self.from < self.to { and } else { or };
+        format!("{now} {ge} {} {join} {now} {lt} {}", self.from, self.to)
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}-{:02}:{:02}", self.from / 100, self.from % 100, self.to / 100, self.to % 100)
+    }
+}
+
+/// 要写入配置的提示符: 默认的一个, 以及按主机名、时间段、当前目录切换的变体
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptSet {
+    pub default: String,
+    /// (主机名通配模式, 提示符), 按顺序匹配, 都不匹配时使用默认提示符
+    pub hosts: Vec<(String, String)>,
+    /// (目录通配模式, 提示符), 每次显示提示符时按顺序匹配, 优先于主机名的变体
+    pub directories: Vec<(String, String)>,
+    /// (时间段, 提示符), 每次显示提示符时按顺序匹配, 优先于主机名的变体, 目录的变体优先于它
+    pub times: Vec<(TimeWindow, String)>,
+    /// 终端标题的模板(bash 风格), 为空时不让 shell 设置标题, None 时保持 shell 的默认行为
+    pub title: Option<String>,
+    /// 是否用 OSC 7 向终端报告当前目录
+    pub report_cwd: bool,
+    /// 是否用 OSC 133 标出提示符、输入与命令输出的位置
+    pub semantic_marks: bool,
+    /// 要集成专有序列的终端
+    pub terminals: Vec<Terminal>,
+    /// 提示符通过变量引用的颜色, 在 shell 启动时按终端能力与背景选择
+    pub colors: Vec<ColorSlot>,
+    /// 提示符调用的内置模块, 与提示符一起写入它们的函数
+    pub modules: Vec<modules::Used>,
+    /// vi 编辑模式下插入模式与普通模式的提示文字, 显示在提示符最后一行的开头
+    pub vi_mode: Option<(String, String)>,
+    /// 命令执行后把上一个提示符收起为这段文字
+    pub transient: Option<String>,
+    /// 终端较窄时去掉片段
+    pub fit: Option<Fit>,
+    /// 在 GNU screen 与 mosh 中不用真彩色, 也不发出标题与 OSC 7、OSC 133 序列
+    pub screen_compat: bool,
+    /// 无障碍模式: 上一条命令失败时在提示符前显示 `[err N]`, 不只用颜色表示
+    pub accessible: bool,
+}
+
+/// 按宽度去掉片段的设置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fit {
+    /// 提示符最多占的列数, 也不超过终端宽度的一半
+    pub max_width: u32,
+    /// 可以去掉的片段(bash 风格, 含颜色), 先去掉的在前
+    pub drop: Vec<String>,
+}
+
+impl PromptSet {
+    /// 只有一个提示符
+    pub fn single(prompt: &str) -> PromptSet {
+        PromptSet {
+            default: prompt.to_string(),
+            hosts: Vec::new(),
+            directories: Vec::new(),
+            times: Vec::new(),
+            title: None,
+            report_cwd: false,
+            semantic_marks: false,
+            terminals: Vec::new(),
+            colors: Vec::new(),
+            modules: Vec::new(),
+            vi_mode: None,
+            transient: None,
+            fit: None,
+            screen_compat: false,
+            accessible: false,
+        }
+    }
+
+    /// 集成这些终端: 它们都支持 OSC 133 与 OSC 7, 所以一并打开
+    pub fn integrate(&mut self, terminals: Vec<Terminal>) {
+        if !terminals.is_empty() {
+            self.report_cwd = true;
+            self.semantic_marks = true;
+        }
+        self.terminals = terminals;
+    }
+
+    /// 其中的所有提示符
+    pub fn prompts(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.default.as_str())
+            .chain(self.hosts.iter().map(|(_, p)| p.as_str()))
+            .chain(self.directories.iter().map(|(_, p)| p.as_str()))
+            .chain(self.times.iter().map(|(_, p)| p.as_str()))
+    }
+
+    /// 要写入函数的模块: 描述文件中设置的, 以及提示符中直接调用、使用默认设置的
+    pub fn used_modules(&self) -> Vec<modules::Used> {
+        let mut used = self.modules.clone();
+        for prompt in self.prompts() {
+            for segment in parse_bash(prompt) {
+                let module = match &segment {
+                    Segment::Command(cmd) => modules::called(cmd),
+                    _ => None,
+                };
+                if let Some(module) = module.filter(|m| !used.iter().any(|u| u.name == m.name)) {
+                    used.push(module.used(false));
+                }
+            }
+        }
+        used
+    }
+
+    /// 提示符中是否调用了插件, 调用时要写入运行插件的函数
+    pub fn uses_plugins(&self) -> bool {
+        self.prompts().any(|prompt| parse_bash(prompt).iter().any(|s| matches!(s, Segment::Command(cmd) if plugins::called(cmd).is_some())))
+    }
+
+    /// 只能写入默认提示符、按主机名的变体与标题的 shell(如 ksh)会略去的设置; `accessible` 表示该 shell 支持无障碍模式
+    pub fn unsupported_extras(&self, accessible: bool) -> Vec<&'static str> {
+        let mut dropped = Vec::new();
+        if !self.directories.is_empty() || !self.times.is_empty() {
+            dropped.push(i18n::tr("[[directory]] and [[time]] rules", "[[directory]] 与 [[time]] 规则"));
+        }
+        if self.report_cwd || self.semantic_marks || !self.terminals.is_empty() {
+            dropped.push(i18n::tr("terminal integration", "终端集成"));
+        }
+        if self.vi_mode.is_some() {
+            dropped.push(i18n::tr("the vi mode indicator", "vi 模式指示"));
+        }
+        if self.transient.is_some() {
+            dropped.push(i18n::tr("transient prompts", "瞬态提示符"));
+        }
+        if self.fit.is_some() {
+            dropped.push(i18n::tr("fitting to the terminal width", "适应终端宽度"));
+        }
+        if self.screen_compat {
+            dropped.push(i18n::tr("screen compatibility", "screen 兼容"));
+        }
+        if self.accessible && !accessible {
+            dropped.push(i18n::tr("the [err N] marker of accessible mode", "无障碍模式的 [err N] 标记"));
+        }
+        dropped
+    }
+}
+
+/// 提示某个 shell 的提示符略去了哪些设置, 没有略去的设置时为 None
+pub fn left_out_notice(shell_name: &str, dropped: &[&str]) -> Option<String> {
+    if dropped.is_empty() {
+        return None;
+    }
+    let dropped = dropped.join(i18n::tr(", ", "、"));
+    Some(i18n::tr(&format!("{} prompts leave out {}", shell_name, dropped), &format!("{} 提示符不包含 {}", shell_name, dropped)).to_string())
+}
+
+/// 检查终端标题模板, 其中不能出现会提前结束 OSC 序列的内容
+pub fn check_title(template: &str) -> Result<(), CliError> {
+    let bad = ["\\a", "\\007", "\\e", "\\033", "\\[", "\\]", "\\n"];
+    match bad.iter().find(|b| template.contains(*b)) {
+        Some(b) => Err(CliError(i18n::tr(&format!("The title template cannot contain {}", b), &format!("标题模板中不能含有 {}", b)).to_string())),
+        None if template.chars().any(char::is_control) => {
+            Err(CliError(i18n::tr("The title template cannot contain control characters", "标题模板中不能含有控制字符").to_string()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// bash 提示符前设置终端标题(OSC 0)的序列, 没有标题时为空
+pub fn bash_title(title: Option<&str>) -> String {
+    match title {
+        Some(template) if !template.is_empty() => format!("\\[\\e]0;{}\\a\\]", template),
+        _ => String::new(),
+    }
+}
+
+/// 把目录模式翻译为 shell 的 case 模式: `~` 换成 `"$HOME"`, `**` 与 `*` 相同,
+/// 以 `/*` 结尾时也匹配该目录本身; 其余部分用 `quote` 引用
+pub fn directory_globs(pattern: &str, quote: impl Fn(&str) -> String) -> Vec<String> {
+    let pattern = pattern.replace("**", "*");
+    let mut globs = vec![pattern.clone()];
+    if let Some(base) = pattern.strip_suffix("/*").filter(|b| !b.is_empty()) {
+        globs.insert(0, base.to_string());
+    }
+    globs
+        .iter()
+        .map(|glob| {
+            let (home, rest) = match glob.strip_prefix('~') {
+                Some(rest) => ("\"$HOME\"", rest),
+                None => ("", glob.as_str()),
+            };
+            let parts: Vec<String> = rest.split('*').map(|p| if p.is_empty() { String::new() } else { quote(p) }).collect();
+            format!("{}{}", home, parts.join("*"))
+        })
+        .collect()
+}
+
+/// 输出一个提示符的 fish 命令
+fn fish_echo(prompt: &str) -> String {
+    let words: Vec<String> = parse_bash(prompt).iter().map(fish_word).collect();
+    format!("echo -n -s {}", words.join(" "))
+}
+
+/// 把 bash 提示符翻译为 fish 的 fish_prompt 函数
+pub fn fish_function(prompts: &PromptSet) -> String {
+    let mut body = String::new();
+    if !prompts.directories.is_empty() {
+        body += "    switch $PWD\n";
+        for (pattern, prompt) in &prompts.directories {
+            let globs = directory_globs(pattern, fish_quote);
+            body += &format!("        case {}\n            {}\n            return\n", globs.join(" "), fish_echo(prompt));
+        }
+        body += "    end\n";
+    }
+    if !prompts.times.is_empty() {
+        // math 把 date 输出的 0830 读作十进制的 830
+        body += "    set -l now (math (date +%H%M))\n";
+        for (window, prompt) in &prompts.times {
+            let condition = window.condition("$now", "-ge", "-lt", "-a", "-o");
+            body += &format!("    if test {}\n        {}\n        return\n    end\n", condition, fish_echo(prompt));
+        }
+    }
+    if prompts.hosts.is_empty() {
+        body += &format!("    {}\n", fish_echo(&prompts.default));
+    } else {
+        body += "    switch $hostname\n";
+        for (pattern, prompt) in &prompts.hosts {
+            body += &format!("        case {}\n            {}\n", fish_quote(pattern), fish_echo(prompt));
+        }
+        body += &format!("        case '*'\n            {}\n    end\n", fish_echo(&prompts.default));
+    }
+    if prompts.accessible {
+        body = format!("    printf '%s' $__prompt_changer_err\n{}", body);
+    }
+    if let Some(symbol) = &prompts.transient {
+        // fish 4 用 --final-rendering 重绘已执行的提示符; 更早的版本在回车时自己标记并重绘
+        let check = format!(
+            "    if contains -- --final-rendering $argv; or set -q __prompt_changer_transient\n        set -e __prompt_changer_transient\n        printf '%s' {}\n        return\n    end\n",
+            fish_quote(symbol)
+        );
+        body = check + &body;
+    }
+    // fish 自己通过 fish_title 设置标题, 由它负责加上 OSC 序列
+    // screen 与 mosh 中不设置标题, 也不发出 OSC 7 与 OSC 133
+    let skip = if prompts.screen_compat { "test -n \"$__prompt_changer_compat\"; and return\n    " } else { "" };
+    let mut extra = if prompts.screen_compat { terminal::FISH_COMPAT.to_string() } else { String::new() };
+    extra += &match prompts.title.as_deref() {
+        Some("") => "\nfunction fish_title\nend\n".to_string(),
+        Some(template) => format!("\nfunction fish_title\n    {}{}\nend\n", skip, fish_echo(template)),
+        None if prompts.screen_compat => "\nif test -n \"$__prompt_changer_compat\"\n    function fish_title\n    end\nend\n".to_string(),
+        None => String::new(),
+    };
+    if prompts.report_cwd {
+        // fish 的单引号中 \\ 才表示一个反斜杠, 所以 ST 的 \\ 要写两遍
+        extra += "\nfunction __prompt_changer_osc7 --on-variable PWD\n";
+        extra += &format!("    {}", skip);
+        extra += "printf '\\e]7;file://%s%s\\e\\\\\\\\' $hostname (string escape --style=url -- $PWD)\nend\n";
+        extra += "__prompt_changer_osc7\n";
+    }
+    if let Some(fit) = &prompts.fit {
+        // 先得到完整的提示符, 过宽时依次去掉各片段的输出
+        extra += &format!("\nfunction __prompt_changer_full_prompt\n{}end\n", body);
+        let pieces: Vec<String> = fit.drop.iter().map(|piece| format!("({} | string collect)", fish_echo(piece))).collect();
+        body = "    set -l prompt (__prompt_changer_full_prompt $argv | string collect)\n".to_string();
+        body += &format!("    set -l budget (math --scale 0 \"$COLUMNS / 2\")\n    test $budget -gt {0}; and set budget {0}\n", fit.max_width);
+        body += &format!("    for piece in {}\n", pieces.join(" "));
+        body += "        test (string length --visible -- $prompt)[-1] -le $budget; and break\n";
+        body += "        set prompt (string replace -- $piece '' $prompt | string collect)\n    end\n    printf '%s' $prompt\n";
+    }
+    if prompts.semantic_marks {
+        // 提示符可能从多处 return, 所以放进单独的函数, 由 fish_prompt 在前后加上 A 与 B
+        extra += &format!("\nfunction __prompt_changer_prompt\n{}end\n", body);
+        let mark = if prompts.screen_compat { "test -n \"$__prompt_changer_compat\"; or " } else { "" };
+        body = format!("    {0}printf '\\e]133;A\\a'\n    __prompt_changer_prompt $argv\n    {0}printf '\\e]133;B\\a'\n", mark);
+        extra += &format!("\nfunction __prompt_changer_osc133_preexec --on-event fish_preexec\n    {}printf '\\e]133;C\\a'\nend\n", mark);
+        extra += "\nfunction __prompt_changer_osc133_postexec --on-event fish_postexec\n";
+        if prompts.screen_compat {
+            // test 会改掉 $status, 先记下命令的结束状态
+            extra += "    set -l last $status\n    test -n \"$__prompt_changer_compat\"; or printf '\\e]133;D;%s\\a' $last\nend\n";
+        } else {
+            extra += "    printf '\\e]133;D;%s\\a' $status\nend\n";
+        }
+    }
+    if prompts.accessible {
+        // 上一条命令失败时在提示符前显示 `[err N]`, 不只用颜色表示; test 会改掉 $status, 先记下
+        extra += "\nset -q __prompt_changer_err; or set -g __prompt_changer_err ''\n";
+        extra += "function __prompt_changer_status --on-event fish_postexec\n    set -l last $status\n    set -g __prompt_changer_err ''\n";
+        extra += "    test $last -ne 0; and set -g __prompt_changer_err \"[err $last] \"\nend\n";
+    }
+    let counts_commands =
+        prompts.prompts().any(|p| parse_bash(p).iter().any(|s| matches!(s, Segment::Escape(e) if e == "#")));
+    if counts_commands {
+        // fish 没有 bash 的命令编号, 在每条命令之后自己计数
+        extra += "\nset -q __prompt_changer_commands; or set -g __prompt_changer_commands 0\n";
+        extra += "function __prompt_changer_count --on-event fish_postexec\n";
+        extra += "    set -g __prompt_changer_commands (math $__prompt_changer_commands + 1)\nend\n";
+    }
+    if prompts.transient.is_some() {
+        extra += "\nif test (string split . -- $version)[1] -ge 4\n    set -g fish_transient_prompt 1\nelse\n";
+        extra += "    function __prompt_changer_transient_execute\n";
+        extra += "        if commandline --is-valid\n            set -g __prompt_changer_transient\n            commandline -f repaint\n        end\n";
+        extra += "        commandline -f execute\n    end\n";
+        extra += "    bind \\r __prompt_changer_transient_execute\n    bind -M insert \\r __prompt_changer_transient_execute\nend\n";
+    }
+    if let Some((insert, normal)) = &prompts.vi_mode {
+        // 只在使用 vi 键绑定时显示, 与 fish 自带的 fish_mode_prompt 相同
+        extra += "\nfunction fish_mode_prompt\n";
+        extra += "    contains -- $fish_key_bindings fish_vi_key_bindings fish_hybrid_key_bindings; or return\n";
+        extra += &format!(
+            "    switch $fish_bind_mode\n        case insert\n            printf '%s' {}\n        case '*'\n            printf '%s' {}\n    end\nend\n",
+            fish_quote(insert),
+            fish_quote(normal)
+        );
+    }
+    extra += &terminal::fish_code(&prompts.terminals);
+    extra += &color::fish_setup(&prompts.colors, prompts.screen_compat);
+    extra += &modules::fish_functions(&prompts.used_modules());
+    if prompts.uses_plugins() {
+        extra += &plugins::fish_function();
+    }
+    format!(
+        "# Generated by prompt-changer, do not edit by hand.\n{}{}\nfunction fish_prompt\n{}end\n{}",
+        SOURCE_MARKER, prompts.default, body, extra
+    )
+}
+
+/// 用 fish 的单引号引用文本
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// 把单个片段翻译为 fish 的 echo 参数
+fn fish_word(segment: &Segment) -> String {
+    match segment {
+        Segment::Text(text) => fish_quote(text),
+        Segment::Variable(name) => format!("${}", name),
+        Segment::Command(cmd) => format!("({})", cmd),
+        Segment::NonPrinting(inner) => parse_bash(inner).iter().map(fish_word).collect::<Vec<_>>().join(" "),
+        Segment::Color(color) => {
+            let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+            format!("\\e{}", fish_quote(rest))
+        }
+        Segment::Escape(escape) => fish_escape(escape),
+    }
+}
+
+/// 把 bash 的反斜杠转义翻译为 fish 表达式
+pub fn fish_escape(escape: &str) -> String {
+    let date = |fmt: &str| format!("(date {})", fish_quote(&format!("+{}", fmt)));
+    match escape {
+        "u" => "$USER".to_string(),
+        "h" => "(prompt_hostname)".to_string(),
+        "H" => "(hostname)".to_string(),
+        "w" => "(string replace -r -- \"^$HOME\" '~' $PWD)".to_string(),
+        "W" => "(basename (string replace -r -- \"^$HOME\\$\" '~' $PWD))".to_string(),
+        "t" => date("%H:%M:%S"),
+        "T" => date("%I:%M:%S"),
+        "@" => date("%I:%M %p"),
+        "A" => date("%H:%M"),
+        "d" => date("%a %b %d"),
+        "$" => "(test (id -u) -eq 0; and echo '#'; or echo '$')".to_string(),
+        "s" => "fish".to_string(),
+        "v" | "V" => "$version".to_string(),
+        "j" => "(count (jobs -p))".to_string(),
+        "!" => "(math (count $history) + 1)".to_string(),
+        "#" => "(math $__prompt_changer_commands + 1)".to_string(),
+        "l" => "(basename (tty))".to_string(),
+        "n" | "e" | "a" | "r" => format!("\\{}", escape),
+        "\\" => "\\\\".to_string(),
+        "[" | "]" => "''".to_string(),
+        _ => {
+            if let Some(fmt) = escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}')) {
+                date(if fmt.is_empty() { "%X" } else { fmt })
+            } else if escape.chars().all(|c| c.is_digit(8)) {
+                format!("\\{}", escape)
+            } else {
+                fish_quote(&format!("\\{}", escape))
+            }
+        }
+    }
+}
+
+/// 颜色编码对应的名称
+pub const COLOR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// 把 SGR 参数(如 `1;32`)翻译为可读的描述
+pub fn describe_sgr(params: &str) -> String {
+    let codes: Vec<u32> = params.split(';').map(|c| c.parse().unwrap_or(0)).collect();
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < codes.len() {
+        let code = codes[i];
+        let part = match code {
+            0 => i18n::tr("reset", "重置").to_string(),
+            1 => i18n::tr("bold", "粗体").to_string(),
+            2 => i18n::tr("dim", "暗淡").to_string(),
+            3 => i18n::tr("italic", "斜体").to_string(),
+            4 => i18n::tr("underline", "下划线").to_string(),
+            5 => i18n::tr("blink", "闪烁").to_string(),
+            7 => i18n::tr("reverse", "反色").to_string(),
+            30..=37 => i18n::tr(&format!("{} text", COLOR_NAMES[(code - 30) as usize]), &format!("{} 文字", COLOR_NAMES[(code - 30) as usize])).to_string(),
+            40..=47 => i18n::tr(&format!("{} background", COLOR_NAMES[(code - 40) as usize]), &format!("{} 背景", COLOR_NAMES[(code - 40) as usize])).to_string(),
+            90..=97 => i18n::tr(&format!("bright {} text", COLOR_NAMES[(code - 90) as usize]), &format!("亮 {} 文字", COLOR_NAMES[(code - 90) as usize])).to_string(),
+            100..=107 => i18n::tr(&format!("bright {} background", COLOR_NAMES[(code - 100) as usize]), &format!("亮 {} 背景", COLOR_NAMES[(code - 100) as usize])).to_string(),
+            39 => i18n::tr("default text", "默认文字").to_string(),
+            49 => i18n::tr("default background", "默认背景").to_string(),
+            38 | 48 => {
+                let target = if code == 38 { i18n::tr("text", "文字") } else { i18n::tr("background", "背景") };
+                match codes.get(i + 1) {
+                    Some(5) if i + 2 < codes.len() => {
+                        i += 2;
+                        i18n::tr(&format!("256-color {} {}", target, codes[i]), &format!("256 色{} {}", target, codes[i])).to_string()
+                    }
+                    Some(2) if i + 4 < codes.len() => {
+                        i += 4;
+                        i18n::tr(
+                            &format!("truecolor {} #{:02x}{:02x}{:02x}", target, codes[i - 2], codes[i - 1], codes[i]),
+                            &format!("真彩色{} #{:02x}{:02x}{:02x}", target, codes[i - 2], codes[i - 1], codes[i]),
+                        )
+                        .to_string()
+                    }
+                    _ => i18n::tr(&format!("extended {} color", target), &format!("扩展{}颜色", target)).to_string(),
+                }
+            }
+            other => format!("SGR {}", other),
+        };
+        parts.push(part);
+        i += 1;
+    }
+    parts.join(", ")
+}
+
+/// 描述单个片段的含义
+pub fn describe_segment(segment: &Segment) -> String {
+    let sgr = Regex::new(r"\\(?:e|033)\[([0-9;]*)m").unwrap();
+    match segment {
+        Segment::Text(_) => i18n::tr("literal text", "文字").to_string(),
+        Segment::Variable(name) => i18n::tr(&format!("value of ${}", name), &format!("${} 的值", name)).to_string(),
+        Segment::Command(cmd) => i18n::tr(&format!("output of `{}`", cmd), &format!("`{}` 的输出", cmd)).to_string(),
+        Segment::Color(color) => match sgr.captures(color) {
+            Some(caps) => i18n::tr(&format!("color: {} (not wrapped in \\[ \\])", describe_sgr(&caps[1])), &format!("颜色: {}(没有包在 \\[ \\] 中)", describe_sgr(&caps[1]))).to_string(),
+            None => i18n::tr("color", "颜色").to_string(),
+        },
+        Segment::NonPrinting(inner) => {
+            let colors: Vec<String> = sgr.captures_iter(inner).map(|caps| describe_sgr(&caps[1])).collect();
+            if colors.is_empty() {
+                i18n::tr("non-printing sequence", "不可打印的序列").to_string()
+            } else {
+                i18n::tr(&format!("color: {}", colors.join(", ")), &format!("颜色: {}", colors.join(", "))).to_string()
+            }
+        }
+        Segment::Escape(escape) => describe_escape(escape),
+    }
+}
+
+/// 描述单个反斜杠转义
+fn describe_escape(escape: &str) -> String {
+    let text = match escape {
+        "u" => i18n::tr("username", "用户名"),
+        "h" => i18n::tr("hostname up to the first dot", "主机名中第一个点之前的部分"),
+        "H" => i18n::tr("full hostname", "完整的主机名"),
+        "w" => i18n::tr("current directory", "当前目录"),
+        "W" => i18n::tr("last part of the current directory", "当前目录的最后一部分"),
+        "t" => i18n::tr("time, 24-hour HH:MM:SS", "时间, 24 小时制 HH:MM:SS"),
+        "T" => i18n::tr("time, 12-hour HH:MM:SS", "时间, 12 小时制 HH:MM:SS"),
+        "@" => i18n::tr("time, 12-hour with am/pm", "时间, 12 小时制, 带 am/pm"),
+        "A" => i18n::tr("time, 24-hour HH:MM", "时间, 24 小时制 HH:MM"),
+        "d" => i18n::tr("date, \"Weekday Month Day\"", "日期, \"星期 月 日\""),
+        "$" => i18n::tr("# for root, $ otherwise", "root 为 #, 其他用户为 $"),
+        "s" => i18n::tr("shell name", "shell 名称"),
+        "v" => i18n::tr("bash version", "bash 版本"),
+        "V" => i18n::tr("bash release", "bash 发行版本"),
+        "j" => i18n::tr("number of jobs", "作业数"),
+        "l" => i18n::tr("terminal device name", "终端设备名"),
+        "!" => i18n::tr("history number", "历史编号"),
+        "#" => i18n::tr("command number", "命令编号"),
+        "n" => i18n::tr("newline", "换行"),
+        "e" | "033" => i18n::tr("escape character", "转义字符"),
+        "a" => i18n::tr("bell", "响铃"),
+        "r" => i18n::tr("carriage return", "回车"),
+        "\\" => i18n::tr("backslash", "反斜杠"),
+        "[" => i18n::tr("unmatched \\[", "不成对的 \\["),
+        "]" => i18n::tr("unmatched \\]", "不成对的 \\]"),
+        _ => {
+            if let Some(fmt) = escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}')) {
+                return i18n::tr(&format!("date/time formatted as {:?}", fmt), &format!("按 {:?} 格式显示的日期时间", fmt)).to_string();
+            }
+            return i18n::tr(&format!("unknown escape \\{}", escape), &format!("未知的转义 \\{}", escape)).to_string();
+        }
+    };
+    text.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_colors_wraps_only_bare_color_codes() {
+        let cases = [
+            (r"\e[32m\u\e[0m \$ ", r"\[\e[32m\]\u\[\e[0m\] \$ ", 2),
+            (r"\033[1;34m\w", r"\[\033[1;34m\]\w", 1),
+            (r"\[\e[32m\]\u\[\e[0m\]", r"\[\e[32m\]\u\[\e[0m\]", 0),
+            (r"\[\e]0;\w\a\e[33m\]x", r"\[\e]0;\w\a\e[33m\]x", 0),
+            (r"\\e[32m", r"\\e[32m", 0),
+            (r"\e[2J plain", r"\e[2J plain", 0),
+            ("方\\e[31m块", "方\\[\\e[31m\\]块", 1),
+        ];
+        for (prompt, expected, count) in cases {
+            assert_eq!(wrap_colors(prompt), (expected.to_string(), count), "{}", prompt);
+        }
+    }
+
+    #[test]
+    fn wrap_colors_is_idempotent() {
+        let (once, _) = wrap_colors(r"\e[1m\h\e[0m:\e[34m\w\e[0m\$ ");
+        assert_eq!(wrap_colors(&once), (once.clone(), 0));
+    }
+
+    /// 不带 --force 校验时的错误信息, 通过时为 None
+    fn rejection(prompt: &str) -> Option<String> {
+        validate_prompt(Shell::Bash, prompt, false).err().map(|err| err.0)
+    }
+
+    #[test]
+    fn strict_validation_rejects_bad_escapes() {
+        for prompt in [r"\q \$ ", r"\[\e[32m \u", r"\u\] ", r"\[\[\e[1m\]\]", r"\e[31 x", "$() "] {
+            assert!(rejection(prompt).is_some(), "{}", prompt);
+        }
+        for prompt in [r"\u@\h:\w\$ ", r"\[\e[1;32m\]\u\[\e[0m\] ", r"\D{%H:%M} \101 ", r"\\ \# \! "] {
+            assert_eq!(rejection(prompt), None, "{}", prompt);
+        }
+    }
+
+    #[test]
+    fn force_keeps_errors_as_issues_but_never_allows_control_characters() {
+        let issues = validate_prompt(Shell::Bash, r"\q \$ ", true).unwrap();
+        assert!(issues.iter().any(|issue| issue.severity == Severity::Error));
+        assert!(validate_prompt(Shell::Bash, "a\u{7}b", true).is_err());
+        assert!(validate_prompt(Shell::Bash, "a\nb", true).is_err());
+    }
+
+    #[test]
+    fn command_substitutions_are_only_warnings() {
+        let issues = validate_prompt(Shell::Bash, r"$(date +%s) `id -u` \$ ", false).unwrap();
+        assert!(!issues.is_empty());
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn directory_globs_quote_everything_but_home_and_wildcards() {
+        let quote = crate::shell::single_quote;
+        let cases: [(&str, &[&str]); 5] = [
+            ("~/work/**", &[r#""$HOME"'/work'"#, r#""$HOME"'/work/'*"#]),
+            ("~", &[r#""$HOME""#]),
+            ("/srv/app", &["'/srv/app'"]),
+            ("/tmp/*", &["'/tmp'", "'/tmp/'*"]),
+            ("~/it's", &[r#""$HOME"'/it'\''s'"#]),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(directory_globs(pattern, quote), expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn time_windows_parse_hh_mm() {
+        assert_eq!(TimeWindow::new("18:00", "08:30").unwrap(), TimeWindow { from: 1800, to: 830 });
+        assert_eq!(TimeWindow::new(" 9:05 ", "23:59").unwrap(), TimeWindow { from: 905, to: 2359 });
+        for (from, to) in [("24:00", "08:00"), ("18:60", "08:00"), ("18", "08:00"), ("18:0", "08:00"), ("6pm", "08:00"), ("12:00", "12:00")] {
+            assert!(TimeWindow::new(from, to).is_err(), "{}-{}", from, to);
+        }
+        assert_eq!(TimeWindow::new("18:00", "08:30").unwrap().to_string(), "18:00-08:30");
+    }
+
+    #[test]
+    fn time_window_conditions_wrap_past_midnight() {
+        let day = TimeWindow::new("09:00", "17:00").unwrap();
+        let night = TimeWindow::new("18:00", "08:30").unwrap();
+        assert_eq!(day.condition("now", ">=", "<", "&&", "||"), "now >= 900 && now < 1700");
+        assert_eq!(night.condition("now", ">=", "<", "&&", "||"), "now >= 1800 || now < 830");
+        assert_eq!(night.condition("$now", "-ge", "-lt", "-a", "-o"), "$now -ge 1800 -o $now -lt 830");
+    }
+}
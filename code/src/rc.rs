@@ -0,0 +1,556 @@
+use crate::config;
+use crate::i18n;
+use crate::logging;
+use crate::output;
+use crate::CliError;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+/// 受管理区块的起始标记
+pub const BLOCK_START: &str = "# >>> prompt-changer >>>";
+/// 受管理区块的结束标记
+pub const BLOCK_END: &str = "# <<< prompt-changer <<<";
+
+/// 受管理区块在文件中的位置(按行号, 从 0 开始, 包含两端标记)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl BlockRange {
+    /// 该行是否位于区块内
+    pub fn contains(&self, line: usize) -> bool {
+        line >= self.start && line <= self.end
+    }
+}
+
+/// 找到受管理区块的位置
+pub fn find_block(content: &str) -> Option<BlockRange> {
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.iter().position(|l| l.trim() == BLOCK_START)?;
+    let end = lines[start..].iter().position(|l| l.trim() == BLOCK_END)? + start;
+    Some(BlockRange { start, end })
+}
+
+/// 读取受管理区块的内容(不含标记)
+pub fn managed_block(content: &str) -> Option<String> {
+    let range = find_block(content)?;
+    let body: Vec<&str> = content.lines().skip(range.start + 1).take(range.end - range.start - 1).collect();
+    Some(body.join("\n"))
+}
+
+/// 用新内容替换受管理区块, 不存在时追加到文件末尾
+pub fn replace_block(content: &str, body: &str) -> String {
+    let block = format!("{}\n{}\n{}", BLOCK_START, body, BLOCK_END);
+    let lines: Vec<&str> = content.lines().collect();
+    let mut out = match find_block(content) {
+        Some(range) => {
+            let mut out: Vec<String> = lines[..range.start].iter().map(|s| s.to_string()).collect();
+            out.push(block);
+            out.extend(lines[range.end + 1..].iter().map(|s| s.to_string()));
+            out.join("\n")
+        }
+        None if content.trim().is_empty() => block,
+        None => format!("{}\n\n{}", content.trim_end_matches('\n'), block),
+    };
+    out.push('\n');
+    out
+}
+
+/// 为 true 时把符号链接换成普通文件, 而不是写入它指向的文件
+static REPLACE_SYMLINKS: OnceLock<bool> = OnceLock::new();
+
+/// 改写时把符号链接换成普通文件(`--no-follow-symlinks`)
+pub fn set_replace_symlinks() -> Result<(), CliError> {
+    REPLACE_SYMLINKS
+        .set(true)
+        .map_err(|_| CliError(i18n::tr("Symlink handling is already set", "符号链接的处理方式已经设置过了").to_string()))
+}
+
+/// 实际改写的文件: 默认写入符号链接指向的文件, 链接保持不变;
+/// 设置了 [`set_replace_symlinks`] 时先把链接换成内容相同的普通文件
+fn write_target(path: &Path) -> std::io::Result<PathBuf> {
+    let is_link = fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink());
+    if !is_link {
+        return Ok(path.to_path_buf());
+    }
+    let link = fs::read_link(path)?;
+    let target = path.parent().map_or_else(|| link.clone(), |dir| dir.join(&link));
+    if REPLACE_SYMLINKS.get().copied().unwrap_or(false) {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let meta = fs::metadata(path).ok();
+        fs::remove_file(path)?;
+        fs::write(path, content)?;
+        if let Some(meta) = meta {
+            fs::set_permissions(path, meta.permissions())?;
+            copy_owner(&meta, path);
+        }
+        output::notice(
+            i18n::tr(
+                &format!("Replaced the symlink {} -> {} with a regular file", path.display(), link.display()),
+                &format!("已把符号链接 {} -> {} 换成普通文件", path.display(), link.display()),
+            ),
+        );
+        return Ok(path.to_path_buf());
+    }
+    if !target.exists() {
+        output::notice(
+            i18n::tr(
+                &format!("{} is a symlink to {}, which does not exist; creating it (use --no-follow-symlinks to replace the link instead)", path.display(), target.display()),
+                &format!("{} 是指向 {} 的符号链接, 目标不存在; 将创建它(使用 --no-follow-symlinks 可改为替换该链接)", path.display(), target.display()),
+            ),
+        );
+    }
+    logging::debug("rc", format!("{} is a symlink; writing to {}", path.display(), target.display()));
+    Ok(target)
+}
+
+/// 新建的文件或目录归属于所在目录的主人, 以 root 身份写入其他用户的家目录时不会留下归属 root 的文件
+pub fn own_like_parent(path: &Path) {
+    if let Some(meta) = path.parent().and_then(|dir| fs::metadata(dir).ok()) {
+        copy_owner(&meta, path);
+    }
+}
+
+/// 创建目录及缺少的上级目录, 新建的各级目录见 [`own_like_parent`]
+pub fn create_dirs(dir: &Path) -> std::io::Result<()> {
+    let missing: Vec<&Path> = dir.ancestors().take_while(|d| !d.as_os_str().is_empty() && !d.exists()).collect();
+    fs::create_dir_all(dir)?;
+    for created in missing.into_iter().rev() {
+        own_like_parent(created);
+    }
+    Ok(())
+}
+
+/// 以 root 身份(如经 sudo)写入时, 让文件的归属与 `like` 相同, 以免用户之后改不了自己的文件
+#[cfg(unix)]
+fn copy_owner(like: &fs::Metadata, path: &Path) {
+    use crate::shell;
+    use std::os::unix::fs::MetadataExt;
+    if !shell::is_root() {
+        return;
+    }
+    if let Err(err) = std::os::unix::fs::chown(path, Some(like.uid()), Some(like.gid())) {
+        logging::debug("rc", format!("could not change the owner of {}: {}", path.display(), err));
+    }
+}
+
+/// 以 root 身份写入时, 让文件的归属与 `like` 相同
+#[cfg(not(unix))]
+fn copy_owner(_like: &fs::Metadata, _path: &Path) {}
+
+/// 按原文件的习惯调整换行: 原来主要用 CRLF 的仍用 CRLF, 原来末尾没有换行的也不加
+pub fn match_line_endings(original: &str, mut updated: String) -> String {
+    if !original.is_empty() && !original.ends_with('\n') && updated.ends_with('\n') {
+        updated.pop();
+    }
+    if original.matches("\r\n").count() * 2 > original.matches('\n').count() {
+        updated = updated.replace('\n', "\r\n");
+    }
+    updated
+}
+
+/// 等待锁的最长时间
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+/// 读取后文件被改动时最多重新读取的次数
+const MAX_ATTEMPTS: usize = 3;
+
+/// 文件上的建议锁, 关闭文件时释放
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// 锁住一个文件, 不存在时创建; 被其他进程锁住时最多等待 [`LOCK_TIMEOUT`]
+    pub fn acquire(path: &Path) -> Result<FileLock, Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            create_dirs(parent)?;
+        }
+        let existed = path.exists();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if !existed {
+            own_like_parent(path);
+        }
+        FileLock::wait(file, path)
+    }
+
+    fn wait(file: File, path: &Path) -> Result<FileLock, Box<dyn std::error::Error>> {
+        let step = Duration::from_millis(100);
+        let mut waited = Duration::ZERO;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(FileLock { _file: file }),
+                Err(TryLockError::WouldBlock) if waited < LOCK_TIMEOUT => {
+                    if waited.is_zero() {
+                        logging::debug("rc", format!("waiting for the lock on {}", path.display()));
+                    }
+                    thread::sleep(step);
+                    waited += step;
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(Box::new(CliError(
+                        i18n::tr(
+                            &format!(
+                                "{} is locked by another process (another prompt-changer or a dotfiles manager); try again once it finishes",
+                                path.display()
+                            ),
+                            &format!("{} 被另一个进程(另一个 prompt-changer 或 dotfiles 管理工具)锁住了; 请等它结束后再试", path.display()),
+                        )
+                        .to_string(),
+                    )))
+                }
+                Err(TryLockError::Error(err)) => return Err(Box::new(err)),
+            }
+        }
+    }
+}
+
+/// 不是 UTF-8 的字节解码后对应的字符从这里开始(第 16 平面私用区的最后 256 个码位)
+const RAW_BYTE_BASE: u32 = 0x10FF00;
+
+/// 把文件内容解码为文本; 不是 UTF-8 的字节(如 Latin-1 写的注释)映射为私用区字符, [`encode`] 时原样还原
+pub fn decode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        text.extend(chunk.invalid().iter().map(|&b| char::from_u32(RAW_BYTE_BASE + u32::from(b)).unwrap_or(char::REPLACEMENT_CHARACTER)));
+    }
+    text
+}
+
+/// [`decode`] 的逆过程
+pub fn encode(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match u32::from(c).checked_sub(RAW_BYTE_BASE).and_then(|b| u8::try_from(b).ok()) {
+            Some(b) => bytes.push(b),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+/// 读取 rc 文件的文本, 不要求是 UTF-8, 见 [`decode`]
+pub fn read_text(path: &Path) -> std::io::Result<String> {
+    fs::read(path).map(|bytes| decode(&bytes))
+}
+
+/// 读取文件, 不存在时为空
+fn read_or_empty(path: &Path) -> std::io::Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// 锁住文件后读取并改写: `update` 收到的内容统一用 LF 换行, 返回 None 时不改动; 读取后文件又被其他程序改动时重新读取,
+/// 多次仍被改动则放弃. `create` 为 false 时文件不存在就不改动; 返回是否写入.
+/// 原地写入, 文件的权限与归属不变, 换行习惯见 [`match_line_endings`]
+pub fn modify(
+    path: &Path,
+    create: bool,
+    mut update: impl FnMut(&str) -> Result<Option<String>, Box<dyn std::error::Error>>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if !create && fs::symlink_metadata(path).is_err() {
+        return Ok(false);
+    }
+    let path = &write_target(path)?;
+    let existed = path.exists();
+    if create {
+        if let Some(parent) = path.parent() {
+            create_dirs(parent)?;
+        }
+    }
+    let file = match OpenOptions::new().read(true).write(true).create(create).truncate(false).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(Box::new(err)),
+    };
+    let _lock = FileLock::wait(file, path)?;
+    if !existed {
+        own_like_parent(path);
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let original = read_or_empty(path)?;
+        let text = decode(&original);
+        let Some(updated) = update(&text.replace("\r\n", "\n"))? else {
+            return Ok(false);
+        };
+        // 建议锁管不住不加锁的程序, 写入前再确认一次
+        if read_or_empty(path)? != original {
+            logging::debug("rc", format!("{} changed while it was being updated; reading it again", path.display()));
+            continue;
+        }
+        let updated = match_line_endings(&text, updated);
+        if std::str::from_utf8(&original).is_ok() {
+            fs::write(path, updated)?;
+        } else {
+            // 只有受管理区块是本工具写的 UTF-8, 其余字节保持原样
+            logging::debug("rc", format!("{} is not UTF-8; keeping its other bytes as they are", path.display()));
+            fs::write(path, encode(&updated))?;
+        }
+        return Ok(true);
+    }
+    Err(Box::new(CliError(
+        i18n::tr(
+            &format!("{} kept changing while prompt-changer was updating it; nothing was written, try again", path.display()),
+            &format!("prompt-changer 更新 {} 时它一直在被改动; 没有写入任何内容, 请重试", path.display()),
+        )
+        .to_string(),
+    )))
+}
+
+/// 把受管理区块写入文件, 文件不存在时创建
+pub fn write_block(path: &Path, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+    modify(path, true, |content| {
+        if config::get().backup == Some(true) && !content.is_empty() {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            // 复制原文件, 连同权限与原来的换行
+            fs::copy(path, &backup)?;
+            if let Ok(meta) = fs::metadata(path) {
+                copy_owner(&meta, Path::new(&backup));
+            }
+            logging::debug("rc", format!("backed up {} to {}", path.display(), Path::new(&backup).display()));
+        }
+        let action = if find_block(content).is_some() { "replacing" } else { "appending" };
+        logging::debug("rc", format!("{} the managed block in {} ({} lines)", action, path.display(), body.lines().count()));
+        logging::trace("rc", format!("block written to {}:\n{}", path.display(), body));
+        Ok(Some(replace_block(content, body)))
+    })?;
+    Ok(())
+}
+
+/// 删除受管理区块(连同写入时在它前面加的空行), 没有区块时返回 None
+pub fn remove_block(content: &str) -> Option<String> {
+    let range = find_block(content)?;
+    let lines: Vec<&str> = content.lines().collect();
+    let mut start = range.start;
+    if start > 0 && lines[start - 1].trim().is_empty() {
+        start -= 1;
+    }
+    let mut out: Vec<&str> = lines[..start].to_vec();
+    out.extend(&lines[range.end + 1..]);
+    let mut out = out.join("\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// 从文件中删除受管理区块, 返回是否有改动
+pub fn remove_block_from(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    modify(path, false, |content| {
+        let updated = remove_block(content);
+        if updated.is_some() {
+            logging::debug("rc", format!("removing the managed block from {}", path.display()));
+        }
+        Ok(updated)
+    })
+}
+
+/// 改写前各文件的内容, 出错时据此恢复
+#[derive(Debug)]
+pub struct Snapshot {
+    /// 文件及其原有内容, 原本不存在时为 None
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl Snapshot {
+    /// 记下这些文件现在的内容
+    pub fn take(paths: &[PathBuf]) -> std::io::Result<Snapshot> {
+        let mut files = Vec::new();
+        for path in paths {
+            let content = match fs::read(path) {
+                Ok(content) => Some(content),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err),
+            };
+            files.push((path.clone(), content));
+        }
+        Ok(Snapshot { files })
+    }
+
+    /// 把文件恢复为记下的内容, 删除原本不存在的文件
+    pub fn restore(&self) -> std::io::Result<()> {
+        for (path, content) in &self.files {
+            match content {
+                Some(content) => fs::write(path, content)?,
+                None if path.exists() => fs::remove_file(path)?,
+                None => {}
+            }
+            logging::debug("rc", format!("restored {}", path.display()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用的临时文件, 释放时删除
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &[u8]) -> TempFile {
+            let path = std::env::temp_dir().join(format!("prompt-changer-rc-test-{}-{}", std::process::id(), name));
+            fs::write(&path, content).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn decode_encode_round_trips_invalid_utf8() {
+        let bytes = b"# caf\xe9\n\xff\xfe PS1='x'\n\xc3\n";
+        let text = decode(bytes);
+        assert!(text.starts_with("# caf"));
+        assert!(text.contains(" PS1='x'\n"));
+        assert_eq!(encode(&text), bytes);
+    }
+
+    #[test]
+    fn decode_keeps_valid_utf8() {
+        let text = "# 提示符 ✓\n";
+        assert_eq!(decode(text.as_bytes()), text);
+        assert_eq!(encode(text), text.as_bytes());
+    }
+
+    #[test]
+    fn match_line_endings_keeps_crlf() {
+        let updated = match_line_endings("a\r\nb\r\n", "a\nb\nc\n".to_string());
+        assert_eq!(updated, "a\r\nb\r\nc\r\n");
+    }
+
+    #[test]
+    fn match_line_endings_follows_the_majority_in_mixed_files() {
+        assert_eq!(match_line_endings("a\r\nb\r\nc\n", "x\ny\n".to_string()), "x\r\ny\r\n");
+        assert_eq!(match_line_endings("a\r\nb\nc\n", "x\ny\n".to_string()), "x\ny\n");
+    }
+
+    #[test]
+    fn match_line_endings_keeps_a_missing_trailing_newline() {
+        assert_eq!(match_line_endings("a\nb", "a\nb\nc\n".to_string()), "a\nb\nc");
+        assert_eq!(match_line_endings("", "a\n".to_string()), "a\n");
+        assert_eq!(match_line_endings("a\r\nb", "a\nb\n".to_string()), "a\r\nb");
+    }
+
+    #[test]
+    fn replace_block_appends_then_replaces() {
+        let appended = replace_block("export A=1", "PS1='a'");
+        assert_eq!(appended, format!("export A=1\n\n{}\nPS1='a'\n{}\n", BLOCK_START, BLOCK_END));
+        let replaced = replace_block(&appended, "PS1='b'");
+        assert_eq!(replaced, format!("export A=1\n\n{}\nPS1='b'\n{}\n", BLOCK_START, BLOCK_END));
+        assert_eq!(replace_block("", "PS1='a'"), format!("{}\nPS1='a'\n{}\n", BLOCK_START, BLOCK_END));
+    }
+
+    #[test]
+    fn modify_preserves_bytes_outside_the_block() {
+        let original = b"# caf\xe9\r\nexport A=1\r\n";
+        let file = TempFile::new("bytes", original);
+        assert!(modify(&file.0, false, |content| Ok(Some(replace_block(content, "PS1='x'")))).unwrap());
+        let written = fs::read(&file.0).unwrap();
+        let expected = [&original[..], format!("\r\n{}\r\nPS1='x'\r\n{}\r\n", BLOCK_START, BLOCK_END).as_bytes()].concat();
+        assert_eq!(written, expected);
+
+        assert!(modify(&file.0, false, |content| Ok(remove_block(content))).unwrap());
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+    }
+
+    #[test]
+    fn modify_keeps_a_missing_trailing_newline() {
+        let file = TempFile::new("newline", b"export A=1");
+        modify(&file.0, false, |content| Ok(Some(replace_block(content, "PS1='x'")))).unwrap();
+        let written = fs::read_to_string(&file.0).unwrap();
+        assert_eq!(written, format!("export A=1\n\n{}\nPS1='x'\n{}", BLOCK_START, BLOCK_END));
+    }
+
+    #[test]
+    fn remove_block_undoes_replace_block() {
+        let cases = ["export A=1\n", "export A=1\nalias ll='ls -l'\n", ""];
+        for original in cases {
+            let written = replace_block(original, "PS1='x'");
+            assert_eq!(remove_block(&written), Some(original.to_string()), "{:?}", original);
+        }
+        let middle = format!("export A=1\n\n{}\nPS1='x'\n{}\nexport B=2\n", BLOCK_START, BLOCK_END);
+        assert_eq!(remove_block(&middle), Some("export A=1\nexport B=2\n".to_string()));
+    }
+
+    #[test]
+    fn remove_block_from_is_a_no_op_the_second_time() {
+        let original = b"export A=1\r\n";
+        let file = TempFile::new("reset", original);
+        assert!(!remove_block_from(&file.0).unwrap());
+        modify(&file.0, false, |content| Ok(Some(replace_block(content, "PS1='x'")))).unwrap();
+        assert!(remove_block_from(&file.0).unwrap());
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+        assert!(!remove_block_from(&file.0).unwrap());
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+        assert_eq!(remove_block("export A=1\n"), None);
+    }
+
+    #[test]
+    fn file_lock_waits_for_the_holder_to_release_it() {
+        let file = TempFile::new("lock", b"");
+        let held = FileLock::acquire(&file.0).unwrap();
+        let path = file.0.clone();
+        let waiter = thread::spawn(move || {
+            let start = std::time::Instant::now();
+            FileLock::acquire(&path).map(|_| start.elapsed()).map_err(|err| err.to_string())
+        });
+        thread::sleep(Duration::from_millis(300));
+        drop(held);
+        let waited = waiter.join().unwrap().unwrap();
+        assert!(waited >= Duration::from_millis(200), "{:?}", waited);
+    }
+
+    // Windows 上的锁是强制的, 持锁时其他句柄写不进去
+    #[cfg(unix)]
+    #[test]
+    fn modify_reads_again_when_the_file_changes_underneath() {
+        let file = TempFile::new("retry", b"export A=1\n");
+        let mut calls = 0;
+        let written = modify(&file.0, false, |content| {
+            calls += 1;
+            if calls == 1 {
+                fs::write(&file.0, "export B=2\n")?;
+            }
+            Ok(Some(replace_block(content, "PS1='x'")))
+        })
+        .unwrap();
+        assert!(written);
+        assert_eq!(calls, 2);
+        assert_eq!(fs::read_to_string(&file.0).unwrap(), replace_block("export B=2\n", "PS1='x'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn modify_gives_up_when_the_file_keeps_changing() {
+        let file = TempFile::new("busy", b"export A=1\n");
+        let mut calls = 0;
+        let result = modify(&file.0, false, |content| {
+            calls += 1;
+            fs::write(&file.0, format!("export A={}\n", calls + 1))?;
+            Ok(Some(replace_block(content, "PS1='x'")))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, MAX_ATTEMPTS);
+        assert_eq!(fs::read_to_string(&file.0).unwrap(), format!("export A={}\n", MAX_ATTEMPTS + 1));
+    }
+}
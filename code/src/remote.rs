@@ -0,0 +1,164 @@
+use crate::elvish;
+use crate::i18n;
+use crate::ksh;
+use crate::logging;
+use crate::oils;
+use crate::powershell;
+use crate::prompt::{self, PromptSet, SOURCE_MARKER};
+use crate::rc;
+use crate::shell::{self, Shell};
+use crate::tcsh;
+use crate::xonsh;
+use crate::CliError;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// 在远程主机上用 sh 执行脚本, 返回标准输出; 输入输出按 [`rc::decode`] 处理, 不是 UTF-8 的字节原样往返
+fn ssh(host: &str, script: &str, input: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    // 远程的登录 shell 可能是 fish, 所以统一交给 sh 执行
+    let mut child = Command::new("ssh")
+        .args(["-o", "BatchMode=yes", host, "--", "sh", "-c", &shell::single_quote(script)])
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| CliError(i18n::tr(&format!("Cannot run ssh: {}", err), &format!("无法运行 ssh: {}", err)).to_string()))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(&rc::encode(input))?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Box::new(CliError(
+            i18n::tr(&format!("ssh {} failed: {}", host, stderr.trim()), &format!("ssh {} 失败: {}", host, stderr.trim())).to_string(),
+        )));
+    }
+    Ok(rc::decode(&output.stdout))
+}
+
+/// 设置 `f` 为远程家目录下的文件; 是符号链接时改为它指向的文件, 与本地写入一致
+fn target_script(rel: &str) -> String {
+    format!(r#"f="$HOME/"{}
+if [ -L "$f" ]; then f=$(readlink -f "$f") || exit 1; fi"#, shell::single_quote(rel))
+}
+
+/// 读取远程文件; 不存在时为空, 存在但读不出来时报错, 以免之后用只有区块的内容覆盖它
+fn read_remote(host: &str, rel: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let script = format!("{}\nif [ -e \"$f\" ]; then cat \"$f\"; fi", target_script(rel));
+    ssh(host, &script, None)
+}
+
+/// 把标准输入写入远程文件: 先写到同一目录下的临时文件(复制原文件的权限), 再用 mv 替换, 中途断开时原文件不变
+fn write_remote(host: &str, rel: &str, content: &str, prepare: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let script = format!(
+        r#"{}
+{}mkdir -p "$(dirname "$f")" || exit 1
+tmp=$(mktemp "$f.XXXXXX") || exit 1
+trap 'rm -f "$tmp"' EXIT
+if [ -e "$f" ]; then cp -p "$f" "$tmp" || exit 1; fi
+cat > "$tmp" && mv "$tmp" "$f""#,
+        target_script(rel),
+        prepare
+    );
+    ssh(host, &script, Some(content))?;
+    Ok(())
+}
+
+/// 替换远程配置文件中的受管理区块, 换行习惯与非 UTF-8 的字节保持不变
+fn update_remote(host: &str, rel: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let content = read_remote(host, rel)?;
+    let updated = rc::match_line_endings(&content, rc::replace_block(&content.replace("\r\n", "\n"), body));
+    write_remote(host, rel, &updated, "")
+}
+
+/// 读取远程主机的登录 shell
+pub fn detect_shell(host: &str) -> Result<Shell, Box<dyn std::error::Error>> {
+    let name = ssh(host, r#"basename "${SHELL:-sh}""#, None)?;
+    let name = name.trim();
+    Shell::from_name(name).map_err(|_| {
+        Box::new(CliError(
+            i18n::tr(
+                &format!("the login shell on {} is {}, which is not supported; pass --shell", host, name),
+                &format!("{} 上的登录 shell 是 {}, 不受支持; 请用 --shell 指定", host, name),
+            )
+            .to_string(),
+        ))
+        .into()
+    })
+}
+
+/// 把提示符写入远程主机的配置, 返回被写入的文件
+fn push(host: &str, shell: Shell, new_prompt: &PromptSet) -> Result<String, Box<dyn std::error::Error>> {
+    let (rel, body) = match shell {
+        Shell::Bash => (".bashrc", shell::bash_block_body(new_prompt)),
+        Shell::Fish => {
+            // 原有的不是本工具生成的 fish_prompt.fish 先改名为 .bak
+            let prepare = format!(
+                "if [ -f \"$f\" ] && ! grep -qF {} \"$f\"; then mv \"$f\" \"$f.bak\" || exit 1; fi\n",
+                shell::single_quote(SOURCE_MARKER.trim_end())
+            );
+            write_remote(host, ".config/fish/functions/fish_prompt.fish", &prompt::fish_function(new_prompt), &prepare)?;
+            return Ok("~/.config/fish/functions/fish_prompt.fish".to_string());
+        }
+        Shell::Pwsh => (".config/powershell/Microsoft.PowerShell_profile.ps1", powershell::block_body(new_prompt)),
+        Shell::Ksh => (".kshrc", ksh::block_body(shell, new_prompt)),
+        Shell::Tcsh => (".tcshrc", tcsh::block_body(new_prompt)),
+        Shell::Posix => (".profile", ksh::block_body(shell, new_prompt)),
+        Shell::Elvish => (".config/elvish/rc.elv", elvish::block_body(new_prompt)),
+        Shell::Xonsh => (".xonshrc", xonsh::block_body(new_prompt)),
+        Shell::Oils => (".config/oils/oshrc", oils::block_body(new_prompt)),
+        Shell::Cmd => {
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    "remote pushes over ssh to Unix shells; set cmd prompts on the Windows machine itself",
+                    "remote 通过 ssh 推送到 Unix shell; 请在 Windows 机器上直接设置 cmd 提示符",
+                )
+                .to_string(),
+            )))
+        }
+    };
+    update_remote(host, rel, &body)?;
+    Ok(format!("~/{}", rel))
+}
+
+/// 把提示符推送到每台主机, 未指定 shell 时使用远程的登录 shell, 返回失败的主机数
+pub fn run(hosts: &[&str], shell: Option<Shell>, new_prompt: Option<&PromptSet>, force: bool) -> usize {
+    let mut failed = 0;
+    for host in hosts {
+        let result = shell
+            .map_or_else(|| detect_shell(host), Ok)
+            .and_then(|shell| {
+                let new_prompt = match new_prompt {
+                    Some(p) => p.clone(),
+                    None => PromptSet::single(&shell.managed_prompt()?.ok_or_else(|| {
+                        let (name, arg) = (shell.display_name(), shell.display_name().to_lowercase());
+                        CliError(
+                            i18n::tr(
+                                &format!("No {} prompt has been set locally; run `prompt-changer set --shell {}` or pass --prompt", name, arg),
+                                &format!("本机还没有设置 {} 提示符; 请运行 `prompt-changer set --shell {}` 或用 --prompt 指定", name, arg),
+                            )
+                            .to_string(),
+                        )
+                    })?),
+                };
+                for variant in new_prompt.prompts() {
+                    for warning in prompt::validate_prompt(shell, variant, force)? {
+                        i18n::warning(format!("{}: {}", host, warning.message));
+                    }
+                }
+                shell.notice_unsupported(&new_prompt);
+                Ok((shell, push(host, shell, &new_prompt)?))
+            });
+        match result {
+            Ok((shell, path)) => logging::info(i18n::tr(
+                &format!("{}: {} prompt written to {}", host, shell.display_name(), path),
+                &format!("{}: {} 提示符已写入 {}", host, shell.display_name(), path),
+            )),
+            Err(err) => {
+                eprintln!("{}", i18n::tr(&format!("Error applying prompt to {}: {}", host, err), &format!("应用提示符到 {} 出错: {}", host, err)));
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
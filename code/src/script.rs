@@ -0,0 +1,123 @@
+use crate::cmd;
+use crate::elvish;
+use crate::ksh;
+use crate::oils;
+use crate::powershell;
+use crate::prompt::{self, PromptSet};
+use crate::shell::{self, Shell};
+use crate::tcsh;
+use crate::xonsh;
+
+/// 各 shell 中载入脚本的写法与通常放置这一行的文件
+fn load_hint(shell: Shell) -> (&'static str, &'static str) {
+    match shell {
+        Shell::Bash => (". ~/my-prompt.sh", "~/.bashrc"),
+        Shell::Fish => ("source ~/my-prompt.fish", "~/.config/fish/config.fish"),
+        Shell::Pwsh => (". ~/my-prompt.ps1", "$PROFILE"),
+        Shell::Ksh => (". ~/my-prompt.ksh", "~/.kshrc"),
+        Shell::Tcsh => ("source ~/my-prompt.tcsh", "~/.tcshrc"),
+        Shell::Posix => (". ~/my-prompt.sh", "~/.profile"),
+        Shell::Elvish => ("eval (slurp < ~/my-prompt.elv)", "~/.config/elvish/rc.elv"),
+        Shell::Xonsh => ("source ~/my-prompt.xsh", "~/.xonshrc"),
+        Shell::Oils => (". ~/my-prompt.osh", "~/.config/oils/oshrc"),
+        Shell::Cmd => ("call %USERPROFILE%\\my-prompt.cmd", "the AutoRun value under HKCU\\Software\\Microsoft\\Command Processor"),
+    }
+}
+
+/// 说明脚本中各部分的注释
+fn outline(shell: Shell, prompts: &PromptSet) -> Vec<&'static str> {
+    let mut parts = Vec::new();
+    if !prompts.colors.is_empty() && shell != Shell::Cmd {
+        parts.push("The first part picks color codes for the terminal's color depth and background (NO_COLOR turns them off).");
+    }
+    parts.push(match shell {
+        Shell::Bash | Shell::Ksh | Shell::Posix | Shell::Oils => "PS1 holds the prompt.",
+        Shell::Cmd => "The prompt command sets the prompt of the cmd window that runs this file.",
+        Shell::Fish => "fish_prompt prints the prompt.",
+        Shell::Pwsh => "The prompt function returns the prompt.",
+        Shell::Tcsh => "The prompt variable holds the prompt.",
+        Shell::Elvish => "The edit:prompt closure draws the prompt; the right-hand prompt is cleared.",
+        Shell::Xonsh => "$PROMPT holds the prompt template; the $PROMPT_FIELDS entries fill in what xonsh has no field for.",
+    });
+    if matches!(shell, Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd) {
+        // ksh、sh、tcsh、Elvish、xonsh 与 Oils 只写入按主机名切换的变体, cmd 连变体也没有; 其余设置在生成时已提示被略去
+        if !prompts.hosts.is_empty() && shell != Shell::Cmd {
+            parts.push("The `case`/`switch`/`if` statement picks the per-host variants listed above.");
+        }
+        return parts;
+    }
+    if !prompts.hosts.is_empty() || !prompts.directories.is_empty() {
+        parts.push("The `case`/`switch` statements pick the per-host and per-directory variants listed above.");
+    }
+    if !prompts.times.is_empty() {
+        parts.push("The clock is checked each time the prompt is shown to pick the time-of-day variants.");
+    }
+    if prompts.vi_mode.is_some() {
+        parts.push(match shell {
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => "The readline settings show the vi editing mode at the start of the prompt (bash 4.4+).",
+            Shell::Fish => "fish_mode_prompt shows the vi editing mode when vi key bindings are on.",
+            Shell::Pwsh => "PSReadLine shows the vi editing mode through the cursor shape.",
+        });
+    }
+    if prompts.fit.is_some() {
+        parts.push("On narrow terminals the lowest-priority segments are dropped until the prompt fits.");
+    }
+    if prompts.screen_compat {
+        parts.push("Inside GNU screen or mosh, truecolor, the title and the OSC 7/133 sequences are left out.");
+    }
+    if prompts.transient.is_some() && shell != Shell::Bash {
+        parts.push("Enter redraws the finished prompt in its short transient form before running the command.");
+    }
+    if shell == Shell::Bash && (!prompts.directories.is_empty() || !prompts.times.is_empty() || prompts.report_cwd || prompts.semantic_marks || !prompts.terminals.is_empty()) {
+        parts.push("The __prompt_changer_* functions run from PROMPT_COMMAND and keep any commands already in it.");
+    }
+    parts
+}
+
+/// 不依赖本工具、可以提交到 dotfiles 并手动载入的提示符脚本
+pub fn standalone(shell: Shell, prompts: &PromptSet, source: &str) -> String {
+    let (line, file) = load_hint(shell);
+    let mut script = String::new();
+    if shell == Shell::Bash {
+        script += "# shellcheck shell=bash\n";
+    }
+    script += &format!(
+        "# {} prompt exported by prompt-changer on {} from {}.\n",
+        shell.display_name(),
+        chrono::Local::now().format("%Y-%m-%d"),
+        source
+    );
+    script += "# It does not need prompt-changer installed. Load it from your shell config, e.g. add\n";
+    script += &format!("#     {}\n# to {}.\n#\n", line, file);
+    script += &format!("# Prompt: {}\n", prompts.default);
+    for (pattern, prompt) in &prompts.hosts {
+        script += &format!("# On hosts matching {}: {}\n", pattern, prompt);
+    }
+    for (pattern, prompt) in &prompts.directories {
+        script += &format!("# In {}: {}\n", pattern, prompt);
+    }
+    for (window, prompt) in &prompts.times {
+        script += &format!("# During {}: {}\n", window, prompt);
+    }
+    script += "#\n";
+    for part in outline(shell, prompts) {
+        script += &format!("# {}\n", part);
+    }
+    if shell == Shell::Cmd {
+        // 批处理文件中 `#` 不是注释, 改用 `::`
+        script = script.lines().map(|line| format!("{}\n", line.strip_prefix('#').map_or(line.to_string(), |rest| format!("::{}", rest)))).collect();
+    }
+    let body = match shell {
+        Shell::Bash => shell::bash_block_body(prompts),
+        Shell::Fish => prompt::fish_function(prompts),
+        Shell::Pwsh => powershell::block_body(prompts),
+        Shell::Ksh | Shell::Posix => ksh::block_body(shell, prompts),
+        Shell::Tcsh => tcsh::block_body(prompts),
+        Shell::Elvish => elvish::block_body(prompts),
+        Shell::Xonsh => xonsh::block_body(prompts),
+        Shell::Oils => oils::block_body(prompts),
+        Shell::Cmd => cmd::script_body(prompts),
+    };
+    script += &format!("\n{}\n", body.trim_end());
+    script
+}
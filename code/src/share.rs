@@ -0,0 +1,242 @@
+use crate::i18n;
+use crate::logging;
+use crate::migrate;
+use crate::output;
+use crate::palette;
+use crate::plugins;
+use crate::spec::{self, Spec};
+use crate::CliError;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 分享字符串的前缀, 格式变化时递增版本号
+const PREFIX: &str = "pc1:";
+
+/// URL 安全的 base64 字母表
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// 用 URL 安全的 base64 编码, 不加填充
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+/// 解码 URL 安全的 base64, 也接受标准字母表与填充
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => ALPHABET.iter().position(|a| *a == c).map(|p| p as u32),
+    };
+    let digits: Vec<u32> = text.trim_end_matches('=').bytes().map(value).collect::<Option<_>>()?;
+    if digits.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::new();
+    for chunk in digits.chunks(4) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, d)| n | (d << (18 - 6 * i)));
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// 把引用调色板槽位的颜色换成槽位中的 `#rrggbb`, 对方不需要同一个调色板
+fn inline_palette(value: &mut toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(name) = value.as_table_mut().and_then(|t| t.remove("palette")) else {
+        return Ok(());
+    };
+    let palette = palette::load(name.as_str().unwrap_or_default())?;
+    // 描述文件自己的颜色槽位优先于调色板中的同名槽位
+    let own: Vec<String> = value.get("slots").and_then(|s| s.as_table()).map(|s| s.keys().cloned().collect()).unwrap_or_default();
+    let resolve = |color: &mut toml::Value| {
+        if let Some(slot) = color.as_str().map(|c| c.trim().to_ascii_lowercase()) {
+            if spec::color_sequence(&slot).is_err() && !own.contains(&slot) {
+                if let Some(hex) = palette.get(&slot) {
+                    *color = toml::Value::String(hex.clone());
+                }
+            }
+        }
+    };
+    let table = value.as_table_mut().unwrap();
+    for segment in table.get_mut("segment").and_then(|s| s.as_array_mut()).into_iter().flatten() {
+        for key in ["color", "background"] {
+            segment.get_mut(key).into_iter().for_each(resolve);
+        }
+        for key in ["value_colors", "thresholds"] {
+            if let Some(colors) = segment.get_mut(key).and_then(|c| c.as_table_mut()) {
+                colors.iter_mut().for_each(|(_, color)| resolve(color));
+            }
+        }
+        if let Some(stops) = segment.get_mut("gradient").and_then(|g| g.as_array_mut()) {
+            stops.iter_mut().for_each(resolve);
+        }
+    }
+    for key in ["host", "directory", "time"] {
+        for rule in table.get_mut(key).and_then(|r| r.as_array_mut()).into_iter().flatten() {
+            if let Some(colors) = rule.get_mut("colors").and_then(|c| c.as_table_mut()) {
+                colors.iter_mut().for_each(|(_, color)| resolve(color));
+            }
+        }
+    }
+    for key in ["light", "dark"] {
+        if let Some(colors) = table.get_mut(key).and_then(|c| c.as_table_mut()) {
+            colors.iter_mut().for_each(|(_, color)| resolve(color));
+        }
+    }
+    if let Some(stops) = table.get_mut("gradient").and_then(|g| g.as_array_mut()) {
+        stops.iter_mut().for_each(resolve);
+    }
+    Ok(())
+}
+
+/// 描述文件去掉注释、内联调色板后的 TOML
+fn portable_spec(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let spec = Spec::load(path)?;
+    let content = fs::read_to_string(path)?;
+    let mut value = match migrate::upgrade(&content, &path.display().to_string())? {
+        Some(upgraded) => toml::Value::Table(upgraded),
+        None => toml::from_str(&content)?,
+    };
+    // 写明版本, 以后的版本改动格式时仍能正确升级
+    if let Some(table) = value.as_table_mut() {
+        table.entry("version").or_insert(toml::Value::Integer(i64::from(migrate::SPEC_VERSION)));
+        // 带上配置文件中的槽位, 槽位中的颜色已换成调色板中的颜色
+        if !spec.slots.is_empty() {
+            let slots = spec.slots.iter().map(|(name, color)| (name.clone(), toml::Value::String(color.clone()))).collect();
+            table.insert("slots".to_string(), toml::Value::Table(slots));
+        }
+    }
+    inline_palette(&mut value)?;
+    Ok(toml::to_string(&value)?)
+}
+
+/// 把描述文件编码为可以粘贴分享的字符串
+pub fn encode(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(format!("{}{}", PREFIX, base64_encode(portable_spec(path)?.as_bytes())))
+}
+
+/// 解码分享字符串, 返回检查过的描述文件内容
+pub fn decode(code: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let invalid = || CliError(
+        i18n::tr(
+            "This is not a prompt-changer share code; it should start with `pc1:`",
+            "这不是 prompt-changer 的分享码; 分享码应以 `pc1:` 开头",
+        )
+        .to_string(),
+    );
+    let compact: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let body = compact.strip_prefix(PREFIX).ok_or_else(invalid)?;
+    let bytes = base64_decode(body).ok_or_else(invalid)?;
+    let content = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let spec = Spec::parse(&content, "in the share code")?;
+    // 可执行文件插件不在沙箱中, 提醒用户分享的提示符会运行它
+    for plugin in spec.segments.iter().filter_map(|s| s.plugin.as_deref()) {
+        if !plugins::is_wasm(plugin) {
+            output::notice(
+                i18n::tr(
+                    &format!("The shared prompt runs your `{}` plugin, which is an executable rather than a sandboxed WASM module", plugin),
+                    &format!("分享的提示符会运行你的 `{}` 插件, 它是可执行文件而不是沙盒中的 WASM 模块", plugin),
+                ),
+            );
+        }
+    }
+    Ok(content)
+}
+
+/// 用 GitHub CLI 把描述文件上传为 gist, 返回 gist 的地址
+pub fn upload_gist(path: &Path, code: &str, public: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let content = format!("# Shared with prompt-changer; apply it with:\n#     prompt-changer import --from-string {}\n{}", code, portable_spec(path)?);
+    let mut cmd = Command::new("gh");
+    cmd.args(["gist", "create", "--filename", "prompt.toml", "--desc", "prompt-changer prompt"]);
+    if public {
+        cmd.arg("--public");
+    }
+    logging::debug("share", "uploading the spec with `gh gist create`");
+    let mut child = cmd
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            CliError(
+                i18n::tr(
+                    &format!("cannot run the GitHub CLI (gh), which uploads gists: {}", err),
+                    &format!("无法运行上传 gist 所用的 GitHub CLI (gh): {}", err),
+                )
+                .to_string(),
+            )
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Box::new(CliError(
+            i18n::tr(&format!("gh gist create failed: {}", stderr.trim()), &format!("gh gist create 失败: {}", stderr.trim())).to_string(),
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips_every_length() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..10 {
+            let encoded = base64_encode(&bytes[250 - len..250]);
+            assert!(!encoded.contains(['+', '/', '=']), "{}", encoded);
+            assert_eq!(base64_decode(&encoded).as_deref(), Some(&bytes[250 - len..250]), "{}", len);
+        }
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(b"fo"), "Zm8");
+    }
+
+    #[test]
+    fn base64_decode_accepts_the_standard_alphabet() {
+        assert_eq!(base64_decode("Zm8=").as_deref(), Some(&b"fo"[..]));
+        assert_eq!(base64_decode("+/8"), base64_decode("-_8"));
+        assert_eq!(base64_decode("Zm9vY"), None);
+        assert_eq!(base64_decode("Zm9v!"), None);
+    }
+
+    #[test]
+    fn share_codes_round_trip_without_comments() {
+        let path = std::env::temp_dir().join(format!("prompt-changer-share-test-{}.toml", std::process::id()));
+        fs::write(&path, "# my prompt\n[[segment]]\ntext = '\\u'\ncolor = \"green\"\n").unwrap();
+        let code = encode(&path);
+        let _ = fs::remove_file(&path);
+        let code = code.unwrap();
+        assert!(code.starts_with(PREFIX));
+
+        // 粘贴时被折行的分享码也能解码
+        let content = decode(&format!("{}\n  {}", &code[..10], &code[10..])).unwrap();
+        assert!(!content.contains("my prompt"), "{}", content);
+        assert!(content.contains(&format!("version = {}", migrate::SPEC_VERSION)), "{}", content);
+        let spec = Spec::parse(&content, "test").unwrap();
+        assert_eq!(spec.segments[0].text, r"\u");
+        assert_eq!(spec.segments[0].color.as_deref(), Some("green"));
+    }
+
+    #[test]
+    fn decode_rejects_other_strings() {
+        for code in ["", "pc2:Zm9v", "Zm9v", "pc1:Zm9vY", "pc1://8"] {
+            assert!(decode(code).is_err(), "{}", code);
+        }
+        assert!(decode(&format!("{}{}", PREFIX, base64_encode(b"[[segment]]\nbogus = 1\n"))).is_err());
+    }
+}
@@ -0,0 +1,1685 @@
+use crate::cmd;
+use crate::color;
+use crate::dotfiles;
+use crate::elvish;
+use crate::i18n;
+use crate::inputrc;
+use crate::ksh;
+use crate::logging;
+use crate::modules;
+use crate::oils;
+use crate::output;
+use crate::plugins;
+use crate::powershell;
+use crate::prompt::{self, PromptSet, SOURCE_MARKER};
+use crate::rc::{self, FileLock, Snapshot};
+use crate::tcsh;
+use crate::terminal;
+use crate::xonsh;
+use crate::CliError;
+use dirs::home_dir;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// 代替真实家目录的根目录, 设置后所有读写都在它下面进行
+static CONFIG_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// 设置代替家目录的根目录
+pub fn set_config_root(root: &Path) -> Result<(), CliError> {
+    if !root.is_dir() {
+        return Err(CliError(
+            i18n::tr(
+                &format!("Config root {} is not a directory", root.display()),
+                &format!("配置根目录 {} 不是目录", root.display()),
+            )
+            .to_string(),
+        ));
+    }
+    CONFIG_ROOT
+        .set(root.to_path_buf())
+        .map_err(|_| CliError(i18n::tr("Config root is already set", "配置根目录已经设置过了").to_string()))
+}
+
+/// 用户指定的配置文件, 设置后代替默认的 rc 文件
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// 设置要写入的配置文件
+pub fn set_config_file(path: &Path) -> Result<(), CliError> {
+    if path.is_dir() {
+        return Err(CliError(
+            i18n::tr(
+                &format!("Config file {} is a directory", path.display()),
+                &format!("配置文件 {} 是目录", path.display()),
+            )
+            .to_string(),
+        ));
+    }
+    CONFIG_FILE
+        .set(path.to_path_buf())
+        .map_err(|_| CliError(i18n::tr("Config file is already set", "配置文件已经设置过了").to_string()))
+}
+
+/// 是否写入 /etc 下的系统级配置
+static SYSTEM_WIDE: OnceLock<bool> = OnceLock::new();
+
+/// 改为写入系统级配置, 与 --config-file 不能同时使用
+pub fn set_system_wide() -> Result<(), CliError> {
+    if CONFIG_FILE.get().is_some() {
+        return Err(CliError(i18n::tr("--system cannot be combined with --config-file", "--system 不能与 --config-file 同时使用").to_string()));
+    }
+    SYSTEM_WIDE
+        .set(true)
+        .map_err(|_| CliError(i18n::tr("System-wide mode is already set", "系统级模式已经设置过了").to_string()))
+}
+
+/// 是否写入系统级配置
+fn system_wide() -> bool {
+    SYSTEM_WIDE.get().copied().unwrap_or(false)
+}
+
+/// 系统级配置所在的根目录, 设置了 config root 时返回它
+pub fn system_root() -> PathBuf {
+    CONFIG_ROOT.get().cloned().unwrap_or_else(|| PathBuf::from("/"))
+}
+
+/// 写入真实的 /etc 之前检查是否以 root 身份运行
+pub fn check_privileges() -> Result<(), CliError> {
+    if !system_wide() || CONFIG_ROOT.get().is_some() {
+        return Ok(());
+    }
+    if is_root() {
+        Ok(())
+    } else {
+        Err(CliError(
+            i18n::tr(
+                "--system writes to /etc and must be run as root (try running it with sudo)",
+                "--system 写入 /etc, 必须以 root 身份运行(试试用 sudo 运行)",
+            )
+            .to_string(),
+        ))
+    }
+}
+
+/// 当前进程的有效用户是否是 root
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid 没有前置条件, 也不会失败
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// 当前进程的有效用户是否是 root
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// fish 是否使用 functions 目录下自动加载的函数文件
+fn uses_function_file() -> bool {
+    CONFIG_FILE.get().is_none() && !system_wide()
+}
+
+/// 提示符的安装方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallMode {
+    /// 直接写入 rc 文件中的受管理区块
+    Append,
+    /// 写入单独的文件, rc 文件中只保留一行 source
+    DropIn,
+}
+
+impl InstallMode {
+    /// 命令行中可选的安装方式
+    pub const NAMES: [&'static str; 2] = ["append", "drop-in"];
+
+    /// 从命令行参数解析安装方式
+    pub fn from_name(name: &str) -> Result<InstallMode, CliError> {
+        match name {
+            "append" => Ok(InstallMode::Append),
+            "drop-in" => Ok(InstallMode::DropIn),
+            other => Err(CliError(i18n::tr(&format!("Unknown install mode: {}", other), &format!("未知的安装方式: {}", other)).to_string())),
+        }
+    }
+}
+
+/// 用户指定的安装方式, 未指定时沿用已有的安装方式
+static INSTALL_MODE: OnceLock<InstallMode> = OnceLock::new();
+
+/// 设置安装方式
+pub fn set_install_mode(mode: InstallMode) -> Result<(), CliError> {
+    INSTALL_MODE
+        .set(mode)
+        .map_err(|_| CliError(i18n::tr("Install mode is already set", "安装方式已经设置过了").to_string()))
+}
+
+/// PowerShell 没有固定位置的系统级配置文件
+fn no_system_profile() -> CliError {
+    CliError(
+        i18n::tr(
+            "--system is not supported for PowerShell; pass the all-users profile ($PSHOME/profile.ps1) with --config-file",
+            "PowerShell 不支持 --system; 请用 --config-file 指定所有用户的配置文件($PSHOME/profile.ps1)",
+        )
+        .to_string(),
+    )
+}
+
+/// Elvish 与 Oils 没有所有用户共用的配置文件
+fn no_system_rc(shell: Shell) -> CliError {
+    let rc = if shell == Shell::Oils { "oshrc" } else { "rc.elv" };
+    CliError(
+        i18n::tr(
+            &format!("--system is not supported for {}, which only reads each user's {}", shell.display_name(), rc),
+            &format!("{} 不支持 --system, 它只读取每个用户自己的 {}", shell.display_name(), rc),
+        )
+        .to_string(),
+    )
+}
+
+/// cmd 没有所有用户共用的提示符设置
+fn no_system_prompt() -> CliError {
+    CliError(
+        i18n::tr(
+            "--system is not supported for cmd; prompt-changer only sets the current user's PROMPT variable",
+            "cmd 不支持 --system; prompt-changer 只设置当前用户的 PROMPT 变量",
+        )
+        .to_string(),
+    )
+}
+
+/// cmd 的 PROMPT 是一个环境变量, 没有可以载入 drop-in 文件的配置
+fn no_cmd_drop_in() -> CliError {
+    CliError(
+        i18n::tr(
+            "--install-mode drop-in is not supported for cmd, which keeps its prompt in the PROMPT variable",
+            "cmd 不支持 --install-mode drop-in, 它的提示符保存在 PROMPT 变量中",
+        )
+        .to_string(),
+    )
+}
+
+/// cmd 的提示符写入的是否是当前用户真实的环境变量; 指定了 config root、其他用户、--config-file 或 --dotfiles-dir 时只更新记录文件
+fn sets_user_environment() -> bool {
+    cfg!(windows) && CONFIG_ROOT.get().is_none() && USER_HOME.get().is_none() && CONFIG_FILE.get().is_none() && !dotfiles::is_active()
+}
+
+/// `--user` 或 sudo 指定的用户的家目录, 设置后代替当前用户的家目录
+static USER_HOME: OnceLock<PathBuf> = OnceLock::new();
+
+/// 改为读写另一个用户的配置, 除了自己以外需要 root 权限
+#[cfg(unix)]
+pub fn set_user(name: &str) -> Result<(), CliError> {
+    let (_, home) = passwd_entry(Lookup::Name(name)).ok_or_else(|| CliError(
+        i18n::tr(
+            &format!("Unknown user: {}", name),
+            &format!("未知的用户: {}", name),
+        )
+        .to_string(),
+    ))?;
+    // SAFETY: geteuid 没有前置条件, 也不会失败
+    let me = passwd_entry(Lookup::Uid(unsafe { libc::geteuid() })).map(|(me, _)| me);
+    if !is_root() && me.as_deref() != Some(name) {
+        return Err(CliError(
+            i18n::tr(
+                &format!("Changing {}'s prompt must be done as root (try running it with sudo)", name),
+                &format!("修改 {} 的提示符必须以 root 身份运行(试试用 sudo 运行)", name),
+            )
+            .to_string(),
+        ));
+    }
+    logging::debug("shell", format!("using {}'s home {}", name, home.display()));
+    USER_HOME.set(home).map_err(|_| CliError(i18n::tr("User is already set", "用户已经设置过了").to_string()))
+}
+
+/// 其他平台上没有其他用户的家目录可查
+#[cfg(not(unix))]
+pub fn set_user(_name: &str) -> Result<(), CliError> {
+    Err(CliError(i18n::tr("--user is only supported on Unix", "--user 只在 Unix 上支持").to_string()))
+}
+
+/// 经 sudo 运行时改为读写调用 sudo 的用户的配置, 而不是 root 的; 要改 root 的配置可以用 `--user root`
+#[cfg(unix)]
+pub fn follow_sudo_user() -> Result<(), CliError> {
+    if CONFIG_ROOT.get().is_some() || system_wide() || !is_root() {
+        return Ok(());
+    }
+    let Some(name) = std::env::var("SUDO_USER").ok().filter(|name| !name.is_empty() && name != "root") else {
+        return Ok(());
+    };
+    set_user(&name)?;
+    output::notice(i18n::tr(
+        &format!("Running under sudo; changing {}'s prompt (pass --user root for root's own)", name),
+        &format!("正在 sudo 下运行; 将修改 {} 的提示符(要修改 root 自己的, 请传入 --user root)", name),
+    ));
+    Ok(())
+}
+
+/// 其他平台上没有 sudo
+#[cfg(not(unix))]
+pub fn follow_sudo_user() -> Result<(), CliError> {
+    Ok(())
+}
+
+/// 配置文件所在的家目录, 设置了 config root 时返回它
+pub fn home() -> Result<PathBuf, CliError> {
+    match CONFIG_ROOT.get().or(USER_HOME.get()) {
+        Some(root) => Ok(root.clone()),
+        None => default_home().ok_or_else(|| CliError(i18n::tr("Failed to get home directory", "无法获取家目录").to_string())),
+    }
+}
+
+/// 用户的家目录. Git Bash 读取 $HOME 下的 .bashrc, 而 HOME 可能不是 Windows 的用户目录, 所以优先使用它
+#[cfg(windows)]
+fn default_home() -> Option<PathBuf> {
+    windows_home(std::env::var("HOME").ok().as_deref(), home_dir)
+}
+
+/// 按 HOME 的值选出 Windows 上的家目录; HOME 未设置或为空时用 `fallback`
+#[cfg(any(windows, test))]
+fn windows_home(home: Option<&str>, fallback: impl FnOnce() -> Option<PathBuf>) -> Option<PathBuf> {
+    home.filter(|home| !home.is_empty()).map(msys_to_windows).or_else(fallback)
+}
+
+/// 把 MSYS 风格的路径(`/c/Users/me`、`/cygdrive/c/Users/me`、`//server/share`)换成 Windows 路径, 其他路径不变
+#[cfg(any(windows, test))]
+fn msys_to_windows(path: &str) -> PathBuf {
+    if let Some(unc) = path.strip_prefix("//").filter(|rest| !rest.is_empty() && !rest.starts_with('/')) {
+        return PathBuf::from(format!("\\\\{}", unc.replace('/', "\\")));
+    }
+    let rest = path.strip_prefix("/cygdrive").unwrap_or(path);
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('/'), Some(drive), None | Some('/')) if drive.is_ascii_alphabetic() => {
+            PathBuf::from(format!("{}:\\{}", drive.to_ascii_uppercase(), rest.get(3..).unwrap_or_default().replace('/', "\\")))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// 用户的家目录. 在 WSL 中 HOME 可能被设成 Windows 的用户目录(/mnt/c/...), 这时改用 Linux 用户自己的家目录
+#[cfg(unix)]
+fn default_home() -> Option<PathBuf> {
+    Some(unix_home(home_dir()?, is_wsl(), passwd_home))
+}
+
+/// 在 WSL 中且 `home` 位于 /mnt 下时改用 `passwd` 查到的家目录, 查不到时仍用 `home`
+#[cfg(unix)]
+fn unix_home(home: PathBuf, wsl: bool, passwd: impl FnOnce() -> Option<PathBuf>) -> PathBuf {
+    if wsl && home.starts_with("/mnt") {
+        if let Some(linux_home) = passwd() {
+            logging::debug("shell", format!("HOME points into Windows ({}); using the Linux home {}", home.display(), linux_home.display()));
+            return linux_home;
+        }
+    }
+    home
+}
+
+/// 其他平台上的家目录
+#[cfg(not(any(unix, windows)))]
+fn default_home() -> Option<PathBuf> {
+    home_dir()
+}
+
+/// 是否运行在 WSL 中
+#[cfg(unix)]
+pub fn is_wsl() -> bool {
+    detect_wsl(std::env::var_os("WSL_DISTRO_NAME").is_some(), || fs::read_to_string("/proc/sys/kernel/osrelease").ok())
+}
+
+/// 设置了 WSL_DISTRO_NAME, 或内核版本中含有 microsoft 时是 WSL
+#[cfg(unix)]
+fn detect_wsl(distro_set: bool, osrelease: impl FnOnce() -> Option<String>) -> bool {
+    distro_set || osrelease().is_some_and(|release| release.to_ascii_lowercase().contains("microsoft"))
+}
+
+/// 密码数据库中当前用户的家目录
+#[cfg(unix)]
+fn passwd_home() -> Option<PathBuf> {
+    // SAFETY: geteuid 没有前置条件, 也不会失败
+    passwd_entry(Lookup::Uid(unsafe { libc::geteuid() })).map(|(_, home)| home)
+}
+
+/// 在密码数据库中按用户 ID 或用户名查找
+#[cfg(unix)]
+enum Lookup<'a> {
+    Uid(libc::uid_t),
+    Name(&'a str),
+}
+
+/// 密码数据库中的用户名与家目录
+#[cfg(unix)]
+fn passwd_entry(lookup: Lookup) -> Option<(String, PathBuf)> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+    let mut buf = vec![0 as libc::c_char; 16384];
+    // SAFETY: passwd 是只含整数与指针的 C 结构体, 全零是合法的初始值
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: 各指针都指向有效的缓冲区; 成功时 pw_name 与 pw_dir 指向 buf 中以 NUL 结尾的字符串
+    let rc = match lookup {
+        Lookup::Uid(uid) => unsafe { libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut result) },
+        Lookup::Name(name) => {
+            let name = CString::new(name).ok()?;
+            unsafe { libc::getpwnam_r(name.as_ptr(), &mut entry, buf.as_mut_ptr(), buf.len(), &mut result) }
+        }
+    };
+    if rc != 0 || result.is_null() || entry.pw_name.is_null() || entry.pw_dir.is_null() {
+        return None;
+    }
+    // SAFETY: 见上, buf 在此之前一直有效
+    let (name, dir) = unsafe { (CStr::from_ptr(entry.pw_name), CStr::from_ptr(entry.pw_dir)) };
+    Some((name.to_string_lossy().into_owned(), PathBuf::from(OsStr::from_bytes(dir.to_bytes()))))
+}
+
+/// 锁住本工具的状态, 同一时间只让一个实例改写配置
+pub fn lock() -> Result<FileLock, Box<dyn std::error::Error>> {
+    FileLock::acquire(&home()?.join(".cache/prompt-changer/lock"))
+}
+
+/// 支持的 shell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Fish,
+    Pwsh,
+    Ksh,
+    Tcsh,
+    /// POSIX sh: dash、busybox ash 等
+    Posix,
+    Elvish,
+    Xonsh,
+    /// Oils 的 OSH; YSH 读取另外的 yshrc
+    Oils,
+    /// Windows 的 cmd.exe, 提示符保存在 PROMPT 环境变量中
+    Cmd,
+}
+
+impl Shell {
+    /// 命令行中可选的 shell 名称
+    pub const NAMES: [&'static str; 10] = ["bash", "fish", "powershell", "ksh", "tcsh", "posix", "elvish", "xonsh", "oils", "cmd"];
+
+    /// 所有支持的 shell
+    pub const ALL: [Shell; 10] = [
+        Shell::Bash,
+        Shell::Fish,
+        Shell::Pwsh,
+        Shell::Ksh,
+        Shell::Tcsh,
+        Shell::Posix,
+        Shell::Elvish,
+        Shell::Xonsh,
+        Shell::Oils,
+        Shell::Cmd,
+    ];
+
+    /// 从命令行参数解析 shell
+    pub fn from_name(name: &str) -> Result<Shell, CliError> {
+        match name {
+            "bash" => Ok(Shell::Bash),
+            "fish" => Ok(Shell::Fish),
+            "powershell" | "pwsh" => Ok(Shell::Pwsh),
+            "ksh" | "mksh" => Ok(Shell::Ksh),
+            "tcsh" | "csh" => Ok(Shell::Tcsh),
+            "posix" | "sh" | "dash" | "ash" => Ok(Shell::Posix),
+            "elvish" => Ok(Shell::Elvish),
+            "xonsh" => Ok(Shell::Xonsh),
+            "oils" | "osh" => Ok(Shell::Oils),
+            // YSH 读取 yshrc, 语法也与 OSH 不同; 不能把它当作 OSH 写入 oshrc
+            "ysh" => Err(CliError(
+                i18n::tr(
+                    "YSH is not supported: it reads yshrc instead of oshrc; use --shell oils to configure OSH",
+                    "不支持 YSH: 它读取 yshrc 而不是 oshrc; 用 --shell oils 配置 OSH",
+                )
+                .to_string(),
+            )),
+            "cmd" => Ok(Shell::Cmd),
+            other => Err(CliError(i18n::tr(&format!("Unsupported shell: {}", other), &format!("不支持的 shell: {}", other)).to_string())),
+        }
+    }
+
+    /// 用于输出的名称
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Shell::Bash => "Bash",
+            Shell::Fish => "Fish",
+            Shell::Pwsh => "PowerShell",
+            Shell::Ksh => "ksh",
+            Shell::Tcsh => "tcsh",
+            Shell::Posix => "sh",
+            Shell::Elvish => "Elvish",
+            Shell::Xonsh => "xonsh",
+            Shell::Oils => "Oils",
+            Shell::Cmd => "cmd",
+        }
+    }
+
+    /// 该 shell 的配置文件路径; 指定了 `--dotfiles-dir` 时, 默认位置的配置文件换成仓库中对应的源文件
+    pub fn config_path(self) -> Result<PathBuf, CliError> {
+        let (path, source) = self.live_config_path()?;
+        let (path, source) = if source == "the default location" && dotfiles::is_active() {
+            (dotfiles::source_path(path)?, "--dotfiles-dir")
+        } else {
+            (path, source)
+        };
+        logging::debug("shell", format!("{} config file: {} (from {})", self.display_name(), path.display(), source));
+        Ok(path)
+    }
+
+    /// shell 启动时读取的配置文件, 以及它的路径从何而来
+    fn live_config_path(self) -> Result<(PathBuf, &'static str), CliError> {
+        Ok(if let Some(path) = CONFIG_FILE.get() {
+            (path.clone(), "--config-file")
+        } else if system_wide() {
+            let path = match self {
+                Shell::Bash => system_root().join("etc/bash.bashrc"),
+                Shell::Fish => system_root().join("etc/fish/config.fish"),
+                Shell::Pwsh => return Err(no_system_profile()),
+                Shell::Ksh => system_root().join("etc/ksh.kshrc"),
+                Shell::Tcsh => system_root().join("etc/csh.cshrc"),
+                Shell::Posix => system_root().join("etc/profile"),
+                Shell::Elvish | Shell::Oils => return Err(no_system_rc(self)),
+                Shell::Cmd => return Err(no_system_prompt()),
+                Shell::Xonsh => system_root().join("etc/xonsh/xonshrc"),
+            };
+            (path, "--system")
+        } else {
+            let home = home()?;
+            // config root 或其他用户的家目录中不看当前进程的 $ENV
+            if matches!(self, Shell::Ksh | Shell::Posix) && CONFIG_ROOT.get().is_none() && USER_HOME.get().is_none() {
+                if let Some(path) = ksh::env_path(&home) {
+                    return Ok((path, "$ENV"));
+                }
+            }
+            let path = match self {
+                Shell::Bash => home.join(".bashrc"),
+                Shell::Fish => home.join(".config/fish/config.fish"),
+                Shell::Pwsh => powershell::profile_path(&home, CONFIG_ROOT.get().is_none()),
+                Shell::Ksh => home.join(".kshrc"),
+                Shell::Tcsh => tcsh::rc_path(&home),
+                // 没有 $ENV 时交互式的 sh 不读取任何文件, 只能写入登录时读取的 .profile
+                Shell::Posix => home.join(".profile"),
+                Shell::Elvish => elvish::rc_path(&home),
+                Shell::Xonsh => xonsh::rc_path(&home),
+                Shell::Oils => oils::rc_path(&home),
+                Shell::Cmd => cmd::record_path(&home),
+            };
+            (path, "the default location")
+        })
+    }
+
+    /// fish 自动加载的 fish_prompt 函数文件
+    pub fn fish_function_path() -> Result<PathBuf, CliError> {
+        let home = home()?;
+        dotfiles::source_path(home.join(".config/fish/functions/fish_prompt.fish"))
+    }
+
+    /// drop-in 方式下存放提示符的文件; 指定了 `--dotfiles-dir` 时是仓库中对应的源文件
+    pub fn drop_in_path(self) -> Result<PathBuf, CliError> {
+        dotfiles::source_path(self.live_drop_in_path()?)
+    }
+
+    /// drop-in 方式下 shell 载入的文件
+    fn live_drop_in_path(self) -> Result<PathBuf, CliError> {
+        if system_wide() {
+            return Ok(match self {
+                Shell::Bash => system_root().join("etc/profile.d/prompt-changer.sh"),
+                Shell::Fish => system_root().join("etc/fish/conf.d/prompt-changer.fish"),
+                Shell::Pwsh => return Err(no_system_profile()),
+                Shell::Ksh => system_root().join("etc/prompt-changer/prompt.ksh"),
+                Shell::Tcsh => system_root().join("etc/prompt-changer/prompt.tcsh"),
+                Shell::Posix => system_root().join("etc/prompt-changer/prompt.sh"),
+                Shell::Elvish | Shell::Oils => return Err(no_system_rc(self)),
+                Shell::Cmd => return Err(no_system_prompt()),
+                Shell::Xonsh => system_root().join("etc/prompt-changer/prompt.xsh"),
+            });
+        }
+        let home = home()?;
+        Ok(match self {
+            Shell::Bash => home.join(".config/prompt-changer/prompt.bash"),
+            Shell::Fish => home.join(".config/fish/conf.d/prompt.fish"),
+            Shell::Pwsh => home.join(".config/prompt-changer/prompt.ps1"),
+            Shell::Ksh => home.join(".config/prompt-changer/prompt.ksh"),
+            Shell::Tcsh => home.join(".config/prompt-changer/prompt.tcsh"),
+            Shell::Posix => home.join(".config/prompt-changer/prompt.sh"),
+            Shell::Elvish => home.join(".config/prompt-changer/prompt.elv"),
+            Shell::Xonsh => home.join(".config/prompt-changer/prompt.xsh"),
+            Shell::Oils => home.join(".config/prompt-changer/prompt.osh"),
+            // cmd 不能载入 drop-in 文件, 这个路径不会被写入
+            Shell::Cmd => home.join(".config/prompt-changer/prompt.cmd"),
+        })
+    }
+
+    /// 本次使用的安装方式: 命令行指定的优先, 否则沿用已有的 drop-in 文件
+    pub fn install_mode(self) -> Result<InstallMode, CliError> {
+        if let Some(mode) = INSTALL_MODE.get() {
+            return Ok(*mode);
+        }
+        let detected = match self {
+            Shell::Bash | Shell::Pwsh | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils => {
+                let content = rc::read_text(&self.config_path()?).unwrap_or_default();
+                rc::managed_block(&content) == Some(self.source_line()?)
+            }
+            // 系统级的 fish 提示符默认放在 /etc/fish/conf.d 中
+            Shell::Fish => system_wide() || self.drop_in_path()?.exists(),
+            Shell::Cmd => false,
+        };
+        let mode = if detected { InstallMode::DropIn } else { InstallMode::Append };
+        logging::debug("shell", format!("{} install mode: {:?} (detected)", self.display_name(), mode));
+        Ok(mode)
+    }
+
+    /// 这个 shell 的提示符不支持的设置, 每条一句. 生成代码的函数本身不输出提示, 由写入或导出提示符的命令调用一次
+    pub fn notice_unsupported(self, prompts: &PromptSet) {
+        let notices = match self {
+            Shell::Bash if prompts.transient.is_some() => vec![i18n::tr(
+                "bash cannot redraw a prompt after the command runs, so `transient` only applies to fish and PowerShell",
+                "bash 无法在命令运行后重画提示符, 因此 `transient` 只对 fish 和 PowerShell 有效",
+            )
+            .to_string()],
+            Shell::Bash | Shell::Fish => Vec::new(),
+            Shell::Pwsh => powershell::notices(prompts),
+            Shell::Ksh | Shell::Posix => ksh::notices(self, prompts),
+            Shell::Tcsh => tcsh::notices(prompts),
+            Shell::Elvish => elvish::notices(prompts),
+            Shell::Xonsh => xonsh::notices(prompts),
+            Shell::Oils => oils::notices(prompts),
+            Shell::Cmd => cmd::notices(prompts),
+        };
+        for notice in notices {
+            output::notice(&notice);
+        }
+    }
+
+    /// 更新该 shell 的提示符
+    pub fn update_prompt(self, new_prompt: &PromptSet) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        check_privileges()?;
+        let mode = self.install_mode()?;
+        match self {
+            Shell::Bash => {
+                ensure_login_loads_bashrc()?;
+                update_block_prompt(self, &bash_block_body(new_prompt), mode)
+            }
+            Shell::Fish => update_fish_prompt(new_prompt, mode),
+            Shell::Pwsh => update_block_prompt(self, &powershell::block_body(new_prompt), mode),
+            Shell::Ksh | Shell::Posix => update_block_prompt(self, &ksh::block_body(self, new_prompt), mode),
+            Shell::Tcsh => update_block_prompt(self, &tcsh::block_body(new_prompt), mode),
+            Shell::Elvish => update_block_prompt(self, &elvish::block_body(new_prompt), mode),
+            Shell::Xonsh => update_block_prompt(self, &xonsh::block_body(new_prompt), mode),
+            Shell::Oils => update_block_prompt(self, &oils::block_body(new_prompt), mode),
+            Shell::Cmd => {
+                if mode == InstallMode::DropIn {
+                    return Err(Box::new(no_cmd_drop_in()));
+                }
+                let path = update_block_prompt(self, &cmd::block_body(new_prompt), mode)?;
+                if sets_user_environment() {
+                    cmd::store(Some(&cmd::translate(new_prompt)))?;
+                }
+                Ok(path)
+            }
+        }
+    }
+
+    /// 写入 `new_prompt` 时会改动的文件; 为空时配置中已是这个提示符, 再次写入不会改动任何文件
+    pub fn pending_changes(self, new_prompt: &PromptSet) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let mode = self.install_mode()?;
+        let config_path = self.config_path()?;
+        let drop_in = self.drop_in_path()?;
+        let mut changed = Vec::new();
+        if self == Shell::Bash && !login_loads_bashrc()? {
+            changed.push(bash_login_file()?);
+        }
+        let body = match self {
+            Shell::Bash => bash_block_body(new_prompt),
+            Shell::Fish => prompt::fish_function(new_prompt).trim_end().to_string(),
+            Shell::Pwsh => powershell::block_body(new_prompt),
+            Shell::Ksh | Shell::Posix => ksh::block_body(self, new_prompt),
+            Shell::Tcsh => tcsh::block_body(new_prompt),
+            Shell::Elvish => elvish::block_body(new_prompt),
+            Shell::Xonsh => xonsh::block_body(new_prompt),
+            Shell::Oils => oils::block_body(new_prompt),
+            Shell::Cmd => cmd::block_body(new_prompt),
+        };
+        // 按写入时的方式比较区块, 忽略换行的差别
+        let has_block = |path: &Path, body: &str| {
+            rc::managed_block(&rc::read_text(path).unwrap_or_default()) == rc::managed_block(&rc::replace_block("", body))
+        };
+        match mode {
+            InstallMode::DropIn => {
+                if !has_block(&drop_in, &body) {
+                    changed.push(drop_in);
+                }
+                if self != Shell::Fish && !has_block(&config_path, &self.source_line()?) {
+                    changed.push(config_path);
+                }
+            }
+            InstallMode::Append => {
+                if self == Shell::Fish && uses_function_file() {
+                    let function_path = Shell::fish_function_path()?;
+                    if fs::read_to_string(&function_path).ok() != Some(prompt::fish_function(new_prompt)) {
+                        changed.push(function_path);
+                    }
+                } else if !has_block(&config_path, &body) {
+                    changed.push(config_path);
+                }
+                if drop_in.exists() {
+                    changed.push(drop_in);
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// 写入提示符时可能改动的文件
+    pub fn touched_files(self) -> Result<Vec<PathBuf>, CliError> {
+        let mut files = vec![self.config_path()?, self.drop_in_path()?];
+        if self == Shell::Bash && login_shell_by_default() {
+            files.push(bash_login_file()?);
+        }
+        if self == Shell::Fish && uses_function_file() {
+            let function_path = Shell::fish_function_path()?;
+            files.push(function_path.with_extension("fish.bak"));
+            files.push(function_path);
+        }
+        Ok(files)
+    }
+
+    /// 记下写入时可能改动的文件的内容
+    pub fn snapshot(self) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        Ok(Snapshot::take(&self.touched_files()?)?)
+    }
+
+    /// 本工具写入过的、现在存在的文件
+    pub fn managed_files(self) -> Result<Vec<PathBuf>, CliError> {
+        Ok(self
+            .touched_files()?
+            .into_iter()
+            .filter(|path| path.exists() && path.extension().is_none_or(|ext| ext != "bak"))
+            .collect())
+    }
+
+    /// 用 `bash -n`、`fish --no-execute`、`ksh -n`、`tcsh -n`、`sh -n`、`osh -n`、xonsh 或 PowerShell 的解析器检查文件的语法, 有错时返回 shell 报告的错误
+    pub fn syntax_check(self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = match self {
+            // edit: 模块只在交互式的 Elvish 中存在, `elvish -compileonly` 会把提示符的赋值报为错误
+            Shell::Elvish => return Ok(()),
+            // 写入的只是 PROMPT 的值, 没有脚本可以检查
+            Shell::Cmd => return Ok(()),
+            Shell::Bash => Command::new("bash"),
+            Shell::Fish => Command::new("fish"),
+            Shell::Pwsh => powershell::syntax_check_command(path),
+            Shell::Ksh => Command::new("ksh"),
+            Shell::Tcsh => Command::new("tcsh"),
+            Shell::Posix => Command::new("sh"),
+            Shell::Xonsh => Command::new("xonsh"),
+            Shell::Oils => Command::new("osh"),
+        };
+        match self {
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Oils => cmd.arg("-n").arg(path),
+            Shell::Fish => cmd.arg("--no-execute").arg(path),
+            // xonsh 没有只检查语法的选项, 用它的 compilex 编译文件而不执行
+            Shell::Xonsh => {
+                let path = xonsh::quote(&path.display().to_string());
+                cmd.args(["--no-rc", "-c", &format!("compilex(open({}).read(), filename={})", path, path)])
+            }
+            Shell::Pwsh | Shell::Elvish | Shell::Cmd => &mut cmd,
+        };
+        logging::debug("shell", format!("checking the syntax of {} with {:?}", path.display(), cmd.get_program()));
+        let out = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Box::new(CliError(String::from_utf8_lossy(&out.stderr).trim_end().to_string())))
+        }
+    }
+
+    /// 在已打开的终端中载入新提示符的命令
+    pub fn reload_command(self) -> Result<String, CliError> {
+        match self {
+            Shell::Bash => Ok(format!("source {}", single_quote(&self.live_config_path()?.0.display().to_string()))),
+            Shell::Fish => Ok("exec fish".to_string()),
+            Shell::Elvish => Ok("exec elvish".to_string()),
+            Shell::Pwsh => Ok(". $PROFILE".to_string()),
+            Shell::Ksh | Shell::Posix | Shell::Oils => Ok(format!(". {}", single_quote(&self.live_config_path()?.0.display().to_string()))),
+            Shell::Tcsh => Ok(format!("source {}", single_quote(&self.live_config_path()?.0.display().to_string()))),
+            Shell::Xonsh => Ok(format!("source {}", xonsh::quote(&self.live_config_path()?.0.display().to_string()))),
+            // 已打开的 cmd 窗口不会读取新的环境变量, 用 prompt 命令直接设置
+            Shell::Cmd => {
+                let content = rc::read_text(&self.live_config_path()?.0).unwrap_or_default();
+                rc::managed_block(&content)
+                    .and_then(|body| body.lines().find_map(|l| l.strip_prefix("PROMPT=")).map(|value| format!("prompt {}", value)))
+                    .ok_or_else(|| CliError(i18n::tr("No cmd prompt has been written", "还没有写入 cmd 提示符").to_string()))
+            }
+        }
+    }
+
+    /// 启动 shell 载入写入的配置并渲染一次提示符, 配置出错时返回 shell 报告的错误
+    pub fn verify_startup(self, written: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let file = match self {
+            Shell::Bash | Shell::Pwsh | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => self.config_path()?,
+            Shell::Fish => written.to_path_buf(),
+        };
+        let mut cmd = match self {
+            // 以交互方式启动, 以免 .bashrc 开头的 `[ -z "$PS1" ] && return` 跳过提示符
+            Shell::Bash => {
+                let mut cmd = Command::new("bash");
+                cmd.args(["--norc", "--noprofile", "-i", "-c", r#"source "$1"; printf '%s' "${PS1@P}""#, "bash"]);
+                cmd.arg(&file);
+                cmd
+            }
+            Shell::Fish => {
+                let mut cmd = Command::new("fish");
+                cmd.args(["--no-config", "-c", "source $argv[1]; and fish_prompt"]);
+                cmd.arg(&file);
+                cmd
+            }
+            Shell::Pwsh => powershell::startup_command(&file),
+            // 与语法检查一样, 非交互的 Elvish 无法载入设置 edit:prompt 的配置
+            Shell::Elvish => return Ok(()),
+            // cmd 启动时才从注册表读取 PROMPT, 值本身不会出错
+            Shell::Cmd => return Ok(()),
+            // ksh、sh 与 OSH 在显示时才展开 PS1, 这里只检查载入配置文件时的错误
+            Shell::Ksh | Shell::Posix | Shell::Oils => {
+                let name = match self {
+                    Shell::Ksh => "ksh",
+                    Shell::Oils => "osh",
+                    _ => "sh",
+                };
+                let mut cmd = Command::new(name);
+                cmd.args(["-c", r#". "$1""#, name]);
+                cmd.arg(&file);
+                cmd
+            }
+            // -f: 不读取用户自己的 .tcshrc
+            Shell::Tcsh => {
+                let mut cmd = Command::new("tcsh");
+                cmd.args(["-f", "-c", &format!("source {}", single_quote(&file.display().to_string()))]);
+                cmd
+            }
+            // --no-rc: 不读取用户自己的 .xonshrc; 模板在显示时才展开, 这里只检查载入配置文件时的错误
+            Shell::Xonsh => {
+                let mut cmd = Command::new("xonsh");
+                cmd.args(["--no-rc", "-c", &format!("source {}", xonsh::quote(&file.display().to_string()))]);
+                cmd
+            }
+        };
+        logging::debug("shell", format!("starting {} to load {}", self.display_name().to_ascii_lowercase(), file.display()));
+        let out = cmd
+            .env("HOME", home()?)
+            .env("HISTFILE", "/dev/null")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()?;
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let errors: Vec<&str> = match self {
+            // 交互式 bash 没有终端时会抱怨作业控制, 只看出自配置文件的错误
+            Shell::Bash => {
+                let path = file.display().to_string();
+                stderr.lines().filter(|line| line.trim_start_matches("bash: ").starts_with(&path)).collect()
+            }
+            Shell::Fish | Shell::Pwsh | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => stderr.lines().collect(),
+        };
+        if errors.is_empty() && (self == Shell::Bash || out.status.success()) {
+            return Ok(());
+        }
+        let errors = errors.join("\n");
+        Err(Box::new(CliError(
+            i18n::tr(
+                &format!("{} reported errors while loading {}:\n{}", self.display_name(), file.display(), errors.trim_end()),
+                &format!("{} 载入 {} 时报告了错误:\n{}", self.display_name(), file.display(), errors.trim_end()),
+            )
+            .to_string(),
+        )))
+    }
+
+    /// 删除本工具写入的所有内容, 恢复 shell 的默认提示符
+    pub fn reset_prompt(self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        check_privileges()?;
+        let mut changes = Vec::new();
+        let config_path = self.config_path()?;
+        if rc::remove_block_from(&config_path)? {
+            changes.push(
+                i18n::tr(
+                    &format!("Removed the prompt-changer block from {}", config_path.display()),
+                    &format!("已从 {} 中删除 prompt-changer 区块", config_path.display()),
+                )
+                .to_string(),
+            );
+            if self == Shell::Cmd && sets_user_environment() {
+                cmd::store(None)?;
+                changes.push(i18n::tr("Removed your PROMPT environment variable", "已删除你的 PROMPT 环境变量").to_string());
+            }
+        }
+        let drop_in = self.drop_in_path()?;
+        if drop_in.exists() {
+            fs::remove_file(&drop_in)?;
+            changes.push(i18n::tr(&format!("Deleted {}", drop_in.display()), &format!("已删除 {}", drop_in.display())).to_string());
+        }
+        if self == Shell::Fish && uses_function_file() {
+            let function_path = Shell::fish_function_path()?;
+            let generated = fs::read_to_string(&function_path)
+                .map(|content| content.contains(SOURCE_MARKER))
+                .unwrap_or(false);
+            if generated {
+                fs::remove_file(&function_path)?;
+                changes.push(i18n::tr(&format!("Deleted {}", function_path.display()), &format!("已删除 {}", function_path.display())).to_string());
+                let backup = function_path.with_extension("fish.bak");
+                if backup.exists() {
+                    fs::rename(&backup, &function_path)?;
+                    changes.push(
+                        i18n::tr(
+                            &format!("Restored your previous fish_prompt from {}", backup.display()),
+                            &format!("已从 {} 恢复你原来的 fish_prompt", backup.display()),
+                        )
+                        .to_string(),
+                    );
+                }
+            }
+        }
+        if self == Shell::Bash && inputrc::remove()? {
+            let path = inputrc::path()?;
+            changes.push(
+                i18n::tr(
+                    &format!("Removed the prompt-changer readline settings from {}", path.display()),
+                    &format!("已从 {} 中删除 prompt-changer 的 readline 设置", path.display()),
+                )
+                .to_string(),
+            );
+        }
+        Ok(changes)
+    }
+
+    /// 设置提示符变量的一行, 第一个捕获组是赋值的右侧; 用于 bash、ksh、sh、tcsh、xonsh 与 cmd 的记录文件
+    pub fn prompt_assignment_pattern(self) -> &'static str {
+        match self {
+            Shell::Cmd => r"^PROMPT=(.*)$",
+            Shell::Tcsh => r"^\s*set\s+prompt\s*=\s*(.*)$",
+            Shell::Xonsh => r"^\s*\$PROMPT\s*=\s*(.*)$",
+            _ => r"^\s*(?:export\s+)?PS1=(.*)$",
+        }
+    }
+
+    /// 旧版本追加到配置文件中的提示符行, 第一个捕获组是引号内的提示符
+    pub fn legacy_line_pattern(self) -> &'static str {
+        match self {
+            Shell::Bash => r"^PS1='(.*)'$",
+            Shell::Fish => r"^\s*set\s+-gx\s+fish_prompt\s+'(.*)'$",
+            // PowerShell 没有旧版本写入的行; 只返回固定文本的单行 prompt 函数可以同样迁移
+            Shell::Pwsh => r"^\s*function\s+(?:global:)?prompt\s*\{\s*'([^']*)'\s*\}\s*$",
+            // 其他工具或手写的单引号 PS1 中可能有 ksh 特有的展开, 只迁移不含 `$` 的
+            Shell::Ksh | Shell::Posix | Shell::Oils => r"^\s*(?:export\s+)?PS1='([^'$]*)'$",
+            // 同样只迁移不含 tcsh 转义的纯文字提示符
+            Shell::Tcsh => r"^\s*set\s+prompt\s*=\s*'([^'%!]*)'$",
+            // 与 PowerShell 一样, 只迁移只输出固定文本的闭包
+            Shell::Elvish => r"^\s*set\s+edit:prompt\s*=\s*\{\s*put\s+'([^']*)'\s*\}\s*$",
+            // 只迁移不含字段与转义的纯文字模板
+            Shell::Xonsh => r"^\s*\$PROMPT\s*=\s*'([^'{}\\]*)'\s*$",
+            // 记录文件只由本工具写入, 只认出不含代码的纯文字提示符
+            Shell::Cmd => r"^PROMPT=([^$%]*)$",
+        }
+    }
+
+    /// 定义提示符函数(PowerShell)或闭包(Elvish)的第一行; 其余 shell 见 [`Shell::prompt_assignment_pattern`]
+    pub fn prompt_function_pattern(self) -> &'static str {
+        match self {
+            Shell::Elvish => r"^\s*set\s+edit:prompt\s*=\s*\{",
+            _ => r"(?i)^\s*function\s+(?:global:)?prompt\s*(\{|$)",
+        }
+    }
+
+    /// fish 启动时读取的配置文件: config.fish 与 conf.d 下的文件
+    pub fn fish_config_sources() -> Result<Vec<PathBuf>, CliError> {
+        let config_path = Shell::Fish.config_path()?;
+        let mut sources = vec![config_path.clone()];
+        if CONFIG_FILE.get().is_some() {
+            return Ok(sources);
+        }
+        if let Some(conf_d) = config_path.parent().map(|p| p.join("conf.d")) {
+            if let Ok(entries) = fs::read_dir(conf_d) {
+                let mut extra: Vec<PathBuf> = entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "fish"))
+                    .collect();
+                extra.sort();
+                sources.extend(extra);
+            }
+        }
+        logging::debug("shell", format!("fish config sources: {:?}", sources));
+        Ok(sources)
+    }
+
+    /// 找到当前生效的提示符定义, 包括不是本工具写入的
+    pub fn locate_prompt(self) -> Result<Option<ConfiguredPrompt>, Box<dyn std::error::Error>> {
+        match self {
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Xonsh | Shell::Oils | Shell::Cmd => locate_sh_prompt(self),
+            Shell::Fish => locate_fish_prompt(),
+            Shell::Pwsh | Shell::Elvish => locate_function_prompt(self),
+        }
+    }
+
+    /// 读取本工具写入的提示符, 不论它是否被其他定义覆盖
+    pub fn managed_prompt(self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match self {
+            Shell::Bash => {
+                let path = match self.install_mode()? {
+                    InstallMode::Append => self.config_path()?,
+                    InstallMode::DropIn => self.drop_in_path()?,
+                };
+                let content = rc::read_text(&path).unwrap_or_default();
+                let re = Regex::new(r"^\s*PS1=(.*)$")?;
+                Ok(rc::managed_block(&content)
+                    .and_then(|body| body.lines().find_map(|l| re.captures(l).map(|caps| unquote(caps[1].trim())))))
+            }
+            Shell::Fish => {
+                let block_file = match self.install_mode()? {
+                    InstallMode::DropIn => Some(self.drop_in_path()?),
+                    InstallMode::Append if !uses_function_file() => Some(self.config_path()?),
+                    InstallMode::Append => None,
+                };
+                let content = match block_file {
+                    Some(path) => rc::managed_block(&rc::read_text(&path).unwrap_or_default()).unwrap_or_default(),
+                    None => fs::read_to_string(Shell::fish_function_path()?).unwrap_or_default(),
+                };
+                Ok(content.lines().find_map(|l| l.strip_prefix(SOURCE_MARKER)).map(str::to_string))
+            }
+            Shell::Pwsh | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Elvish | Shell::Xonsh | Shell::Oils | Shell::Cmd => {
+                let path = match self.install_mode()? {
+                    InstallMode::Append => self.config_path()?,
+                    InstallMode::DropIn => self.drop_in_path()?,
+                };
+                let content = rc::managed_block(&rc::read_text(&path).unwrap_or_default()).unwrap_or_default();
+                Ok(content.lines().find_map(|l| l.strip_prefix(SOURCE_MARKER)).map(str::to_string))
+            }
+        }
+    }
+
+    /// 使用 --config-file 时, 检查该文件看起来是否是这个 shell 的脚本
+    pub fn check_config_file(self) -> Result<(), CliError> {
+        let path = match CONFIG_FILE.get() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(CliError(
+                i18n::tr(
+                    &format!("Cannot read {}: {}", path.display(), err),
+                    &format!("无法读取 {}: {}", path.display(), err),
+                )
+                .to_string(),
+            )),
+        };
+        let not_script = |reason: String| {
+            CliError(
+                i18n::tr(
+                    &format!("{} does not look like a {} script: {}", path.display(), self.display_name(), reason),
+                    &format!("{} 看起来不是 {} 脚本: {}", path.display(), self.display_name(), reason),
+                )
+                .to_string(),
+            )
+        };
+        if bytes.contains(&0) {
+            return Err(not_script(i18n::tr("it contains binary data", "它包含二进制数据").to_string()));
+        }
+        let content = String::from_utf8_lossy(&bytes);
+        let others: Vec<Shell> = Shell::ALL.into_iter().filter(|other| *other != self).collect();
+
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        if others.iter().any(|other| other.extensions().contains(&ext)) {
+            return Err(not_script(
+                i18n::tr(
+                    &format!("the .{} extension belongs to another shell", ext),
+                    &format!(".{} 扩展名属于另一个 shell", ext),
+                )
+                .to_string(),
+            ));
+        }
+        if let Some(shebang) = content.lines().next().filter(|l| l.starts_with("#!")) {
+            let names = |shell: Shell| shell.interpreters().iter().any(|name| shebang.contains(name));
+            if !names(self) && others.iter().any(|other| names(*other)) {
+                return Err(not_script(
+                    i18n::tr(
+                        &format!("its shebang `{}` is for another shell", shebang),
+                        &format!("它的 shebang `{}` 属于另一个 shell", shebang),
+                    )
+                    .to_string(),
+                ));
+            }
+        }
+        let own = Regex::new(self.syntax_pattern()).unwrap();
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim_start().starts_with('#')).collect();
+        if !lines.iter().any(|l| own.is_match(l)) {
+            for other in others {
+                let foreign = Regex::new(other.syntax_pattern()).unwrap();
+                if let Some(line) = lines.iter().find(|l| foreign.is_match(l)) {
+                    return Err(not_script(
+                        i18n::tr(
+                            &format!("it contains {} syntax such as `{}`", other.display_name(), line.trim()),
+                            &format!("它包含 {} 语法, 例如 `{}`", other.display_name(), line.trim()),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 该 shell 的脚本常用的扩展名
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Shell::Bash => &["sh", "bash", "zsh"],
+            Shell::Fish => &["fish"],
+            Shell::Pwsh => &["ps1"],
+            Shell::Ksh => &["ksh", "mksh"],
+            Shell::Tcsh => &["tcsh", "csh"],
+            // .sh 也是 bash 脚本常用的扩展名, 不能据此区分
+            Shell::Posix => &[],
+            Shell::Elvish => &["elv"],
+            Shell::Xonsh => &["xsh"],
+            Shell::Oils => &["osh"],
+            Shell::Cmd => &["cmd", "bat"],
+        }
+    }
+
+    /// shebang 中表示该 shell 的解释器名称
+    fn interpreters(self) -> &'static [&'static str] {
+        match self {
+            Shell::Bash => &["bash", "/sh"],
+            Shell::Fish => &["fish"],
+            Shell::Pwsh => &["pwsh", "powershell"],
+            Shell::Ksh => &["ksh"],
+            Shell::Tcsh => &["csh"],
+            Shell::Posix => &["/sh", "dash", "/ash"],
+            Shell::Elvish => &["elvish"],
+            Shell::Xonsh => &["xonsh"],
+            Shell::Oils => &["osh", "oils-for-unix"],
+            Shell::Cmd => &["cmd.exe"],
+        }
+    }
+
+    /// 只在该 shell 中出现的典型语法
+    fn syntax_pattern(self) -> &'static str {
+        match self {
+            Shell::Bash | Shell::Ksh | Shell::Posix | Shell::Oils => r"^\s*(export\s+\w+=|\w+=|fi\s*$|then\s*$|esac\s*$|\w+\s*\(\)\s*\{?)",
+            Shell::Fish => r"^\s*(set\s+-[a-zA-Z]+\s|end\s*$|function\s+\S+(\s+--?\S+)*\s*$|abbr\s)",
+            Shell::Pwsh => r"^\s*(\$[\w:]+\s*=|function\s+[\w:-]+\s*\{|[A-Z]\w+-[A-Z]\w+\s|Import-Module\s|param\s*\()",
+            Shell::Elvish => r"^\s*(var\s+[\w-]+\s*=|set\s+edit:|fn\s+[\w-]+\s*\{|use\s+[\w/]+\s*$)",
+            Shell::Tcsh => r"^\s*(set\s+\w+\s*=|setenv\s|endif\s*$|endsw\s*$|breaksw\s*$|if\s*\(.*\)\s*then\s*$)",
+            Shell::Xonsh => r"^\s*(\$[A-Z_]+\s*=|xontrib\s+load\s|aliases\[)",
+            Shell::Cmd => r"(?i)^\s*@?(echo\s+off\s*$|rem\s|goto\s|setlocal\b|set\s+/[ap]\s)",
+        }
+    }
+
+    /// drop-in 方式下配置文件受管理区块中载入 drop-in 文件的一行
+    fn source_line(self) -> Result<String, CliError> {
+        match self {
+            Shell::Pwsh => {
+                let path = self.live_drop_in_path()?;
+                let shown = match path.strip_prefix(home()?) {
+                    Ok(rel) => format!("\"$HOME/{}\"", rel.display()),
+                    Err(_) => powershell::quote(&path.display().to_string()),
+                };
+                Ok(format!("if (Test-Path {0}) {{ . {0} }}", shown))
+            }
+            Shell::Bash | Shell::Ksh | Shell::Tcsh | Shell::Posix | Shell::Oils => sh_source_line(self),
+            // Elvish 没有 source, 读取文件后用 eval 执行
+            Shell::Elvish => {
+                let path = self.live_drop_in_path()?;
+                let shown = match path.strip_prefix(home()?) {
+                    Ok(rel) => format!("~/{}", rel.display()),
+                    Err(_) => elvish::quote(&path.display().to_string()),
+                };
+                Ok(format!("use path; if (path:is-regular {0}) {{ eval (slurp < {0}) }}", shown))
+            }
+            Shell::Xonsh => {
+                let path = self.live_drop_in_path()?;
+                let shown = match path.strip_prefix(home()?) {
+                    Ok(rel) => format!("p'~/{}'", rel.display()),
+                    Err(_) => format!("p{}", xonsh::quote(&path.display().to_string())),
+                };
+                Ok(format!("if {0}.expanduser().is_file(): source {0}", shown))
+            }
+            Shell::Fish => Err(CliError(i18n::tr("fish loads conf.d files by itself", "fish 会自己载入 conf.d 中的文件").to_string())),
+            Shell::Cmd => Err(no_cmd_drop_in()),
+        }
+    }
+
+    /// 读取当前生效的 bash 风格提示符字符串
+    pub fn read_configured_prompt(self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.locate_prompt()?.and_then(|found| found.prompt))
+    }
+}
+
+/// 配置文件中找到的提示符定义
+pub struct ConfiguredPrompt {
+    /// bash 风格的提示符字符串, 定义是 fish 代码时为 None
+    pub prompt: Option<String>,
+    /// 定义的原文
+    pub raw: String,
+    /// 定义所在的文件
+    pub path: PathBuf,
+    /// 定义开始的行号(从 1 开始)
+    pub line: usize,
+    /// 是否由本工具写入
+    pub managed: bool,
+}
+
+/// 找到 PowerShell 配置文件中最后一个 prompt 函数(Elvish 是 edit:prompt 闭包), drop-in 方式下跟随载入行进入 drop-in 文件
+fn locate_function_prompt(shell: Shell) -> Result<Option<ConfiguredPrompt>, Box<dyn std::error::Error>> {
+    let path = shell.config_path()?;
+    let found = locate_prompt_function(shell, &path)?;
+    if found.is_none() && shell.install_mode()? == InstallMode::DropIn {
+        return locate_prompt_function(shell, &shell.drop_in_path()?);
+    }
+    Ok(found)
+}
+
+/// 找到文件中最后一个 `function prompt` 定义(Elvish 是 `set edit:prompt`)
+fn locate_prompt_function(shell: Shell, path: &Path) -> Result<Option<ConfiguredPrompt>, Box<dyn std::error::Error>> {
+    logging::debug("shell", format!("looking for a prompt function in {}", path.display()));
+    let content = match rc::read_text(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Box::new(err)),
+    };
+    let definition = Regex::new(shell.prompt_function_pattern())?;
+    let legacy = Regex::new(shell.legacy_line_pattern())?;
+    let lines: Vec<&str> = content.lines().collect();
+    let Some(last) = lines.iter().rposition(|l| definition.is_match(l)) else {
+        return Ok(None);
+    };
+    // 区块中之后的定义是按主机名切换的变体, 第一个是默认提示符
+    let start = match rc::find_block(&content).filter(|b| b.contains(last)) {
+        Some(b) => (b.start..=last).find(|n| definition.is_match(lines[*n])).unwrap_or(last),
+        None => last,
+    };
+    let end = if legacy.is_match(lines[start]) {
+        start + 1
+    } else {
+        lines[start..].iter().position(|l| l.trim_end() == "}").map_or(lines.len(), |e| start + e + 1)
+    };
+    let block = rc::find_block(&content).filter(|b| b.contains(start));
+    let source = block.and_then(|b| lines[b.start..b.end].iter().find_map(|l| l.strip_prefix(SOURCE_MARKER)));
+    let prompt = match source {
+        Some(source) => Some(source.to_string()),
+        None => legacy.captures(lines[start]).map(|caps| caps[1].to_string()),
+    };
+    Ok(Some(ConfiguredPrompt {
+        prompt,
+        raw: lines[start..end].join("\n"),
+        path: path.to_path_buf(),
+        line: start + 1,
+        managed: source.is_some(),
+    }))
+}
+
+/// 找到 .bashrc、.kshrc 中最后一次 PS1 赋值(tcsh 是 `set prompt`, xonsh 是 `$PROMPT`), drop-in 方式下跟随 source 行进入 drop-in 文件
+fn locate_sh_prompt(shell: Shell) -> Result<Option<ConfiguredPrompt>, Box<dyn std::error::Error>> {
+    let path = shell.config_path()?;
+    let found = locate_ps1(shell, &path)?;
+    if shell.install_mode()? == InstallMode::DropIn {
+        let content = rc::read_text(&path).unwrap_or_default();
+        let block = rc::find_block(&content);
+        // 区块之后的 PS1 赋值会覆盖 drop-in 文件中的提示符
+        if found.as_ref().is_none_or(|f| block.is_some_and(|b| f.line <= b.end + 1)) {
+            if let Some(in_drop_in) = locate_ps1(shell, &shell.drop_in_path()?)? {
+                return Ok(Some(in_drop_in));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// 找到文件中最后一次 PS1 赋值; tcsh 与 xonsh 的提示符不是 bash 风格的, 只认出纯文字的
+fn locate_ps1(shell: Shell, path: &Path) -> Result<Option<ConfiguredPrompt>, Box<dyn std::error::Error>> {
+    logging::debug("shell", format!("looking for PS1 in {}", path.display()));
+    let content = match rc::read_text(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            logging::debug("shell", format!("{} does not exist", path.display()));
+            return Ok(None);
+        }
+        Err(err) => return Err(Box::new(err)),
+    };
+    let block = rc::find_block(&content);
+    let re = Regex::new(shell.prompt_assignment_pattern())?;
+    let lines: Vec<&str> = content.lines().collect();
+    let last = match lines.iter().rposition(|line| re.is_match(line)) {
+        Some(n) => n,
+        None => return Ok(None),
+    };
+    logging::debug("shell", format!("last PS1 assignment in {} is on line {}", path.display(), last + 1));
+    if let Some(block) = block.filter(|b| b.contains(last)) {
+        // 区块中第一个赋值是默认提示符, 之后的是按主机名切换的变体; ksh 与 tcsh 的是翻译后的, 原始提示符记在区块开头
+        let first = (block.start..=last).find(|&n| re.is_match(lines[n])).unwrap_or(last);
+        let source = lines[block.start..block.end].iter().find_map(|l| l.strip_prefix(SOURCE_MARKER));
+        return Ok(Some(ConfiguredPrompt {
+            prompt: Some(source.map_or_else(|| unquote(re.captures(lines[first]).unwrap()[1].trim()), str::to_string)),
+            raw: lines[block.start + 1..block.end].join("\n"),
+            path: path.to_path_buf(),
+            line: first + 1,
+            managed: true,
+        }));
+    }
+    let legacy = Regex::new(shell.legacy_line_pattern())?;
+    Ok(re.captures(lines[last]).map(|caps| ConfiguredPrompt {
+        prompt: match shell {
+            Shell::Tcsh | Shell::Xonsh | Shell::Cmd => legacy.captures(lines[last]).map(|caps| caps[1].to_string()),
+            _ => Some(unquote(caps[1].trim())),
+        },
+        raw: lines[last].to_string(),
+        path: path.to_path_buf(),
+        line: last + 1,
+        managed: false,
+    }))
+}
+
+/// 找到生效的 fish_prompt: 配置文件中的定义优先于自动加载的函数文件
+fn locate_fish_prompt() -> Result<Option<ConfiguredPrompt>, Box<dyn std::error::Error>> {
+    let definition = Regex::new(r"^\s*function\s+fish_prompt(\s|$)")?;
+    let legacy = Regex::new(Shell::Fish.legacy_line_pattern())?;
+    let mut legacy_found = None;
+    for path in Shell::fish_config_sources()? {
+        let content = match rc::read_text(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        if let Some(start) = lines.iter().rposition(|l| definition.is_match(l)) {
+            let end = lines[start..].iter().position(|l| l.trim_end() == "end").map_or(lines.len(), |e| start + e + 1);
+            let source = rc::find_block(&content)
+                .filter(|b| b.contains(start))
+                .and_then(|b| lines[b.start..b.end].iter().find_map(|l| l.strip_prefix(SOURCE_MARKER)));
+            return Ok(Some(ConfiguredPrompt {
+                prompt: source.map(str::to_string),
+                raw: lines[start..end].join("\n"),
+                path,
+                line: start + 1,
+                managed: source.is_some(),
+            }));
+        }
+        if let Some((n, caps)) = lines.iter().enumerate().rev().find_map(|(n, l)| legacy.captures(l).map(|c| (n, c))) {
+            legacy_found = Some(ConfiguredPrompt {
+                prompt: Some(caps[1].to_string()),
+                raw: lines[n].to_string(),
+                path: path.clone(),
+                line: n + 1,
+                managed: false,
+            });
+        }
+    }
+
+    if !uses_function_file() {
+        return Ok(legacy_found);
+    }
+    let function_path = Shell::fish_function_path()?;
+    if let Ok(content) = fs::read_to_string(&function_path) {
+        let source = content.lines().find_map(|l| l.strip_prefix(SOURCE_MARKER));
+        return Ok(Some(ConfiguredPrompt {
+            prompt: source.map(str::to_string),
+            raw: content.trim_end().to_string(),
+            path: function_path,
+            line: 1,
+            managed: source.is_some(),
+        }));
+    }
+    Ok(legacy_found)
+}
+
+/// 去掉赋值语句两侧的引号
+pub fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("'\\''", "'");
+    }
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return value[1..value.len() - 1].to_string();
+    }
+    value.to_string()
+}
+
+/// PATH 中是否有这个程序
+pub fn on_path(name: &str) -> bool {
+    let file = if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() };
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(&file).is_file()))
+}
+
+/// 用 shell 的单引号引用字符串
+pub fn single_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// .bashrc 中受管理区块的内容
+pub fn bash_block_body(prompts: &PromptSet) -> String {
+    let title = prompt::bash_title(prompts.title.as_deref());
+    let (mark_start, mark_end) = if prompts.semantic_marks {
+        ("\\[\\e]133;A\\a\\]", "\\[\\e]133;B\\a\\]")
+    } else {
+        ("", "")
+    };
+    // 无障碍模式下上一条命令失败时显示 `[err N]`, 由 __prompt_changer_status 设置
+    let err = if prompts.accessible { "${__prompt_changer_err}" } else { "" };
+    let ps1 = |prompt: &str| single_quote(&format!("{}{}{}{}{}", mark_start, title, err, prompt, mark_end));
+    let mut body = if prompts.screen_compat { terminal::BASH_COMPAT.to_string() } else { String::new() };
+    body += &color::bash_setup(&prompts.colors, prompts.screen_compat);
+    body += &modules::bash_functions(&prompts.used_modules());
+    if prompts.uses_plugins() {
+        body += &plugins::bash_function();
+    }
+    body += &format!("PS1={}", ps1(&prompts.default));
+    if !prompts.hosts.is_empty() {
+        // 模式不能加引号, 否则通配符不生效; 写入 spec 时已限制了其中的字符
+        body += "\ncase \"$HOSTNAME\" in";
+        for (pattern, prompt) in &prompts.hosts {
+            body += &format!("\n    {})\n        PS1={}\n        ;;", pattern, ps1(prompt));
+        }
+        body += "\nesac";
+    }
+    if !prompts.directories.is_empty() || !prompts.times.is_empty() {
+        body += "\n__prompt_changer_ps1=$PS1";
+    }
+    if !prompts.times.is_empty() {
+        // 按时钟选择; bash 3.2 的 printf 不支持 %()T, 这时改用 date
+        body += "\n__prompt_changer_time() {\n    local now\n    printf -v now '%(%H%M)T' -1 2>/dev/null || now=$(date +%H%M)\n    now=$((10#$now))";
+        for (i, (window, prompt)) in prompts.times.iter().enumerate() {
+            let keyword = if i == 0 { "if" } else { "elif" };
+            body += &format!("\n    {} (( {} )); then\n        PS1={}", keyword, window.condition("now", ">=", "<", "&&", "||"), ps1(prompt));
+        }
+        body += "\n    else\n        PS1=$__prompt_changer_ps1\n    fi\n}";
+    }
+    if !prompts.directories.is_empty() {
+        // PS1 不能随目录变化, 所以在每次显示提示符前由 PROMPT_COMMAND 重新选择
+        body += "\n__prompt_changer_dir() {\n    case \"$PWD\" in";
+        for (pattern, prompt) in &prompts.directories {
+            let globs = prompt::directory_globs(pattern, single_quote);
+            body += &format!("\n        {})\n            PS1={}\n            ;;", globs.join("|"), ps1(prompt));
+        }
+        let fallback = if prompts.times.is_empty() { "PS1=$__prompt_changer_ps1" } else { "__prompt_changer_time" };
+        body += &format!("\n        *)\n            {}\n            ;;\n    esac\n}}", fallback);
+        body += &prompt_command_hook("__prompt_changer_dir", false);
+    } else if !prompts.times.is_empty() {
+        body += &prompt_command_hook("__prompt_changer_time", false);
+    }
+    let skip = if prompts.screen_compat { "\n    [ -n \"$__prompt_changer_compat\" ] && return" } else { "" };
+    if prompts.screen_compat && !(title.is_empty() && mark_start.is_empty()) {
+        // screen 与 mosh 中从选好的 PS1 里去掉标题与 OSC 133 序列; 在去掉片段之前运行, 以免它把 PS1 当成新的
+        body += "\n__prompt_changer_compat_ps1() {\n    [ -z \"$__prompt_changer_compat\" ] && return";
+        for sequence in [title.as_str(), mark_start, mark_end].into_iter().filter(|s| !s.is_empty()) {
+            body += &format!("\n    PS1=${{PS1//{}/}}", single_quote(sequence));
+        }
+        body += "\n}";
+        body += &prompt_command_hook("__prompt_changer_compat_ps1", false);
+    }
+    if let Some(fit) = &prompts.fit {
+        // 在其他钩子选好 PS1 之后运行; 上次去掉过片段时先恢复完整的 PS1. ${var@P} 需要 bash 4.4
+        let pieces: Vec<String> = fit.drop.iter().map(|piece| single_quote(piece)).collect();
+        body += "\n__prompt_changer_width() {\n    local text=${1@P}\n    text=${text##*$'\\n'}";
+        body += "\n    while [[ $text == *$'\\001'*$'\\002'* ]]; do\n        text=${text%%$'\\001'*}${text#*$'\\002'}\n    done";
+        body += "\n    __prompt_changer_columns=${#text}\n}";
+        body += "\n__prompt_changer_fit() {\n    (( BASH_VERSINFO[0] * 100 + BASH_VERSINFO[1] >= 404 )) || return 0";
+        body += "\n    [ \"$PS1\" = \"${__prompt_changer_fitted-}\" ] && PS1=$__prompt_changer_unfitted\n    __prompt_changer_unfitted=$PS1";
+        body += &format!("\n    local budget=$(( ${{COLUMNS:-80}} / 2 )) width piece\n    (( budget > {0} )) && budget={0}", fit.max_width);
+        body += "\n    __prompt_changer_width \"$PS1\"\n    width=$__prompt_changer_columns";
+        body += &format!("\n    for piece in {}; do", pieces.join(" "));
+        body += "\n        (( width <= budget )) && break\n        [[ $PS1 == *\"$piece\"* ]] || continue";
+        body += "\n        __prompt_changer_width \"$piece\"\n        PS1=${PS1/\"$piece\"/}\n        (( width -= __prompt_changer_columns ))\n    done";
+        body += "\n    __prompt_changer_fitted=$PS1\n}";
+        body += &prompt_command_hook("__prompt_changer_fit", false);
+    }
+    if prompts.report_cwd {
+        // OSC 7: 把当前目录以 file:// URL 告诉终端, 路径中的 % 与空格需要编码
+        body += &format!("\n__prompt_changer_osc7() {{{}", skip);
+        body += "\n    local path=${PWD//%/%25}\n    path=${path// /%20}";
+        body += "\n    printf '\\e]7;file://%s%s\\e\\\\' \"$HOSTNAME\" \"$path\"\n}";
+        body += &prompt_command_hook("__prompt_changer_osc7", false);
+    }
+    if let Some((insert, normal)) = &prompts.vi_mode {
+        // readline 4.4 起可以在提示符前显示编辑模式; emacs 模式下不显示
+        body += "\nif [[ $- == *i* ]] && (( BASH_VERSINFO[0] * 100 + BASH_VERSINFO[1] >= 404 )); then";
+        body += "\n    bind 'set show-mode-in-prompt on'\n    bind 'set emacs-mode-string \"\"'";
+        body += &format!("\n    bind {}", single_quote(&format!("set vi-ins-mode-string \"{}\"", insert)));
+        body += &format!("\n    bind {}\nfi", single_quote(&format!("set vi-cmd-mode-string \"{}\"", normal)));
+    }
+    body += &terminal::bash_code(&prompts.terminals);
+    if prompts.semantic_marks {
+        // PS0 在命令开始执行前显示, 标出输出的开始; 结束状态由 PROMPT_COMMAND 中最先运行的钩子读取
+        if prompts.screen_compat {
+            body += "\n[ -n \"$__prompt_changer_compat\" ] || PS0='\\e]133;C\\a'";
+        } else {
+            body += "\nPS0='\\e]133;C\\a'";
+        }
+        if prompts.screen_compat {
+            body += "\n__prompt_changer_osc133() {\n    local status=$?";
+            body += skip;
+            body += "\n    printf '\\e]133;D;%s\\a' \"$status\"\n}";
+        } else {
+            body += "\n__prompt_changer_osc133() {\n    printf '\\e]133;D;%s\\a' \"$?\"\n}";
+        }
+        body += &prompt_command_hook("__prompt_changer_osc133", true);
+    }
+    if prompts.accessible {
+        // 最后加入, 因而在 PROMPT_COMMAND 的最前面读取 $?; 原样返回, 之后的钩子仍能读到
+        body += "\n__prompt_changer_status() {\n    local status=$?\n    __prompt_changer_err=";
+        body += "\n    [ \"$status\" -ne 0 ] && __prompt_changer_err=\"[err $status] \"\n    return \"$status\"\n}";
+        body += &prompt_command_hook("__prompt_changer_status", true);
+    }
+    body
+}
+
+/// 把函数加入 PROMPT_COMMAND 的代码, 保留用户已有的命令, 重复 source 时不会加入两次.
+/// `first` 时放在最前(要读取上一条命令的 `$?`), 否则接在已有命令之后;
+/// 用换行分隔, 已有命令以 `;` 结尾时也不会拼出 `;;`
+pub fn prompt_command_hook(function: &str, first: bool) -> String {
+    let chained = if first {
+        format!("{}${{PROMPT_COMMAND:+$'\\n'$PROMPT_COMMAND}}", function)
+    } else {
+        format!("${{PROMPT_COMMAND:+$PROMPT_COMMAND$'\\n'}}{}", function)
+    };
+    format!("\ncase \"$PROMPT_COMMAND\" in\n    *{0}*) ;;\n    *) PROMPT_COMMAND={1} ;;\nesac", function, chained)
+}
+
+/// drop-in 方式下 .bashrc、.kshrc、.profile 或 .tcshrc 受管理区块中的 source 行
+fn sh_source_line(shell: Shell) -> Result<String, CliError> {
+    let path = shell.live_drop_in_path()?;
+    let line = |shown: String| match shell {
+        Shell::Tcsh => format!("if ( -f {0} ) source {0}", shown),
+        _ => format!("[ -f {0} ] && . {0}", shown),
+    };
+    if system_wide() {
+        // 区块写在 config root 下时, 引用的仍是目标系统中的 /etc
+        let target = Path::new("/").join(path.strip_prefix(system_root()).unwrap_or(&path));
+        return Ok(line(single_quote(&target.display().to_string())));
+    }
+    let shown = match path.strip_prefix(home()?) {
+        Ok(rel) => format!("\"$HOME/{}\"", rel.display()),
+        Err(_) => single_quote(&path.display().to_string()),
+    };
+    Ok(line(shown))
+}
+
+/// 终端是否默认启动登录 shell(macOS), 登录 shell 只读 .bash_profile 等文件而不读 .bashrc
+fn login_shell_by_default() -> bool {
+    cfg!(target_os = "macos") && CONFIG_FILE.get().is_none() && !system_wide()
+}
+
+/// 登录 shell 读取的文件: 按 bash 的顺序取第一个存在的, 都不存在时为 .bash_profile
+fn bash_login_file() -> Result<PathBuf, CliError> {
+    let home = home()?;
+    dotfiles::source_path(
+        [".bash_profile", ".bash_login", ".profile"]
+            .iter()
+            .map(|name| home.join(name))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| home.join(".bash_profile")),
+    )
+}
+
+/// 终端默认启动的登录 shell 是否会载入 .bashrc; 终端启动的不是登录 shell 时总为 true
+fn login_loads_bashrc() -> Result<bool, Box<dyn std::error::Error>> {
+    if !login_shell_by_default() {
+        return Ok(true);
+    }
+    let login = bash_login_file()?;
+    let content = rc::read_text(&login).unwrap_or_default();
+    let loads = Regex::new(r"(^|[\s;&])(\.|source)\s+\S*\.bashrc")?;
+    if content.lines().any(|line| !line.trim_start().starts_with('#') && loads.is_match(line)) {
+        logging::debug("shell", format!("{} already loads .bashrc", login.display()));
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// 登录 shell 不载入 .bashrc 时, 在它读取的文件中加入载入 .bashrc 的标准写法
+fn ensure_login_loads_bashrc() -> Result<(), Box<dyn std::error::Error>> {
+    if login_loads_bashrc()? {
+        return Ok(());
+    }
+    let login = bash_login_file()?;
+    rc::write_block(&login, "[ -f \"$HOME/.bashrc\" ] && . \"$HOME/.bashrc\"")?;
+    output::notice(i18n::tr(
+        &format!(
+            "Terminals on macOS start login shells, which read {} instead of ~/.bashrc; added a line to it that loads ~/.bashrc",
+            login.display()
+        ),
+        &format!("macOS 的终端启动登录 shell, 它读取 {} 而不是 ~/.bashrc; 已在其中加入载入 ~/.bashrc 的一行", login.display()),
+    ));
+    Ok(())
+}
+
+/// 更新 Bash 或 PowerShell 的提示符: 区块写在配置文件中, 或写在 drop-in 文件中并由配置文件载入
+fn update_block_prompt(shell: Shell, body: &str, mode: InstallMode) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let config_path = shell.config_path()?;
+    let drop_in = shell.drop_in_path()?;
+    match mode {
+        InstallMode::Append => {
+            rc::write_block(&config_path, body)?;
+            if drop_in.exists() {
+                logging::debug("shell", format!("removing the drop-in file {}", drop_in.display()));
+                fs::remove_file(&drop_in)?;
+            }
+            Ok(config_path)
+        }
+        InstallMode::DropIn => {
+            rc::write_block(&drop_in, body)?;
+            let source = shell.source_line()?;
+            let content = rc::read_text(&config_path).unwrap_or_default();
+            if rc::managed_block(&content).as_deref() != Some(source.as_str()) {
+                rc::write_block(&config_path, &source)?;
+            }
+            Ok(drop_in)
+        }
+    }
+}
+
+/// 更新 Fish 的提示符
+fn update_fish_prompt(new_prompt: &PromptSet, mode: InstallMode) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let drop_in = Shell::Fish.drop_in_path()?;
+    if mode == InstallMode::DropIn {
+        rc::write_block(&drop_in, prompt::fish_function(new_prompt).trim_end())?;
+        return Ok(drop_in);
+    }
+    if drop_in.exists() {
+        logging::debug("shell", format!("removing the drop-in file {}", drop_in.display()));
+        fs::remove_file(&drop_in)?;
+    }
+    if !uses_function_file() {
+        let config_path = Shell::Fish.config_path()?;
+        rc::write_block(&config_path, prompt::fish_function(new_prompt).trim_end())?;
+        return Ok(config_path);
+    }
+    let function_path = Shell::fish_function_path()?;
+    if let Ok(existing) = fs::read_to_string(&function_path) {
+        if !existing.contains(SOURCE_MARKER) {
+            let backup = function_path.with_extension("fish.bak");
+            fs::rename(&function_path, &backup)?;
+            output::notice(i18n::tr(
+                &format!("Moved the existing fish_prompt function to {}", backup.display()),
+                &format!("已把原有的 fish_prompt 函数移到 {}", backup.display()),
+            ));
+        }
+    }
+    if let Some(parent) = function_path.parent() {
+        rc::create_dirs(parent)?;
+    }
+    logging::debug("shell", format!("writing the fish_prompt function to {}", function_path.display()));
+    fs::write(&function_path, prompt::fish_function(new_prompt))?;
+    rc::own_like_parent(&function_path);
+    Ok(function_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msys_paths_become_windows_paths() {
+        let cases = [
+            ("/c/Users/x", r"C:\Users\x"),
+            ("/d/projects/a b", r"D:\projects\a b"),
+            ("/c", r"C:\"),
+            ("/c/", r"C:\"),
+            ("/cygdrive/c/Users/x", r"C:\Users\x"),
+            ("//server/share/home", r"\\server\share\home"),
+            ("//server", r"\\server"),
+            (r"C:\Users\x", r"C:\Users\x"),
+            ("C:/Users/x", "C:/Users/x"),
+            ("/usr/home", "/usr/home"),
+            ("/cd/x", "/cd/x"),
+            ("/1/x", "/1/x"),
+            ("///x", "///x"),
+            ("relative/path", "relative/path"),
+        ];
+        for (msys, windows) in cases {
+            assert_eq!(msys_to_windows(msys), PathBuf::from(windows), "{}", msys);
+        }
+    }
+
+    #[test]
+    fn windows_home_prefers_a_non_empty_home() {
+        let fallback = || Some(PathBuf::from(r"C:\Users\fallback"));
+        let cases = [
+            (Some("/c/Users/x"), r"C:\Users\x"),
+            (Some(r"D:\home"), r"D:\home"),
+            (Some(""), r"C:\Users\fallback"),
+            (None, r"C:\Users\fallback"),
+        ];
+        for (home, expected) in cases {
+            assert_eq!(windows_home(home, fallback), Some(PathBuf::from(expected)), "{:?}", home);
+        }
+        assert_eq!(windows_home(None, || None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_home_leaves_windows_mounts_under_wsl() {
+        let linux = || Some(PathBuf::from("/home/x"));
+        let cases = [
+            ("/mnt/c/Users/x", true, "/home/x"),
+            ("/mnt/c/Users/x", false, "/mnt/c/Users/x"),
+            ("/home/x", true, "/home/x"),
+            ("/mnt2/x", true, "/mnt2/x"),
+            ("/srv/x", false, "/srv/x"),
+        ];
+        for (home, wsl, expected) in cases {
+            assert_eq!(unix_home(PathBuf::from(home), wsl, linux), PathBuf::from(expected), "{} wsl={}", home, wsl);
+        }
+        assert_eq!(unix_home(PathBuf::from("/mnt/c/Users/x"), true, || None), PathBuf::from("/mnt/c/Users/x"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wsl_is_detected_from_the_distro_or_kernel() {
+        let release = |s: &str| Some(s.to_string());
+        assert!(detect_wsl(true, || None));
+        assert!(detect_wsl(false, || release("5.15.153.1-microsoft-standard-WSL2")));
+        assert!(detect_wsl(false, || release("4.4.0-19041-Microsoft")));
+        assert!(!detect_wsl(false, || release("6.8.0-45-generic")));
+        assert!(!detect_wsl(false, || None));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::i18n;
+use crate::output::{self, Json};
+use crate::prompt::{self, Segment};
+use crate::shell::Shell;
+use std::io;
+
+/// 打印当前生效的提示符及其组成
+pub fn run(shell: Shell) -> Result<(), Box<dyn std::error::Error>> {
+    // 以 NotFound 返回, 使退出码为 3
+    let found = shell.locate_prompt()?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No {} prompt is configured; the shell default is in use", shell.display_name()),
+        )
+    })?;
+
+    if output::is_json() {
+        let segments = found.prompt.as_deref().map(|prompt| {
+            let list = prompt::parse_bash(prompt)
+                .iter()
+                .map(|segment| {
+                    Json::Object(vec![
+                        ("raw", segment.to_string().into()),
+                        ("meaning", prompt::describe_segment(segment).into()),
+                    ])
+                })
+                .collect();
+            Json::Array(list)
+        });
+        output::print(
+            "show",
+            vec![
+                ("shell", shell.display_name().to_ascii_lowercase().into()),
+                ("file", found.path.display().to_string().into()),
+                ("line", found.line.into()),
+                ("managed", found.managed.into()),
+                ("raw", found.raw.as_str().into()),
+                ("segments", segments.unwrap_or(Json::Null)),
+            ],
+        );
+        return Ok(());
+    }
+
+    let origin = if found.managed { "written by prompt-changer" } else { "not written by prompt-changer" };
+    println!("{}:{} ({})", found.path.display(), found.line, origin);
+    println!();
+    println!("{}", found.raw);
+
+    match found.prompt {
+        Some(prompt) => {
+            println!();
+            println!("{}", i18n::tr("Segments:", "片段:"));
+            for segment in prompt::parse_bash(&prompt) {
+                let raw = match &segment {
+                    Segment::Text(text) => format!("{:?}", text),
+                    other => other.to_string(),
+                };
+                println!("  {:<24} {}", raw, prompt::describe_segment(&segment));
+            }
+        }
+        None => {
+            println!();
+            println!("{}", i18n::tr("This prompt is fish code, so it cannot be broken down into segments.", "这个提示符是 fish 代码, 无法拆分为片段。"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,1044 @@
+use crate::color::{self, ColorSlot, Fallback};
+use crate::config;
+use crate::i18n;
+use crate::icons;
+use crate::logging;
+use crate::migrate;
+use crate::modules::{self, Coloring};
+use crate::palette;
+use crate::plugins;
+use crate::prompt::{self, Fit, PromptSet, TimeWindow, COLOR_NAMES};
+use crate::suggest;
+use crate::terminal::Terminal;
+use crate::CliError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+/// 提示符描述文件: 依次排列的片段, 以及按主机名或目录覆盖的颜色
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    /// 格式版本, 没有时为第 1 版; 读取时旧版本会先升级, 见 [`migrate`]
+    pub version: Option<u32>,
+    #[serde(default, rename = "segment")]
+    pub segments: Vec<SegmentSpec>,
+    #[serde(default, rename = "host")]
+    pub hosts: Vec<Override>,
+    #[serde(default, rename = "directory")]
+    pub directories: Vec<Override>,
+    #[serde(default, rename = "time")]
+    pub times: Vec<TimeOverride>,
+    /// 终端标题的模板, 如 `\u@\h: \w`; 为空字符串时不设置标题
+    pub title: Option<String>,
+    /// 是否用 OSC 7 报告当前目录
+    #[serde(default)]
+    pub report_cwd: bool,
+    /// 是否加上 OSC 133 语义标记
+    #[serde(default)]
+    pub semantic_marks: bool,
+    /// 在 GNU screen 与 mosh 中不用真彩色, 也不发出标题与 OSC 序列
+    #[serde(default)]
+    pub screen_compat: bool,
+    /// 无障碍模式: 不只用颜色表达信息, 不显示装饰性图标, 文字与背景保持高对比度
+    #[serde(default)]
+    pub accessible: bool,
+    /// 要集成的终端, 如 `auto`、`kitty`
+    pub terminal: Option<String>,
+    /// 是否在 shell 启动时按终端能力选择颜色, 并在 NO_COLOR 时不使用颜色
+    #[serde(default)]
+    pub color_fallback: bool,
+    /// 浅色背景下片段名称到颜色的映射
+    #[serde(default)]
+    pub light: BTreeMap<String, String>,
+    /// 深色背景下片段名称到颜色的映射
+    #[serde(default)]
+    pub dark: BTreeMap<String, String>,
+    /// 导入的调色板名称, 颜色可以引用其中的槽位, 如 `base08`、`accent`
+    pub palette: Option<String>,
+    /// 颜色槽位, 如 `primary = "#5f87ff"`、`warn = "208"`; 片段用槽位名称引用颜色, 换配色时只改这里.
+    /// 值也可以是调色板中的槽位; 与配置文件中的 `[slots]` 合并, 这里的优先
+    #[serde(default)]
+    pub slots: BTreeMap<String, String>,
+    /// 用 ASCII 替代 Nerd Font 图标, 供没有补丁字体的终端使用
+    #[serde(default)]
+    pub ascii: bool,
+    /// 主题的标签, 如 `minimal`、`powerline`、`two-line`, 供 `themes preview --tag` 筛选
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 使用 vi 键绑定时显示编辑模式
+    pub vi_mode: Option<ViMode>,
+    /// 命令执行后把上一个提示符收起为这段文字, 如 `❯ `; 只支持 fish 与 PowerShell
+    pub transient: Option<String>,
+    /// 提示符最多占的列数, 也不超过终端宽度的一半; 超出时按 `priority` 去掉片段
+    pub max_width: Option<u32>,
+    /// 贯穿整个提示符的渐变色, 用于没有自己的颜色与渐变色的片段
+    #[serde(default)]
+    pub gradient: Vec<String>,
+}
+
+/// vi 编辑模式的提示, 显示在提示符最后一行的开头
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ViMode {
+    /// 插入模式下的文字
+    #[serde(default = "ViMode::default_insert")]
+    pub insert: String,
+    /// 普通(命令)模式下的文字
+    #[serde(default = "ViMode::default_normal")]
+    pub normal: String,
+}
+
+impl ViMode {
+    fn default_insert() -> String {
+        "[I] ".to_string()
+    }
+
+    fn default_normal() -> String {
+        "[N] ".to_string()
+    }
+}
+
+/// 提示符中的一个片段
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SegmentSpec {
+    /// 供覆盖规则引用的名称
+    pub name: Option<String>,
+    /// bash 风格的内容, 如 `\u@\h`
+    #[serde(default)]
+    pub text: String,
+    /// 显示在内容前的 Nerd Font 图标名称, 如 `git-branch`、`os`
+    pub icon: Option<String>,
+    /// 颜色名称, `hash` 表示按主机名自动挑选
+    pub color: Option<String>,
+    /// 背景色名称, 用于 powerline 风格的色块
+    pub background: Option<String>,
+    /// 渐变色的起止颜色(中间可以再加颜色), 如 `["#ff5f00", "#af00ff"]`; 逐字符插值为真彩色,
+    /// 终端不支持真彩色时整段使用第一个颜色
+    #[serde(default)]
+    pub gradient: Vec<String>,
+    /// 内置模块的名称, 如 `nix`、`direnv`; 模块自带图标, 只在相应环境中显示
+    pub module: Option<String>,
+    /// 外部插件的名称, 即 `~/.config/prompt-changer/plugins/` 中的可执行文件; 插件自己决定文字与颜色
+    pub plugin: Option<String>,
+    /// WASM 插件可以读取的环境变量, 如 `["KUBECONFIG"]`; 可执行文件插件总能读取全部环境变量
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// 模块是否同时显示区域, 如 `aws` 模块的 `$AWS_REGION`
+    #[serde(default)]
+    pub region: bool,
+    /// 模块用重复的符号代替数字, 如 `shlvl` 模块每层显示一个 `❯`; `char` 模块中是普通用户的提示符号
+    pub symbol: Option<String>,
+    /// `char` 模块中 root 的提示符号, 默认为 `#`
+    pub root_symbol: Option<String>,
+    /// 提示符超过 `max_width` 时去掉片段的顺序, 数字小的先去掉; 不设置的片段总是保留
+    pub priority: Option<u32>,
+    /// 模块显示的值的通配模式到颜色的映射, 如 `prod* = "red"`; 按模式的字母顺序取第一个匹配的
+    #[serde(default)]
+    pub value_colors: BTreeMap<String, String>,
+    /// 模块显示的数值达到阈值时的颜色, 如 `"2.0" = "yellow"`; 取达到的最严重的一档
+    #[serde(default)]
+    pub thresholds: BTreeMap<String, String>,
+}
+
+impl SegmentSpec {
+    /// 片段显示的内容: 有图标时图标在前
+    pub fn display_text(&self, ascii: bool) -> String {
+        if let Some(module) = self.module.as_deref().and_then(|m| modules::find(m).ok()) {
+            if module.symbols {
+                return modules::prompt_char(self.symbol.as_deref().unwrap_or("$"), self.root_symbol.as_deref().unwrap_or("#"));
+            }
+            return module.call();
+        }
+        if let Some(plugin) = &self.plugin {
+            return plugins::call(plugin, &self.env);
+        }
+        match self.icon.as_deref().and_then(|icon| icons::glyph(icon, ascii).ok()) {
+            Some(glyph) if self.text.is_empty() => glyph.to_string(),
+            Some(glyph) => format!("{} {}", glyph, self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+/// 主机名或当前目录匹配时改用的颜色
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    /// 主机名的通配模式, 如 `prod-*`; 或目录的通配模式, 如 `~/work/**`
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// 片段名称到颜色的映射
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+}
+
+/// 一天中某个时间段改用的颜色, 如下班后把提示符变成红色
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeOverride {
+    /// 开始时间 `HH:MM`, 包含在内
+    pub from: String,
+    /// 结束时间 `HH:MM`, 不包含在内; 早于开始时间时跨过午夜
+    pub to: String,
+    /// 片段名称到颜色的映射
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+}
+
+impl TimeOverride {
+    /// 时间段
+    pub fn window(&self) -> Result<TimeWindow, CliError> {
+        TimeWindow::new(&self.from, &self.to)
+    }
+}
+
+/// `hash` 颜色: 在显示提示符时由主机名的校验和挑选一种颜色, 同一主机总是同一种
+const HOST_HASH_COLOR: &str =
+    r#"$(uname -n | cksum | awk '{ c = $1 % 12; printf "\033[%dm", (c < 6 ? 31 + c : 85 + c) }')"#;
+
+/// 前景色与背景色之间可接受的最低对比度, 再低时文字难以辨认
+const MIN_CONTRAST: f64 = 3.0;
+
+/// 无障碍模式要求的对比度, 即 WCAG AA 对正文的要求
+const ACCESSIBLE_CONTRAST: f64 = 4.5;
+
+/// 设置颜色的转义序列, `hash` 时是按主机名生成颜色的命令替换
+pub fn color_sequence(color: &str) -> Result<String, CliError> {
+    if color.trim().eq_ignore_ascii_case("hash") {
+        return Ok(HOST_HASH_COLOR.to_string());
+    }
+    Ok(format!("\\e[{}m", color_code(color)?))
+}
+
+/// 与拼错的颜色最接近的颜色名称, 如 `gren` 时为 `green`
+pub fn closest_color(color: &str) -> Option<String> {
+    let names: Vec<String> = COLOR_NAMES
+        .iter()
+        .map(|name| name.to_string())
+        .chain(COLOR_NAMES.iter().map(|name| format!("bright-{}", name)))
+        .chain(["hash".to_string()])
+        .collect();
+    suggest::closest(color, names.iter().map(String::as_str)).map(str::to_string)
+}
+
+/// 把颜色名称翻译为 SGR 参数: 颜色名, `bright-` 加颜色名, 0-255 或 `#rrggbb`
+pub fn color_code(color: &str) -> Result<String, CliError> {
+    let invalid = || match closest_color(color) {
+        Some(close) => CliError(
+            i18n::tr(
+                &format!("Unknown color `{}`, did you mean `{}`?", color, close),
+                &format!("未知的颜色 `{}`, 你是不是想用 `{}`?", color, close),
+            )
+            .to_string(),
+        ),
+        None => CliError(
+            i18n::tr(
+                &format!("Unknown color `{}`; use a name like red, bright-red, 0-255, #rrggbb, hash, a [slots] name or a slot of the spec's palette", color),
+                &format!("未知的颜色 `{}`; 请使用 red、bright-red 这样的名称, 0-255, #rrggbb, hash, [slots] 中的名称或描述文件调色板中的槽位", color),
+            )
+            .to_string(),
+        ),
+    };
+    let color = color.trim().to_ascii_lowercase();
+    if let Some(i) = COLOR_NAMES.iter().position(|name| *name == color) {
+        return Ok((30 + i).to_string());
+    }
+    if let Some(i) = color.strip_prefix("bright-").and_then(|c| COLOR_NAMES.iter().position(|name| *name == c)) {
+        return Ok((90 + i).to_string());
+    }
+    if let Ok(n) = color.parse::<u8>() {
+        return Ok(format!("38;5;{}", n));
+    }
+    if let Some(hex) = color.strip_prefix('#').filter(|h| h.len() == 6) {
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        return Ok(format!("38;2;{};{};{}", channel(0)?, channel(2)?, channel(4)?));
+    }
+    Err(invalid())
+}
+
+/// 解析 `thresholds` 中的阈值
+fn threshold(limit: &str) -> Result<f64, CliError> {
+    limit
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .ok_or_else(|| CliError(
+            i18n::tr(
+                &format!("Threshold `{}` must be a non-negative number such as 2 or 1.5", limit),
+                &format!("阈值 `{}` 必须是非负数, 例如 2 或 1.5", limit),
+            )
+            .to_string(),
+        ))
+}
+
+/// 渐变色中颜色的 RGB 值, 只接受固定的颜色
+fn gradient_rgb(color: &str) -> Result<(u8, u8, u8), CliError> {
+    color::sgr_rgb(&color_code(color)?)
+        .ok_or_else(|| CliError(
+            i18n::tr(
+                &format!("Gradient color `{}` must be a fixed color such as #ff5f00, 208 or red", color),
+                &format!("渐变颜色 `{}` 必须是固定的颜色, 例如 #ff5f00、208 或 red", color),
+            )
+            .to_string(),
+        ))
+}
+
+/// 检查渐变色
+fn check_gradient(stops: &[String]) -> Result<(), CliError> {
+    if stops.len() == 1 {
+        return Err(CliError(
+            i18n::tr(
+                "A gradient needs at least two colors, such as [\"#ff5f00\", \"#af00ff\"]",
+                "渐变至少需要两种颜色, 例如 [\"#ff5f00\", \"#af00ff\"]",
+            )
+            .to_string(),
+        ));
+    }
+    for stop in stops {
+        if stop.trim().eq_ignore_ascii_case("hash") {
+            return Err(CliError(i18n::tr("The hash color cannot be part of a gradient", "hash 颜色不能用在渐变中").to_string()));
+        }
+        gradient_rgb(stop)?;
+    }
+    Ok(())
+}
+
+/// 渐变色逐个上色的单位: 一个字符, 或一个转义、变量、命令替换(显示时才展开, 整体同一颜色);
+/// 不可打印的部分不占位置, 标为 false
+fn gradient_units(text: &str) -> Vec<(String, bool)> {
+    let mut units = Vec::new();
+    for part in prompt::parse_bash(text) {
+        match part {
+            prompt::Segment::Text(text) => units.extend(text.chars().map(|c| (c.to_string(), true))),
+            prompt::Segment::NonPrinting(_) | prompt::Segment::Color(_) => units.push((part.to_string(), false)),
+            other => units.push((other.to_string(), true)),
+        }
+    }
+    units
+}
+
+/// 描述文件无法解析的错误, 拼错的字段名附上建议
+fn invalid(origin: &str, err: toml::de::Error) -> CliError {
+    CliError(suggest::unknown_field(i18n::tr(&format!("Invalid spec {}: {}", origin, err), &format!("无效的描述文件 {}: {}", origin, err))))
+}
+
+impl Spec {
+    /// 读取并检查描述文件
+    pub fn load(path: &Path) -> Result<Spec, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| CliError(
+                i18n::tr(
+                    &format!("Cannot read spec {}: {}", path.display(), err),
+                    &format!("无法读取描述文件 {}: {}", path.display(), err),
+                )
+                .to_string(),
+            ))?;
+        Spec::parse(&content, &path.display().to_string())
+    }
+
+    /// 从文本读取并检查描述文件, `origin` 是错误信息中的来源
+    pub fn parse(content: &str, origin: &str) -> Result<Spec, Box<dyn std::error::Error>> {
+        let mut spec: Spec = match migrate::upgrade(content, origin)? {
+            Some(upgraded) => Value::Table(upgraded).try_into().map_err(|err| invalid(origin, err))?,
+            None => toml::from_str(content).map_err(|err| invalid(origin, err))?,
+        };
+        logging::debug("spec", format!("read {} (format version {})", origin, spec.version.unwrap_or(1)));
+        let palette = spec.palette.as_deref().map(palette::load).transpose()?;
+        spec.resolve_slots(palette.as_ref())?;
+        let slots = spec.slots.clone();
+        spec.apply_palette(&slots);
+        if let Some(palette) = &palette {
+            spec.apply_palette(palette);
+        }
+        spec.check()?;
+        if spec.accessible {
+            spec.make_accessible();
+        }
+        Ok(spec)
+    }
+
+    /// 切换到无障碍模式: 图标换成文字, 去掉装饰性的图标, 带背景的片段对比度不足时文字改用黑色或白色
+    pub fn make_accessible(&mut self) {
+        self.accessible = true;
+        self.ascii = true;
+        let rgb = |color: &str| color_code(color).ok().and_then(|sgr| color::sgr_rgb(&sgr));
+        for segment in &mut self.segments {
+            if segment.icon.as_deref().is_some_and(icons::is_decorative) {
+                segment.icon = None;
+            }
+            let Some(bg) = segment.background.as_deref().and_then(rgb) else {
+                continue;
+            };
+            let readable = |color: &mut String| {
+                if rgb(color).is_some_and(|fg| color::contrast(fg, bg) < ACCESSIBLE_CONTRAST) {
+                    let white = color::contrast((255, 255, 255), bg) >= color::contrast((0, 0, 0), bg);
+                    *color = if white { "bright-white" } else { "black" }.to_string();
+                }
+            };
+            segment.color.iter_mut().for_each(readable);
+            // 渐变色难以保证每个字符的对比度, 改用第一个颜色
+            if let Some(first) = segment.gradient.first().cloned().filter(|_| segment.color.is_none()) {
+                let mut color = first;
+                readable(&mut color);
+                segment.color = Some(color);
+                segment.gradient.clear();
+            }
+            let Some(name) = segment.name.as_deref() else {
+                continue;
+            };
+            let rules = self.hosts.iter_mut().chain(self.directories.iter_mut()).map(|rule| &mut rule.colors);
+            let maps = rules.chain(self.times.iter_mut().map(|rule| &mut rule.colors)).chain([&mut self.light, &mut self.dark]);
+            for colors in maps {
+                colors.get_mut(name).into_iter().for_each(readable);
+            }
+        }
+    }
+
+    /// 合并配置文件与描述文件中的颜色槽位, 把槽位中引用调色板的颜色换成 `#rrggbb`, 并检查槽位
+    fn resolve_slots(&mut self, palette: Option<&palette::Palette>) -> Result<(), CliError> {
+        let mut slots: BTreeMap<String, String> = config::get().slots.clone();
+        let own: Vec<String> = self.slots.keys().map(|name| name.to_ascii_lowercase()).collect();
+        slots.extend(std::mem::take(&mut self.slots));
+        for (name, color) in slots {
+            let name = name.to_ascii_lowercase();
+            let origin = if own.contains(&name) { "the spec" } else { "config.toml" };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(CliError(
+                    i18n::tr(
+                        &format!("Slot `{}` in {} may only contain letters, digits, `-` and `_`", name, origin),
+                        &format!("{1} 中的槽位 `{0}` 只能包含字母、数字、`-` 和 `_`", name, origin),
+                    )
+                    .to_string(),
+                ));
+            }
+            if color_sequence(&name).is_ok() {
+                return Err(CliError(
+                    i18n::tr(
+                        &format!("Slot `{}` in {} is a built-in color name, which always means that color; rename it", name, origin),
+                        &format!("{1} 中的槽位 `{0}` 是内置的颜色名称, 总是表示那种颜色; 请重新命名", name, origin),
+                    )
+                    .to_string(),
+                ));
+            }
+            let mut color = color;
+            if color_sequence(&color).is_err() {
+                if let Some(hex) = palette.and_then(|p| p.get(&color.trim().to_ascii_lowercase())) {
+                    color = hex.clone();
+                }
+            }
+            color_sequence(&color).map_err(|err| CliError(
+                i18n::tr(
+                    &format!("Slot `{}` in {}: {}", name, origin, err.0),
+                    &format!("{1} 中的槽位 `{0}`: {2}", name, origin, err.0),
+                )
+                .to_string(),
+            ))?;
+            self.slots.insert(name, color);
+        }
+        Ok(())
+    }
+
+    /// 检查颜色, 拼错的槽位名称附上建议
+    fn check_color(&self, color: &str) -> Result<String, CliError> {
+        color_sequence(color).map_err(|err| match suggest::closest(color, self.slots.keys().map(String::as_str)) {
+            Some(slot) => CliError(
+                i18n::tr(
+                    &format!("Unknown color `{}`, did you mean the slot `{}`?", color, slot),
+                    &format!("未知的颜色 `{}`, 你是不是想用槽位 `{}`?", color, slot),
+                )
+                .to_string(),
+            ),
+            None => err,
+        })
+    }
+
+    /// 把引用调色板或颜色槽位的颜色替换为其中的颜色; 内置的颜色名称优先
+    fn apply_palette(&mut self, palette: &palette::Palette) {
+        let resolve = |color: &mut String| {
+            let slot = color.trim().to_ascii_lowercase();
+            if color_sequence(&slot).is_err() {
+                if let Some(hex) = palette.get(&slot) {
+                    *color = hex.clone();
+                }
+            }
+        };
+        for segment in &mut self.segments {
+            segment.color.iter_mut().chain(segment.background.iter_mut()).chain(segment.gradient.iter_mut()).for_each(resolve);
+            segment.value_colors.values_mut().chain(segment.thresholds.values_mut()).for_each(resolve);
+        }
+        for rule in self.hosts.iter_mut().chain(self.directories.iter_mut()) {
+            rule.colors.values_mut().for_each(resolve);
+        }
+        for rule in &mut self.times {
+            rule.colors.values_mut().for_each(resolve);
+        }
+        self.light.values_mut().chain(self.dark.values_mut()).chain(self.gradient.iter_mut()).for_each(resolve);
+    }
+
+    /// 检查片段、颜色与覆盖规则
+    fn check(&self) -> Result<(), CliError> {
+        if self.segments.is_empty() {
+            return Err(CliError(i18n::tr("The spec has no [[segment]] entries", "描述文件中没有 [[segment]] 条目").to_string()));
+        }
+        if let Some(title) = &self.title {
+            prompt::check_title(title)?;
+        }
+        if let Some(terminal) = &self.terminal {
+            Terminal::from_name(terminal)?;
+        }
+        for segment in &self.segments {
+            if let Some(module) = &segment.module {
+                modules::find(module)?;
+                if !segment.text.is_empty() || segment.icon.is_some() {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("The `{}` module segment brings its own text and icon; remove `text` and `icon`", module),
+                            &format!("`{}` 模块片段自带文字和图标; 请删除 `text` 和 `icon`", module),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+            if let Some(plugin) = &segment.plugin {
+                plugins::check_name(plugin)?;
+                if segment.module.is_some() {
+                    return Err(CliError(
+                        i18n::tr(
+                            "A segment can use either `module` or `plugin`, not both",
+                            "片段只能使用 `module` 或 `plugin` 之一, 不能同时使用",
+                        )
+                        .to_string(),
+                    ));
+                }
+                if !segment.text.is_empty() || segment.icon.is_some() {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("The `{}` plugin segment prints its own text; remove `text` and `icon`", plugin),
+                            &format!("`{}` 插件片段自己输出文字; 请删除 `text` 和 `icon`", plugin),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+            if !segment.env.is_empty() && segment.plugin.is_none() {
+                return Err(CliError(i18n::tr("`env` only applies to plugin segments", "`env` 只适用于插件片段").to_string()));
+            }
+            for var in &segment.env {
+                plugins::check_env(var)?;
+            }
+            let module = segment.module.as_deref().map(modules::find).transpose()?;
+            if segment.region && !module.is_some_and(|m| m.has_region()) {
+                return Err(CliError(
+                    i18n::tr(
+                        "`region` only applies to module segments that show a region, such as `aws`",
+                        "`region` 只适用于显示区域的模块片段, 例如 `aws`",
+                    )
+                    .to_string(),
+                ));
+            }
+            if segment.symbol.is_some() && !module.is_some_and(|m| m.has_symbol() || m.symbols) {
+                return Err(CliError(
+                    i18n::tr(
+                        "`symbol` only applies to the `char` module and modules that show a count, such as `shlvl`",
+                        "`symbol` 只适用于 `char` 模块和显示计数的模块, 例如 `shlvl`",
+                    )
+                    .to_string(),
+                ));
+            }
+            if segment.root_symbol.is_some() && !module.is_some_and(|m| m.symbols) {
+                return Err(CliError(i18n::tr("`root_symbol` only applies to the `char` module", "`root_symbol` 只适用于 `char` 模块").to_string()));
+            }
+            for (key, symbol) in [("symbol", &segment.symbol), ("root_symbol", &segment.root_symbol)] {
+                if symbol.as_deref().is_some_and(|s| s.is_empty() || s.contains('\'') || s.chars().any(char::is_control)) {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("`{}` must be a non-empty string without `'` or control characters", key),
+                            &format!("`{}` 必须是不含 `'` 和控制字符的非空字符串", key),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+            if !segment.value_colors.is_empty() && !module.is_some_and(|m| m.coloring == Coloring::Patterns) {
+                return Err(CliError(
+                    i18n::tr(
+                        "`value_colors` only applies to module segments that support it, such as `aws`",
+                        "`value_colors` 只适用于支持它的模块片段, 例如 `aws`",
+                    )
+                    .to_string(),
+                ));
+            }
+            for (pattern, color) in &segment.value_colors {
+                let allowed = |c: char| c.is_ascii_alphanumeric() || "-_.:@/*?[]".contains(c);
+                if pattern.is_empty() || !pattern.chars().all(allowed) {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("Value pattern `{}` may only contain letters, digits, `-`, `_`, `.`, `:`, `@`, `/` and the wildcards * ? [ ]", pattern),
+                            &format!("取值模式 `{}` 只能包含字母、数字、`-`、`_`、`.`、`:`、`@`、`/` 和通配符 * ? [ ]", pattern),
+                        )
+                        .to_string(),
+                    ));
+                }
+                color_code(color)?;
+            }
+            if let Some(icon) = &segment.icon {
+                icons::glyph(icon, self.ascii)?;
+            }
+            if let Some(color) = &segment.color {
+                self.check_color(color)?;
+            }
+            if !segment.gradient.is_empty() {
+                if segment.color.is_some() {
+                    return Err(CliError(
+                        i18n::tr(
+                            "A segment can use either `color` or `gradient`, not both",
+                            "片段只能使用 `color` 或 `gradient` 之一, 不能同时使用",
+                        )
+                        .to_string(),
+                    ));
+                }
+                if let Some(plugin) = &segment.plugin {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("The `{}` plugin segment chooses its own colors; remove `gradient`", plugin),
+                            &format!("`{}` 插件片段自己选择颜色; 请删除 `gradient`", plugin),
+                        )
+                        .to_string(),
+                    ));
+                }
+                check_gradient(&segment.gradient)?;
+            }
+            if let Some(background) = &segment.background {
+                if background.trim().eq_ignore_ascii_case("hash") {
+                    return Err(CliError(
+                        i18n::tr(
+                            "The hash color can only be used for text, not as a background",
+                            "hash 颜色只能用于文字, 不能用作背景",
+                        )
+                        .to_string(),
+                    ));
+                }
+                self.check_color(background)?;
+            }
+        }
+        check_gradient(&self.gradient)?;
+        if let Some(vi_mode) = &self.vi_mode {
+            for text in [&vi_mode.insert, &vi_mode.normal] {
+                if text.chars().any(|c| c.is_control() || "\\\"'".contains(c)) {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("vi_mode text `{}` cannot contain quotes, backslashes or control characters", text),
+                            &format!("vi_mode 文字 `{}` 不能包含引号、反斜杠或控制字符", text),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+        }
+        if let Some(text) = &self.transient {
+            if text.is_empty() || text.chars().any(char::is_control) {
+                return Err(CliError(i18n::tr("transient must be non-empty text without control characters", "transient 必须是不含控制字符的非空文字").to_string()));
+            }
+        }
+        if self.max_width == Some(0) {
+            return Err(CliError(i18n::tr("max_width must be at least 1", "max_width 至少为 1").to_string()));
+        }
+        if self.max_width.is_none() && self.segments.iter().any(|s| s.priority.is_some()) {
+            return Err(CliError(
+                i18n::tr(
+                    "Segment priorities only take effect with a top-level max_width",
+                    "片段优先级只在设置了顶层 max_width 时生效",
+                )
+                .to_string(),
+            ));
+        }
+        for host in &self.hosts {
+            let allowed = |c: char| c.is_ascii_alphanumeric() || "-_.*?[]".contains(c);
+            if host.pattern.is_empty() || !host.pattern.chars().all(allowed) {
+                return Err(CliError(
+                    i18n::tr(
+                        &format!("Host pattern `{}` may only contain letters, digits, `-`, `_`, `.` and the wildcards * ? [ ]", host.pattern),
+                        &format!("主机模式 `{}` 只能包含字母、数字、`-`、`_`、`.` 和通配符 * ? [ ]", host.pattern),
+                    )
+                    .to_string(),
+                ));
+            }
+            self.check_colors(i18n::tr(&format!("Host `{}`", host.pattern), &format!("主机 `{}`", host.pattern)), &host.colors)?;
+        }
+        for directory in &self.directories {
+            let pattern = &directory.pattern;
+            if !(pattern.starts_with('/') || pattern == "~" || pattern.starts_with("~/")) {
+                return Err(CliError(
+                    i18n::tr(
+                        &format!("Directory pattern `{}` must be an absolute path or start with ~/", pattern),
+                        &format!("目录模式 `{}` 必须是绝对路径或以 ~/ 开头", pattern),
+                    )
+                    .to_string(),
+                ));
+            }
+            self.check_colors(i18n::tr(&format!("Directory `{}`", pattern), &format!("目录 `{}`", pattern)), &directory.colors)?;
+        }
+        for time in &self.times {
+            let window = time.window()?;
+            self.check_colors(i18n::tr(&format!("Time {}", window), &format!("时间段 {}", window)), &time.colors)?;
+        }
+        for (palette, colors) in [("light", &self.light), ("dark", &self.dark)] {
+            self.check_colors(i18n::tr(&format!("The {} palette", palette), &format!("{} 调色板", palette)), colors)?;
+            let hash = |c: &String| c.trim().eq_ignore_ascii_case("hash");
+            for (name, color) in colors {
+                let segment = self.segments.iter().find(|s| s.name.as_deref() == Some(name.as_str()));
+                if hash(color) || segment.and_then(|s| s.color.as_ref()).is_some_and(hash) {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("Segment `{}` uses the hash color, which cannot vary between light and dark", name),
+                            &format!("片段 `{}` 使用 hash 颜色, 它不能随浅色和深色变化", name),
+                        )
+                        .to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查覆盖规则引用的片段与颜色
+    fn check_colors(&self, rule: &str, colors: &BTreeMap<String, String>) -> Result<(), CliError> {
+        for (name, color) in colors {
+            if !self.segments.iter().any(|s| s.name.as_deref() == Some(name.as_str())) {
+                let hint = suggest::did_you_mean(name, self.segments.iter().filter_map(|s| s.name.as_deref()));
+                return Err(CliError(
+                    i18n::tr(
+                        &format!("{} overrides unknown segment `{}`{}", rule, name, hint),
+                        &format!("{} 覆盖了未知的片段 `{}`{}", rule, name, hint),
+                    )
+                    .to_string(),
+                ));
+            }
+            self.check_color(color)?;
+        }
+        Ok(())
+    }
+
+    /// 用到的不同颜色(前景与背景)的数量
+    pub fn color_count(&self) -> usize {
+        let colors: std::collections::BTreeSet<String> = self
+            .segments
+            .iter()
+            .flat_map(|s| s.color.iter().chain(s.background.iter()).chain(s.gradient.iter()))
+            .chain(self.gradient.iter())
+            .chain(self.light.values())
+            .chain(self.dark.values())
+            .map(|c| c.trim().to_ascii_lowercase())
+            .collect();
+        colors.len()
+    }
+
+    /// 是否有片段显示 Nerd Font 图标
+    pub fn uses_icons(&self) -> bool {
+        !self.ascii && self.segments.iter().any(|s| s.icon.is_some())
+    }
+
+    /// 前景色与背景色对比度过低、文字可能难以辨认的片段
+    pub fn contrast_warnings(&self) -> Vec<String> {
+        let rgb = |color: &str| color_code(color).ok().and_then(|sgr| color::sgr_rgb(&sgr));
+        let mut warnings = Vec::new();
+        for segment in &self.segments {
+            let (background, bg) = match segment.background.as_deref().map(|b| (b, rgb(b))) {
+                Some((background, Some(bg))) => (background, bg),
+                _ => continue,
+            };
+            // 片段自己的颜色, 以及覆盖规则换上的颜色
+            let mut colors: Vec<(String, &String)> = segment.color.iter().map(|c| (String::new(), c)).collect();
+            if let Some(name) = &segment.name {
+                for host in &self.hosts {
+                    colors.extend(host.colors.get(name).map(|c| (format!(" on hosts matching `{}`", host.pattern), c)));
+                }
+                for directory in &self.directories {
+                    colors.extend(directory.colors.get(name).map(|c| (format!(" in `{}`", directory.pattern), c)));
+                }
+                for time in &self.times {
+                    colors.extend(time.colors.get(name).map(|c| (format!(" between {} and {}", time.from, time.to), c)));
+                }
+                colors.extend(self.light.get(name).map(|c| (" on a light background".to_string(), c)));
+                colors.extend(self.dark.get(name).map(|c| (" on a dark background".to_string(), c)));
+            }
+            let label = segment.name.as_deref().unwrap_or(&segment.text);
+            for (context, color) in colors {
+                if let Some(fg) = rgb(color) {
+                    let ratio = color::contrast(fg, bg);
+                    if ratio < MIN_CONTRAST {
+                        warnings.push(format!(
+                            "segment `{}`{}: {} text on {} has a contrast of {:.1}:1 and may be hard to read",
+                            label, context, color, background, ratio
+                        ));
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
+    /// 片段使用的渐变色: 自己的, 或没有颜色时整个提示符的; 覆盖规则与深浅背景的配色给片段换上颜色时不用渐变色
+    fn gradient_of<'a>(&'a self, segment: &'a SegmentSpec, overrides: &BTreeMap<String, String>) -> Option<&'a [String]> {
+        if let Some(name) = &segment.name {
+            if overrides.contains_key(name) || self.dark.contains_key(name) || self.light.contains_key(name) {
+                return None;
+            }
+        }
+        if !segment.gradient.is_empty() {
+            return Some(&segment.gradient);
+        }
+        Some(self.gradient.as_slice()).filter(|g| !g.is_empty() && segment.color.is_none() && segment.plugin.is_none())
+    }
+
+    /// 用给定的颜色覆盖拼出提示符; 颜色随终端变化时记入 `colors`, 提示符中只引用变量;
+    /// 设置了 `priority` 的片段连同颜色记入 `pieces`
+    fn render(
+        &self,
+        overrides: &BTreeMap<String, String>,
+        colors: &mut Vec<ColorSlot>,
+        pieces: &mut Vec<(u32, String)>,
+    ) -> Result<String, CliError> {
+        // 渐变色在 shell 启动时按终端能力选择, 也通过变量引用
+        let uses_variables = self.color_fallback
+            || !self.light.is_empty()
+            || !self.dark.is_empty()
+            || !self.gradient.is_empty()
+            || self.segments.iter().any(|s| !s.gradient.is_empty());
+        let resolve = |sgr: &str| if self.color_fallback { color::fallback(sgr) } else { Fallback::fixed(sgr) };
+        let mut variable = |slot: ColorSlot| {
+            let i = colors.iter().position(|c| *c == slot).unwrap_or_else(|| {
+                colors.push(slot);
+                colors.len() - 1
+            });
+            format!("${{{}}}", color::color_var(i))
+        };
+        // 整个提示符的渐变色按所有用到它的片段的总长度分配
+        let shared = |s: &SegmentSpec| s.gradient.is_empty() && self.gradient_of(s, overrides).is_some();
+        let shared_units: usize = self
+            .segments
+            .iter()
+            .filter(|s| shared(s))
+            .map(|s| gradient_units(&s.display_text(self.ascii)).iter().filter(|(_, counted)| *counted).count())
+            .sum();
+        let mut shared_offset = 0;
+        let mut prompt = String::new();
+        for segment in &self.segments {
+            let name = segment.name.as_ref();
+            let gradient = self.gradient_of(segment, overrides);
+            // 主机、目录的覆盖在深浅两种背景下都生效
+            let forced = name.and_then(|n| overrides.get(n));
+            let dark = forced.or_else(|| name.and_then(|n| self.dark.get(n))).or(segment.color.as_ref());
+            let light = forced.or_else(|| name.and_then(|n| self.light.get(n))).or(segment.color.as_ref());
+            let mut start = String::new();
+            let mut literal = true;
+            match (dark, light) {
+                _ if gradient.is_some() => {}
+                (None, None) => {}
+                (Some(color), _) | (None, Some(color)) if !uses_variables || color.trim().eq_ignore_ascii_case("hash") => {
+                    start += &color_sequence(color)?;
+                }
+                _ => {
+                    // 某种背景下没有颜色的片段使用终端的默认前景色
+                    let code = |c: Option<&String>| c.map_or(Ok("39".to_string()), |c| color_code(c));
+                    start += &variable(ColorSlot { dark: resolve(&code(dark)?), light: resolve(&code(light)?) });
+                    literal = false;
+                }
+            }
+            if let Some(background) = &segment.background {
+                let sgr = color::background_sgr(&color_code(background)?);
+                if uses_variables {
+                    start += &variable(ColorSlot { dark: resolve(&sgr), light: resolve(&sgr) });
+                    literal = false;
+                } else {
+                    start += &format!("\\e[{}m", sgr);
+                }
+            }
+            let mut text = segment.display_text(self.ascii);
+            if let Some(stops) = gradient {
+                let units = gradient_units(&text);
+                let count = units.iter().filter(|(_, counted)| *counted).count();
+                let (total, offset) = if segment.gradient.is_empty() { (shared_units, shared_offset) } else { (count, 0) };
+                let stops_rgb = stops.iter().map(|stop| gradient_rgb(stop)).collect::<Result<Vec<_>, _>>()?;
+                let mut steps = color::gradient(&stops_rgb, total).into_iter().skip(offset);
+                // 不支持真彩色时整段使用第一个颜色
+                let first = color::fallback(&color_code(&stops[0])?);
+                text = String::new();
+                for (unit, counted) in units {
+                    let step = if counted { steps.next() } else { None };
+                    // 空白不显示颜色, 只占位置
+                    if let Some((r, g, b)) = step.filter(|_| !unit.trim().is_empty()) {
+                        let sgr = Fallback { truecolor: format!("38;2;{};{};{}", r, g, b), ..first.clone() };
+                        text += &format!("\\[{}\\]", variable(ColorSlot { dark: sgr.clone(), light: sgr }));
+                    }
+                    text += &unit;
+                }
+                if segment.gradient.is_empty() {
+                    shared_offset += count;
+                }
+                literal = false;
+            }
+            let piece = if start.is_empty() && gradient.is_none() {
+                text
+            } else {
+                let reset = if literal {
+                    "\\e[0m".to_string()
+                } else {
+                    variable(ColorSlot { dark: resolve("0"), light: resolve("0") })
+                };
+                // 只有渐变色的片段, 颜色已在文字中
+                let open = if start.is_empty() { String::new() } else { format!("\\[{}\\]", start) };
+                format!("{}{}\\[{}\\]", open, text, reset)
+            };
+            if let Some(priority) = segment.priority {
+                pieces.push((priority, piece.clone()));
+            }
+            prompt += &piece;
+        }
+        Ok(prompt)
+    }
+
+    /// 生成默认提示符与各主机、各目录的变体
+    pub fn prompts(&self) -> Result<PromptSet, CliError> {
+        let mut colors = Vec::new();
+        let mut pieces = Vec::new();
+        let mut prompts = PromptSet::single(&self.render(&BTreeMap::new(), &mut colors, &mut pieces)?);
+        for host in &self.hosts {
+            prompts.hosts.push((host.pattern.clone(), self.render(&host.colors, &mut colors, &mut pieces)?));
+        }
+        for directory in &self.directories {
+            prompts.directories.push((directory.pattern.clone(), self.render(&directory.colors, &mut colors, &mut pieces)?));
+        }
+        for time in &self.times {
+            prompts.times.push((time.window()?, self.render(&time.colors, &mut colors, &mut pieces)?));
+        }
+        prompts.colors = colors;
+        if let Some(max_width) = self.max_width {
+            // 优先级低的先去掉; 各变体中同一片段的颜色可能不同, 都要列出
+            pieces.sort_by_key(|(priority, _)| *priority);
+            let mut drop = Vec::new();
+            for (_, piece) in pieces {
+                if !drop.contains(&piece) {
+                    drop.push(piece);
+                }
+            }
+            prompts.fit = Some(Fit { max_width, drop });
+        }
+        for segment in &self.segments {
+            // 带参数的模块不需要设置, 写入函数时从提示符中找出
+            let module = segment.module.as_deref().map(modules::find).transpose()?.filter(|m| !m.symbols);
+            let Some(mut used) = module.map(|m| m.used(self.ascii)) else {
+                continue;
+            };
+            used.region = segment.region;
+            used.markers = self.accessible;
+            used.symbol = segment.symbol.clone();
+            used.value_colors = segment
+                .value_colors
+                .iter()
+                .map(|(pattern, color)| Ok((pattern.clone(), color_code(color)?)))
+                .collect::<Result<_, CliError>>()?;
+            if let Coloring::Thresholds { scale, rising } = modules::find(used.name)?.coloring {
+                for (limit, color) in &segment.thresholds {
+                    used.thresholds.push(((threshold(limit)? * f64::from(scale)).round() as u64, color_code(color)?));
+                }
+                used.thresholds.sort_by_key(|(limit, _)| if rising { u64::MAX - limit } else { *limit });
+            }
+            match prompts.modules.iter().find(|u| u.name == used.name) {
+                Some(other) if *other != used => {
+                    return Err(CliError(
+                        i18n::tr(
+                            &format!("The `{}` module is used twice with different settings", used.name),
+                            &format!("`{}` 模块以不同的设置使用了两次", used.name),
+                        )
+                        .to_string(),
+                    ));
+                }
+                Some(_) => {}
+                None => prompts.modules.push(used),
+            }
+        }
+        prompts.title = self.title.clone();
+        prompts.report_cwd = self.report_cwd;
+        prompts.semantic_marks = self.semantic_marks;
+        prompts.screen_compat = self.screen_compat;
+        prompts.accessible = self.accessible;
+        prompts.transient = self.transient.clone();
+        prompts.vi_mode = self.vi_mode.as_ref().map(|v| (v.insert.clone(), v.normal.clone()));
+        if let Some(terminal) = &self.terminal {
+            prompts.integrate(Terminal::from_name(terminal)?);
+        }
+        Ok(prompts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell;
+
+    /// 一个绿色 `\u` 片段加上给定的覆盖规则
+    fn user_spec(rules: &str) -> String {
+        format!("[[segment]]\nname = \"user\"\ntext = '\\u'\ncolor = \"green\"\n\n{}", rules)
+    }
+
+    #[test]
+    fn host_overrides_recolor_the_default_prompt() {
+        let spec = Spec::parse(&user_spec("[[host]]\nmatch = \"prod-*\"\n[host.colors]\nuser = \"red\"\n"), "test").unwrap();
+        let prompts = spec.prompts().unwrap();
+        assert_eq!(prompts.default, r"\[\e[32m\]\u\[\e[0m\]");
+        assert_eq!(prompts.hosts, [("prod-*".to_string(), r"\[\e[31m\]\u\[\e[0m\]".to_string())]);
+        let body = shell::bash_block_body(&prompts);
+        assert!(body.contains("case \"$HOSTNAME\" in\n    prod-*)\n        PS1='\\[\\e[31m\\]\\u\\[\\e[0m\\]'\n        ;;\nesac"), "{}", body);
+    }
+
+    #[test]
+    fn host_patterns_cannot_break_out_of_the_case_pattern() {
+        for pattern in ["", "prod)", "a b", "$(id)", "x|y", "a'b"] {
+            let rules = format!("[[host]]\nmatch = {:?}\n", pattern);
+            assert!(Spec::parse(&user_spec(&rules), "test").is_err(), "{}", pattern);
+        }
+        assert!(Spec::parse(&user_spec("[[host]]\nmatch = \"web-[0-9]?.example.com\"\n"), "test").is_ok());
+    }
+
+    #[test]
+    fn directory_overrides_are_chosen_before_each_prompt() {
+        let spec = Spec::parse(&user_spec("[[directory]]\nmatch = \"~/work/**\"\n[directory.colors]\nuser = \"blue\"\n"), "test").unwrap();
+        let prompts = spec.prompts().unwrap();
+        assert_eq!(prompts.directories, [("~/work/**".to_string(), r"\[\e[34m\]\u\[\e[0m\]".to_string())]);
+        let body = shell::bash_block_body(&prompts);
+        assert!(body.contains("\n        \"$HOME\"'/work'|\"$HOME\"'/work/'*)\n            PS1='\\[\\e[34m\\]\\u\\[\\e[0m\\]'"), "{}", body);
+        assert!(body.contains("\n        *)\n            PS1=$__prompt_changer_ps1\n"), "{}", body);
+        assert!(body.contains("PROMPT_COMMAND=${PROMPT_COMMAND:+$PROMPT_COMMAND$'\\n'}__prompt_changer_dir"), "{}", body);
+    }
+
+    #[test]
+    fn directory_patterns_must_be_absolute() {
+        for pattern in ["work/**", "~work", "", "./x"] {
+            let rules = format!("[[directory]]\nmatch = {:?}\n", pattern);
+            assert!(Spec::parse(&user_spec(&rules), "test").is_err(), "{}", pattern);
+        }
+        for pattern in ["/srv/app", "~", "~/work/**"] {
+            let rules = format!("[[directory]]\nmatch = {:?}\n", pattern);
+            assert!(Spec::parse(&user_spec(&rules), "test").is_ok(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn time_overrides_are_chosen_by_the_clock() {
+        let rules = "[[time]]\nfrom = \"18:00\"\nto = \"08:30\"\n[time.colors]\nuser = \"red\"\n";
+        let prompts = Spec::parse(&user_spec(rules), "test").unwrap().prompts().unwrap();
+        assert_eq!(prompts.times, [(TimeWindow { from: 1800, to: 830 }, r"\[\e[31m\]\u\[\e[0m\]".to_string())]);
+        let body = shell::bash_block_body(&prompts);
+        // 0830 以 10# 读作十进制, 否则 bash 把它当作八进制而出错
+        assert!(body.contains("now=$((10#$now))"), "{}", body);
+        assert!(body.contains("\n    if (( now >= 1800 || now < 830 )); then\n        PS1='\\[\\e[31m\\]\\u\\[\\e[0m\\]'\n    else\n        PS1=$__prompt_changer_ps1\n    fi"), "{}", body);
+        assert!(body.contains("}__prompt_changer_time ;;"), "{}", body);
+        assert!(Spec::parse(&user_spec("[[time]]\nfrom = \"18:00\"\nto = \"18:00\"\n"), "test").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::i18n;
+use regex::Regex;
+
+/// 两个名称间的编辑距离(插入、删除、替换与相邻字符对调各算一步), 不分大小写
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// 与输入最接近的候选名称; 差得太多(超过名称长度的三分之一, 至少允许一处)时为 None
+pub fn closest<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let input = input.trim();
+    let limit = (input.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|c| !c.eq_ignore_ascii_case(input))
+        .map(|c| (distance(input, c), c))
+        .filter(|(d, _)| *d <= limit)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// 接在错误信息后的建议, 如 ", did you mean `green`?"; 没有接近的名称时为空
+pub fn did_you_mean<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    closest(input, candidates).map(|c| i18n::tr(&format!(", did you mean `{}`?", c), &format!(", 你是不是想用 `{}`?", c)).to_string()).unwrap_or_default()
+}
+
+/// 把 serde 的 "unknown field `colr`, expected one of `name`, …" 换成 "unknown field `colr`, did you mean `color`?";
+/// 没有接近的字段名时保持原样
+pub fn unknown_field(message: &str) -> String {
+    let re = Regex::new(r"unknown field `([^`]*)`, expected ([^\n]*)").unwrap();
+    let Some(caps) = re.captures(message) else {
+        return message.to_string();
+    };
+    let fields: Vec<&str> = caps[2].split('`').skip(1).step_by(2).collect();
+    match closest(&caps[1], fields) {
+        Some(close) => re.replace(message, format!("unknown field `{}`, did you mean `{}`?", &caps[1], close)).into_owned(),
+        None => message.to_string(),
+    }
+}
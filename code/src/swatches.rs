@@ -0,0 +1,141 @@
+#[cfg(unix)]
+use crate::color::{self, ColorDepth};
+use crate::i18n;
+#[cfg(unix)]
+use crate::output;
+use crate::prompt::COLOR_NAMES;
+use std::io;
+#[cfg(unix)]
+use std::io::{IsTerminal, Write};
+
+/// 颜色编号在向导中的写法: 16 色用名称, 其余用编号
+pub fn answer(n: u8) -> String {
+    match n {
+        0..=7 => COLOR_NAMES[usize::from(n)].to_string(),
+        8..=15 => format!("bright-{}", COLOR_NAMES[usize::from(n - 8)]),
+        _ => n.to_string(),
+    }
+}
+
+/// 编号在色块上的颜色: 黑或白中对比度较高的一个
+#[cfg(unix)]
+fn label_color(n: u8) -> u8 {
+    let rgb = color::palette_rgb(n);
+    if color::contrast(rgb, color::palette_rgb(0)) >= color::contrast(rgb, color::palette_rgb(15)) {
+        0
+    } else {
+        15
+    }
+}
+
+/// 一页色块: 16 色分两行, 256 色每行 16 个
+#[cfg(unix)]
+struct Page {
+    count: usize,
+    columns: usize,
+    /// 每个色块的宽度
+    width: usize,
+}
+
+#[cfg(unix)]
+const PAGES: [Page; 2] = [
+    Page { count: 16, columns: 8, width: 6 },
+    Page { count: 256, columns: 16, width: 4 },
+];
+
+/// 画出一页色块, 选中的色块反色显示
+#[cfg(unix)]
+fn grid(page: &Page, selected: usize) -> String {
+    let mut out = String::new();
+    for i in 0..page.count {
+        let n = u8::try_from(i).unwrap_or(u8::MAX);
+        let reverse = if i == selected { ";7" } else { "" };
+        out += &format!("\x1b[48;5;{};38;5;{}{}m{:^width$}\x1b[0m", n, label_color(n), reverse, n, width = page.width);
+        if (i + 1) % page.columns == 0 {
+            out += "\r\n";
+        }
+    }
+    out
+}
+
+/// 在终端中选择颜色: 方向键移动, Tab 切换 16 色与 256 色, 也可以直接输入编号; 返回颜色编号, 取消时为 None.
+/// 不在终端中或处于无障碍模式时只列出 16 色的编号, 由调用者重新询问
+pub fn pick() -> io::Result<Option<u8>> {
+    #[cfg(unix)]
+    if io::stdin().is_terminal() && io::stdout().is_terminal() && color::detect() > ColorDepth::None && !output::is_accessible() {
+        return interactive();
+    }
+    let names: Vec<String> = (0..16).map(|n| format!("{}={}", n, answer(n))).collect();
+    println!("{}", names.join("  "));
+    println!("{}", i18n::tr("16-255 pick from the 256-color palette.", "16-255 为 256 色调色板中的颜色。"));
+    Ok(None)
+}
+
+#[cfg(unix)]
+fn interactive() -> io::Result<Option<u8>> {
+    use crate::picker::{read_key, Key, RawMode};
+
+    let pages = if color::detect() >= ColorDepth::Ansi256 { &PAGES[..] } else { &PAGES[..1] };
+    let _raw = RawMode::enable()?;
+    print!("\x1b[?1049h");
+    let result = (|| {
+        let mut page = 0;
+        let mut selected = 0;
+        let mut typed = String::new();
+        loop {
+            let current = &pages[page];
+            let n = u8::try_from(selected).unwrap_or(u8::MAX);
+            let mut out = String::from("\x1b[H\x1b[2J");
+            out += &format!(
+                "{}\r\n\r\n",
+                i18n::tr(
+                    "←↑→↓ to move, Tab for 16/256 colors, type a number, Enter to pick, Esc to cancel",
+                    "←↑→↓ 移动, Tab 切换 16/256 色, 可直接输入编号, Enter 选定, Esc 取消",
+                )
+            );
+            out += &grid(current, selected);
+            out += &format!("\r\n{} {} {}", i18n::tr("Selected:", "已选:"), n, color::paint(&answer(n), &format!("38;5;{}", n)));
+            if !typed.is_empty() {
+                out += &format!("   > {}", typed);
+            }
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(out.as_bytes())?;
+            stdout.flush()?;
+            drop(stdout);
+            let last = current.count - 1;
+            match read_key()? {
+                Key::Left => selected = selected.saturating_sub(1),
+                Key::Right => selected = (selected + 1).min(last),
+                Key::Up => selected = selected.saturating_sub(current.columns),
+                Key::Down => selected = (selected + current.columns).min(last),
+                Key::Tab => {
+                    page = (page + 1) % pages.len();
+                    selected = selected.min(pages[page].count - 1);
+                    typed.clear();
+                }
+                Key::Char(c) if c.is_ascii_digit() => {
+                    // 超出范围时从这个数字重新开始
+                    typed.push(c);
+                    let count = pages[pages.len() - 1].count;
+                    if typed.parse::<usize>().map_or(true, |n| n >= count) {
+                        typed = c.to_string();
+                    }
+                    selected = typed.parse().unwrap_or(0);
+                    // 16 色页中输入更大的编号时换到 256 色页
+                    if selected >= current.count {
+                        page = pages.len() - 1;
+                    }
+                }
+                Key::Backspace => {
+                    typed.pop();
+                }
+                Key::Enter => return Ok(Some(n)),
+                Key::Cancel => return Ok(None),
+                Key::Char(_) | Key::Other => {}
+            }
+        }
+    })();
+    print!("\x1b[?1049l");
+    io::stdout().flush()?;
+    result
+}
@@ -0,0 +1,157 @@
+use crate::color;
+use crate::prompt::{self, PromptSet, Segment, SOURCE_MARKER};
+use std::path::{Path, PathBuf};
+
+/// tcsh 的提示符中没有对应写法的 bash 转义
+const UNSUPPORTED_ESCAPES: [&str; 2] = ["#", "r"];
+
+/// 转义在 tcsh 中是否没有对应写法: 时间格式固定, 不支持 `\D{...}`; 八进制字符只支持 ESC 与 BEL
+pub fn is_unsupported(escape: &str) -> bool {
+    let octal = escape.len() == 3 && escape.chars().all(|c| c.is_digit(8));
+    UNSUPPORTED_ESCAPES.contains(&escape) || escape.starts_with("D{") || (octal && escape != "033" && escape != "007")
+}
+
+/// 交互式 tcsh 读取的配置文件: ~/.tcshrc, 只有 ~/.cshrc 时是它(tcsh 在没有 .tcshrc 时读取 .cshrc)
+pub fn rc_path(home: &Path) -> PathBuf {
+    let tcshrc = home.join(".tcshrc");
+    let cshrc = home.join(".cshrc");
+    if !tcshrc.exists() && cshrc.exists() {
+        cshrc
+    } else {
+        tcshrc
+    }
+}
+
+/// 提示符的一部分: 原样的文字, 或在 `set prompt` 时展开的变量
+enum Piece {
+    Text(String),
+    Var(String),
+}
+
+/// 提示符中用到的变量; 都在写入时固定, 不随每次显示变化
+const SETUP: &str = "set __prompt_changer_esc = \"`printf '\\033'`\"
+set __prompt_changer_bel = \"`printf '\\007'`\"
+set __prompt_changer_sign = '$'
+if ( $uid == 0 ) set __prompt_changer_sign = '#'
+";
+
+/// 把 bash 的反斜杠转义翻译为 tcsh 的 `%` 转义
+fn escape_piece(escape: &str) -> Piece {
+    let text = |s: &str| Piece::Text(s.to_string());
+    match escape {
+        "u" => text("%n"),
+        "h" => text("%m"),
+        "H" => text("%M"),
+        "w" => text("%~"),
+        "W" => text("%c"),
+        "t" => text("%P"),
+        "T" => text("%p"),
+        "@" => text("%t"),
+        "A" => text("%T"),
+        "d" => text("%d %w %D"),
+        "$" => Piece::Var("__prompt_changer_sign".to_string()),
+        "s" => text("tcsh"),
+        "v" | "V" => Piece::Var("tcsh".to_string()),
+        "!" => text("%h"),
+        "j" => text("%j"),
+        "l" => text("%l"),
+        "n" => text("\n"),
+        "e" | "033" => Piece::Var("__prompt_changer_esc".to_string()),
+        "a" | "007" => Piece::Var("__prompt_changer_bel".to_string()),
+        "\\" => text("\\"),
+        _ => text(""),
+    }
+}
+
+/// 把单个片段翻译为提示符的各部分; 颜色序列不在 `\[ \]` 中(`nested`)时同样用 `%{ %}` 标为不可打印
+fn pieces(segment: &Segment, nested: bool, out: &mut Vec<Piece>) {
+    let hidden = |inner: Vec<Piece>, out: &mut Vec<Piece>| {
+        out.push(Piece::Text("%{".to_string()));
+        out.extend(inner);
+        out.push(Piece::Text("%}".to_string()));
+    };
+    match segment {
+        Segment::Text(text) => out.push(Piece::Text(text.replace('%', "%%").replace('!', "\\!"))),
+        Segment::Escape(escape) => out.push(escape_piece(escape)),
+        Segment::NonPrinting(inner) => {
+            let mut inside = Vec::new();
+            for segment in prompt::parse_bash(inner) {
+                pieces(&segment, true, &mut inside);
+            }
+            hidden(inside, out);
+        }
+        Segment::Color(color) => {
+            let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+            let code = vec![Piece::Var("__prompt_changer_esc".to_string()), Piece::Text(rest.to_string())];
+            if nested {
+                out.extend(code);
+            } else {
+                hidden(code, out);
+            }
+        }
+        // 本工具的颜色变量在写入时展开, 其余变量在每次显示时由 `%$` 读取
+        Segment::Variable(name) if name.starts_with("__prompt_changer") => out.push(Piece::Var(name.clone())),
+        Segment::Variable(name) => out.push(Piece::Text(format!("%${}", name))),
+        // tcsh 不能在显示提示符时运行命令, lint 已把它报为错误
+        Segment::Command(_) => {}
+    }
+}
+
+/// `set prompt = ` 右侧的单词: 文字用单引号(换行前加 `\`), 变量用双引号, 拼接在一起
+fn prompt_word(parts: &[Piece]) -> String {
+    let mut word = String::new();
+    let mut text = String::new();
+    let flush = |text: &mut String, word: &mut String| {
+        if !text.is_empty() {
+            *word += &format!("'{}'", text.replace('\'', "'\\''").replace('\n', "\\\n"));
+            text.clear();
+        }
+    };
+    for part in parts {
+        match part {
+            Piece::Text(t) => text += t,
+            Piece::Var(name) => {
+                flush(&mut text, &mut word);
+                word += &format!("\"${{{}}}\"", name);
+            }
+        }
+    }
+    flush(&mut text, &mut word);
+    if word.is_empty() {
+        "''".to_string()
+    } else {
+        word
+    }
+}
+
+/// 提示符翻译为 `set prompt` 的右侧
+fn translate(prompt: &str) -> String {
+    let mut parts = Vec::new();
+    for segment in prompt::parse_bash(prompt) {
+        pieces(&segment, false, &mut parts);
+    }
+    prompt_word(&parts)
+}
+
+/// 提示符略去的设置
+pub fn notices(prompts: &PromptSet) -> Vec<String> {
+    prompt::left_out_notice("tcsh", &prompts.unsupported_extras(false)).into_iter().collect()
+}
+
+/// 写入受管理区块的代码: 第一行记下原始的 bash 风格提示符. 只支持按主机名切换的变体与标题
+pub fn block_body(prompts: &PromptSet) -> String {
+    let title = prompt::bash_title(prompts.title.as_deref());
+    let prompt_line = |prompt: &str| format!("set prompt = {}", translate(&format!("{}{}", title, prompt)));
+    let mut body = format!("{}{}\n{}", SOURCE_MARKER, prompts.default, SETUP);
+    body += &color::tcsh_setup(&prompts.colors);
+    body += &prompt_line(&prompts.default);
+    if !prompts.hosts.is_empty() {
+        // 模式不能加引号, 否则通配符不生效; 写入 spec 时已限制了其中的字符
+        body += "\nswitch ( \"$HOST\" )";
+        for (pattern, prompt) in &prompts.hosts {
+            body += &format!("\n    case {}:\n        {}\n        breaksw", pattern, prompt_line(prompt));
+        }
+        body += "\nendsw";
+    }
+    body
+}
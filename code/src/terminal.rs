@@ -0,0 +1,94 @@
+use crate::i18n;
+use crate::shell;
+use crate::CliError;
+
+/// 有专门 shell 集成的终端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terminal {
+    ITerm2,
+    WezTerm,
+    Kitty,
+}
+
+impl Terminal {
+    /// 命令行中可选的终端, `auto` 表示在 shell 启动时识别所有支持的终端
+    pub const NAMES: [&'static str; 4] = ["auto", "iterm2", "wezterm", "kitty"];
+
+    /// 从命令行参数解析要集成的终端
+    pub fn from_name(name: &str) -> Result<Vec<Terminal>, CliError> {
+        match name {
+            "auto" => Ok(vec![Terminal::ITerm2, Terminal::WezTerm, Terminal::Kitty]),
+            "iterm2" => Ok(vec![Terminal::ITerm2]),
+            "wezterm" => Ok(vec![Terminal::WezTerm]),
+            "kitty" => Ok(vec![Terminal::Kitty]),
+            other => Err(CliError(i18n::tr(&format!("Unsupported terminal: {}", other), &format!("不支持的终端: {}", other)).to_string())),
+        }
+    }
+}
+
+/// bash 中识别 GNU screen 与 mosh 的代码: 在其中时 `__prompt_changer_compat` 不为空, 提示符不用真彩色,
+/// 也不发出标题与 OSC 7、OSC 133 序列. mosh 不设置环境变量, 只能看父进程是不是 mosh-server
+pub const BASH_COMPAT: &str = "__prompt_changer_compat=
+if [ -n \"$STY\" ] || [[ $TERM == screen* ]]; then
+    __prompt_changer_compat=screen
+else
+    { read -r __prompt_changer_compat < \"/proc/$PPID/comm\"; } 2>/dev/null || __prompt_changer_compat=$(ps -o comm= -p \"$PPID\" 2>/dev/null)
+    [[ $__prompt_changer_compat == *mosh-server* ]] && __prompt_changer_compat=mosh || __prompt_changer_compat=
+fi
+";
+
+/// fish 中对应 [`BASH_COMPAT`] 的代码
+pub const FISH_COMPAT: &str = "
+set -g __prompt_changer_compat
+if set -q STY; or string match -q 'screen*' -- $TERM
+    set __prompt_changer_compat screen
+else if string match -q '*mosh-server*' -- (ps -o comm= -p (ps -o ppid= -p $fish_pid | string trim) 2>/dev/null)
+    set __prompt_changer_compat mosh
+end
+";
+
+/// PowerShell 中对应 [`BASH_COMPAT`] 的代码
+pub const POWERSHELL_COMPAT: &str = "$global:__prompt_changer_compat = $env:STY -or $env:TERM -like 'screen*' -or (Get-Process -Id $PID).Parent.ProcessName -eq 'mosh-server'
+";
+
+/// bash 中识别 iTerm2 与 WezTerm 并在每次显示提示符前发出它们专有序列的代码;
+/// kitty 只需要 OSC 133 与 OSC 7, 没有额外的代码。在其他终端中什么也不做
+pub fn bash_code(terminals: &[Terminal]) -> String {
+    let mut detect = String::new();
+    let mut hook = String::new();
+    if terminals.contains(&Terminal::ITerm2) {
+        // 经 ssh 登录时只有 LC_TERMINAL 会被转发
+        detect += "\nif [ \"$LC_TERMINAL\" = iTerm2 ] || [ \"$TERM_PROGRAM\" = iTerm.app ]; then\n    __prompt_changer_term=iterm2\nfi";
+        hook += "\n        iterm2)\n            printf '\\e]1337;RemoteHost=%s@%s\\a\\e]1337;CurrentDir=%s\\a' \"$USER\" \"$HOSTNAME\" \"$PWD\"\n            ;;";
+    }
+    if terminals.contains(&Terminal::WezTerm) {
+        detect += "\nif [ \"$TERM_PROGRAM\" = WezTerm ]; then\n    __prompt_changer_term=wezterm\n    __prompt_changer_host64=$(printf %s \"$HOSTNAME\" | base64)\nfi";
+        hook += "\n        wezterm)\n            printf '\\e]1337;SetUserVar=WEZTERM_HOST=%s\\a' \"$__prompt_changer_host64\"\n            ;;";
+    }
+    if hook.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\n__prompt_changer_term={}\n__prompt_changer_term_hook() {{\n    case \"$__prompt_changer_term\" in{}\n    esac\n}}{}",
+        detect, hook, shell::prompt_command_hook("__prompt_changer_term_hook", false)
+    )
+}
+
+/// fish 中对应 [`bash_code`] 的代码, 在 fish_prompt 事件中发出
+pub fn fish_code(terminals: &[Terminal]) -> String {
+    let mut code = String::new();
+    if terminals.contains(&Terminal::ITerm2) {
+        code += "\nif test \"$LC_TERMINAL\" = iTerm2 -o \"$TERM_PROGRAM\" = iTerm.app\n";
+        code += "    function __prompt_changer_iterm2 --on-event fish_prompt\n";
+        code += "        printf '\\e]1337;RemoteHost=%s@%s\\a\\e]1337;CurrentDir=%s\\a' $USER $hostname $PWD\n";
+        code += "    end\nend\n";
+    }
+    if terminals.contains(&Terminal::WezTerm) {
+        code += "\nif test \"$TERM_PROGRAM\" = WezTerm\n";
+        code += "    set -g __prompt_changer_host64 (printf %s $hostname | base64)\n";
+        code += "    function __prompt_changer_wezterm --on-event fish_prompt\n";
+        code += "        printf '\\e]1337;SetUserVar=WEZTERM_HOST=%s\\a' $__prompt_changer_host64\n";
+        code += "    end\nend\n";
+    }
+    code
+}
@@ -0,0 +1,827 @@
+use crate::color;
+use crate::config;
+use crate::i18n;
+use crate::logging;
+use crate::preview;
+use crate::rc;
+use crate::shell;
+use crate::spec::Spec;
+use crate::CliError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+/// 安装的主题(描述文件)保存的目录
+pub fn themes_dir() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".config/prompt-changer/themes"))
+}
+
+/// 记录每个安装的主题来自哪里的文件, `themes update` 据此重新下载
+fn sources_path() -> Result<PathBuf, CliError> {
+    Ok(themes_dir()?.join("sources.toml"))
+}
+
+/// 主题的来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    /// 主题索引中的一项
+    Index,
+    /// 直接给出的描述文件地址
+    Url,
+    /// git 仓库中的文件
+    Git,
+}
+
+/// 一个安装的主题的来源记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Source {
+    kind: Kind,
+    /// 索引、描述文件或仓库的地址
+    url: String,
+    /// 仓库中的路径
+    path: Option<String>,
+    /// 安装时文件的 SHA-256
+    sha256: String,
+    /// 安装时固定的 SHA-256(`--sha256`), 更新时内容必须与之相同
+    pinned: Option<String>,
+    /// 是否以 `--insecure` 安装, 没有可核对的 SHA-256; 更新时同样不校验
+    #[serde(default)]
+    insecure: bool,
+}
+
+/// 主题索引: `[[theme]]` 列出名称、地址与 SHA-256
+#[derive(Debug, Deserialize)]
+struct Index {
+    #[serde(default, rename = "theme")]
+    themes: Vec<IndexEntry>,
+}
+
+/// 主题索引中的一项
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    name: String,
+    /// 描述文件的地址, 相对地址相对于索引
+    url: String,
+    sha256: String,
+}
+
+/// 计算 SHA-256, 返回小写十六进制
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// 用 curl 下载; 远程地址只接受 https, 另外接受 file:// 以便使用本地镜像
+fn download(url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !url.starts_with("https://") && !url.starts_with("file://") {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("Refusing to download {}: only https:// and file:// addresses are allowed", url),
+                &format!("拒绝下载 {}: 只允许 https:// 和 file:// 地址", url),
+            )
+            .to_string(),
+        )));
+    }
+    logging::debug("themes", format!("downloading {}", url));
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", "--proto", "=https,file", url])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| CliError(
+            i18n::tr(
+                &format!("cannot run curl, which downloads themes: {}", err),
+                &format!("无法运行用来下载主题的 curl: {}", err),
+            )
+            .to_string(),
+        ))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("Cannot download {}: {}", url, stderr.trim()),
+                &format!("无法下载 {}: {}", url, stderr.trim()),
+            )
+            .to_string(),
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// 是否是 git 仓库的地址
+fn is_git(source: &str) -> bool {
+    source.ends_with(".git") || source.starts_with("git@") || source.starts_with("git://") || source.starts_with("ssh://")
+}
+
+/// 浅克隆仓库到临时目录, 返回的目录在 [`TempDir`] 释放时删除
+fn clone(url: &str) -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = TempDir::create("prompt-changer-themes")?;
+    logging::debug("themes", format!("cloning {}", url));
+    let output = Command::new("git")
+        .args(["clone", "--depth", "1", "--quiet", url])
+        .arg(&dir.0)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| CliError(
+            i18n::tr(
+                &format!("cannot run git, which fetches theme repositories: {}", err),
+                &format!("无法运行用来获取主题仓库的 git: {}", err),
+            )
+            .to_string(),
+        ))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("Cannot clone {}: {}", url, stderr.trim()),
+                &format!("无法克隆 {}: {}", url, stderr.trim()),
+            )
+            .to_string(),
+        )));
+    }
+    Ok(dir)
+}
+
+/// 释放时删除的临时目录
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// 新建只有自己能访问的临时目录; 目录名已被占用时换一个, 不会沿用别人事先建好的目录
+    fn create(prefix: &str) -> io::Result<TempDir> {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        for attempt in 0..100u32 {
+            let path = std::env::temp_dir().join(format!("{}-{}-{:08x}", prefix, process::id(), nanos.wrapping_add(attempt.wrapping_mul(0x9e37_79b9))));
+            match builder.create(&path) {
+                Ok(()) => return Ok(TempDir(path)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::AlreadyExists, "cannot create a unique temporary directory"))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 索引中相对地址的完整地址
+fn resolve_url(index: &str, url: &str) -> String {
+    if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("{}/{}", index.rsplit_once('/').map_or(index, |(base, _)| base), url.trim_start_matches("./"))
+    }
+}
+
+/// 检查主题名称
+fn check_name(name: &str) -> Result<(), CliError> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(CliError(
+            i18n::tr(
+                &format!("Theme name `{}` may only contain letters, digits, `-` and `_`", name),
+                &format!("主题名称 `{}` 只能包含字母、数字、`-` 和 `_`", name),
+            )
+            .to_string(),
+        ))
+    }
+}
+
+/// 检查下载的内容: SHA-256 相符且是有效的描述文件, 返回文本与实际的 SHA-256
+fn verify(name: &str, bytes: &[u8], expected: Option<&str>) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let actual = sha256(bytes);
+    if let Some(expected) = expected {
+        if !expected.trim().eq_ignore_ascii_case(&actual) {
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("Checksum mismatch for theme `{}`: expected {}, got {}; nothing was installed", name, expected.trim(), actual),
+                    &format!("主题 `{}` 的校验和不符: 应为 {}, 实际为 {}; 没有安装任何内容", name, expected.trim(), actual),
+                )
+                .to_string(),
+            )));
+        }
+    }
+    let content = String::from_utf8(bytes.to_vec()).map_err(|_| CliError(
+        i18n::tr(
+            &format!("Theme `{}` is not UTF-8 text", name),
+            &format!("主题 `{}` 不是 UTF-8 文本", name),
+        )
+        .to_string(),
+    ))?;
+    Spec::parse(&content, &format!("in theme `{}`", name))?;
+    Ok((content, actual))
+}
+
+/// 取到的一个主题
+struct Fetched {
+    name: String,
+    content: String,
+    source: Source,
+}
+
+/// 读取索引中的主题; `only` 指定时只取这个名称
+fn fetch_index(url: &str, index: &str, only: Option<&str>, read: impl Fn(&str) -> Result<Vec<u8>, Box<dyn std::error::Error>>) -> Result<Vec<Fetched>, Box<dyn std::error::Error>> {
+    let index: Index = toml::from_str(index).map_err(|err| CliError(
+        i18n::tr(
+            &format!("Invalid theme index {}: {}", url, err),
+            &format!("无效的主题索引 {}: {}", url, err),
+        )
+        .to_string(),
+    ))?;
+    let entries: Vec<&IndexEntry> = index.themes.iter().filter(|e| only.is_none_or(|name| e.name == name)).collect();
+    if entries.is_empty() {
+        return Err(Box::new(match only {
+            Some(name) => CliError(
+                i18n::tr(
+                    &format!("No theme named `{}` in {}", name, url),
+                    &format!("{1} 中没有名为 `{0}` 的主题", name, url),
+                )
+                .to_string(),
+            ),
+            None => CliError(i18n::tr(&format!("The theme index {} lists no themes", url), &format!("主题索引 {} 中没有列出任何主题", url)).to_string()),
+        }));
+    }
+    let mut fetched = Vec::new();
+    for entry in entries {
+        check_name(&entry.name)?;
+        let (content, sha256) = verify(&entry.name, &read(&entry.url)?, Some(&entry.sha256))?;
+        let source = Source { kind: Kind::Index, url: url.to_string(), path: Some(entry.url.clone()), sha256, pinned: None, insecure: false };
+        fetched.push(Fetched { name: entry.name.clone(), content, source });
+    }
+    Ok(fetched)
+}
+
+/// 从 git 仓库取主题: 有 `index.toml` 时按索引校验, 否则在 `insecure` 时取 `themes/`(没有时取根目录)中的描述文件
+fn fetch_git(url: &str, only: Option<&str>, insecure: bool) -> Result<Vec<Fetched>, Box<dyn std::error::Error>> {
+    let repo = clone(url)?;
+    let root = repo.0.clone();
+    let read = |path: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        fs::read(root.join(path)).map_err(|err| Box::new(CliError(
+            i18n::tr(
+                &format!("Cannot read {} in {}: {}", path, url, err),
+                &format!("无法读取 {1} 中的 {0}: {2}", path, url, err),
+            )
+            .to_string(),
+        )) as _)
+    };
+    let mut fetched = if let Ok(index) = fs::read_to_string(root.join("index.toml")) {
+        fetch_index(url, &index, only, read)?
+    } else if !insecure {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("{} has no index.toml listing the SHA-256 of its themes; pass --insecure to install its specs unverified", url),
+                &format!("{} 没有列出主题 SHA-256 的 index.toml; 传入 --insecure 可不经校验安装其中的描述文件", url),
+            )
+            .to_string(),
+        )));
+    } else {
+        let dir = if root.join("themes").is_dir() { "themes" } else { "" };
+        let mut files: Vec<PathBuf> = fs::read_dir(root.join(dir))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        files.sort();
+        let mut fetched = Vec::new();
+        for file in files {
+            let name = file.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+            if only.is_some_and(|only| only != name) || check_name(&name).is_err() {
+                continue;
+            }
+            let path = Path::new(dir).join(file.file_name().unwrap()).to_string_lossy().replace('\\', "/");
+            match verify(&name, &fs::read(&file)?, None) {
+                Ok((content, sha256)) => {
+                    let source = Source { kind: Kind::Git, url: url.to_string(), path: Some(path), sha256, pinned: None, insecure: true };
+                    fetched.push(Fetched { name, content, source });
+                }
+                Err(err) => logging::debug("themes", format!("skipping {}: {}", path, err)),
+            }
+        }
+        fetched
+    };
+    for theme in &mut fetched {
+        theme.source.kind = Kind::Git;
+    }
+    if fetched.is_empty() {
+        return Err(Box::new(match only {
+            Some(name) => CliError(
+                i18n::tr(
+                    &format!("No theme named `{}` in {}", name, url),
+                    &format!("{1} 中没有名为 `{0}` 的主题", name, url),
+                )
+                .to_string(),
+            ),
+            None => CliError(i18n::tr(&format!("No valid theme specs found in {}", url), &format!("{} 中没有找到有效的主题描述文件", url)).to_string()),
+        }));
+    }
+    Ok(fetched)
+}
+
+/// 按来源取主题: git 仓库、索引或单个描述文件的地址, 或在索引中查找的名称.
+/// 单个描述文件须给出 `pin`, 或以 `insecure` 跳过校验
+fn fetch(source: &str, name: Option<&str>, pin: Option<&str>, index: Option<&str>, insecure: bool) -> Result<Vec<Fetched>, Box<dyn std::error::Error>> {
+    if is_git(source) {
+        return fetch_git(source, name, insecure);
+    }
+    if !source.contains("://") {
+        check_name(source)?;
+        let Some(index) = index.map(str::to_string).or_else(|| config::get().theme_index.clone()) else {
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("`{}` is not an address; pass --index URL or set `prompt-changer config set theme-index URL` to install themes by name", source),
+                    &format!("`{}` 不是地址; 要按名称安装主题, 请传入 --index URL 或设置 `prompt-changer config set theme-index URL`", source),
+                )
+                .to_string(),
+            )));
+        };
+        if is_git(&index) {
+            return fetch_git(&index, Some(source), insecure);
+        }
+        let content = String::from_utf8(download(&index)?)?;
+        return fetch_index(&index, &content, Some(source), |url| download(&resolve_url(&index, url)));
+    }
+    let bytes = download(source)?;
+    let text = String::from_utf8_lossy(&bytes);
+    if toml::from_str::<Index>(&text).is_ok_and(|index| !index.themes.is_empty()) {
+        return fetch_index(source, &text, name, |url| download(&resolve_url(source, url)));
+    }
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => {
+            let file = source.rsplit('/').next().unwrap_or_default();
+            file.strip_suffix(".toml").unwrap_or(file).to_string()
+        }
+    };
+    check_name(&name).map_err(|err| CliError(i18n::tr(&format!("{}; pass --name", err), &format!("{}; 请传入 --name", err)).to_string()))?;
+    if pin.is_none() && !insecure {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("No checksum for {}; pass --sha256 HEX with its expected SHA-256, or --insecure to install it unverified", source),
+                &format!("{} 没有校验和; 请用 --sha256 HEX 传入预期的 SHA-256, 或传入 --insecure 不经校验安装", source),
+            )
+            .to_string(),
+        )));
+    }
+    let (content, sha256) = verify(&name, &bytes, pin)?;
+    let source = Source {
+        kind: Kind::Url,
+        url: source.to_string(),
+        path: None,
+        sha256,
+        pinned: pin.map(|p| p.trim().to_ascii_lowercase()),
+        insecure: pin.is_none(),
+    };
+    Ok(vec![Fetched { name, content, source }])
+}
+
+/// 读取来源记录
+fn read_sources() -> Result<BTreeMap<String, Source>, Box<dyn std::error::Error>> {
+    let path = sources_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(toml::from_str(&content).map_err(|err| CliError(
+            i18n::tr(
+                &format!("Invalid {}: {}", path.display(), err),
+                &format!("无效的 {}: {}", path.display(), err),
+            )
+            .to_string(),
+        ))?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+/// 写入主题文件并记录来源
+fn save(fetched: &[Fetched], sources: &mut BTreeMap<String, Source>) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = themes_dir()?;
+    rc::create_dirs(&dir)?;
+    for theme in fetched {
+        let path = dir.join(format!("{}.toml", theme.name));
+        fs::write(&path, &theme.content)?;
+        rc::own_like_parent(&path);
+        sources.insert(theme.name.clone(), theme.source.clone());
+    }
+    fs::write(sources_path()?, toml::to_string(sources)?)?;
+    rc::own_like_parent(&sources_path()?);
+    Ok(())
+}
+
+/// 安装主题; 同名的主题只有来自同一来源或指定 `force` 时才会被覆盖
+pub fn install(
+    source: &str,
+    name: Option<&str>,
+    pin: Option<&str>,
+    index: Option<&str>,
+    insecure: bool,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fetched = fetch(source, name, pin, index, insecure)?;
+    let mut sources = read_sources()?;
+    let dir = themes_dir()?;
+    for theme in &fetched {
+        let path = dir.join(format!("{}.toml", theme.name));
+        let same_source = sources.get(&theme.name).is_some_and(|s| s.url == theme.source.url);
+        if path.exists() && !same_source && !force {
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("Theme `{}` is already installed from elsewhere ({}); pass --force to replace it", theme.name, path.display()),
+                    &format!("主题 `{}` 已从别处安装({}); 传入 --force 可替换它", theme.name, path.display()),
+                )
+                .to_string(),
+            )));
+        }
+    }
+    save(&fetched, &mut sources)?;
+    for theme in &fetched {
+        if theme.source.insecure {
+            logging::info(
+                i18n::tr(
+                    &format!("Installed theme `{}` unverified (sha256 {}).", theme.name, &theme.source.sha256[..12]),
+                    &format!("已安装主题 `{}`, 未经校验(sha256 {})。", theme.name, &theme.source.sha256[..12]),
+                ),
+            );
+        } else {
+            logging::info(
+                i18n::tr(
+                    &format!("Installed theme `{}` (sha256 {}).", theme.name, &theme.source.sha256[..12]),
+                    &format!("已安装主题 `{}`(sha256 {})。", theme.name, &theme.source.sha256[..12]),
+                ),
+            );
+        }
+    }
+    logging::info(
+        i18n::tr(
+            &format!("Apply one with `prompt-changer set --spec {}`.", fetched[0].name),
+            &format!("用 `prompt-changer set --spec {}` 应用主题。", fetched[0].name),
+        ),
+    );
+    Ok(())
+}
+
+/// 重新下载安装的主题, 校验后替换有变化的文件; 未指定名称时更新全部
+pub fn update(names: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut sources = read_sources()?;
+    if sources.is_empty() {
+        logging::info(i18n::tr("No themes were installed with `prompt-changer themes install`.", "没有用 `prompt-changer themes install` 安装过主题。"));
+        return Ok(());
+    }
+    for name in names {
+        if !sources.contains_key(*name) {
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("Theme `{}` was not installed with `prompt-changer themes install`", name),
+                    &format!("主题 `{}` 不是用 `prompt-changer themes install` 安装的", name),
+                )
+                .to_string(),
+            )));
+        }
+    }
+    let selected: Vec<(String, Source)> =
+        sources.iter().filter(|(name, _)| names.is_empty() || names.contains(&name.as_str())).map(|(n, s)| (n.clone(), s.clone())).collect();
+    let mut failed = 0;
+    for (name, source) in selected {
+        let result = match source.kind {
+            Kind::Git => fetch_git(&source.url, Some(&name), source.insecure),
+            Kind::Index => fetch(&source.url, Some(&name), None, None, false),
+            Kind::Url => fetch(&source.url, Some(&name), source.pinned.as_deref(), None, source.insecure),
+        };
+        match result {
+            Ok(fetched) if fetched[0].source.sha256 == source.sha256 => logging::info(
+                i18n::tr(
+                    &format!("Theme `{}` is up to date.", name),
+                    &format!("主题 `{}` 已是最新。", name),
+                ),
+            ),
+            Ok(fetched) => {
+                save(&fetched, &mut sources)?;
+                logging::info(
+                    i18n::tr(
+                        &format!("Updated theme `{}` ({} -> {}).", name, &source.sha256[..12], &fetched[0].source.sha256[..12]),
+                        &format!("已更新主题 `{}`({} -> {})。", name, &source.sha256[..12], &fetched[0].source.sha256[..12]),
+                    ),
+                );
+            }
+            Err(err) => {
+                i18n::warning(i18n::tr(&format!("cannot update theme `{}`: {}", name, err), &format!("无法更新主题 `{}`: {}", name, err)));
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Box::new(CliError(
+            i18n::tr(
+                &format!("{} theme(s) could not be updated; the installed copies were kept", failed),
+                &format!("{} 个主题无法更新; 已保留安装的版本", failed),
+            )
+            .to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// 内置的主题: 名称与描述文件内容
+const BUILTIN: [(&str, &str); 5] = [
+    (
+        "classic",
+        r#"tags = ["classic"]
+
+[[segment]]
+text = '\u@\h'
+color = "green"
+
+[[segment]]
+text = ":"
+
+[[segment]]
+text = '\w'
+color = "blue"
+
+[[segment]]
+text = '\$ '
+"#,
+    ),
+    (
+        "minimal",
+        r#"tags = ["minimal"]
+
+[[segment]]
+text = '\W '
+color = "cyan"
+
+[[segment]]
+text = "❯ "
+color = "magenta"
+"#,
+    ),
+    (
+        "lean",
+        r#"tags = ["minimal", "two-line"]
+
+[[segment]]
+text = '\w'
+color = "blue"
+
+[[segment]]
+text = '\n'
+
+[[segment]]
+text = "❯ "
+color = "bright-black"
+"#,
+    ),
+    (
+        "powerline",
+        r#"tags = ["powerline"]
+
+[[segment]]
+text = ' \u '
+color = "black"
+background = "blue"
+
+[[segment]]
+icon = "separator"
+color = "blue"
+background = "bright-black"
+
+[[segment]]
+text = ' \w '
+color = "white"
+background = "bright-black"
+
+[[segment]]
+icon = "separator"
+color = "bright-black"
+
+[[segment]]
+text = " "
+"#,
+    ),
+    (
+        "two-line",
+        r#"tags = ["two-line"]
+
+[[segment]]
+text = "┌─ "
+color = "bright-black"
+
+[[segment]]
+text = '\u@\h'
+color = "green"
+
+[[segment]]
+text = " "
+
+[[segment]]
+text = '\w'
+color = "yellow"
+
+[[segment]]
+text = '\n'
+
+[[segment]]
+text = '└─\$ '
+color = "bright-black"
+"#,
+    ),
+];
+
+/// 内置主题的描述文件内容
+fn builtin(name: &str) -> Option<&'static str> {
+    BUILTIN.iter().find(|(n, _)| *n == name).map(|(_, content)| *content)
+}
+
+/// 读取 `--spec` 的值: 文件, 安装的主题, 或内置主题的名称
+pub fn load(value: &Path) -> Result<Spec, Box<dyn std::error::Error>> {
+    let path = spec_path(value);
+    match value.to_str().and_then(builtin) {
+        Some(content) if !path.exists() => Spec::parse(content, &format!("built-in theme {}", value.display())),
+        _ => Spec::load(&path),
+    }
+}
+
+/// `--spec` 的值: 不是已有的文件而是安装的主题名称时, 换成主题文件
+pub fn spec_path(value: &Path) -> PathBuf {
+    if value.exists() || value.components().count() != 1 || value.extension().is_some() {
+        return value.to_path_buf();
+    }
+    match themes_dir() {
+        Ok(dir) if dir.join(value).with_extension("toml").is_file() => dir.join(value).with_extension("toml"),
+        _ => value.to_path_buf(),
+    }
+}
+
+/// 读取的主题描述文件, 无效时是错误
+pub type Loaded = Result<Spec, Box<dyn std::error::Error>>;
+
+/// 所有可用的主题, 按名称排列, 安装的同名主题替换内置的; 每项是名称、是否内置与描述文件
+pub fn all_themes() -> Vec<(String, bool, Loaded)> {
+    let mut themes: BTreeMap<String, (bool, Loaded)> = BTreeMap::new();
+    for (name, content) in BUILTIN {
+        themes.insert(name.to_string(), (true, Spec::parse(content, &format!("built-in theme {}", name))));
+    }
+    if let Some(entries) = themes_dir().ok().and_then(|dir| fs::read_dir(dir).ok()) {
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+            if path.extension().is_some_and(|ext| ext == "toml") && name != "sources" {
+                themes.insert(name, (false, Spec::load(&path)));
+            }
+        }
+    }
+    themes.into_iter().map(|(name, (builtin, spec))| (name, builtin, spec)).collect()
+}
+
+/// 每行预览一个主题; `tag` 指定时只列出带这个标签的主题
+pub fn preview(tag: Option<&str>, ascii: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let themes = all_themes();
+    let width = themes.iter().map(|(name, _, _)| name.chars().count()).max().unwrap_or(0);
+    let mut shown = 0;
+    for (name, builtin, spec) in themes {
+        let mut spec = match spec {
+            Ok(spec) => spec,
+            Err(err) if tag.is_none() => {
+                println!("{:<width$}  ({})", name, err, width = width);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        if tag.is_some_and(|tag| !spec.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))) {
+            continue;
+        }
+        spec.ascii |= ascii;
+        let prompts = spec.prompts()?;
+        let rendered = preview::sample(&prompts, color::output_depth(), false);
+        let mut label = if builtin { "built-in".to_string() } else { "installed".to_string() };
+        if !spec.tags.is_empty() {
+            label += &format!(", {}", spec.tags.join(", "));
+        }
+        // 多行的提示符换行后与第一行对齐
+        let rendered = rendered.replace('\n', &format!("\n{:width$}  ", "", width = width));
+        println!("{:<width$}  {}  {}", name, rendered, color::paint(&format!("({})", label), "2"), width = width);
+        shown += 1;
+    }
+    if shown == 0 {
+        if let Some(tag) = tag {
+            let tags: std::collections::BTreeSet<String> = BUILTIN.iter().filter_map(|(_, c)| Spec::parse(c, "").ok()).flat_map(|s| s.tags).collect();
+            let tags = tags.into_iter().collect::<Vec<_>>().join(", ");
+            return Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("No theme is tagged `{}`; built-in tags are {}", tag, tags),
+                    &format!("没有标签为 `{}` 的主题; 内置的标签有 {}", tag, tags),
+                )
+                .to_string(),
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// 不需要密码学强度的随机数: 以当前时间与进程号为种子的 xorshift
+fn random_order(len: usize) -> Vec<usize> {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    let mut state = nanos ^ (u64::from(process::id()) << 32) | 1;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut order: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        order.swap(i, (next() % (i as u64 + 1)) as usize);
+    }
+    order
+}
+
+/// 随机挑选主题并预览, 由用户决定接受、换一个或取消; 取消时返回 None
+pub fn random(tags: &[&str], max_colors: Option<usize>, ascii: bool) -> Result<Option<(String, Spec)>, Box<dyn std::error::Error>> {
+    let candidates: Vec<(String, Spec)> = all_themes()
+        .into_iter()
+        .filter_map(|(name, _, spec)| spec.ok().map(|spec| (name, spec)))
+        .filter(|(_, spec)| tags.iter().all(|tag| spec.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+        .filter(|(_, spec)| max_colors.is_none_or(|max| spec.color_count() <= max))
+        .collect();
+    if candidates.is_empty() {
+        return Err(Box::new(CliError(i18n::tr("No theme matches the given --tag and --max-colors", "没有主题符合给定的 --tag 和 --max-colors").to_string())));
+    }
+    let mut candidates: Vec<Option<(String, Spec)>> = candidates.into_iter().map(Some).collect();
+    let mut order = random_order(candidates.len()).into_iter().cycle();
+    let mut seen = 0;
+    loop {
+        let i = order.next().unwrap();
+        let (name, spec) = candidates[i].as_mut().unwrap();
+        spec.ascii |= ascii;
+        println!("\n{}  {}", name, preview::sample(&spec.prompts()?, color::output_depth(), false));
+        seen += 1;
+        if seen == candidates.len() {
+            println!("{}", crate::i18n::tr("(that was the last matching theme; retrying starts over)", "(这是最后一个符合条件的主题; 再换会从头开始)"));
+            seen = 0;
+        }
+        print!("{}", crate::i18n::tr("Use this theme? [a]ccept, [r]etry, [c]ancel: ", "使用这个主题吗? [a] 接受, [r] 换一个, [c] 取消: "));
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().lock().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        match input.trim().to_ascii_lowercase().as_str() {
+            "a" | "accept" | "y" | "yes" => return Ok(candidates[i].take()),
+            "c" | "cancel" | "q" | "n" | "no" => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THEME: &str = "[[segment]]\ntext = '\\u '\ncolor = \"green\"\n";
+
+    #[test]
+    fn verify_checks_the_sha256() {
+        assert_eq!(sha256(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        let digest = sha256(THEME.as_bytes());
+        assert_eq!(verify("t", THEME.as_bytes(), None).unwrap(), (THEME.to_string(), digest.clone()));
+        assert!(verify("t", THEME.as_bytes(), Some(&format!(" {} ", digest.to_uppercase()))).is_ok());
+        assert!(verify("t", THEME.as_bytes(), Some(&sha256(b"other"))).is_err());
+        assert!(verify("t", b"\xff[[segment]]", None).is_err());
+        assert!(verify("t", b"[[segment]]\nbogus = 1\n", None).is_err());
+    }
+
+    #[test]
+    fn index_entries_are_verified_before_anything_is_kept() {
+        let good = sha256(THEME.as_bytes());
+        let read = |url: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            assert!(url == "a.toml" || url == "b.toml", "{}", url);
+            Ok(THEME.as_bytes().to_vec())
+        };
+        let index = format!("[[theme]]\nname = \"a\"\nurl = \"a.toml\"\nsha256 = \"{0}\"\n\n[[theme]]\nname = \"b\"\nurl = \"b.toml\"\nsha256 = \"{0}\"\n", good);
+        let fetched = fetch_index("https://example.com/index.toml", &index, None, read).unwrap();
+        let names: Vec<&str> = fetched.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(fetched[0].source.sha256, good);
+        assert_eq!(fetch_index("index", &index, Some("b"), read).unwrap().len(), 1);
+        assert!(fetch_index("index", &index, Some("c"), read).is_err());
+
+        let tampered = index.replacen(&good, &sha256(b"other"), 1);
+        assert!(fetch_index("index", &tampered, None, read).is_err());
+        let bad_name = index.replacen("name = \"a\"", "name = \"../a\"", 1);
+        assert!(fetch_index("index", &bad_name, None, read).is_err());
+    }
+
+    #[test]
+    fn relative_index_urls_resolve_next_to_the_index() {
+        let index = "https://example.com/themes/index.toml";
+        assert_eq!(resolve_url(index, "./dark.toml"), "https://example.com/themes/dark.toml");
+        assert_eq!(resolve_url(index, "sub/dark.toml"), "https://example.com/themes/sub/dark.toml");
+        assert_eq!(resolve_url(index, "https://other.example/x.toml"), "https://other.example/x.toml");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::i18n;
+use crate::logging;
+use crate::prompt::{self, Segment};
+use crate::rc;
+use crate::shell::{self, single_quote};
+use crate::spec::Spec;
+
+/// `hash` 颜色在 tmux 中的写法: 与提示符中挑选的颜色相同
+const HOST_HASH_COLOUR: &str =
+    r#"#(uname -n | cksum | awk '{ c = $1 % 12; print "colour" (c < 6 ? 1 + c : 3 + c) }')"#;
+
+/// 把描述文件中的颜色翻译为 tmux 的颜色名
+fn tmux_colour(color: &str) -> String {
+    let color = color.trim().to_ascii_lowercase();
+    if color == "hash" {
+        return HOST_HASH_COLOUR.to_string();
+    }
+    if let Some(name) = color.strip_prefix("bright-") {
+        return format!("bright{}", name);
+    }
+    match color.parse::<u8>() {
+        Ok(n) => format!("colour{}", n),
+        Err(_) => color,
+    }
+}
+
+/// 把 bash 转义翻译为 tmux 的格式, 没有对应功能时返回 None
+fn tmux_escape(escape: &str) -> Option<String> {
+    let text = match escape {
+        "u" => "#(whoami)",
+        "h" => "#h",
+        "H" => "#H",
+        "w" => "#{pane_current_path}",
+        "W" => "#{b:pane_current_path}",
+        "t" => "%H:%M:%S",
+        "T" => "%I:%M:%S",
+        "@" => "%I:%M %p",
+        "A" => "%H:%M",
+        "d" => "%a %b %d",
+        "\\" => "\\",
+        "$" | "n" | "e" | "a" | "r" | "[" | "]" => "",
+        _ => {
+            let fmt = escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}'))?;
+            return Some(if fmt.is_empty() { "%X".to_string() } else { fmt.to_string() });
+        }
+    };
+    Some(text.to_string())
+}
+
+/// 把片段内容翻译为 tmux 的格式, 无法翻译的部分记入 warnings
+fn tmux_text(text: &str, warnings: &mut Vec<String>) -> String {
+    prompt::parse_bash(text)
+        .iter()
+        .map(|segment| match segment {
+            // 状态栏会经过 strftime 展开, 所以 % 也要转义
+            Segment::Text(text) => text.replace('#', "##").replace('%', "%%"),
+            Segment::Variable(name) => format!("#(echo \"${}\")", name),
+            Segment::Command(cmd) => format!("#({})", cmd),
+            Segment::NonPrinting(_) | Segment::Color(_) => String::new(),
+            Segment::Escape(escape) => tmux_escape(escape).unwrap_or_else(|| {
+                warnings.push(
+                    i18n::tr(
+                        &format!("\\{} has no tmux equivalent and was left out", escape),
+                        &format!("\\{} 在 tmux 中没有对应的写法, 已省略", escape),
+                    )
+                    .to_string(),
+                );
+                String::new()
+            }),
+        })
+        .collect()
+}
+
+/// 由描述文件生成状态栏的内容; 主机名的覆盖规则翻译为 tmux 的条件格式
+pub fn status_line(spec: &Spec) -> (String, Vec<String>) {
+    let mut warnings = Vec::new();
+    if !spec.directories.is_empty() {
+        warnings.push(
+            i18n::tr(
+                "directory overrides are not supported in the tmux status line and were left out",
+                "tmux 状态栏不支持目录覆盖规则, 已省略",
+            )
+            .to_string(),
+        );
+    }
+    if !spec.times.is_empty() {
+        warnings.push(i18n::tr("time overrides are not supported in the tmux status line and were left out", "tmux 状态栏不支持时间覆盖规则, 已省略").to_string());
+    }
+    let mut line = String::new();
+    for segment in &spec.segments {
+        if let Some(module) = &segment.module {
+            warnings.push(
+                i18n::tr(
+                    &format!("the `{}` module has no tmux equivalent and was left out", module),
+                    &format!("`{}` 模块在 tmux 中没有对应的写法, 已省略", module),
+                )
+                .to_string(),
+            );
+            continue;
+        }
+        let text = tmux_text(&segment.display_text(spec.ascii), &mut warnings);
+        let mut colour = segment.color.as_deref().map_or("default".to_string(), tmux_colour);
+        if let Some(name) = &segment.name {
+            for host in spec.hosts.iter().rev() {
+                if let Some(color) = host.colors.get(name) {
+                    colour = format!("#{{?#{{m:{},#h}},{},{}}}", host.pattern, tmux_colour(color), colour);
+                }
+            }
+        }
+        let mut style = Vec::new();
+        let mut reset = Vec::new();
+        if colour != "default" {
+            style.push(format!("fg={}", colour));
+            reset.push("fg=default");
+        }
+        if let Some(background) = &segment.background {
+            style.push(format!("bg={}", tmux_colour(background)));
+            reset.push("bg=default");
+        }
+        if style.is_empty() {
+            line += &text;
+        } else {
+            line += &format!("#[{}]{}#[{}]", style.join(","), text, reset.join(","));
+        }
+    }
+    (line, warnings)
+}
+
+/// 生成设置状态栏的 tmux 配置
+pub fn snippet(spec: &Spec, right: bool) -> (String, Vec<String>) {
+    let option = if right { "status-right" } else { "status-left" };
+    let (line, warnings) = status_line(spec);
+    let snippet = format!("set -g {0} {1}\nset -g {0}-length 100", option, single_quote(&line));
+    (snippet, warnings)
+}
+
+/// 打印 tmux 配置, `write` 时改为写入 ~/.tmux.conf 的受管理区块
+pub fn run(spec: &Spec, right: bool, write: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (snippet, warnings) = snippet(spec, right);
+    for warning in warnings {
+        i18n::warning(warning);
+    }
+    if !write {
+        println!("{}", snippet);
+        return Ok(());
+    }
+    let path = shell::home()?.join(".tmux.conf");
+    rc::write_block(&path, &snippet)?;
+    logging::info(
+        i18n::tr(
+            &format!("Wrote the tmux status line to {}; reload it with `tmux source-file {}`.", path.display(), path.display()),
+            &format!("已把 tmux 状态栏写入 {}; 用 `tmux source-file {}` 重新载入。", path.display(), path.display()),
+        ),
+    );
+    Ok(())
+}
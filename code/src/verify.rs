@@ -0,0 +1,164 @@
+use crate::dotfiles;
+use crate::i18n;
+use crate::logging;
+use crate::output::{self, Json};
+use crate::rc::Snapshot;
+use crate::shell::Shell;
+use crate::CliError;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 错误是否来自无法启动 shell, 而不是配置本身
+fn cannot_run(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>().is_some()
+}
+
+/// 定义提示符的文件, 没有时是配置文件
+fn prompt_file(shell: Shell) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(match shell.locate_prompt()? {
+        Some(found) => found.path,
+        None => shell.config_path()?,
+    })
+}
+
+/// 写入前 shell 能否正常载入配置; 无法启动 shell, 或改写的是 dotfiles 仓库中的源文件时为 None
+pub fn starts_cleanly(shell: Shell) -> Option<bool> {
+    // 源文件中的提示符要等 dotfiles 工具更新家目录后才会被 shell 载入
+    if dotfiles::is_active() {
+        return None;
+    }
+    let file = prompt_file(shell).ok()?;
+    match shell.verify_startup(&file) {
+        Ok(()) => Some(true),
+        Err(err) if cannot_run(err.as_ref()) => None,
+        Err(_) => Some(false),
+    }
+}
+
+/// 检查本工具写入的文件的语法, 再启动 shell 载入配置并渲染提示符; 返回是否全部通过
+pub fn run(shell: Shell) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut files: Vec<(PathBuf, Option<String>)> = Vec::new();
+    for file in shell.managed_files()? {
+        let error = match shell.syntax_check(&file) {
+            Ok(()) => None,
+            Err(err) if cannot_run(err.as_ref()) => {
+                let name = shell.display_name().to_ascii_lowercase();
+                return Err(Box::new(CliError(i18n::tr(&format!("cannot start {}: {}", name, err), &format!("无法启动 {}: {}", name, err)).to_string())));
+            }
+            Err(err) => Some(err.to_string()),
+        };
+        files.push((file, error));
+    }
+    let startup = shell.verify_startup(&prompt_file(shell)?).err().map(|err| err.to_string());
+    let ok = startup.is_none() && files.iter().all(|(_, error)| error.is_none());
+
+    if output::is_json() {
+        let list = files
+            .iter()
+            .map(|(file, error)| {
+                Json::Object(vec![("file", file.display().to_string().into()), ("error", error.clone().into())])
+            })
+            .collect();
+        output::print(
+            "verify",
+            vec![
+                ("shell", shell.display_name().to_ascii_lowercase().into()),
+                ("ok", ok.into()),
+                ("files", Json::Array(list)),
+                ("startup_error", startup.into()),
+            ],
+        );
+        return Ok(ok);
+    }
+
+    if files.is_empty() {
+        println!("{}", i18n::tr("prompt-changer has not written any files for this shell.", "prompt-changer 还没有为这个 shell 写入文件。"));
+    }
+    for (file, error) in &files {
+        match error {
+            None => println!("ok     {}", file.display()),
+            Some(error) => {
+                println!("error  {}", file.display());
+                for line in error.lines() {
+                    println!("       {}", line);
+                }
+            }
+        }
+    }
+    match startup {
+        None => println!(
+            "ok     {}",
+            i18n::tr(
+                &format!("{} starts with the config and renders the prompt", shell.display_name()),
+                &format!("{} 能载入配置并显示提示符", shell.display_name()),
+            )
+        ),
+        Some(error) => {
+            println!("error  {}", error.lines().next().unwrap_or_default());
+            for line in error.lines().skip(1) {
+                println!("       {}", line);
+            }
+        }
+    }
+    Ok(ok)
+}
+
+/// 写入前就有语法错误的文件, 写入后不把它们算作本工具造成的错误
+pub fn failing_files(shell: Shell) -> Vec<PathBuf> {
+    shell
+        .managed_files()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|file| shell.syntax_check(file).is_err_and(|err| !cannot_run(err.as_ref())))
+        .collect()
+}
+
+/// 写入后检查语法, 并启动 shell 载入新配置; 出现写入前没有的错误时把文件恢复为写入前的内容
+pub fn check_written(
+    shell: Shell,
+    written: &Path,
+    snapshot: &Snapshot,
+    failing_before: &[PathBuf],
+    started_before: Option<bool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for file in shell.managed_files()? {
+        if failing_before.contains(&file) {
+            continue;
+        }
+        match shell.syntax_check(&file) {
+            Ok(()) => {}
+            Err(err) if cannot_run(err.as_ref()) => {
+                logging::debug("verify", format!("skipping the syntax check: {}", err));
+                return Ok(());
+            }
+            Err(err) => {
+                snapshot.restore()?;
+                return Err(Box::new(CliError(
+                    i18n::tr(
+                        &format!("the new prompt left a syntax error in {}, so the change was rolled back:\n{}", file.display(), err),
+                        &format!("新的提示符在 {} 中留下了语法错误, 改动已撤销:\n{}", file.display(), err),
+                    )
+                    .to_string(),
+                )));
+            }
+        }
+    }
+    // 写入前就无法正常启动时, 错误不一定来自新的提示符
+    if started_before != Some(true) {
+        return Ok(());
+    }
+    match shell.verify_startup(written) {
+        Ok(()) => Ok(()),
+        Err(err) if cannot_run(err.as_ref()) => Ok(()),
+        Err(err) => {
+            snapshot.restore()?;
+            Err(Box::new(CliError(
+                i18n::tr(
+                    &format!("{} no longer starts cleanly with the new prompt, so the change was rolled back:\n{}", shell.display_name(), err),
+                    &format!("使用新的提示符后 {} 无法正常启动, 改动已撤销:\n{}", shell.display_name(), err),
+                )
+                .to_string(),
+            )))
+        }
+    }
+}
@@ -0,0 +1,514 @@
+use crate::color::{self, ColorDepth};
+use crate::i18n;
+use crate::lint::{self, Severity};
+use crate::logging;
+use crate::modules;
+use crate::powershell;
+use crate::prompt::{self, Segment, COLOR_NAMES};
+use crate::rc;
+use crate::shell::{self, Shell};
+use crate::spec;
+use crate::swatches;
+use crate::CliError;
+use regex::Regex;
+use std::fs;
+use std::io::{Write, BufRead, ErrorKind, stdin};
+use std::path::PathBuf;
+
+/// bash 提示符的组成要素与颜色编码(中文)
+const BASH_HINT_ZH: &str = "bash命令行提示符的组成要素:\n
+\\u (当前登录用户名), \\h (主机名的简称), \\w (当前工作目录)\n
+\\v (版本号), \\H (完整的主机名), \\W (当前工作目录的最后一部分)\n
+\\T (当前时间,12小时制), \\A (当前时间，格式为 “HH:MM:SS”)\n
+\\t (当前时间,24小时制), \\@ (当前时间，格式为 “HH:MM”)\n
+\\d (当前日期，格式为 “Weekday Month Day”)\n
+\\! (这条命令的历史编号), \\# (本次会话中的命令编号), 可以写成 [\\!]、#\\# 等形式\n
+常用的文本颜色编码:\n
+\\[\\e[30m\\](黑色), \\[\\e[31m\\](红色), \\[\\e[32m\\](绿色), \\[\\e[33m\\](黄色)\n
+\\[\\e[34m\\](蓝色), \\[\\e[35m\\](洋红), \\[\\e[36m\\](青色), \\[\\e[37m\\](白色)";
+
+/// bash 提示符的组成要素与颜色编码(英文)
+const BASH_HINT_EN: &str = "Parts of a bash prompt:\n
+\\u (user name), \\h (short host name), \\w (current directory)\n
+\\v (bash version), \\H (full host name), \\W (last part of the current directory)\n
+\\T (time, 12-hour HH:MM:SS), \\A (time, 24-hour HH:MM)\n
+\\t (time, 24-hour HH:MM:SS), \\@ (time, 12-hour am/pm)\n
+\\d (date as “Weekday Month Day”)\n
+\\! (history number of this command), \\# (command number in this session), e.g. [\\!] or #\\#\n
+Common text color codes:\n
+\\[\\e[30m\\](black), \\[\\e[31m\\](red), \\[\\e[32m\\](green), \\[\\e[33m\\](yellow)\n
+\\[\\e[34m\\](blue), \\[\\e[35m\\](magenta), \\[\\e[36m\\](cyan), \\[\\e[37m\\](white)";
+
+/// 打印 bash 提示符的组成要素与颜色编码
+pub fn bash_hint(){
+    println!("{}", i18n::tr(BASH_HINT_EN, BASH_HINT_ZH));
+
+    // 只介绍当前终端能显示的颜色; NO_COLOR 或输出不是终端时不显示预览
+    let depth = color::output_depth();
+    if depth == ColorDepth::None {
+        return;
+    }
+    let samples: Vec<String> = COLOR_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| color::paint(name, &(30 + i).to_string()))
+        .collect();
+    println!("\n{}: {}", i18n::tr("Color preview", "颜色预览"), samples.join(" "));
+    if depth >= ColorDepth::Ansi256 {
+        println!("\n{}", i18n::tr("Your terminal supports 256 colors: \\[\\e[38;5;Nm\\] (N is 0-255)", "终端支持 256 色: \\[\\e[38;5;编号m\\] (编号 0-255)"));
+    }
+    if depth >= ColorDepth::TrueColor {
+        println!("\n{}", i18n::tr("Your terminal supports true color: \\[\\e[38;2;R;G;Bm\\] (each 0-255)", "终端支持真彩色: \\[\\e[38;2;红;绿;蓝m\\] (各 0-255)"));
+    }
+}
+
+/// `hints` 支持的 shell; zsh 只有速查表, 还不能写入它的配置
+pub const HINT_SHELLS: [&str; 4] = ["bash", "fish", "powershell", "zsh"];
+
+/// 向导中常用的 bash 转义, 用于对照 fish 中的写法
+const COMMON_ESCAPES: [&str; 13] = ["u", "h", "H", "w", "W", "t", "T", "@", "A", "d", "!", "#", "$"];
+
+/// 打印 fish 提示符的写法, 以及向导把 bash 转义翻译成的 fish 表达式
+pub fn fish_hint() {
+    println!("{}", i18n::tr("A fish prompt is the fish_prompt function. When editing it by hand, use:\n", "fish 的提示符是 fish_prompt 函数, 手动编辑时使用:\n"));
+    println!("$USER, (prompt_hostname), $hostname, (prompt_pwd), $PWD, (date +%H:%M:%S)");
+    println!("set_color red, set_color brred, set_color ff8800, set_color --bold, set_color normal\n");
+    println!("{}", i18n::tr("The wizard takes bash-style parts and translates them for fish:\n", "向导接受 bash 风格的要素, 并翻译为 fish 的写法:\n"));
+    for escape in COMMON_ESCAPES {
+        println!("\\{:<3} -> {}", escape, prompt::fish_escape(escape));
+    }
+    println!("\\[\\e[31m\\] -> \\e'[31m' ({})", i18n::tr("same as set_color red", "等同于 set_color red"));
+}
+
+/// 打印 PowerShell 提示符的写法, 以及向导的要素如何翻译为 PowerShell
+pub fn powershell_hint() {
+    println!("{}", i18n::tr("A PowerShell prompt is the prompt function. When editing it by hand, use:\n", "PowerShell 的提示符是 prompt 函数, 手动编辑时使用:\n"));
+    println!("[Environment]::UserName, [Environment]::MachineName, $PWD.Path, (Get-Date -UFormat '%H:%M:%S')");
+    println!("\"$([char]27)[31m\" ... \"$([char]27)[0m\"\n");
+    println!("{}", i18n::tr("The wizard takes bash-style parts and translates them for PowerShell:\n", "向导接受 bash 风格的要素, 并翻译为 PowerShell 的写法:\n"));
+    for escape in COMMON_ESCAPES {
+        println!("\\{:<3} -> {}", escape, powershell::escape_word(escape));
+    }
+}
+
+/// 打印 zsh 提示符的组成要素与颜色写法
+pub fn zsh_hint() {
+    println!("{}", i18n::tr("Parts of a zsh prompt (PROMPT):\n", "zsh 命令行提示符(PROMPT)的组成要素:\n"));
+    println!("%n (user), %m (host), %M (full host), %~ (cwd), %1~ (last dir)");
+    println!("%* (HH:MM:SS), %T (HH:MM), %t (12-hour), %D{{%Y-%m-%d}} (strftime), %# (# / %)\n");
+    println!("%F{{red}}...%f, %F{{208}}...%f, %F{{#ff8800}}...%f, %B...%b (bold), %{{...%}} (non-printing)\n");
+    println!("{}", i18n::tr("prompt-changer cannot write zsh configs yet; add PROMPT='...' to ~/.zshrc yourself.", "prompt-changer 还不能写入 zsh 配置, 请自行把 PROMPT='...' 加入 ~/.zshrc。"));
+}
+
+/// 打印所选 shell 的提示符速查表
+pub fn hints(shell: &str) {
+    match shell {
+        "fish" => fish_hint(),
+        "powershell" | "pwsh" => powershell_hint(),
+        "zsh" => zsh_hint(),
+        _ => bash_hint(),
+    }
+}
+
+/// 向导问的部分数
+const PARTS: usize = 4;
+
+/// 可以按编号选择的提示符号
+const SYMBOLS: [&str; 5] = ["$", "❯", "➜", "λ", ">"];
+
+/// 向导的一步: 第几部分的要素或颜色, 或普通用户与 root 的提示符号
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Name(usize),
+    Color(usize),
+    Symbol,
+    RootSymbol,
+}
+
+/// 一次输入的结果
+enum Answer {
+    Value(String),
+    /// `:b`, 回到上一步
+    Back,
+    /// `:q`, 放弃
+    Cancel,
+    /// 输入结束(Ctrl-D 或终端断开), 保留进度
+    Closed,
+}
+
+impl Step {
+    /// 这一步的问题
+    fn question(self) -> String {
+        match self {
+            Step::Name(i) => i18n::tr(&format!("Enter part {} of the prompt:", i), &format!("请输入第{}部分要素:", i)).to_string(),
+            Step::Color(i) => i18n::tr(
+                &format!("Enter the color of part {} (a name like red, 0-255, #rrggbb or \\e[31m; ? to pick from swatches; empty for none):", i),
+                &format!("请输入第{}部分要素颜色(如 red、0-255、#rrggbb 或 \\e[31m; 输入 ? 从色块中选择; 留空不设颜色):", i),
+            )
+            .to_string(),
+            Step::Symbol => {
+                let choices: Vec<String> = SYMBOLS.iter().enumerate().map(|(i, s)| format!("{}) {}", i + 1, s)).collect();
+                i18n::tr(
+                    &format!("Choose the prompt character: {}, or type your own (empty for $):", choices.join("  ")),
+                    &format!("请选择提示符号: {}, 或输入自定义符号(留空为 $):", choices.join("  ")),
+                )
+                .to_string()
+            }
+            Step::RootSymbol => i18n::tr("Enter the prompt character for root (empty for #):", "请输入 root 的提示符号(留空为 #):").to_string(),
+        }
+    }
+
+    /// 检查输入, 返回写入提示符的内容
+    fn check(self, input: &str) -> Result<String, CliError> {
+        match self {
+            Step::Name(_) => {
+                if input.chars().any(char::is_control) {
+                    return Err(CliError(i18n::tr("control characters cannot be typed into a prompt part", "要素中不能含控制字符").to_string()));
+                }
+                let errors: Vec<String> = lint::check_prompt(Shell::Bash, input)
+                    .into_iter()
+                    .filter(|issue| issue.severity == Severity::Error)
+                    .map(|issue| issue.message)
+                    .collect();
+                if errors.is_empty() {
+                    Ok(input.to_string())
+                } else {
+                    Err(CliError(errors.join("; ")))
+                }
+            }
+            Step::Color(_) => {
+                let escape = Regex::new(r"^(\\\[)?\\(e|033|x1[bB])\[[0-9;]*m(\\\])?$").unwrap();
+                if input.is_empty() || escape.is_match(input) {
+                    return Ok(input.to_string());
+                }
+                spec::color_sequence(input).map_err(|_| {
+                    if let Some(close) = spec::closest_color(input) {
+                        return CliError(
+                            i18n::tr(&format!("`{}` is not a color (did you mean `{}`)", input, close), &format!("`{}` 不是颜色(是不是想输入 `{}`)", input, close))
+                                .to_string(),
+                        );
+                    }
+                    CliError(
+                        i18n::tr(
+                            &format!("`{}` is not a color; use a name like red or bright-red, 0-255, #rrggbb or an escape like \\e[31m", input),
+                            &format!("`{}` 不是颜色; 请使用 red、bright-red 等名称, 0-255, #rrggbb 或 \\e[31m 这样的编码", input),
+                        )
+                        .to_string(),
+                    )
+                })
+            }
+            Step::Symbol | Step::RootSymbol => {
+                if input.contains('\'') || input.chars().any(char::is_control) {
+                    return Err(CliError(i18n::tr("the prompt character cannot contain ' or control characters", "提示符号中不能含 ' 或控制字符").to_string()));
+                }
+                let choice = input.parse::<usize>().ok().filter(|n| (1..=SYMBOLS.len()).contains(n));
+                Ok(match (self, choice) {
+                    (Step::Symbol, Some(n)) => SYMBOLS[n - 1].to_string(),
+                    _ => input.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// 问一个问题并读取一行输入
+fn ask(question: &str) -> std::io::Result<Answer> {
+    println!("{}", question);
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    if stdin().lock().read_line(&mut input)? == 0 {
+        return Ok(Answer::Closed);
+    }
+    Ok(match input.trim() {
+        ":b" | ":back" => Answer::Back,
+        ":q" | ":quit" => Answer::Cancel,
+        input => Answer::Value(input.to_string()),
+    })
+}
+
+/// 保存向导进度的文件, 中途按 Ctrl-C 或终端断开后可以接着回答
+fn session_path() -> Result<PathBuf, CliError> {
+    Ok(shell::home()?.join(".cache/prompt-changer/wizard-session"))
+}
+
+/// 读取上次未完成的回答, 每行一个
+fn load_session() -> Vec<String> {
+    session_path()
+        .and_then(|path| fs::read_to_string(path).map_err(|err| CliError(err.to_string())))
+        .map(|content| content.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// 保存已完成的回答; 保存失败只影响续答, 不中断向导
+fn save_session(answers: &[String]) {
+    let result = session_path().and_then(|path| {
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                rc::create_dirs(dir)?;
+            }
+            fs::write(&path, answers.iter().map(|answer| format!("{}\n", answer)).collect::<String>())?;
+            rc::own_like_parent(&path);
+            Ok(())
+        };
+        write().map_err(|err| CliError(format!("{}: {}", path.display(), err)))
+    });
+    if let Err(err) = result {
+        logging::debug("wizard", format!("cannot save the session: {}", err));
+    }
+}
+
+/// 删除保存的进度
+fn clear_session() {
+    if let Ok(path) = session_path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// 有未完成的进度时询问是否继续, 返回继续使用的回答
+fn resume(steps: usize) -> std::io::Result<Vec<String>> {
+    let mut saved = load_session();
+    if saved.is_empty() {
+        return Ok(saved);
+    }
+    saved.truncate(steps - 1);
+    println!(
+        "{}",
+        i18n::tr(
+            &format!("An unfinished session was found ({} of {} answers). Resume it? [Y/n]", saved.len(), steps),
+            &format!("发现未完成的进度(已回答 {}/{} 项), 是否继续? [Y/n]", saved.len(), steps),
+        )
+    );
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input)?;
+    if matches!(input.trim().to_ascii_lowercase().as_str(), "" | "y" | "yes" | "是") {
+        Ok(saved)
+    } else {
+        clear_session();
+        Ok(Vec::new())
+    }
+}
+
+/// 从色块中选择颜色, 返回颜色的写法并回显; 取消时为 None
+fn pick_color() -> std::io::Result<Option<String>> {
+    let picked = swatches::pick()?.map(swatches::answer);
+    if let Some(color) = &picked {
+        println!("{}", color);
+    }
+    Ok(picked)
+}
+
+/// 逐部分读取要素与颜色, 拼出新的提示符; 输入无效时重新询问, 放弃时返回 `None`
+pub fn build_prompt() -> Result<Option<String>, Box<dyn std::error::Error>> {
+    println!("{}", i18n::tr("(type :b to go back a step, :q to quit)\n", "(输入 :b 回到上一步, :q 退出)\n"));
+    let mut steps: Vec<Step> = (1..=PARTS).flat_map(|i| [Step::Name(i), Step::Color(i)]).collect();
+    steps.extend([Step::Symbol, Step::RootSymbol]);
+    let mut answers = resume(steps.len())?;
+
+    while let Some(&step) = steps.get(answers.len()) {
+        let input = match ask(&step.question()) {
+            Ok(Answer::Value(input)) => input,
+            Ok(Answer::Back) => {
+                if answers.pop().is_none() {
+                    println!("{}", i18n::tr("This is the first step.", "已经是第一步。"));
+                }
+                save_session(&answers);
+                continue;
+            }
+            Ok(Answer::Cancel) => {
+                clear_session();
+                return Ok(None);
+            }
+            Ok(Answer::Closed) => {
+                if !answers.is_empty() {
+                    println!("{}", i18n::tr("Your answers are saved; run the wizard again to resume.", "已保存回答, 再次运行向导即可继续。"));
+                }
+                return Ok(None);
+            }
+            // 如输入了非 UTF-8 的内容, 重新询问
+            Err(err) if err.kind() == ErrorKind::InvalidData => {
+                eprintln!("{}", i18n::tr(&format!("Cannot read that input: {}", err), &format!("无法读取输入: {}", err)));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let input = match step {
+            Step::Color(_) if input == "?" => match pick_color()? {
+                Some(color) => color,
+                None => continue,
+            },
+            _ => input,
+        };
+        match step.check(&input) {
+            Ok(value) => {
+                answers.push(value);
+                save_session(&answers);
+            }
+            Err(err) => eprintln!("{}", i18n::tr(&format!("{}. Please try again.", err), &format!("{}。请重新输入。", err))),
+        }
+    }
+
+    let mut new_prompt = String::new();
+    for part in answers[..PARTS * 2].chunks(2) {
+        new_prompt += &part[1];
+        new_prompt += &part[0];
+        new_prompt += " ";
+    }
+    let symbol = |answer: &String, default: &'static str| if answer.is_empty() { default.to_string() } else { answer.clone() };
+    new_prompt += &modules::prompt_char(&symbol(&answers[PARTS * 2], "$"), &symbol(&answers[PARTS * 2 + 1], "#"));
+    clear_session();
+    Ok(Some(new_prompt))
+}
+
+/// 向导的最后一步: 询问是写入配置文件还是复制到剪贴板, 选择复制时返回 true
+pub fn ask_copy() -> std::io::Result<bool> {
+    println!(
+        "{}",
+        i18n::tr(
+            "Press Enter to write it to your shell config, or type c to copy the script to the clipboard instead:",
+            "回车写入 shell 配置文件, 或输入 c 改为把脚本复制到剪贴板:",
+        )
+    );
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_ascii_lowercase().as_str(), "c" | "copy" | "复制"))
+}
+
+/// 询问是否同时把配套的 readline 设置写入 ~/.inputrc, 默认不写
+pub fn ask_inputrc() -> std::io::Result<bool> {
+    println!(
+        "{}",
+        i18n::tr(
+            "Also write matching readline settings (colored completions, vi mode strings) to ~/.inputrc? [y/N]",
+            "是否同时把配套的 readline 设置(彩色补全、vi 模式提示)写入 ~/.inputrc? [y/N]",
+        )
+    );
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_ascii_lowercase().as_str(), "y" | "yes" | "是"))
+}
+
+/// 已有提示符的一部分: 颜色及其后直到下一个颜色的内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Part {
+    color: String,
+    text: String,
+}
+
+/// 按颜色把提示符分成几部分, 第一个颜色之前的内容单独成为没有颜色的一部分
+fn split_parts(prompt: &str) -> Vec<Part> {
+    let sgr = Regex::new(r"^\\(e|033|x1[bB])\[[0-9;]*m$").unwrap();
+    let mut parts: Vec<Part> = Vec::new();
+    for segment in prompt::parse_bash(prompt) {
+        let is_color = match &segment {
+            Segment::Color(_) => true,
+            Segment::NonPrinting(inner) => sgr.is_match(inner),
+            _ => false,
+        };
+        match parts.last_mut() {
+            Some(part) if !is_color => part.text += &segment.to_string(),
+            _ if is_color => parts.push(Part { color: segment.to_string(), text: String::new() }),
+            _ => parts.push(Part { color: String::new(), text: segment.to_string() }),
+        }
+    }
+    parts
+}
+
+/// 修改第 `i` 部分: 直接回车保留原样, 颜色输入 `-` 时去掉颜色
+fn edit_part(part: &mut Part, i: usize) -> std::io::Result<bool> {
+    loop {
+        let question = i18n::tr(
+            &format!("New text of part {} (Enter keeps `{}`):", i, part.text),
+            &format!("第{}部分的新内容(回车保留 `{}`):", i, part.text),
+        )
+        .to_string();
+        let input = match ask(&question)? {
+            Answer::Value(input) => input,
+            Answer::Back => return Ok(true),
+            Answer::Cancel | Answer::Closed => return Ok(false),
+        };
+        if input.is_empty() {
+            break;
+        }
+        match Step::Name(i).check(&input) {
+            Ok(text) => {
+                // 输入会去掉首尾空白, 保留原来与下一部分之间的空格
+                let gap = &part.text[part.text.trim_end().len()..];
+                part.text = text + gap;
+                break;
+            }
+            Err(err) => eprintln!("{}", i18n::tr(&format!("{}. Please try again.", err), &format!("{}。请重新输入。", err))),
+        }
+    }
+    loop {
+        let question = i18n::tr(
+            &format!("New color of part {} (Enter keeps `{}`, - for none, ? to pick from swatches):", i, part.color),
+            &format!("第{}部分的新颜色(回车保留 `{}`, - 表示不设颜色, ? 从色块中选择):", i, part.color),
+        )
+        .to_string();
+        let input = match ask(&question)? {
+            Answer::Value(input) => input,
+            Answer::Back => return Ok(true),
+            Answer::Cancel | Answer::Closed => return Ok(false),
+        };
+        let input = match input.as_str() {
+            "?" => match pick_color()? {
+                Some(color) => color,
+                None => continue,
+            },
+            _ => input,
+        };
+        match input.as_str() {
+            "" => return Ok(true),
+            "-" => {
+                part.color.clear();
+                return Ok(true);
+            }
+            _ => match Step::Color(i).check(&input) {
+                Ok(color) => {
+                    part.color = color;
+                    return Ok(true);
+                }
+                Err(err) => eprintln!("{}", i18n::tr(&format!("{}. Please try again.", err), &format!("{}。请重新输入。", err))),
+            },
+        }
+    }
+}
+
+/// 列出已有提示符的各部分, 逐个修改后返回新的提示符; 放弃时返回 `None`
+pub fn edit_prompt(current: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut parts = split_parts(current);
+    loop {
+        println!("\n{}", i18n::tr("Parts of the current prompt:", "当前提示符的各部分:"));
+        for (i, part) in parts.iter().enumerate() {
+            let color = if part.color.is_empty() { i18n::tr("(none)", "(无)") } else { &part.color };
+            println!("  {}. {} {:<16} {} `{}`", i + 1, i18n::tr("color", "颜色"), color, i18n::tr("text", "内容"), part.text);
+        }
+        let question = i18n::tr(
+            "Number of the part to change (Enter to save, :q to quit; :b inside a part returns here):",
+            "请输入要修改的部分编号(回车保存, :q 退出; 修改时输入 :b 回到这里):",
+        );
+        let input = match ask(question)? {
+            Answer::Value(input) => input,
+            Answer::Back => continue,
+            Answer::Cancel | Answer::Closed => return Ok(None),
+        };
+        if input.is_empty() {
+            break;
+        }
+        match input.parse::<usize>().ok().filter(|n| (1..=parts.len()).contains(n)) {
+            Some(n) => {
+                if !edit_part(&mut parts[n - 1], n)? {
+                    return Ok(None);
+                }
+            }
+            None => eprintln!(
+                "{}",
+                i18n::tr(&format!("Enter a number from 1 to {}.", parts.len()), &format!("请输入 1 到 {} 之间的编号。", parts.len()))
+            ),
+        }
+    }
+    Ok(Some(parts.iter().map(|part| format!("{}{}", part.color, part.text)).collect()))
+}
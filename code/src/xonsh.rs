@@ -0,0 +1,269 @@
+use crate::color::{self, ColorDepth, ColorSlot};
+use crate::prompt::{self, PromptSet, Segment, COLOR_NAMES, SOURCE_MARKER};
+use std::path::{Path, PathBuf};
+
+/// xonsh 的提示符中没有对应写法的 bash 转义
+const UNSUPPORTED_ESCAPES: [&str; 7] = ["!", "#", "j", "l", "r", "v", "V"];
+
+/// 转义在 xonsh 中是否没有对应写法; 提示符模板中的颜色用 `{RED}` 等写出, 其余控制字符都不支持
+pub fn is_unsupported(escape: &str) -> bool {
+    let octal = escape.len() == 3 && escape.chars().all(|c| c.is_digit(8));
+    UNSUPPORTED_ESCAPES.contains(&escape) || escape == "a" || (octal && escape != "033")
+}
+
+/// xonsh 启动时读取的 ~/.xonshrc; 只有 ~/.config/xonsh/rc.xsh 存在时用它
+pub fn rc_path(home: &Path) -> PathBuf {
+    let xonshrc = home.join(".xonshrc");
+    let rc_xsh = home.join(".config/xonsh/rc.xsh");
+    if !xonshrc.exists() && rc_xsh.exists() {
+        rc_xsh
+    } else {
+        xonshrc
+    }
+}
+
+/// 生成的代码用到的模块, 加上前缀以免与用户的名称冲突
+const SETUP: &str = "import fnmatch as __prompt_changer_fnmatch
+import os as __prompt_changer_os
+import socket as __prompt_changer_socket
+import subprocess as __prompt_changer_subprocess
+import time as __prompt_changer_time
+";
+
+/// Python 的单引号字符串
+pub fn quote(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('\'', "\\'").replace('\n', "\\n");
+    format!("'{}'", escaped)
+}
+
+/// SGR 参数对应的 xonsh 颜色名称; 修饰与前景色合成一个名称, 如 `BOLD_GREEN`
+fn tokens(sgr: &str) -> Vec<String> {
+    let codes: Vec<u32> = sgr.split(';').map(|code| code.parse().unwrap_or(0)).collect();
+    let hex = |i: usize| {
+        let rgb = match codes.get(i + 1) {
+            Some(5) => codes.get(i + 2).map(|n| color::palette_rgb(*n as u8)),
+            Some(2) if i + 4 < codes.len() => Some((codes[i + 2] as u8, codes[i + 3] as u8, codes[i + 4] as u8)),
+            _ => None,
+        };
+        let skip = if codes.get(i + 1) == Some(&5) { 2 } else { 4 };
+        (rgb.map(|(r, g, b)| format!("#{:02x}{:02x}{:02x}", r, g, b)), skip)
+    };
+    let mut out = Vec::new();
+    let mut modifiers = Vec::new();
+    let mut foreground = None;
+    let mut i = 0;
+    while i < codes.len() {
+        let code = codes[i];
+        match code {
+            0 => {
+                out.push("RESET".to_string());
+                modifiers.clear();
+                foreground = None;
+            }
+            1 => modifiers.push("BOLD"),
+            2 => modifiers.push("FAINT"),
+            3 => modifiers.push("ITALIC"),
+            4 => modifiers.push("UNDERLINE"),
+            5 => modifiers.push("SLOWBLINK"),
+            7 => modifiers.push("INVERT"),
+            30..=37 => foreground = Some(COLOR_NAMES[(code - 30) as usize].to_ascii_uppercase()),
+            90..=97 => foreground = Some(format!("INTENSE_{}", COLOR_NAMES[(code - 90) as usize].to_ascii_uppercase())),
+            39 => foreground = Some("DEFAULT".to_string()),
+            40..=47 => out.push(format!("BACKGROUND_{}", COLOR_NAMES[(code - 40) as usize].to_ascii_uppercase())),
+            100..=107 => out.push(format!("BACKGROUND_INTENSE_{}", COLOR_NAMES[(code - 100) as usize].to_ascii_uppercase())),
+            38 | 48 => {
+                let (color, skip) = hex(i);
+                match (code, color) {
+                    (38, Some(color)) => foreground = Some(color),
+                    (_, Some(color)) => out.push(format!("BACKGROUND_{}", color)),
+                    _ => {}
+                }
+                i += skip;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if foreground.is_some() || !modifiers.is_empty() {
+        let mut name = modifiers.join("_");
+        if !name.is_empty() {
+            name.push('_');
+        }
+        name += foreground.as_deref().unwrap_or("DEFAULT");
+        out.push(name);
+    }
+    out.into_iter().map(|name| format!("{{{}}}", name)).collect()
+}
+
+/// 是否是显示 git 分支的命令替换, 换成 xonsh 自带的 `{curr_branch}`
+pub fn is_git_branch(cmd: &str) -> bool {
+    ["__git_ps1", "git branch --show-current", "git rev-parse --abbrev-ref", "git symbolic-ref --short"].iter().any(|c| cmd.contains(c))
+}
+
+/// 翻译时收集的自定义字段: 名称与 Python 表达式, 写入 `$PROMPT_FIELDS`
+struct Fields {
+    fields: Vec<(String, String)>,
+}
+
+impl Fields {
+    /// 引用一个字段, 第一次引用时记下它的取值
+    fn field(&mut self, name: String, value: String) -> String {
+        if !self.fields.iter().any(|(n, _)| *n == name) {
+            self.fields.push((name.clone(), value));
+        }
+        format!("{{{}}}", name)
+    }
+
+    /// 按 strftime 格式显示当前时间的字段
+    fn time(&mut self, fmt: &str) -> String {
+        let n = self.fields.iter().filter(|(n, _)| n.starts_with("prompt_changer_time_")).count();
+        let value = format!("lambda: __prompt_changer_time.strftime({})", quote(fmt));
+        match self.fields.iter().find(|(_, v)| *v == value) {
+            Some((name, _)) => format!("{{{}}}", name),
+            None => self.field(format!("prompt_changer_time_{}", n), value),
+        }
+    }
+
+    /// 把 bash 的反斜杠转义翻译为模板中的内容
+    fn escape(&mut self, escape: &str) -> String {
+        match escape {
+            "u" => "{user}".to_string(),
+            "h" => "{hostname}".to_string(),
+            "H" => self.field("prompt_changer_host".to_string(), "__prompt_changer_socket.gethostname()".to_string()),
+            "w" => "{cwd}".to_string(),
+            "W" => "{cwd_base}".to_string(),
+            "t" => self.time("%H:%M:%S"),
+            "T" => self.time("%I:%M:%S"),
+            "@" => self.time("%I:%M %p"),
+            "A" => self.time("%H:%M"),
+            "d" => self.time("%a %b %d"),
+            "$" => self.field(
+                "prompt_changer_sign".to_string(),
+                "'#' if hasattr(__prompt_changer_os, 'geteuid') and __prompt_changer_os.geteuid() == 0 else '$'".to_string(),
+            ),
+            "s" => "xonsh".to_string(),
+            "n" => "\n".to_string(),
+            "\\" => "\\".to_string(),
+            _ => match escape.strip_prefix("D{").and_then(|s| s.strip_suffix('}')) {
+                Some(fmt) => self.time(if fmt.is_empty() { "%X" } else { fmt }),
+                None => String::new(),
+            },
+        }
+    }
+
+    /// 把单个片段翻译为模板中的内容; `current` 是当前生效的颜色, git 分支改变颜色后要恢复它
+    fn segment(&mut self, segment: &Segment, colors: &[Vec<String>], current: &mut Vec<String>) -> String {
+        let apply = |tokens: Vec<String>, current: &mut Vec<String>| {
+            if tokens.iter().any(|t| t == "{RESET}") {
+                current.clear();
+            }
+            current.extend(tokens.iter().filter(|t| *t != "{RESET}").cloned());
+            tokens.concat()
+        };
+        match segment {
+            Segment::Text(text) => text.replace('{', "{{").replace('}', "}}"),
+            Segment::Escape(escape) => self.escape(escape),
+            Segment::Color(color) => {
+                let rest = color.strip_prefix("\\033").or_else(|| color.strip_prefix("\\e")).unwrap_or(color);
+                apply(tokens(rest.trim_start_matches('[').trim_end_matches('m')), current)
+            }
+            Segment::Variable(name) => match name.strip_prefix("__prompt_changer_color_").and_then(|i| i.parse::<usize>().ok()) {
+                Some(i) => apply(colors.get(i).cloned().unwrap_or_default(), current),
+                None => self.field(
+                    format!("prompt_changer_env_{}", name),
+                    format!("lambda: ${{...}}.get({}, '')", quote(name)),
+                ),
+            },
+            // `\[ \]` 中只有颜色能写进模板, 标题等控制序列由 $TITLE 设置
+            Segment::NonPrinting(inner) => {
+                let mut text = String::new();
+                for segment in prompt::parse_bash(inner) {
+                    if matches!(segment, Segment::Color(_) | Segment::Variable(_)) {
+                        text += &self.segment(&segment, colors, current);
+                    }
+                }
+                text
+            }
+            Segment::Command(cmd) if is_git_branch(cmd) => format!("{{branch_color}}{{curr_branch}}{{RESET}}{}", current.concat()),
+            // bash 的命令替换交给 sh 运行, 去掉输出末尾的换行
+            Segment::Command(cmd) => {
+                let n = self.fields.iter().filter(|(n, _)| n.starts_with("prompt_changer_cmd_")).count();
+                let value = format!(
+                    "lambda: __prompt_changer_subprocess.run(['sh', '-c', {}], capture_output=True, text=True).stdout.rstrip('\\n')",
+                    quote(cmd)
+                );
+                match self.fields.iter().find(|(_, v)| *v == value) {
+                    Some((name, _)) => format!("{{{}}}", name),
+                    None => self.field(format!("prompt_changer_cmd_{}", n), value),
+                }
+            }
+        }
+    }
+
+    /// 提示符翻译为 `$PROMPT` 模板, 颜色变量取 `colors` 中的颜色
+    fn template(&mut self, prompt: &str, colors: &[Vec<String>]) -> String {
+        let mut current = Vec::new();
+        let template: String = prompt::parse_bash(prompt).iter().map(|s| self.segment(s, colors, &mut current)).collect();
+        quote(&template)
+    }
+}
+
+/// 颜色变量在深色或浅色背景下对应的 xonsh 颜色; xonsh 按终端能力自行降级, 所以总是取真彩色
+fn slot_tokens(colors: &[ColorSlot], light: bool) -> Vec<Vec<String>> {
+    colors
+        .iter()
+        .map(|slot| {
+            let fallback = if light { &slot.light } else { &slot.dark };
+            fallback.at(ColorDepth::TrueColor).map(tokens).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// 提示符略去的设置
+pub fn notices(prompts: &PromptSet) -> Vec<String> {
+    prompt::left_out_notice("xonsh", &prompts.unsupported_extras(false)).into_iter().collect()
+}
+
+/// 写入受管理区块的代码: 第一行记下原始的 bash 风格提示符. 只支持按主机名切换的变体与标题
+pub fn block_body(prompts: &PromptSet) -> String {
+    let mut fields = Fields { fields: Vec::new() };
+    let themed = prompts.colors.iter().any(|c| c.dark != c.light);
+    let dark = slot_tokens(&prompts.colors, false);
+    let light = slot_tokens(&prompts.colors, true);
+    // 浅色、深色配色不同时按 PROMPT_CHANGER_APPEARANCE 或 COLORFGBG 选择模板
+    let mut value = |prompt: &str| {
+        let dark_template = fields.template(prompt, &dark);
+        if themed {
+            format!("{} if __prompt_changer_light else {}", fields.template(prompt, &light), dark_template)
+        } else {
+            dark_template
+        }
+    };
+    let default = value(&prompts.default);
+    let hosts: Vec<(&String, String)> = prompts.hosts.iter().map(|(pattern, prompt)| (pattern, value(prompt))).collect();
+    let title = prompts.title.as_deref().map(&mut value);
+
+    let mut body = format!("{}{}\n{}", SOURCE_MARKER, prompts.default, SETUP);
+    if themed {
+        body += "__prompt_changer_appearance = ${...}.get('PROMPT_CHANGER_APPEARANCE', '')\n";
+        body += "__prompt_changer_light = __prompt_changer_appearance == 'light' or (\n";
+        body += "    not __prompt_changer_appearance and ${...}.get('COLORFGBG', '').split(';')[-1] in ('7', '15')\n)\n";
+    }
+    for (name, value) in &fields.fields {
+        body += &format!("$PROMPT_FIELDS[{}] = {}\n", quote(name), value);
+    }
+    if let Some(title) = title {
+        body += &format!("$TITLE = {}\n", title);
+    }
+    body += &format!("$PROMPT = {}", default);
+    for (n, (pattern, prompt)) in hosts.iter().enumerate() {
+        let keyword = if n == 0 { "if" } else { "elif" };
+        body += &format!(
+            "\n{} __prompt_changer_fnmatch.fnmatch(__prompt_changer_socket.gethostname(), {}):\n    $PROMPT = {}",
+            keyword,
+            quote(pattern),
+            prompt
+        );
+    }
+    body
+}